@@ -0,0 +1,54 @@
+// Load and save round trips of JsonFileStorage for big lists
+// Run with `cargo bench`, every size is saved and loaded in both output formats
+// `cargo test --benches` runs every case once instead, as a smoke test
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use tempfile::NamedTempFile;
+use todo_cli::{JsonFileStorage, OutputFormat, Task, TodoStorage};
+
+const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+
+// Tasks that look like real ones, some tags and some completed
+fn synthetic_tasks(count: usize) -> Vec<Task> {
+    (1..=count as u32)
+        .map(|id| {
+            let mut task = Task::new(id, format!("Task number {}", id), format!("Description of task {}", id));
+            task.completed = id % 3 == 0;
+            if id % 2 == 0 {
+                task.set_tags(vec!["work".to_string(), format!("group-{}", id % 10)]);
+            }
+            task
+        })
+        .collect()
+}
+
+fn storage_benches(c: &mut Criterion) {
+    let mut group = c.benchmark_group("storage");
+    group.sample_size(10); // 100k tasks take a while per run
+
+    for size in SIZES {
+        let tasks = synthetic_tasks(size);
+        group.throughput(Throughput::Elements(size as u64));
+        for format in [OutputFormat::Pretty, OutputFormat::Compact] {
+            let file = NamedTempFile::new().expect("temp file");
+            let storage = JsonFileStorage::with_path(file.path().to_string_lossy()).with_format(format);
+            let name = format!("{:?}", format).to_lowercase();
+
+            group.bench_with_input(BenchmarkId::new(format!("save/{}", name), size), &tasks, |b, tasks| {
+                b.iter(|| storage.save(tasks).expect("save"))
+            });
+            // Load needs the file even when a name filter skipped the save bench
+            storage.save(&tasks).expect("save");
+            group.bench_with_input(BenchmarkId::new(format!("load/{}", name), size), &size, |b, &size| {
+                b.iter(|| {
+                    let loaded = storage.load().expect("load");
+                    assert_eq!(loaded.len(), size);
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, storage_benches);
+criterion_main!(benches);
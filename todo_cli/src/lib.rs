@@ -1,63 +1,321 @@
 use clap::{Parser, Subcommand};
 use serde::{Deserialize, Serialize};
 
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
-use std::path::Path;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::cell::RefCell;
+
+// Trait for asking the user before something that cant be undone
+// Same idea as TodoStorage, main uses the terminal and the tests answer from a string
+pub trait Confirm {
+    fn confirm(&mut self, question: &str) -> Result<bool, Box<dyn std::error::Error>>;
+}
+
+// Writes the question to output and reads one line of answer from input
+// main passes stdin and stdout, tests a Cursor and a Vec<u8>
+pub struct Prompt<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Prompt<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self { input, output }
+    }
+}
+
+impl<R: BufRead, W: Write> Confirm for Prompt<R, W> {
+    fn confirm(&mut self, question: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        // No newline, the answer goes on the same line. print! doesnt flush on its own
+        write!(self.output, "{} [y/N] ", question)?;
+        self.output.flush()?;
+        let mut answer = String::new();
+        // Closed stdin reads 0 bytes and an empty answer, so it counts as no
+        self.input.read_line(&mut answer)?;
+        Ok(is_yes(&answer))
+    }
+}
+
+// Says yes to everything without asking, for --yes and when nobody is there to answer
+pub struct AutoConfirm;
+
+impl Confirm for AutoConfirm {
+    fn confirm(&mut self, _question: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(true)
+    }
+}
+
+// Only y or yes (any case) confirm, N is the default so everything else aborts
+pub fn is_yes(answer: &str) -> bool {
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
 
 // Constant holding the name of the JSON file to store tasks
 pub const TODO_FILE: &str = "todo.json";
 
+// Characters between the brackets of the stats progress bar
+pub const STATS_BAR_WIDTH: usize = 20;
+
 // Trait defining the interface for different storage backends
 pub trait TodoStorage {
     fn load(&self) -> Result<Vec<Task>, Box<dyn std::error::Error>>;
     fn save(&self, tasks: &Vec<Task>) -> Result<(), Box<dyn std::error::Error>>;
 }
 
+// How the JSON file is written, both are read back the same way
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Pretty, // Indented, easy to read and diff by hand
+    Compact, // Single line, smaller and faster for big lists
+}
+
 // JSON file storage implementation of TodoStorage trait
 pub struct JsonFileStorage {
     file_path: String,
+    format: OutputFormat,
 }
 
 impl JsonFileStorage {
 pub fn new() -> Self {
         let file_path = std::env::var("TODO_FILE").ok().unwrap_or_else(|| TODO_FILE.to_string());
-        Self { file_path }
+        Self::with_path(file_path)
+    }
+
+    // Storage on a given file, ignores TODO_FILE (used by the benchmarks)
+    pub fn with_path(file_path: impl Into<String>) -> Self {
+        Self { file_path: file_path.into(), format: OutputFormat::default() }
+    }
+
+    pub fn with_format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn path(&self) -> &str {
+        &self.file_path
     }
 }
 
 // I/O operations for JSON file storage
 impl TodoStorage for JsonFileStorage {
     fn load(&self) -> Result<Vec<Task>, Box<dyn std::error::Error>> {
-        let path = Path::new(&self.file_path);
-        if !path.exists() {
-            return Ok(Vec::new());
-        }
-        let file = File::open(path)?;
-        let metadata = file.metadata()?;
+        // Whole file in one read, fs::read sizes the buffer from the file length up front
+        // Parsing from memory is a lot faster than from_reader, which goes byte by byte through the reader
+        // A missing file is told apart by the error, no separate exists/metadata calls
+        let bytes = match std::fs::read(&self.file_path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
 
         // Handle empty files
-        if metadata.len() == 0 {
+        if bytes.is_empty() {
             return Ok(Vec::new());
         }
 
-        let reader = BufReader::new(file);
-        let tasks: Vec<Task> = serde_json::from_reader(reader)?;
+        let tasks: Vec<Task> = serde_json::from_slice(&bytes)?;
         Ok(tasks)
     }
 
     fn save(&self, tasks: &Vec<Task>) -> Result<(), Box<dyn std::error::Error>> {
         let file = File::create(&self.file_path)?;
         let writer = BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, &tasks)?;
+        match self.format {
+            OutputFormat::Pretty => serde_json::to_writer_pretty(writer, &tasks)?,
+            OutputFormat::Compact => serde_json::to_writer(writer, &tasks)?,
+        }
         Ok(())
     }
 }
 
+// Env var that turns the audit log on without --audit, any of 1/true/yes
+pub const AUDIT_ENV: &str = "TODO_AUDIT";
+
+pub fn audit_from_env() -> bool {
+    std::env::var(AUDIT_ENV).is_ok_and(|value| matches!(value.trim().to_lowercase().as_str(), "1" | "true" | "yes"))
+}
+
+// One line of the audit log, what a command changed and when (seconds since 1970, UTC)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditEvent {
+    pub timestamp: u64,
+    pub action: String,
+    pub task_id: u32,
+}
+
+impl AuditEvent {
+    pub fn new(action: &str, task_id: u32) -> Self {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        Self { timestamp, action: action.to_string(), task_id }
+    }
+}
+
+impl fmt::Display for AuditEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let seconds = self.timestamp % 86_400;
+        write!(
+            f,
+            "{} {:02}:{:02}:{:02}  {} task {}",
+            Date::from_unix_seconds(self.timestamp),
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60,
+            self.action,
+            self.task_id,
+        )
+    }
+}
+
+// Append only JSON Lines file, one event per line, nothing is ever rewritten
+// Lines are appended as they come so a crash never loses older events
+pub struct AuditLog {
+    file_path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(file_path: impl Into<PathBuf>) -> Self {
+        Self { file_path: file_path.into() }
+    }
+
+    // todo_audit.jsonl next to todo.json, each todo file gets its own log
+    pub fn for_todo_file(todo_path: &str) -> Self {
+        let path = Path::new(todo_path);
+        let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("todo");
+        Self::new(path.with_file_name(format!("{}_audit.jsonl", stem)))
+    }
+
+    pub fn append(&self, events: &[AuditEvent]) -> Result<(), Box<dyn std::error::Error>> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(&self.file_path)?;
+        let mut writer = BufWriter::new(file);
+        for event in events {
+            serde_json::to_writer(&mut writer, event)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    // Last limit events, oldest first. No file yet is no events
+    // A line that doesnt parse (cut off by a crash) is skipped instead of hiding the rest
+    pub fn recent(&self, limit: usize) -> Result<Vec<AuditEvent>, Box<dyn std::error::Error>> {
+        let text = match std::fs::read_to_string(&self.file_path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let events: Vec<AuditEvent> = text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect();
+        Ok(events[events.len().saturating_sub(limit)..].to_vec())
+    }
+}
+
+// Storage wrapper that writes an audit event for everything a save changed
+// The commands dont know about it, the events come from comparing the saved list with the
+// previous one. Without a log (audit off) it only passes the calls through
+// A failed audit write is a warning, the tasks themselves are already saved at that point
+pub struct AuditedStorage<S: TodoStorage> {
+    inner: S,
+    log: Option<AuditLog>,
+    // What the file held after the last load or save, the "before" of the next save
+    last_saved: RefCell<Vec<Task>>,
+}
+
+impl<S: TodoStorage> AuditedStorage<S> {
+    pub fn new(inner: S, log: Option<AuditLog>) -> Self {
+        Self { inner, log, last_saved: RefCell::new(Vec::new()) }
+    }
+}
+
+impl<S: TodoStorage> TodoStorage for AuditedStorage<S> {
+    fn load(&self) -> Result<Vec<Task>, Box<dyn std::error::Error>> {
+        let tasks = self.inner.load()?;
+        if self.log.is_some() {
+            *self.last_saved.borrow_mut() = tasks.clone();
+        }
+        Ok(tasks)
+    }
+
+    fn save(&self, tasks: &Vec<Task>) -> Result<(), Box<dyn std::error::Error>> {
+        self.inner.save(tasks)?;
+        if let Some(log) = &self.log {
+            let events = audit_events(&self.last_saved.borrow(), tasks);
+            if let Err(e) = log.append(&events) {
+                eprintln!("Warning: could not write the audit log: {}", e);
+            }
+            *self.last_saved.borrow_mut() = tasks.clone();
+        }
+        Ok(())
+    }
+}
+
+// Events for going from before to after, by task id
+// Renumber is the one change that keeps every task but moves ids, matching by id would see it as
+// removes and adds, so the same tasks in the same order with other ids count as a renumber
+pub fn audit_events(before: &[Task], after: &[Task]) -> Vec<AuditEvent> {
+    let same_tasks = before.len() == after.len()
+        && before.iter().zip(after).all(|(old, new)| old.title == new.title && old.description == new.description);
+    if same_tasks && before.iter().zip(after).any(|(old, new)| old.id != new.id) {
+        return after.iter()
+            .zip(before)
+            .filter(|(new, old)| new.id != old.id)
+            .map(|(new, _)| AuditEvent::new("renumber", new.id))
+            .collect();
+    }
+
+    let mut events = Vec::new();
+    for old in before {
+        match after.iter().find(|new| new.id == old.id) {
+            None => events.push(AuditEvent::new("remove", old.id)),
+            Some(new) => {
+                if new.completed && !old.completed {
+                    events.push(AuditEvent::new("complete", new.id));
+                }
+                if new.due_date != old.due_date {
+                    events.push(AuditEvent::new("defer", new.id));
+                }
+            }
+        }
+    }
+    for new in after.iter().filter(|new| !before.iter().any(|old| old.id == new.id)) {
+        events.push(AuditEvent::new("add", new.id));
+    }
+    events
+}
+
+
+// Status markers the list prints in front of every task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GlyphSet {
+    #[default]
+    Unicode, // [✓] for completed tasks
+    Ascii, // [x], for terminals that cant show the check mark
+}
+
+impl GlyphSet {
+    pub fn status(&self, completed: bool) -> &'static str {
+        match (self, completed) {
+            (_, false) => "[ ]",
+            (GlyphSet::Unicode, true) => "[✓]",
+            (GlyphSet::Ascii, true) => "[x]",
+        }
+    }
+}
 
 pub struct TodoList<S: TodoStorage> {
     storage: S,
     tasks: Vec<Task>,
+    glyphs: GlyphSet,
+    // Commands change the list in memory as usual, but nothing is written back (--dry-run)
+    dry_run: bool,
 }
 
 // Represents the in memory list of tasks with methods to manipulate it
@@ -68,23 +326,44 @@ impl<S: TodoStorage> TodoList<S> {
     pub fn load(storage: S) -> Result<Self, Box<dyn std::error::Error>> {
         // Calls load method based on the storage type we passed (JSON file in this case)
         let tasks = storage.load()?;
-        Ok(Self { tasks, storage })
+        Ok(Self { tasks, storage, glyphs: GlyphSet::default(), dry_run: false })
+    }
+
+    pub fn with_glyphs(mut self, glyphs: GlyphSet) -> Self {
+        self.glyphs = glyphs;
+        self
+    }
+
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
     }
 
     // Internal save
+    // Every command ends up here, so skipping the write is all a dry run needs
     fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.dry_run {
+            return Ok(());
+        }
         self.storage.save(&self.tasks)
     }
 
     // Add a task to the in memory vector and save to file
     pub fn add(&mut self, title: String, description: String)
         -> Result<u32, Box<dyn std::error::Error>> {
+        self.add_with_tags(title, description, Vec::new())
+    }
+
+    // Same as add but the task starts with tags
+    pub fn add_with_tags(&mut self, title: String, description: String, tags: Vec<String>)
+        -> Result<u32, Box<dyn std::error::Error>> {
 
         // Convert into iterator, map projects(extracts) the id field from each task
         // max returns an option of either the max value of task.ids or None if no tasks exist
         // then we have unwrap_or(0) to return 0 if no tasks exist, and add 1 to get the next id
         let next_id = Task::find_next_id(&self.tasks);
-        let new_task = Task::new(next_id, title, description);
+        let mut new_task = Task::new(next_id, title, description);
+        new_task.set_tags(tags);
         self.tasks.push(new_task);
         self.save()?;
         Ok(next_id)
@@ -92,19 +371,90 @@ impl<S: TodoStorage> TodoList<S> {
 
     // List tasks from memory
     pub fn list(&self) {
-        if self.tasks.is_empty() {
+        self.list_filtered(ListFilter::All);
+    }
+
+    // List only the tasks matching the filter
+    pub fn list_filtered(&self, filter: ListFilter) {
+        // Collect first so we know if the filter left anything to show
+        let tasks: Vec<&Task> = self.tasks.iter().filter(|task| filter.matches(task)).collect();
+        self.print_tasks(&tasks);
+    }
+
+    // Tasks matching every part of the filter, in list order
+    pub fn query(&self, filter: &QueryFilter) -> Vec<&Task> {
+        self.tasks.iter().filter(|task| filter.matches(task)).collect()
+    }
+
+    // One line per task, the format every listing command uses
+    pub fn print_tasks(&self, tasks: &[&Task]) {
+        if tasks.is_empty() {
             println!("No tasks found.");
         } else {
-            for task in &self.tasks {
-                let status = if task.completed { "[✓]" } else { "[ ]" };
+            for task in tasks {
+                let status = self.glyphs.status(task.completed);
+                // Tags only show up when there are some, so untagged tasks look like before
+                let tags = if task.tags.is_empty() {
+                    String::new()
+                } else {
+                    format!(" | Tags: {}", task.tags.join(", "))
+                };
+                let due = task.due_date.map(|date| format!(" | Due: {}", date)).unwrap_or_default();
                 println!(
-                    "{} ID: {} - Title: {} | Description: {}",
-                    status, task.id, task.title, task.description
+                    "{} ID: {} - Title: {} | Description: {}{}{}",
+                    status, task.id, task.title, task.description, tags, due
                 );
             }
         }
     }
 
+    // How many tasks carry each tag, BTreeMap keeps the tags sorted alphabetically
+    pub fn tag_counts(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for task in &self.tasks {
+            for tag in &task.tags {
+                *counts.entry(tag.clone()).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    pub fn untagged_count(&self) -> usize {
+        self.tasks.iter().filter(|task| task.tags.is_empty()).count()
+    }
+
+    // Print every tag with its count, then the untagged tasks
+    pub fn list_tags(&self) {
+        if self.tasks.is_empty() {
+            println!("No tasks found.");
+            return;
+        }
+        for (tag, count) in self.tag_counts() {
+            println!("{}: {}", tag, count);
+        }
+        // In parentheses so it cant be mistaken for a tag called untagged
+        println!("(untagged): {}", self.untagged_count());
+    }
+
+    // Count the tasks on each side of the completed flag
+    pub fn stats(&self) -> Stats {
+        let completed = self.tasks.iter().filter(|task| task.completed).count();
+        Stats {
+            total: self.tasks.len(),
+            completed,
+            pending: self.tasks.len() - completed,
+        }
+    }
+
+    // Print the counts and a progress bar of the completed tasks
+    pub fn print_stats(&self) {
+        let stats = self.stats();
+        println!("Total: {}", stats.total);
+        println!("Completed: {}", stats.completed);
+        println!("Pending: {}", stats.pending);
+        println!("{}", render_bar(stats.completed_fraction(), STATS_BAR_WIDTH));
+    }
+
     // Complete a task by id and save the updated vector to file
     pub fn complete(&mut self, id: u32) -> Result<(), Box<dyn std::error::Error>> {
         Task::mark_task_completed(&mut self.tasks[..], id)?;
@@ -112,6 +462,34 @@ impl<S: TodoStorage> TodoList<S> {
         Ok(())
     }
 
+    // Mark every pending task completed and save once, returns how many were pending
+    // Nothing pending means nothing changed, so there is nothing to save either
+    pub fn complete_all(&mut self) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut count = 0;
+        for task in self.tasks.iter_mut().filter(|task| !task.completed) {
+            task.completed = true;
+            count += 1;
+        }
+        if count > 0 {
+            self.save()?;
+        }
+        Ok(count)
+    }
+
+    // Remove after asking, returns false when the user said no and nothing changed
+    // An unknown id fails before the question, no point asking about a task that isnt there
+    pub fn remove_confirmed(&mut self, id: u32, confirm: &mut impl Confirm)
+        -> Result<bool, Box<dyn std::error::Error>> {
+        if !self.tasks.iter().any(|t| t.id == id) {
+            return Err(format!("Task {} not found", id).into());
+        }
+        if !confirm.confirm(&format!("Remove task {}?", id))? {
+            return Ok(false);
+        }
+        self.remove(id)?;
+        Ok(true)
+    }
+
     // Remove a task from vector by id and save the updated vector to file
     pub fn remove(&mut self, id: u32) -> Result<(), Box<dyn std::error::Error>> {
 
@@ -145,6 +523,32 @@ impl<S: TodoStorage> TodoList<S> {
         //println!("Task {} removed successfully", id);
         //Ok(())
     }
+
+    // Push the due date of a task days into the future and save, returns the new due date
+    // A task without one gets due days after today, today is a parameter so tests can fix it
+    pub fn defer(&mut self, id: u32, days: i64, today: Date) -> Result<Date, Box<dyn std::error::Error>> {
+        if days < 0 {
+            return Err(format!("Cant defer by {} days, use 0 or more", days).into());
+        }
+        let task = self.tasks
+            .iter_mut()
+            .find(|task| task.id == id)
+            .ok_or_else(|| format!("Task {} not found", id))?;
+        let due_date = task.due_date.unwrap_or(today).add_days(days);
+        task.due_date = Some(due_date);
+        self.save()?;
+        Ok(due_date)
+    }
+
+    // Give the tasks the ids 1..N in their current order and save
+    // Returns how many tasks got a new id, nothing is saved when there were no gaps
+    pub fn renumber(&mut self) -> Result<usize, Box<dyn std::error::Error>> {
+        let changed = Task::renumber(&mut self.tasks);
+        if changed > 0 {
+            self.save()?;
+        }
+        Ok(changed)
+    }
 }
 
 
@@ -158,7 +562,13 @@ pub struct Task {
     pub id: u32,
     pub title: String,
     pub description: String,
-    pub completed: bool
+    pub completed: bool,
+    // Files saved before tags existed dont have the field, default gives them no tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // Same for due dates, and None isnt written at all so undated tasks save like before
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due_date: Option<Date>,
 }
 
 impl Task {
@@ -168,8 +578,21 @@ impl Task {
             title,
             description,
             completed: false,
+            tags: Vec::new(),
+            due_date: None,
         }
    }
+
+    // Trimmed, empty ones dropped and each tag only once (BTreeSet also sorts them)
+    // so a task never counts twice for the same tag
+    pub fn set_tags(&mut self, tags: Vec<String>) {
+        let unique: BTreeSet<String> = tags
+            .into_iter()
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        self.tags = unique.into_iter().collect();
+    }
     // &[Task] is the default to pass collections as references in Rust way better than
     // passing ownership of the vector
     // Use &[T] slice when only need to read data(looping, searching, etc)
@@ -183,6 +606,20 @@ impl Task {
             .unwrap_or(0) + 1
     }
 
+    // Ids 1..N in slice order, closes the gaps left by removed tasks
+    // Only on request: add keeps using max + 1, so an id never changes behind the user's back
+    // Nothing else stores task ids yet, if something does (subtasks) it has to be remapped here too
+    pub fn renumber(tasks: &mut [Task]) -> usize {
+        let mut changed = 0;
+        for (task, id) in tasks.iter_mut().zip(1..) {
+            if task.id != id {
+                task.id = id;
+                changed += 1;
+            }
+        }
+        changed
+    }
+
     // Find method returns an Options, so we can combine it with an if let pattern
     // so we are checking if it returned Some(task), means we found the task with that id
     // then do something with it
@@ -196,6 +633,194 @@ impl Task {
     }
 }
 
+// Calendar day without a time of day, saved as "YYYY-MM-DD"
+// A date crate would be a big dependency for one field. Counting days since 1970-01-01 makes
+// adding days a plain addition, the conversion from and to year/month/day is the usual
+// civil calendar algorithm (eras of 400 years, years starting in March so the leap day comes last)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Date {
+    days: i64, // Since 1970-01-01
+}
+
+impl Date {
+    // None for days that dont exist, like 2023-02-29
+    pub fn from_ymd(year: i64, month: u32, day: u32) -> Option<Self> {
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return None;
+        }
+        let year_from_march = if month <= 2 { year - 1 } else { year };
+        let era = year_from_march.div_euclid(400);
+        let year_of_era = year_from_march.rem_euclid(400);
+        let month_from_march = (month as i64 + 9) % 12; // March is 0, February 11
+        let day_of_year = (153 * month_from_march + 2) / 5 + day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let date = Self { days: era * 146_097 + day_of_era - 719_468 };
+        // Day 31 of a 30 day month rolls over into the next month, so it doesnt come back the same
+        (date.ymd() == (year, month, day)).then_some(date)
+    }
+
+    // In UTC, a clock set before 1970 counts as 1970-01-01
+    pub fn today() -> Self {
+        let seconds = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        Self::from_unix_seconds(seconds)
+    }
+
+    // Day of a timestamp in seconds since 1970, in UTC
+    pub fn from_unix_seconds(seconds: u64) -> Self {
+        Self { days: (seconds / 86_400) as i64 }
+    }
+
+    pub fn add_days(self, days: i64) -> Self {
+        Self { days: self.days + days }
+    }
+
+    pub fn ymd(self) -> (i64, u32, u32) {
+        let days = self.days + 719_468; // Days since 0000-03-01
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_from_march = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+        let month = ((month_from_march + 2) % 12 + 1) as u32;
+        let year = era * 400 + year_of_era + if month <= 2 { 1 } else { 0 };
+        (year, month, day)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (year, month, day) = self.ymd();
+        write!(f, "{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+impl std::str::FromStr for Date {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid date {:?}, expected YYYY-MM-DD", text);
+        let mut parts = text.trim().splitn(3, '-');
+        let mut next = || parts.next().and_then(|part| part.parse::<i64>().ok());
+        let (year, month, day) = (next().ok_or_else(invalid)?, next().ok_or_else(invalid)?, next().ok_or_else(invalid)?);
+        let month = u32::try_from(month).map_err(|_| invalid())?;
+        let day = u32::try_from(day).map_err(|_| invalid())?;
+        Self::from_ymd(year, month, day).ok_or_else(invalid)
+    }
+}
+
+// For serde, through the same text as Display and FromStr
+impl TryFrom<String> for Date {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+impl From<Date> for String {
+    fn from(date: Date) -> Self {
+        date.to_string()
+    }
+}
+
+// Task counts shown by the stats command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    pub total: usize,
+    pub completed: usize,
+    pub pending: usize,
+}
+
+impl Stats {
+    // 0 when there are no tasks, instead of the NaN we would get from 0 / 0
+    pub fn completed_fraction(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.completed as f64 / self.total as f64
+        }
+    }
+}
+
+// ASCII progress bar like [######----] 60%
+// Fraction is clamped to 0..1, both the filled part and the percentage are rounded to the nearest
+pub fn render_bar(fraction: f64, width: usize) -> String {
+    // clamp keeps NaN as NaN, which would print as NaN%
+    let fraction = if fraction.is_nan() { 0.0 } else { fraction.clamp(0.0, 1.0) };
+    let filled = (fraction * width as f64).round() as usize;
+    format!(
+        "[{}{}] {}%",
+        "#".repeat(filled),
+        "-".repeat(width - filled),
+        (fraction * 100.0).round()
+    )
+}
+
+// Which tasks the list command shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListFilter {
+    All,
+    Completed,
+    Pending,
+}
+
+impl ListFilter {
+    // Build the filter from the list flags, clap already rejects passing both
+    pub fn from_flags(completed: bool, pending: bool) -> Self {
+        match (completed, pending) {
+            (true, false) => ListFilter::Completed,
+            (false, true) => ListFilter::Pending,
+            _ => ListFilter::All,
+        }
+    }
+
+    pub fn matches(&self, task: &Task) -> bool {
+        match self {
+            ListFilter::All => true,
+            ListFilter::Completed => task.completed,
+            ListFilter::Pending => !task.completed,
+        }
+    }
+}
+
+// Status part of a query, --status pending or --status completed (all is the same as leaving it out)
+impl std::str::FromStr for ListFilter {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.trim().to_lowercase().as_str() {
+            "all" => Ok(ListFilter::All),
+            "completed" | "done" => Ok(ListFilter::Completed),
+            "pending" => Ok(ListFilter::Pending),
+            _ => Err(format!("Invalid status {:?}, expected pending, completed or all", text)),
+        }
+    }
+}
+
+// Filters of the query command, a task has to match all of them
+// Each one left as None (or ListFilter::All) matches every task
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct QueryFilter {
+    pub tag: Option<String>,
+    pub status: Option<ListFilter>,
+    // Due strictly before this day, tasks without a due date dont match once it is set
+    pub due_before: Option<Date>,
+}
+
+impl QueryFilter {
+    pub fn matches(&self, task: &Task) -> bool {
+        let tag = self.tag.as_ref().is_none_or(|tag| task.tags.iter().any(|task_tag| task_tag == tag.trim()));
+        let status = self.status.is_none_or(|status| status.matches(task));
+        let due = self.due_before.is_none_or(|before| task.due_date.is_some_and(|due| due < before));
+        tag && status && due
+    }
+}
+
 // Enum Commands holds the different commands for the CLI that we can use
 #[derive(Subcommand)]
 pub enum Commands {
@@ -205,17 +830,66 @@ pub enum Commands {
         title: String,
         /// Description of the task
         description: String,
+        /// Tag for the task, repeat to add several (--tag work --tag urgent)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
     },
     /// List all tasks
-    List,
+    List {
+        /// Only show completed tasks
+        #[arg(long, conflicts_with = "pending")]
+        completed: bool,
+        /// Only show tasks that are not completed yet
+        #[arg(long)]
+        pending: bool,
+    },
     /// Mark a task as completed
     Complete {
-        id: u32,
+        /// Id of the task, not needed with --all
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        id: Option<u32>,
+        /// Mark every pending task as completed
+        #[arg(long)]
+        all: bool,
     },
-    /// Remove a task
+    /// Remove a task, asks first when run in a terminal
     Remove {
         id: u32,
-    }
+        /// Remove without asking
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Show every tag and how many tasks have it
+    Tags,
+    /// Show how many tasks are completed and pending, with a progress bar
+    Stats,
+    /// Renumber the tasks 1..N in list order, closing the gaps left by removed tasks
+    Renumber,
+    /// Push the due date of a task forward, a task without one becomes due that many days from today
+    Defer {
+        id: u32,
+        /// Days to add, negative numbers are parsed so the error can say why they are rejected
+        #[arg(allow_negative_numbers = true)]
+        days: i64,
+    },
+    /// List the tasks matching every given filter, e.g. pending work tasks due before Friday
+    Query {
+        /// Only tasks with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// pending, completed or all
+        #[arg(long)]
+        status: Option<ListFilter>,
+        /// Only tasks due before this day (YYYY-MM-DD), tasks without a due date are left out
+        #[arg(long)]
+        due_before: Option<Date>,
+    },
+    /// Show the latest entries of the audit log (see --audit)
+    Log {
+        /// How many entries to show
+        #[arg(short = 'n', long, default_value_t = 10)]
+        limit: usize,
+    },
 }
 
 // Struct CLI holds the command line arguments of type Commands
@@ -223,8 +897,21 @@ pub enum Commands {
 #[command(name = "todo")]
 #[command(about = "Simple CLI Todo Application")]
 pub struct Cli {
+    // Optional so a bare `todo` runs, main treats None as List
     #[command(subcommand)]
-    pub command: Commands,
+    pub command: Option<Commands>,
+    /// Save the file as single line JSON instead of indented, faster for big lists
+    #[arg(long, global = true)]
+    pub compact: bool,
+    /// Mark completed tasks with [x] instead of [✓], for terminals without UTF-8
+    #[arg(long, global = true)]
+    pub ascii: bool,
+    /// Show what a command would change without saving it
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+    /// Record every change in todo_audit.jsonl next to the todo file (also TODO_AUDIT=1)
+    #[arg(long, global = true)]
+    pub audit: bool,
 }
 
 
@@ -267,7 +954,7 @@ pub fn save_tasks(tasks: &Vec<Task>) -> Result<(), Box<dyn std::error::Error>> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{Task, TodoList, TodoStorage};
+    use crate::{audit_events, is_yes, render_bar, AuditEvent, AuditLog, AuditedStorage, AutoConfirm, Date, Prompt, GlyphSet, JsonFileStorage, ListFilter, OutputFormat, QueryFilter, Stats, Task, TodoList, TodoStorage};
 
     // Mock storage struct for testing purposes
     struct MockStorage {
@@ -314,6 +1001,35 @@ mod tests {
         assert_eq!(Task::find_next_id(&tasks), 4);
     }
 
+    #[test]
+    fn test_renumber_closes_gaps_and_keeps_order() {
+        let initial = vec![
+            Task::new(2, "A".to_string(), "".to_string()),
+            Task::new(5, "B".to_string(), "".to_string()),
+            Task::new(3, "C".to_string(), "".to_string()), // order of the list, not of the ids
+        ];
+        let storage = MockStorage::new(initial);
+        let mut todo_list = TodoList::load(storage).unwrap();
+        // C already has 3, only A and B change
+        assert_eq!(todo_list.renumber().unwrap(), 2);
+        let ids: Vec<(u32, &str)> = todo_list.tasks.iter().map(|task| (task.id, task.title.as_str())).collect();
+        assert_eq!(ids, vec![(1, "A"), (2, "B"), (3, "C")]);
+        assert!(todo_list.storage.was_save_called());
+        assert_eq!(Task::find_next_id(&todo_list.tasks), 4);
+    }
+
+    #[test]
+    fn test_renumber_without_gaps_does_not_save() {
+        let initial = vec![
+            Task::new(1, "A".to_string(), "".to_string()),
+            Task::new(2, "B".to_string(), "".to_string()),
+        ];
+        let storage = MockStorage::new(initial);
+        let mut todo_list = TodoList::load(storage).unwrap();
+        assert_eq!(todo_list.renumber().unwrap(), 0);
+        assert!(!todo_list.storage.was_save_called());
+    }
+
     #[test]
     fn test_load_with_initial_tasks() {
         let initial = vec![Task::new(1, "Test".to_string(), "Desc".to_string())];
@@ -344,6 +1060,86 @@ mod tests {
         assert!(todo_list.storage.was_save_called());
     }
 
+    #[test]
+    fn test_date_text_round_trip() {
+        let date: Date = "2024-02-29".parse().unwrap();
+        assert_eq!(date.ymd(), (2024, 2, 29));
+        assert_eq!(date.to_string(), "2024-02-29");
+        assert_eq!(Date::from_ymd(1970, 1, 1).unwrap().to_string(), "1970-01-01");
+        // Not a leap year, and not dates at all
+        assert!("2023-02-29".parse::<Date>().is_err());
+        assert!("2024-04-31".parse::<Date>().is_err());
+        assert!("tomorrow".parse::<Date>().is_err());
+    }
+
+    #[test]
+    fn test_add_days_crosses_months_and_years() {
+        let date = Date::from_ymd(2023, 12, 30).unwrap();
+        assert_eq!(date.add_days(3).to_string(), "2024-01-02");
+        assert_eq!(Date::from_ymd(2024, 2, 28).unwrap().add_days(1).to_string(), "2024-02-29");
+        assert_eq!(Date::from_ymd(2100, 2, 28).unwrap().add_days(1).to_string(), "2100-03-01");
+        assert_eq!(date.add_days(366).add_days(-366), date);
+    }
+
+    #[test]
+    fn test_defer_moves_existing_due_date() {
+        let today = Date::from_ymd(2025, 6, 10).unwrap();
+        let mut task = Task::new(1, "Taxes".to_string(), "".to_string());
+        task.due_date = Date::from_ymd(2025, 6, 1);
+        let storage = MockStorage::new(vec![task]);
+        let mut todo_list = TodoList::load(storage).unwrap();
+        // From the old due date, not from today, even when that one already passed
+        assert_eq!(todo_list.defer(1, 3, today).unwrap(), Date::from_ymd(2025, 6, 4).unwrap());
+        assert_eq!(todo_list.tasks[0].due_date, Date::from_ymd(2025, 6, 4));
+        assert!(todo_list.storage.was_save_called());
+    }
+
+    #[test]
+    fn test_defer_without_due_date_counts_from_today() {
+        let today = Date::from_ymd(2025, 6, 29).unwrap();
+        let storage = MockStorage::new(vec![Task::new(1, "Call".to_string(), "".to_string())]);
+        let mut todo_list = TodoList::load(storage).unwrap();
+        assert_eq!(todo_list.defer(1, 2, today).unwrap(), Date::from_ymd(2025, 7, 1).unwrap());
+    }
+
+    #[test]
+    fn test_defer_rejects_negative_days_and_unknown_ids() {
+        let today = Date::from_ymd(2025, 6, 10).unwrap();
+        let storage = MockStorage::new(vec![Task::new(1, "Call".to_string(), "".to_string())]);
+        let mut todo_list = TodoList::load(storage).unwrap();
+        assert!(todo_list.defer(1, -1, today).is_err());
+        assert!(todo_list.defer(2, 1, today).is_err());
+        assert_eq!(todo_list.tasks[0].due_date, None);
+        assert!(!todo_list.storage.was_save_called());
+    }
+
+    #[test]
+    fn test_complete_all_with_mixed_tasks() {
+        let mut done = Task::new(2, "Done".to_string(), "".to_string());
+        done.completed = true;
+        let initial = vec![
+            Task::new(1, "A".to_string(), "".to_string()),
+            done,
+            Task::new(3, "B".to_string(), "".to_string()),
+        ];
+        let storage = MockStorage::new(initial);
+        let mut todo_list = TodoList::load(storage).unwrap();
+        // The one already completed doesnt count
+        assert_eq!(todo_list.complete_all().unwrap(), 2);
+        assert!(todo_list.tasks.iter().all(|task| task.completed));
+        assert!(todo_list.storage.was_save_called());
+    }
+
+    #[test]
+    fn test_complete_all_without_pending_does_not_save() {
+        let mut done = Task::new(1, "Done".to_string(), "".to_string());
+        done.completed = true;
+        let storage = MockStorage::new(vec![done]);
+        let mut todo_list = TodoList::load(storage).unwrap();
+        assert_eq!(todo_list.complete_all().unwrap(), 0);
+        assert!(!todo_list.storage.was_save_called());
+    }
+
     #[test]
     fn test_complete_nonexistent_task() {
         let storage = MockStorage::new(vec![]);
@@ -363,6 +1159,284 @@ mod tests {
         assert!(todo_list.storage.was_save_called());
     }
 
+    #[test]
+    fn test_dry_run_changes_memory_but_does_not_save() {
+        let initial = vec![Task::new(1, "Test".to_string(), "Desc".to_string())];
+        let mut todo_list = TodoList::load(MockStorage::new(initial)).unwrap().with_dry_run(true);
+        todo_list.add("New".to_string(), "".to_string()).unwrap();
+        todo_list.complete(1).unwrap();
+        todo_list.defer(2, 3, Date::from_ymd(2024, 1, 1).unwrap()).unwrap();
+        todo_list.remove(1).unwrap();
+        assert_eq!(todo_list.tasks.len(), 1);
+        assert_eq!(todo_list.tasks[0].id, 2);
+        assert!(!todo_list.storage.was_save_called());
+    }
+
+    fn actions(events: &[AuditEvent]) -> Vec<(&str, u32)> {
+        events.iter().map(|event| (event.action.as_str(), event.task_id)).collect()
+    }
+
+    #[test]
+    fn test_audit_events_from_changes() {
+        let before = vec![
+            Task::new(1, "A".to_string(), "".to_string()),
+            Task::new(2, "B".to_string(), "".to_string()),
+        ];
+        let mut after = before.clone();
+        after[0].completed = true;
+        after[1].due_date = Date::from_ymd(2024, 1, 1);
+        after.push(Task::new(3, "C".to_string(), "".to_string()));
+        assert_eq!(actions(&audit_events(&before, &after)), vec![("complete", 1), ("defer", 2), ("add", 3)]);
+
+        assert_eq!(actions(&audit_events(&before, &before[1..])), vec![("remove", 1)]);
+        assert!(audit_events(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn test_audit_events_for_renumber() {
+        let before = vec![
+            Task::new(1, "A".to_string(), "".to_string()),
+            Task::new(4, "B".to_string(), "".to_string()),
+        ];
+        let mut after = before.clone();
+        Task::renumber(&mut after);
+        assert_eq!(actions(&audit_events(&before, &after)), vec![("renumber", 2)]);
+    }
+
+    #[test]
+    fn test_audited_storage_appends_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let todo_path = dir.path().join("todo.json");
+        let log = AuditLog::for_todo_file(todo_path.to_str().unwrap());
+        let storage = AuditedStorage::new(JsonFileStorage::with_path(todo_path.to_str().unwrap()), Some(log));
+
+        let mut todo_list = TodoList::load(storage).unwrap();
+        todo_list.add("A".to_string(), "".to_string()).unwrap();
+        todo_list.complete(1).unwrap();
+
+        assert!(dir.path().join("todo_audit.jsonl").exists());
+        let events = AuditLog::for_todo_file(todo_path.to_str().unwrap()).recent(10).unwrap();
+        assert_eq!(actions(&events), vec![("add", 1), ("complete", 1)]);
+        let events = AuditLog::for_todo_file(todo_path.to_str().unwrap()).recent(1).unwrap();
+        assert_eq!(actions(&events), vec![("complete", 1)]);
+    }
+
+    #[test]
+    fn test_audit_write_failure_does_not_fail_the_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let todo_path = dir.path().join("todo.json");
+        // A directory cant be opened for appending
+        let log = AuditLog::new(dir.path());
+        let storage = AuditedStorage::new(JsonFileStorage::with_path(todo_path.to_str().unwrap()), Some(log));
+
+        let mut todo_list = TodoList::load(storage).unwrap();
+        assert_eq!(todo_list.add("A".to_string(), "".to_string()).unwrap(), 1);
+        assert!(todo_path.exists());
+    }
+
+    #[test]
+    fn test_is_yes() {
+        assert!(is_yes("y\n"));
+        assert!(is_yes(" YES "));
+        assert!(!is_yes("\n")); // Just enter takes the default
+        assert!(!is_yes("n"));
+        assert!(!is_yes("yep"));
+    }
+
+    #[test]
+    fn test_prompt_asks_and_reads_answer() {
+        let mut output = Vec::new();
+        let mut prompt = Prompt::new(std::io::Cursor::new("y\n"), &mut output);
+        assert!(crate::Confirm::confirm(&mut prompt, "Remove task 1?").unwrap());
+        assert_eq!(String::from_utf8(output).unwrap(), "Remove task 1? [y/N] ");
+
+        // Nothing to read, like a closed stdin
+        let mut prompt = Prompt::new(std::io::Cursor::new(""), Vec::new());
+        assert!(!crate::Confirm::confirm(&mut prompt, "Remove task 1?").unwrap());
+    }
+
+    #[test]
+    fn test_remove_confirmed_keeps_task_when_declined() {
+        let initial = vec![Task::new(1, "Test".to_string(), "Desc".to_string())];
+        let mut todo_list = TodoList::load(MockStorage::new(initial)).unwrap();
+        let mut prompt = Prompt::new(std::io::Cursor::new("n\n"), Vec::new());
+        assert!(!todo_list.remove_confirmed(1, &mut prompt).unwrap());
+        assert_eq!(todo_list.tasks.len(), 1);
+        assert!(!todo_list.storage.was_save_called());
+
+        assert!(todo_list.remove_confirmed(1, &mut AutoConfirm).unwrap());
+        assert_eq!(todo_list.tasks.len(), 0);
+        assert!(todo_list.storage.was_save_called());
+    }
+
+    #[test]
+    fn test_remove_confirmed_unknown_id_does_not_ask() {
+        let mut todo_list = TodoList::load(MockStorage::new(vec![])).unwrap();
+        let mut output = Vec::new();
+        let mut prompt = Prompt::new(std::io::Cursor::new("y\n"), &mut output);
+        assert!(todo_list.remove_confirmed(999, &mut prompt).is_err());
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_list_filter_matches() {
+        let pending = Task::new(1, "A".to_string(), "".to_string());
+        let mut completed = Task::new(2, "B".to_string(), "".to_string());
+        completed.completed = true;
+
+        assert!(ListFilter::All.matches(&pending) && ListFilter::All.matches(&completed));
+        assert!(ListFilter::Completed.matches(&completed) && !ListFilter::Completed.matches(&pending));
+        assert!(ListFilter::Pending.matches(&pending) && !ListFilter::Pending.matches(&completed));
+    }
+
+    #[test]
+    fn test_list_filter_from_flags() {
+        assert_eq!(ListFilter::from_flags(false, false), ListFilter::All);
+        assert_eq!(ListFilter::from_flags(true, false), ListFilter::Completed);
+        assert_eq!(ListFilter::from_flags(false, true), ListFilter::Pending);
+    }
+
+    fn tagged(id: u32, tags: &[&str]) -> Task {
+        let mut task = Task::new(id, format!("Task {}", id), "".to_string());
+        task.set_tags(tags.iter().map(|tag| tag.to_string()).collect());
+        task
+    }
+
+    #[test]
+    fn test_tag_counts_with_overlapping_tags() {
+        let initial = vec![
+            tagged(1, &["work", "urgent"]),
+            tagged(2, &["work"]),
+            tagged(3, &["home", "urgent"]),
+            tagged(4, &[]),
+        ];
+        let todo_list = TodoList::load(MockStorage::new(initial)).unwrap();
+
+        let counts = todo_list.tag_counts();
+        let expected = [("home", 1), ("urgent", 2), ("work", 2)];
+        assert_eq!(counts.iter().map(|(tag, count)| (tag.as_str(), *count)).collect::<Vec<_>>(), expected);
+        assert_eq!(todo_list.untagged_count(), 1);
+    }
+
+    #[test]
+    fn test_set_tags_trims_and_deduplicates() {
+        let task = tagged(1, &[" work", "work", "", "home "]);
+        assert_eq!(task.tags, vec!["home", "work"]);
+    }
+
+    #[test]
+    fn test_add_with_tags() {
+        let mut todo_list = TodoList::load(MockStorage::new(vec![])).unwrap();
+        todo_list.add_with_tags("A".to_string(), "".to_string(), vec!["work".to_string()]).unwrap();
+        todo_list.add("B".to_string(), "".to_string()).unwrap();
+        assert_eq!(todo_list.tag_counts().get("work"), Some(&1));
+        assert_eq!(todo_list.untagged_count(), 1);
+    }
+
+    #[test]
+    fn test_query_combines_filters() {
+        let mut done = tagged(2, &["work"]);
+        done.completed = true;
+        let mut due_soon = tagged(3, &["work"]);
+        due_soon.due_date = Date::from_ymd(2026, 3, 2);
+        let mut due_later = tagged(4, &["work"]);
+        due_later.due_date = Date::from_ymd(2026, 3, 20);
+        let initial = vec![tagged(1, &["home"]), done, due_soon, due_later];
+        let todo_list = TodoList::load(MockStorage::new(initial)).unwrap();
+        let ids = |filter: QueryFilter| todo_list.query(&filter).iter().map(|task| task.id).collect::<Vec<_>>();
+
+        // Nothing set matches everything
+        assert_eq!(ids(QueryFilter::default()), vec![1, 2, 3, 4]);
+        assert_eq!(ids(QueryFilter { tag: Some("work".to_string()), ..Default::default() }), vec![2, 3, 4]);
+        let pending_work = QueryFilter {
+            tag: Some("work".to_string()),
+            status: Some(ListFilter::Pending),
+            ..Default::default()
+        };
+        assert_eq!(ids(pending_work.clone()), vec![3, 4]);
+        assert_eq!(ids(QueryFilter { due_before: Date::from_ymd(2026, 3, 9), ..pending_work }), vec![3]);
+        assert_eq!(ids(QueryFilter { tag: Some("garden".to_string()), ..Default::default() }), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn test_list_filter_from_status_text() {
+        assert_eq!("pending".parse(), Ok(ListFilter::Pending));
+        assert_eq!("Completed".parse(), Ok(ListFilter::Completed));
+        assert_eq!("all".parse(), Ok(ListFilter::All));
+        assert!("later".parse::<ListFilter>().is_err());
+    }
+
+    #[test]
+    fn test_json_storage_round_trip_in_both_formats() {
+        let tasks = vec![tagged(1, &["work"]), tagged(2, &[])];
+        for format in [OutputFormat::Pretty, OutputFormat::Compact] {
+            let file = tempfile::NamedTempFile::new().unwrap();
+            let storage = JsonFileStorage::with_path(file.path().to_str().unwrap()).with_format(format);
+
+            // Empty file counts as no tasks
+            assert!(storage.load().unwrap().is_empty());
+            storage.save(&tasks).unwrap();
+            let loaded = storage.load().unwrap();
+            assert_eq!(loaded.len(), 2);
+            assert_eq!(loaded[0].tags, vec!["work"]);
+
+            let lines = std::fs::read_to_string(file.path()).unwrap().lines().count();
+            assert_eq!(lines == 1, format == OutputFormat::Compact);
+        }
+    }
+
+    #[test]
+    fn test_json_storage_missing_file_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = JsonFileStorage::with_path(dir.path().join("missing.json").to_str().unwrap());
+        assert!(storage.load().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_stats_counts_completed_and_pending() {
+        let mut done = Task::new(1, "A".to_string(), "".to_string());
+        done.completed = true;
+        let initial = vec![done, Task::new(2, "B".to_string(), "".to_string()), Task::new(3, "C".to_string(), "".to_string())];
+        let todo_list = TodoList::load(MockStorage::new(initial)).unwrap();
+        assert_eq!(todo_list.stats(), Stats { total: 3, completed: 1, pending: 2 });
+    }
+
+    #[test]
+    fn test_stats_without_tasks() {
+        let todo_list = TodoList::load(MockStorage::new(vec![])).unwrap();
+        let stats = todo_list.stats();
+        assert_eq!(stats.completed_fraction(), 0.0);
+        assert_eq!(render_bar(stats.completed_fraction(), 10), "[----------] 0%");
+    }
+
+    #[test]
+    fn test_render_bar() {
+        assert_eq!(render_bar(0.0, 10), "[----------] 0%");
+        assert_eq!(render_bar(0.5, 10), "[#####-----] 50%");
+        assert_eq!(render_bar(1.0, 10), "[##########] 100%");
+        assert_eq!(render_bar(0.6, 10), "[######----] 60%");
+    }
+
+    #[test]
+    fn test_render_bar_rounds_to_nearest() {
+        // 2 of 3 done: 6.67 cells and 66.67%
+        assert_eq!(render_bar(2.0 / 3.0, 10), "[#######---] 67%");
+        // 1 of 3 done: 3.33 cells and 33.33%
+        assert_eq!(render_bar(1.0 / 3.0, 10), "[###-------] 33%");
+        // Out of range and NaN never panic on the repeat counts
+        assert_eq!(render_bar(1.5, 4), "[####] 100%");
+        assert_eq!(render_bar(-1.0, 4), "[----] 0%");
+        assert_eq!(render_bar(f64::NAN, 4), "[----] 0%");
+    }
+
+    #[test]
+    fn test_glyph_sets() {
+        assert_eq!(GlyphSet::Unicode.status(true), "[✓]");
+        assert_eq!(GlyphSet::Ascii.status(true), "[x]");
+        assert_eq!(GlyphSet::Unicode.status(false), GlyphSet::Ascii.status(false));
+        assert_eq!(GlyphSet::default(), GlyphSet::Unicode);
+    }
+
     #[test]
     fn test_remove_nonexistent_task() {
         let storage = MockStorage::new(vec![]);
@@ -1,32 +1,118 @@
 use todo_cli::*;
 use clap::Parser;
+use std::io::IsTerminal;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Cli::parse();
     // Initialize storage backend (JSON file in this case)
-    let storage = JsonFileStorage::new();
+    // --compact only changes how the file is written, both formats load the same
+    let format = if args.compact { OutputFormat::Compact } else { OutputFormat::Pretty };
+    let storage = JsonFileStorage::new().with_format(format);
+    // Opt in, without --audit or TODO_AUDIT the wrapper only passes the calls through
+    let audit = args.audit || audit_from_env();
+    let log = audit.then(|| AuditLog::for_todo_file(storage.path()));
+    // The log command reads it even when this run doesnt record
+    let audit_log = AuditLog::for_todo_file(storage.path());
+    let storage = AuditedStorage::new(storage, log);
     // Load tasks from file into memory using the storage backend
-    let mut todo_list = TodoList::load(storage)?;
+    let glyphs = if args.ascii { GlyphSet::Ascii } else { GlyphSet::Unicode };
+    // --dry-run runs every command on the loaded list but never saves it
+    let dry_run = args.dry_run;
+    let mut todo_list = TodoList::load(storage)?.with_glyphs(glyphs).with_dry_run(dry_run);
 
-    match args.command {
-        Commands::Add { title, description } => {
+    // No subcommand lists every task
+    let command = args.command.unwrap_or(Commands::List { completed: false, pending: false });
+
+    match command {
+        Commands::Add { title, description, tags } => {
             // Adds task and returns next id
-            let next_id = todo_list.add(title, description)?;
-            println!("Task added successfully with ID: {}", next_id);
+            let next_id = todo_list.add_with_tags(title.clone(), description, tags)?;
+            if dry_run {
+                println!("Would add task: {} with ID: {}", title, next_id);
+            } else {
+                println!("Task added successfully with ID: {}", next_id);
+            }
             Ok(())
         }
-        Commands::List => {
-            todo_list.list();
+        Commands::List { completed, pending } => {
+            todo_list.list_filtered(ListFilter::from_flags(completed, pending));
             Ok(())
         }
-        Commands::Complete { id } => {
+        Commands::Complete { id: Some(id), .. } => {
             todo_list.complete(id)?;
-            println!("Task {} marked as completed", id);
+            if dry_run {
+                println!("Would mark task {} as completed", id);
+            } else {
+                println!("Task {} marked as completed", id);
+            }
+            Ok(())
+        }
+        // clap only lets the id be left out together with --all
+        Commands::Complete { id: None, .. } => {
+            let count = todo_list.complete_all()?;
+            if dry_run {
+                println!("Would mark {} tasks complete", count);
+            } else {
+                println!("Marked {} tasks complete", count);
+            }
+            Ok(())
+        }
+        Commands::Remove { id, yes } => {
+            // Only ask when someone can answer, scripts and pipes go ahead like before
+            // A dry run doesnt remove anything, so there is nothing to confirm
+            let removed = if yes || dry_run || !std::io::stdout().is_terminal() {
+                todo_list.remove_confirmed(id, &mut AutoConfirm)?
+            } else {
+                todo_list.remove_confirmed(id, &mut Prompt::new(std::io::stdin().lock(), std::io::stdout()))?
+            };
+            if dry_run {
+                println!("Would remove task {}", id);
+            } else if removed {
+                println!("Task {} removed successfully", id);
+            } else {
+                println!("Aborted, task {} kept", id);
+            }
+            Ok(())
+        }
+        Commands::Tags => {
+            todo_list.list_tags();
+            Ok(())
+        }
+        Commands::Stats => {
+            todo_list.print_stats();
+            Ok(())
+        }
+        Commands::Renumber => {
+            let changed = todo_list.renumber()?;
+            if dry_run {
+                println!("Would renumber {} task(s)", changed);
+            } else {
+                println!("Renumbered {} task(s)", changed);
+            }
+            Ok(())
+        }
+        Commands::Query { tag, status, due_before } => {
+            let filter = QueryFilter { tag, status, due_before };
+            todo_list.print_tasks(&todo_list.query(&filter));
+            Ok(())
+        }
+        Commands::Log { limit } => {
+            let events = audit_log.recent(limit)?;
+            if events.is_empty() {
+                println!("No audit events yet, run commands with --audit to record them");
+            }
+            for event in events {
+                println!("{}", event);
+            }
             Ok(())
         }
-        Commands::Remove { id } => {
-            todo_list.remove(id)?;
-            println!("Task {} removed successfully", id);
+        Commands::Defer { id, days } => {
+            let due_date = todo_list.defer(id, days, Date::today())?;
+            if dry_run {
+                println!("Would defer task {}, due {}", id, due_date);
+            } else {
+                println!("Task {} deferred, now due {}", id, due_date);
+            }
             Ok(())
         }
     }
@@ -89,4 +89,373 @@ fn test_remove_nonexistent_integration() {
     cmd.env("TODO_FILE", &temp_path);
     cmd.arg("remove").arg("999");
     cmd.assert().failure().stderr(predicate::str::contains("not found"));
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_dry_run_integration() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let temp_path = temp_file.path().to_str().unwrap().to_string();
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("add").arg("Keep me").arg("Desc");
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("--dry-run").arg("add").arg("Preview").arg("Desc");
+    cmd.assert().success().stdout(predicate::str::contains("Would add task: Preview with ID: 2"));
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("remove").arg("1").arg("--dry-run");
+    cmd.assert().success().stdout(predicate::str::contains("Would remove task 1"));
+
+    // Neither change made it to the file
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("list");
+    cmd.assert().success()
+        .stdout(predicate::str::contains("Keep me"))
+        .stdout(predicate::str::contains("Preview").not());
+}
+
+#[test]
+fn test_remove_yes_integration() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let temp_path = temp_file.path().to_str().unwrap().to_string();
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("add").arg("Task to Remove").arg("Desc");
+    cmd.assert().success();
+
+    // stdout is a pipe here so there would be no prompt anyway, -y just has to be accepted
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("remove").arg("1").arg("-y");
+    cmd.assert().success().stdout(predicate::str::contains("Task 1 removed successfully"));
+}
+#[test]
+fn test_list_filters_integration() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let temp_path = temp_file.path().to_str().unwrap().to_string();
+
+    // Setup: one pending and one completed task
+    for title in ["Pending Task", "Done Task"] {
+        let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+        cmd.env("TODO_FILE", &temp_path);
+        cmd.arg("add").arg(title).arg("Desc");
+        cmd.assert().success();
+    }
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("complete").arg("2");
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("list").arg("--pending");
+    cmd.assert().success()
+        .stdout(predicate::str::contains("Pending Task"))
+        .stdout(predicate::str::contains("Done Task").not());
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("list").arg("--completed");
+    cmd.assert().success()
+        .stdout(predicate::str::contains("[✓] ID: 2 - Title: Done Task"))
+        .stdout(predicate::str::contains("Pending Task").not());
+
+    // Both flags at once make no sense
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("list").arg("--completed").arg("--pending");
+    cmd.assert().failure();
+}
+
+#[test]
+fn test_tags_integration() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let temp_path = temp_file.path().to_str().unwrap().to_string();
+
+    // Setup: two work tasks, one of them also urgent, and one without tags
+    let tasks: [(&str, &[&str]); 3] = [("Report", &["work", "urgent"]), ("Meeting", &["work"]), ("Groceries", &[])];
+    for (title, tags) in tasks {
+        let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+        cmd.env("TODO_FILE", &temp_path);
+        cmd.arg("add").arg(title).arg("Desc");
+        for tag in tags {
+            cmd.arg("--tag").arg(tag);
+        }
+        cmd.assert().success();
+    }
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("tags");
+    cmd.assert().success().stdout("urgent: 1\nwork: 2\n(untagged): 1\n");
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("list");
+    cmd.assert().success().stdout(predicate::str::contains("Title: Report | Description: Desc | Tags: urgent, work"));
+}
+
+#[test]
+fn test_no_subcommand_lists_integration() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let temp_path = temp_file.path().to_str().unwrap().to_string();
+
+    // Setup: Add a task
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("add").arg("Default Task").arg("Desc");
+    cmd.assert().success();
+
+    // Bare command behaves like list
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.assert().success().stdout(predicate::str::contains("[ ] ID: 1 - Title: Default Task"));
+
+    // Help still works
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("--help");
+    cmd.assert().success().stdout(predicate::str::contains("Usage"));
+}
+
+#[test]
+fn test_compact_flag_integration() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let temp_path = temp_file.path().to_str().unwrap().to_string();
+
+    // Default output is indented over several lines
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("add").arg("Pretty Task").arg("Desc");
+    cmd.assert().success();
+    assert!(std::fs::read_to_string(&temp_path).unwrap().lines().count() > 1);
+
+    // --compact rewrites it on a single line
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("add").arg("Compact Task").arg("Desc").arg("--compact");
+    cmd.assert().success();
+    assert_eq!(std::fs::read_to_string(&temp_path).unwrap().lines().count(), 1);
+
+    // And still loads like before
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("list");
+    cmd.assert().success().stdout(predicate::str::contains("ID: 2 - Title: Compact Task"));
+}
+
+#[test]
+fn test_stats_integration() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let temp_path = temp_file.path().to_str().unwrap().to_string();
+
+    // No tasks yet, empty bar instead of a division by zero
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("stats");
+    cmd.assert().success().stdout(predicate::str::contains("[--------------------] 0%"));
+
+    // Setup: two tasks, complete one of them
+    for title in ["First", "Second"] {
+        let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+        cmd.env("TODO_FILE", &temp_path);
+        cmd.arg("add").arg(title).arg("Desc");
+        cmd.assert().success();
+    }
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("complete").arg("1");
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("stats");
+    cmd.assert().success().stdout("Total: 2\nCompleted: 1\nPending: 1\n[##########----------] 50%\n");
+}
+
+#[test]
+fn test_ascii_flag_integration() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let temp_path = temp_file.path().to_str().unwrap().to_string();
+
+    // Setup: one completed task
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("add").arg("Done Task").arg("Desc");
+    cmd.assert().success();
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("complete").arg("1");
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("list").arg("--ascii");
+    cmd.assert().success()
+        .stdout(predicate::str::contains("[x] ID: 1 - Title: Done Task"))
+        .stdout(predicate::str::contains("✓").not());
+}
+
+#[test]
+fn test_renumber_integration() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let temp_path = temp_file.path().to_str().unwrap().to_string();
+
+    // Setup: three tasks, remove the first one so the ids start at 2
+    for title in ["First", "Second", "Third"] {
+        let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+        cmd.env("TODO_FILE", &temp_path);
+        cmd.arg("add").arg(title).arg("Desc");
+        cmd.assert().success();
+    }
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("remove").arg("1");
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("renumber");
+    cmd.assert().success().stdout(predicate::str::contains("Renumbered 2 task(s)"));
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("list");
+    cmd.assert().success()
+        .stdout(predicate::str::contains("ID: 1 - Title: Second"))
+        .stdout(predicate::str::contains("ID: 2 - Title: Third"));
+}
+
+#[test]
+fn test_complete_all_integration() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let temp_path = temp_file.path().to_str().unwrap().to_string();
+
+    for title in ["First", "Second", "Third"] {
+        let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+        cmd.env("TODO_FILE", &temp_path);
+        cmd.arg("add").arg(title).arg("Desc");
+        cmd.assert().success();
+    }
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("complete").arg("2");
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("complete").arg("--all");
+    cmd.assert().success().stdout(predicate::str::contains("Marked 2 tasks complete"));
+
+    // Without --all the id is still required
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("complete");
+    cmd.assert().failure();
+}
+
+#[test]
+fn test_defer_integration() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let temp_path = temp_file.path().to_str().unwrap().to_string();
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("add").arg("Taxes").arg("Desc");
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("defer").arg("1").arg("2");
+    cmd.assert().success().stdout(predicate::str::contains("Task 1 deferred, now due"));
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("list");
+    cmd.assert().success().stdout(predicate::str::contains("| Due: "));
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("defer").arg("1").arg("-1");
+    cmd.assert().failure().stderr(predicate::str::contains("Cant defer by -1 days"));
+}
+
+#[test]
+fn test_audit_log_integration() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let temp_path = temp_dir.path().join("todo.json").to_str().unwrap().to_string();
+
+    // Not recorded without --audit
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("add").arg("Quiet").arg("Desc");
+    cmd.assert().success();
+    assert!(!temp_dir.path().join("todo_audit.jsonl").exists());
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("--audit").arg("complete").arg("1");
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("log");
+    cmd.assert().success().stdout(predicate::str::contains("complete task 1"));
+}
+
+#[test]
+fn test_query_integration() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let temp_path = temp_file.path().to_str().unwrap().to_string();
+
+    // Setup: three work tasks (one done, one due in 2 days) and one home task
+    let tasks = [("Report", "work"), ("Meeting", "work"), ("Slides", "work"), ("Groceries", "home")];
+    for (title, tag) in tasks {
+        let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+        cmd.env("TODO_FILE", &temp_path);
+        cmd.arg("add").arg(title).arg("Desc").arg("--tag").arg(tag);
+        cmd.assert().success();
+    }
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("complete").arg("2");
+    cmd.assert().success();
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("defer").arg("3").arg("2");
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("query").arg("--tag").arg("work").arg("--status").arg("pending");
+    cmd.assert().success()
+        .stdout(predicate::str::contains("ID: 1 - Title: Report"))
+        .stdout(predicate::str::contains("ID: 3 - Title: Slides"))
+        .stdout(predicate::str::contains("Meeting").not())
+        .stdout(predicate::str::contains("Groceries").not());
+
+    let next_week = todo_cli::Date::today().add_days(7).to_string();
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("query").arg("--tag").arg("work").arg("--status").arg("pending").arg("--due-before").arg(&next_week);
+    cmd.assert().success()
+        .stdout(predicate::str::contains("Slides"))
+        .stdout(predicate::str::contains("Report").not());
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("query").arg("--tag").arg("garden");
+    cmd.assert().success().stdout("No tasks found.\n");
+
+    let mut cmd = Command::cargo_bin("todo_cli").unwrap();
+    cmd.env("TODO_FILE", &temp_path);
+    cmd.arg("query").arg("--status").arg("later");
+    cmd.assert().failure().stderr(predicate::str::contains("Invalid status"));
+}
@@ -1,18 +1,30 @@
 use std::collections::VecDeque;
 use std::thread;
-use crossbeam_channel::{bounded, Receiver, Sender};
+use crossbeam_channel::{bounded, unbounded, Receiver, RecvTimeoutError, SendTimeoutError, Sender};
 use pixels::{Pixels, SurfaceTexture};
 use winit::application::ApplicationHandler;
 use std::sync::{Arc, Mutex};
 use winit::dpi::LogicalSize;
-use winit::event::{StartCause, WindowEvent};
+use winit::event::{ButtonSource, ElementState, MouseButton, StartCause, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop, ActiveEventLoop};
 use winit::window::{Window, WindowAttributes, WindowId};
-use std::path::Path;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use winit::keyboard::{KeyCode, ModifiersState, PhysicalKey};
 use winit::monitor::Fullscreen;
 
+mod osd;
+mod playlist;
+mod subtitles;
+mod thumbnail;
+mod zoom;
+use subtitles::embedded::SubtitleTrackInfo;
+use subtitles::srt::SubtitleCue;
+use thumbnail::ThumbnailOptions;
+use osd::level_meter::{self, LevelMeter};
+use osd::progress_bar::{self, ProgressBar};
+
 // Important notes:
 // Use of unsafe to cast raw bytes to f32 samples. Look into zerocopy or bytemuck for safer conversions.
 // Sync primitives using Arc Mutex, they can cause problems, SPSC ring buffer could be better for audio buffer.
@@ -21,40 +33,336 @@ use winit::monitor::Fullscreen;
 
 const VIDEO_BUFFER_FRAMES: usize = 60; // Buffer up to 60 video frames (~2 seconds at 30fps)
 const AUDIO_CHANNEL_SIZE: usize = 100; // Channel can hold 100 audio chunks
+// How often the event loop wakes up in audio only mode, there are no redraws to drive it
+const AUDIO_ONLY_TICK: std::time::Duration = std::time::Duration::from_millis(100);
+// How long a decoder waits on a full channel before checking the running flag again
+const SEND_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+// Packets the demux thread can get ahead of each decoder. Generous on purpose: the demuxer
+// blocks on whichever channel is full, so a small video channel could starve the audio
+const PACKET_CHANNEL_SIZE: usize = 512;
+// Played when no input is given on the command line
+const DEFAULT_INPUT: &str = "sample_video.mp4";
+// How far the left and right arrows jump
+const SEEK_STEP_SECS: f64 = 5.0;
+// How long the seek target stays on screen
+const OSD_DURATION: std::time::Duration = std::time::Duration::from_millis(1500);
+// Volume is fixed point so the audio callback can read it from an atomic, 1000 is full volume
+const VOLUME_MAX: u32 = 1000;
+// Up and down arrows change it by 5%
+const VOLUME_STEP: i32 = 50;
+// The volume stays on screen a bit longer than the seek target
+const VOLUME_OSD_DURATION: std::time::Duration = std::time::Duration::from_secs(2);
+// Two left clicks closer together than this toggle fullscreen
+const DOUBLE_CLICK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(400);
+// The progress bar hides when the mouse hasnt moved for this long
+const PROGRESS_BAR_HIDE_DELAY: std::time::Duration = std::time::Duration::from_secs(3);
+// Playback speeds the bracket keys step through, fixed point like the volume: 100 is normal speed
+const PLAYBACK_RATES: [u32; 6] = [25, 50, 100, 150, 200, 400];
+const NORMAL_RATE: u32 = 100;
+// Each press of + or - zooms in or out by this factor, see zoom.rs
+const ZOOM_STEP: f32 = 1.25;
+// Upper bound for --threads, more than that only costs memory (every frame thread keeps its own frames)
+const MAX_DECODE_THREADS: usize = 64;
+// Tries to open a url again after the connection dropped, then the input just ends
+const RECONNECT_ATTEMPTS: u32 = 3;
+const RECONNECT_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+// Network options for url inputs, unknown ones are ignored by protocols that dont have them
+// Socket timeout in microseconds, without it a dead connection blocks the demuxer forever
+const NETWORK_TIMEOUT_US: &str = "10000000";
+// Receive buffer of the rtsp (udp) socket, the default drops packets of high bitrate streams
+const NETWORK_BUFFER_SIZE: &str = "4194304";
+
+// Where the video comes from, the positional argument
+#[derive(Debug, Clone, PartialEq)]
+enum Source {
+    File(PathBuf),
+    // http, https, rtsp or hls, ffmpeg downloads it with its own network protocols
+    Url(String),
+    Stdin, // "-", read through ffmpeg's pipe protocol so we can do: cat video.mkv | vid_player -
+}
+
+impl Source {
+    // "-" and network urls are special, anything else is a file path (even "ftp://..." style
+    // arguments, ffmpeg would otherwise pick a protocol from the part before the colon)
+    fn parse(arg: &str) -> Source {
+        if arg == "-" {
+            return Source::Stdin;
+        }
+        if let Some((scheme, rest)) = arg.split_once("://")
+            && matches!(scheme.to_ascii_lowercase().as_str(), "http" | "https" | "rtsp" | "hls")
+            && !rest.is_empty()
+        {
+            return Source::Url(arg.to_string());
+        }
+        Source::File(PathBuf::from(arg))
+    }
+
+    // ffmpeg protocol a url is read with, None for files and stdin
+    // hls:// is no protocol of ffmpeg (its hls one wants hls+https://), the playlist is fetched
+    // over https and the hls demuxer recognizes it from the content
+    fn protocol(&self) -> Option<String> {
+        let Source::Url(url) = self else {
+            return None;
+        };
+        let scheme = url.split_once("://").map(|(scheme, _)| scheme.to_ascii_lowercase())?;
+        Some(if scheme == "hls" { "https".to_string() } else { scheme })
+    }
+
+    // What format::input gets, ffmpeg picks the protocol from the prefix
+    // File paths get an explicit file: so a name like "clip:1.mp4" isnt read as a protocol
+    fn ffmpeg_input(&self) -> PathBuf {
+        match self {
+            Source::File(path) => {
+                let mut input = std::ffi::OsString::from("file:");
+                input.push(path);
+                PathBuf::from(input)
+            }
+            Source::Url(url) => match url.split_once("://") {
+                Some((scheme, rest)) if scheme.eq_ignore_ascii_case("hls") => PathBuf::from(format!("https://{}", rest)),
+                _ => PathBuf::from(url),
+            },
+            Source::Stdin => PathBuf::from("pipe:0"),
+        }
+    }
+
+    // Stdin can only be read once, so the input has to be opened a single time and shared
+    // That is why one demux thread feeds both decoders, see spawn_demuxer
+    // Urls get the network options, http reconnects on its own for drops in the middle of a
+    // request, what it cant recover from the demuxer retries (see reconnect)
+    fn open(&self) -> Result<ffmpeg_next::format::context::Input, ffmpeg_next::Error> {
+        let Source::Url(_) = self else {
+            return ffmpeg_next::format::input(&self.ffmpeg_input());
+        };
+        let mut options = ffmpeg_next::Dictionary::new();
+        options.set("reconnect", "1");
+        options.set("reconnect_streamed", "1");
+        options.set("rw_timeout", NETWORK_TIMEOUT_US);
+        options.set("timeout", NETWORK_TIMEOUT_US); // rtsp has its own name for it
+        options.set("buffer_size", NETWORK_BUFFER_SIZE);
+        ffmpeg_next::format::input_with_dictionary(&self.ffmpeg_input(), options)
+    }
+}
+
+// Protocols this ffmpeg build can read from, they depend on how it was configured
+// e.g. https is only there when ffmpeg was built with a TLS library (openssl, gnutls, ...)
+// Print them with --protocols
+fn input_protocols() -> Vec<String> {
+    let mut protocols = Vec::new();
+    let mut opaque = std::ptr::null_mut();
+    loop {
+        // 0 asks for input protocols, the opaque pointer keeps the position between calls
+        let name = unsafe { ffmpeg_next::ffi::avio_enum_protocols(&mut opaque, 0) };
+        if name.is_null() {
+            break;
+        }
+        protocols.push(unsafe { std::ffi::CStr::from_ptr(name) }.to_string_lossy().into_owned());
+    }
+    protocols
+}
+
+// Command line options
+struct Options {
+    source: Source, // File path, network url or - for stdin, DEFAULT_INPUT when missing
+    max_height: Option<u32>, // --max-height <pixels>, taller videos are scaled down to it
+    no_video: bool, // --no-video, only play the audio, no window and no video decoding
+    channels: Option<u16>, // --channels <n>, 1 downmixes to mono. None: stereo, or mono on a mono device
+    hw_decode: bool, // --hw-decode, decode the video on the GPU when the platform accelerator can
+    threads: Option<usize>, // --threads <n>, video decoder threads. None: auto, one per core
+    list_protocols: bool, // --protocols, print the input protocols ffmpeg was built with and exit
+    thumbnail: Option<ThumbnailOptions>, // --thumbnail, save PNGs instead of playing, see thumbnail.rs
+}
+
+// One stream of the input, the decoder thread gets its packets from the demux thread
+struct Track {
+    // Made from the stream parameters, a copy that doesnt point into the input context
+    codec: ffmpeg_next::codec::context::Context,
+    // What codec was made from, a hardware decoder needs its own context
+    parameters: ffmpeg_next::codec::Parameters,
+    time_base: ffmpeg_next::Rational,
+    packets: Receiver<TrackMessage>,
+}
+
+// One of the audio streams of the input, the digit keys switch between them
+struct AudioTrackInfo {
+    stream_index: usize,
+    language: String, // From the stream metadata, "und" when the file doesnt say
+    codec: String,
+    // Copies, so a decoder for the stream can be made after the demux thread took the input
+    parameters: ffmpeg_next::codec::Parameters,
+    time_base: ffmpeg_next::Rational,
+}
+
+impl AudioTrackInfo {
+    fn new(stream: &ffmpeg_next::Stream) -> Self {
+        let parameters = stream.parameters();
+        Self {
+            stream_index: stream.index(),
+            language: stream.metadata().get("language").unwrap_or("und").to_string(),
+            codec: parameters.id().name().to_string(),
+            parameters: parameters.clone(),
+            time_base: stream.time_base(),
+        }
+    }
+
+    fn open(&self) -> (Track, (usize, Sender<TrackMessage>)) {
+        open_route(self.parameters.clone(), self.time_base, self.stream_index)
+    }
+}
+
+// Route change for the demux thread when another audio or subtitle track is picked
+// The route of old_stream goes away, dropping its sender closes the channel and that ends the
+// old decoder. Either can be None: turning subtitles on only adds one, turning them off only removes
+// The new route gets a Seek to generation before its first packet. The seeks come on their own
+// channel, so the decoder cant count on seeing the one that was sent for the switch
+struct RouteCmd {
+    old_stream: Option<usize>,
+    route: Option<(usize, Sender<TrackMessage>)>,
+    generation: u64,
+}
+
+// Seeking
+// Every seek gets a new generation number (App::seek_generation). The demux thread jumps, tells
+// each decoder to flush and from then on the decoders tag what they decode with the new number.
+// Frames and audio chunks already on their way carry the old number and are dropped at the end
+// of the pipeline, so nothing has to reach into the channels to empty them.
+
+// Jump request from the window to the demux thread
+struct SeekCmd {
+    target_secs: f64,
+    generation: u64,
+}
+
+// What the demux thread sends each decoder
+enum TrackMessage {
+    Packet(ffmpeg_next::Packet),
+    // The input jumped: drop what the decoder still holds, tag what comes next with this generation
+    Seek(u64),
+    // End of input, drain the decoder. A seek back can still follow, so the channel stays open
+    Eof,
+}
 
 // Video frame with timestamp
 struct VideoFrame {
     pts: f64,
     data: Vec<u8>,
+    generation: u64,
 }
 
 // Audio chunk with timestamp
+// A chunk without samples marks the end of the track, the decoder never sends empty ones otherwise
 struct AudioChunk {
     pts: f64,
-    samples: Vec<f32>, // Stereo interleaved
+    samples: Vec<f32>, // Interleaved, AudioClock::channels per frame
+    generation: u64,
 }
 
 // Thread-safe audio clock tracking playback position
+// At another playback rate the decoder resamples the audio to fewer (faster) or more (slower)
+// samples, so every sample played stands for rate / 100 samples of the media. The rate only
+// changes together with a seek (App::change_rate), set_time then restarts the count at the new rate.
+// samples_played counts frames (one sample per channel), the channel count only matters when
+// going from time to interleaved samples
 struct AudioClock {
     samples_played: AtomicU64,
     sample_rate: u32,
+    channels: u16, // What the decoder resamples to, see App::start_audio
+    playback_rate: Arc<AtomicU32>,
 }
 
 impl AudioClock {
-    fn new(sample_rate: u32) -> Self {
+    fn new(sample_rate: u32, channels: u16, playback_rate: Arc<AtomicU32>) -> Self {
         Self {
             samples_played: AtomicU64::new(0),
             sample_rate,
+            channels,
+            playback_rate,
         }
     }
 
+    // Sample rate of the media time, how many played samples make one second of the video
+    fn media_sample_rate(&self) -> f64 {
+        self.sample_rate as f64 * NORMAL_RATE as f64 / self.playback_rate.load(Ordering::Acquire) as f64
+    }
+
     fn current_time(&self) -> f64 {
-        self.samples_played.load(Ordering::Acquire) as f64 / self.sample_rate as f64
+        self.samples_played.load(Ordering::Acquire) as f64 / self.media_sample_rate()
     }
 
     fn advance(&self, frames: u64) {
         self.samples_played.fetch_add(frames, Ordering::Release);
     }
+
+    // Jump the clock to a time in seconds, the next advance counts from there
+    fn set_time(&self, secs: f64) {
+        let samples = (secs * self.media_sample_rate()) as u64;
+        self.samples_played.store(samples, Ordering::Release);
+    }
+
+    // Interleaved samples played in this many seconds of the media
+    fn interleaved_samples(&self, secs: f64) -> usize {
+        (secs * self.media_sample_rate()) as usize * self.channels as usize
+    }
+}
+
+// Which subtitles are shown, S cycles off, the .srt file (when there is one) and each
+// subtitle stream of the input
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SubtitleSelection {
+    Off,
+    File,
+    Embedded(usize), // Index into App::subtitle_tracks
+}
+
+impl SubtitleSelection {
+    fn next(self, has_file: bool, embedded_tracks: usize) -> Self {
+        let first_embedded = if embedded_tracks > 0 { Self::Embedded(0) } else { Self::Off };
+        match self {
+            Self::Off if has_file => Self::File,
+            Self::Off | Self::File => first_embedded,
+            Self::Embedded(index) if index + 1 < embedded_tracks => Self::Embedded(index + 1),
+            Self::Embedded(_) => Self::Off,
+        }
+    }
+}
+
+// What happens at the end of the input, L cycles through them
+// Looping restarts through App::seek like a jump back by hand, the demuxer already waits for a
+// seek at the end and the clock, buffers and generation are reset the same way
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LoopMode {
+    NoLoop,
+    LoopFile,
+}
+
+impl LoopMode {
+    fn next(self) -> Self {
+        match self {
+            LoopMode::NoLoop => LoopMode::LoopFile,
+            LoopMode::LoopFile => LoopMode::NoLoop,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            LoopMode::NoLoop => "LOOP OFF",
+            LoopMode::LoopFile => "LOOP FILE",
+        }
+    }
+}
+
+// Space toggles between playing and paused
+// The audio clock drives the video, so pausing the cpal stream freezes both
+struct PlaybackState {
+    is_playing: bool,
+    // Clock time when we paused, the clock is put back to it on resume in case the stream
+    // played (and counted) a few more samples after we asked it to pause
+    paused_at_audio_time: f64,
+}
+
+impl Default for PlaybackState {
+    fn default() -> Self {
+        Self { is_playing: true, paused_at_audio_time: 0.0 }
+    }
 }
 
 // Blocking ring buffer for audio samples
@@ -100,6 +408,12 @@ impl AudioRingBuffer {
         to_write
     }
 
+    // Forget everything buffered, used when seeking
+    fn clear(&mut self) {
+        self.read_pos = self.write_pos;
+        self.filled = 0;
+    }
+
     // Read samples from ring buffer
     fn read(&mut self, output: &mut [f32]) -> usize {
         let to_read = output.len().min(self.available());
@@ -119,49 +433,396 @@ impl AudioRingBuffer {
     }
 }
 
+// Backpressure contract for the decoder threads
+// The channels are bounded, so a decoder that gets ahead of playback waits in send until the
+// consumer takes something out. That waiting is the whole point, it keeps memory bounded.
+// But a plain send waits forever if the consumer stops reading without dropping the receiver
+// (window closed, Ctrl-C, audio device gone), so every send site goes through send_or_stop:
+//   - waits while the channel is full, checking the running flag every SEND_POLL_INTERVAL
+//   - returns true once the item is in the channel
+//   - returns false if running was cleared or the receiver was dropped, the caller must then
+//     stop decoding and return, including in the EOF drain loops
+// Nothing is ever dropped silently while running, try_send is not used on the decoder side.
+fn send_or_stop<T>(sender: &Sender<T>, mut item: T, running: &AtomicBool) -> bool {
+    loop {
+        if !running.load(Ordering::Acquire) {
+            return false;
+        }
+        match sender.send_timeout(item, SEND_POLL_INTERVAL) {
+            Ok(()) => return true,
+            Err(SendTimeoutError::Timeout(unsent)) => item = unsent, // Still full, try again
+            Err(SendTimeoutError::Disconnected(_)) => return false, // Receiver dropped
+        }
+    }
+}
+
+// Index into App::audio_tracks for the digit keys, Digit1 is the first track
+fn audio_track_key(code: KeyCode) -> Option<usize> {
+    const KEYS: [KeyCode; 9] = [
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+        KeyCode::Digit8,
+        KeyCode::Digit9,
+    ];
+    KEYS.iter().position(|&key| key == code)
+}
+
+// Decoder setup for one stream and the route the demux thread sends its packets through
+fn open_track(stream: &ffmpeg_next::Stream) -> (Track, (usize, Sender<TrackMessage>)) {
+    open_route(stream.parameters(), stream.time_base(), stream.index())
+}
+
+fn open_route(
+    parameters: ffmpeg_next::codec::Parameters,
+    time_base: ffmpeg_next::Rational,
+    index: usize,
+) -> (Track, (usize, Sender<TrackMessage>)) {
+    let codec = ffmpeg_next::codec::context::Context::from_parameters(parameters.clone()).unwrap();
+    let (sender, packets) = bounded(PACKET_CHANNEL_SIZE);
+    (Track { codec, parameters, time_base, packets }, (index, sender))
+}
+
+// Single thread reading the input, each packet goes to the decoder of its stream
+// Streams nobody decodes (subtitles, the video with --no-video) are dropped here
+// Runs until shutdown, after the end of the input it waits for a seek back
+// An end of a url input that comes before its duration (or any end of a live stream) is taken
+// as a dropped connection, the url is opened again before the decoders get the Eof
+fn spawn_demuxer(
+    mut input_ctx: ffmpeg_next::format::context::Input,
+    source: Source,
+    mut routes: Vec<(usize, Sender<TrackMessage>)>,
+    seeks: Receiver<SeekCmd>,
+    route_changes: Receiver<RouteCmd>,
+    running: Arc<AtomicBool>,
+) {
+    thread::Builder::new()
+        .name("demuxer".to_string())
+        .spawn(move || {
+            let mut at_eof = false;
+            // Time of the newest packet read, where a reconnect continues
+            let mut position_secs = 0.0;
+            // Reconnects since the last packet, a url that opens but ends right away gives up too
+            let mut reconnects = 0;
+            loop {
+                if !running.load(Ordering::Acquire) {
+                    return;
+                }
+
+                // Another audio or subtitle track was picked, packets of the old stream are dropped from now on
+                for cmd in route_changes.try_iter() {
+                    if let Some(old_stream) = cmd.old_stream {
+                        routes.retain(|(index, _)| *index != old_stream);
+                    }
+                    if let Some((index, sender)) = cmd.route {
+                        if !send_or_stop(&sender, TrackMessage::Seek(cmd.generation), &running) {
+                            return;
+                        }
+                        routes.push((index, sender));
+                    }
+                }
+
+                // Nothing left to read at the end, block on the seek channel instead of spinning
+                let waited = if at_eof {
+                    match seeks.recv_timeout(SEND_POLL_INTERVAL) {
+                        Ok(seek) => Some(seek),
+                        Err(RecvTimeoutError::Timeout) => continue,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                } else {
+                    None
+                };
+                // Several quick presses, only the last one matters
+                if let Some(seek) = waited.into_iter().chain(seeks.try_iter()).last() {
+                    // Lands on the keyframe at or before the target, decoding has to start on one
+                    let ts = (seek.target_secs * ffmpeg_next::ffi::AV_TIME_BASE as f64) as i64;
+                    if let Err(e) = input_ctx.seek(ts, ..ts) {
+                        // The decoders still switch generation, playback goes on from here
+                        eprintln!("Seek failed: {}", e);
+                    }
+                    for (_, sender) in &routes {
+                        if !send_or_stop(sender, TrackMessage::Seek(seek.generation), &running) {
+                            return;
+                        }
+                    }
+                    at_eof = false;
+                    position_secs = seek.target_secs;
+                }
+
+                let mut packet = ffmpeg_next::Packet::empty();
+                match packet.read(&mut input_ctx) {
+                    Ok(()) => {
+                        reconnects = 0;
+                        if let (Some(pts), Some(stream)) = (packet.pts(), input_ctx.stream(packet.stream())) {
+                            position_secs = f64::max(position_secs, pts as f64 * f64::from(stream.time_base()));
+                        }
+                        let Some((_, sender)) = routes.iter().find(|(index, _)| *index == packet.stream()) else {
+                            continue;
+                        };
+                        if !send_or_stop(sender, TrackMessage::Packet(packet), &running) {
+                            return;
+                        }
+                    }
+                    Err(ffmpeg_next::Error::Eof) => {
+                        if matches!(source, Source::Url(_)) && !reached_end(&input_ctx, position_secs) {
+                            if reconnects < RECONNECT_ATTEMPTS {
+                                // A failed open leaves the old input, reading it ends again and tries the next attempt
+                                reconnects += 1;
+                                if let Some(reopened) = reconnect(&source, position_secs, reconnects, &running) {
+                                    input_ctx = reopened;
+                                }
+                                continue;
+                            }
+                            eprintln!("Giving up on {} after {} attempts", source.ffmpeg_input().display(), RECONNECT_ATTEMPTS);
+                        }
+                        for (_, sender) in &routes {
+                            if !send_or_stop(sender, TrackMessage::Eof, &running) {
+                                return;
+                            }
+                        }
+                        at_eof = true;
+                    }
+                    // Broken packet, skipped like input_ctx.packets() does
+                    Err(_) => {}
+                }
+            }
+        })
+        .expect("Failed to spawn demuxer thread");
+}
+
+// Eof is the real end when the input has a duration and the last packet was close to it
+// Live streams have none, for them an end always means the connection dropped
+fn reached_end(input_ctx: &ffmpeg_next::format::context::Input, position_secs: f64) -> bool {
+    const END_TOLERANCE_SECS: f64 = 1.0;
+    let duration = input_ctx.duration();
+    duration > 0 && position_secs + END_TOLERANCE_SECS >= duration as f64 / ffmpeg_next::ffi::AV_TIME_BASE as f64
+}
+
+// Open the url again after RECONNECT_DELAY, one attempt of the RECONNECT_ATTEMPTS the demuxer allows
+// Inputs with a duration continue at position_secs, live ones from wherever the stream is now
+// The streams are expected in the same order as before, the routes keep their indices
+fn reconnect(
+    source: &Source,
+    position_secs: f64,
+    attempt: u32,
+    running: &AtomicBool,
+) -> Option<ffmpeg_next::format::context::Input> {
+    thread::sleep(RECONNECT_DELAY);
+    if !running.load(Ordering::Acquire) {
+        return None;
+    }
+    eprintln!("Connection lost at {:.1}s, reconnecting ({}/{})", position_secs, attempt, RECONNECT_ATTEMPTS);
+    match source.open() {
+        Ok(mut input_ctx) => {
+            if input_ctx.duration() > 0 {
+                let ts = (position_secs * ffmpeg_next::ffi::AV_TIME_BASE as f64) as i64;
+                if let Err(e) = input_ctx.seek(ts, ..ts) {
+                    eprintln!("Cant continue at {:.1}s, starting over: {}", position_secs, e);
+                }
+            }
+            Some(input_ctx)
+        }
+        Err(e) => {
+            eprintln!("Reconnect failed: {}", e);
+            None
+        }
+    }
+}
+
+// Hardware decoding (--hw-decode): ffmpeg decodes on the GPU through the accelerator of the platform
+// and the frames stay in video memory. They are copied back before the scaler, which only reads
+// normal frames. Saves CPU on big videos, the copy back costs a bit of it again.
+// Anything that doesnt work (ffmpeg built without it, no driver, codec not supported) falls back
+// to decoding in software.
+
+// av_hwdevice_find_type_by_name name of the accelerator this platform usually has
+fn platform_hw_accel() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "videotoolbox"
+    } else if cfg!(target_os = "windows") {
+        "dxva2"
+    } else {
+        "vaapi"
+    }
+}
+
+// AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX, the decoder works with a device made by av_hwdevice_ctx_create
+// bindgen puts it in an unnamed enum, so it is repeated here
+const HW_CONFIG_METHOD_DEVICE_CTX: i32 = 0x01;
+
+// How the video decode thread sets up its decoder
+#[derive(Clone, Copy)]
+struct VideoDecodeSetup {
+    hw_decode: bool,
+    threads: Option<usize>,
+}
+
+// Thread setup for a decoder, has to be set on the context before it is opened
+// Frame threading decodes that many frames at once, each thread a frame of its own. Throughput
+// goes up on big videos (4K H.264/HEVC), at the cost of a few frames of delay in the decoder.
+// A count of 0 is auto, ffmpeg picks one thread per core. libavcodec itself defaults to 1.
+fn decoder_threading(threads: Option<usize>) -> ffmpeg_next::codec::threading::Config {
+    let mut config = ffmpeg_next::codec::threading::Config::count(threads.unwrap_or(0));
+    config.kind = ffmpeg_next::codec::threading::Type::Frame;
+    config
+}
+
+// Decoder with a hardware device attached, None (and why on stderr) when that cant be done
+fn try_init_hardware_decoder(
+    codec_params: ffmpeg_next::codec::Parameters,
+    accel_type: &str,
+    threading: ffmpeg_next::codec::threading::Config,
+) -> Option<ffmpeg_next::codec::decoder::Video> {
+    let name = std::ffi::CString::new(accel_type).ok()?;
+    let device_type = unsafe { ffmpeg_next::ffi::av_hwdevice_find_type_by_name(name.as_ptr()) };
+    if device_type == ffmpeg_next::ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_NONE {
+        eprintln!("This ffmpeg was built without {}", accel_type);
+        return None;
+    }
+
+    // The decoder lists the devices it can use, without a match it would quietly decode in software
+    let codec = ffmpeg_next::decoder::find(codec_params.id())?;
+    let supported = (0..)
+        .map_while(|i| unsafe { ffmpeg_next::ffi::avcodec_get_hw_config(codec.as_ptr(), i).as_ref() })
+        .any(|config| config.device_type == device_type && config.methods & HW_CONFIG_METHOD_DEVICE_CTX != 0);
+    if !supported {
+        eprintln!("{} cant decode {} in hardware", accel_type, codec.name());
+        return None;
+    }
+
+    let mut context = ffmpeg_next::codec::context::Context::from_parameters(codec_params).ok()?;
+    let mut device = std::ptr::null_mut();
+    let result = unsafe {
+        ffmpeg_next::ffi::av_hwdevice_ctx_create(&mut device, device_type, std::ptr::null(), std::ptr::null_mut(), 0)
+    };
+    if result < 0 {
+        eprintln!("Cant open the {} device: {}", accel_type, ffmpeg_next::Error::from(result));
+        return None;
+    }
+    // The context owns that reference now, avcodec_free_context releases it
+    // With a device set ffmpeg picks the hardware pixel format on its own (avcodec_default_get_format)
+    unsafe { (*context.as_mut_ptr()).hw_device_ctx = device };
+    context.set_threading(threading);
+    context.decoder().video().ok()
+}
+
+// Decoded frame to RGBA at the target size. Hardware frames (hw_frames_ctx set) are copied to
+// memory first with av_hwframe_transfer_data, usually as NV12.
+// The scaler is made for the first frame and again whenever format or size change, only then is
+// the format of a hardware decoder known
+fn to_rgba_frame(
+    frame: &ffmpeg_next::util::frame::Video,
+    scaler: &mut Option<ffmpeg_next::software::scaling::Context>,
+    target_width: u32,
+    target_height: u32,
+) -> Option<ffmpeg_next::util::frame::Video> {
+    let mut downloaded = None;
+    if unsafe { !(*frame.as_ptr()).hw_frames_ctx.is_null() } {
+        let mut software_frame = ffmpeg_next::util::frame::Video::empty();
+        if unsafe { ffmpeg_next::ffi::av_hwframe_transfer_data(software_frame.as_mut_ptr(), frame.as_ptr(), 0) } < 0 {
+            return None;
+        }
+        downloaded = Some(software_frame);
+    }
+    let source = downloaded.as_ref().unwrap_or(frame);
+
+    let matches = |scaler: &ffmpeg_next::software::scaling::Context| {
+        let input = scaler.input();
+        input.format == source.format() && input.width == source.width() && input.height == source.height()
+    };
+    if !scaler.as_ref().is_some_and(matches) {
+        *scaler = ffmpeg_next::software::scaling::Context::get(
+            source.format(),
+            source.width(),
+            source.height(),
+            ffmpeg_next::format::Pixel::RGBA,
+            target_width,
+            target_height,
+            ffmpeg_next::software::scaling::flag::Flags::BILINEAR,
+        ).ok();
+    }
+
+    let mut rgb_frame = ffmpeg_next::util::frame::Video::empty();
+    scaler.as_mut()?.run(source, &mut rgb_frame).ok()?;
+    Some(rgb_frame)
+}
+
 // Separate thread for video decoding
 fn spawn_video_decoder(
-    video_path: &Path,
+    track: Track,
     sender: Sender<VideoFrame>,
     target_width: u32,
     target_height: u32,
+    setup: VideoDecodeSetup,
+    seek_generation: Arc<AtomicU64>,
+    running: Arc<AtomicBool>,
 ) {
-    let path = video_path.to_owned();
-
     thread::Builder::new()
         .name("video-decoder".to_string())
         .spawn(move || {
-            ffmpeg_next::init().unwrap();
+            let time_base = track.time_base;
+            let threading = decoder_threading(setup.threads);
+            let hardware_decoder = if setup.hw_decode {
+                try_init_hardware_decoder(track.parameters, platform_hw_accel(), threading)
+            } else {
+                None
+            };
+            let mut decoder = match hardware_decoder {
+                Some(decoder) => {
+                    println!("Video decoding: hardware ({})", platform_hw_accel());
+                    decoder
+                }
+                None => {
+                    println!("Video decoding: software");
+                    let mut codec = track.codec;
+                    codec.set_threading(threading);
+                    codec.decoder().video().unwrap()
+                }
+            };
+            // What ffmpeg went with, auto resolved to a count and the type the codec supports
+            let active = decoder.threading();
+            println!("Video decode threads: {} ({:?})", active.count, active.kind);
+            let mut generation = 0;
+            let mut scaler = None;
+
+            // Decode the video packets, ends at shutdown (the demuxer keeps the channel open)
+            for message in track.packets.iter() {
+                // Shutdown requested, stop decoding
+                if !running.load(Ordering::Acquire) {
+                    return;
+                }
 
-            let mut input_ctx = ffmpeg_next::format::input(&path)
-                .expect("Failed to open video file");
+                let packet = match message {
+                    TrackMessage::Packet(packet) => packet,
+                    TrackMessage::Seek(next) => {
+                        // Frames of the old position still inside the decoder
+                        decoder.flush();
+                        generation = next;
+                        continue;
+                    }
+                    TrackMessage::Eof => {
+                        // Drain decoder
+                        let _ = decoder.send_eof();
+                        let mut frame = ffmpeg_next::util::frame::Video::empty();
+                        while decoder.receive_frame(&mut frame).is_ok() {
+                            if let Some(rgb_frame) = to_rgba_frame(&frame, &mut scaler, target_width, target_height) {
+                                let pts = frame.pts().unwrap_or(0) as f64 * f64::from(time_base);
+                                let data = extract_rgba_data(&rgb_frame, target_width, target_height);
+                                if !send_or_stop(&sender, VideoFrame { pts, data, generation }, &running) {
+                                    return;
+                                }
+                            }
+                        }
+                        continue;
+                    }
+                };
 
-            let video_stream = input_ctx
-                .streams()
-                .best(ffmpeg_next::media::Type::Video)
-                .expect("No video stream");
-
-            let video_idx = video_stream.index();
-            let time_base = video_stream.time_base();
-
-            let ctx = ffmpeg_next::codec::context::Context::from_parameters(
-                video_stream.parameters()
-            ).unwrap();
-            let mut decoder = ctx.decoder().video().unwrap();
-
-            let mut scaler = ffmpeg_next::software::scaling::Context::get(
-                decoder.format(),
-                decoder.width(),
-                decoder.height(),
-                ffmpeg_next::format::Pixel::RGBA,
-                target_width,
-                target_height,
-                ffmpeg_next::software::scaling::flag::Flags::BILINEAR,
-            ).unwrap();
-
-            // Demux and decode video packets
-            for (stream, packet) in input_ctx.packets() {
-                if stream.index() != video_idx {
+                // A seek is on its way, this packet is from the old position and not worth decoding
+                if generation != seek_generation.load(Ordering::Acquire) {
                     continue;
                 }
 
@@ -171,77 +832,109 @@ fn spawn_video_decoder(
 
                 let mut frame = ffmpeg_next::util::frame::Video::empty();
                 while decoder.receive_frame(&mut frame).is_ok() {
-                    let mut rgb_frame = ffmpeg_next::util::frame::Video::empty();
-                    if scaler.run(&frame, &mut rgb_frame).is_err() {
+                    let Some(rgb_frame) = to_rgba_frame(&frame, &mut scaler, target_width, target_height) else {
                         continue;
-                    }
+                    };
 
                     let pts = frame.pts().unwrap_or(0) as f64 * f64::from(time_base);
                     let data = extract_rgba_data(&rgb_frame, target_width, target_height);
 
-                    // This blocks if channel is full (backpressure)
-                    if sender.send(VideoFrame { pts, data }).is_err() {
-                        return; // Receiver dropped
+                    // Waits while the channel is full (backpressure), see send_or_stop
+                    if !send_or_stop(&sender, VideoFrame { pts, data, generation }, &running) {
+                        return;
                     }
                 }
             }
-
-            // Drain decoder
-            let _ = decoder.send_eof();
-            let mut frame = ffmpeg_next::util::frame::Video::empty();
-            while decoder.receive_frame(&mut frame).is_ok() {
-                let mut rgb_frame = ffmpeg_next::util::frame::Video::empty();
-                if scaler.run(&frame, &mut rgb_frame).is_ok() {
-                    let pts = frame.pts().unwrap_or(0) as f64 * f64::from(time_base);
-                    let data = extract_rgba_data(&rgb_frame, target_width, target_height);
-                    let _ = sender.send(VideoFrame { pts, data });
-                }
-            }
         })
         .expect("Failed to spawn video decoder thread");
 }
 
 // Separate thread for audio decoding
 fn spawn_audio_decoder(
-    video_path: &Path,
+    track: Track,
     sender: Sender<AudioChunk>,
     target_sample_rate: u32,
+    target_channels: u16,
+    playback_rate: Arc<AtomicU32>,
+    seek_generation: Arc<AtomicU64>,
+    running: Arc<AtomicBool>,
 ) {
-    let path = video_path.to_owned();
-
     thread::Builder::new()
         .name("audio-decoder".to_string())
         .spawn(move || {
-            ffmpeg_next::init().unwrap();
+            let time_base = track.time_base;
+            let mut decoder = track.codec.decoder().audio().unwrap();
+            let mut generation = 0;
+
+            // Faster playback resamples to a lower rate than the device plays at: at 2x a second
+            // of audio becomes half a second of samples. Like speeding up a tape, the pitch goes up too
+            let mut rate = playback_rate.load(Ordering::Acquire);
+            let resampler_for = |decoder: &ffmpeg_next::decoder::Audio, rate: u32| {
+                ffmpeg_next::software::resampling::Context::get(
+                    decoder.format(),
+                    decoder.channel_layout(),
+                    decoder.rate(),
+                    ffmpeg_next::format::Sample::F32(ffmpeg_next::format::sample::Type::Packed),
+                    ffmpeg_next::channel_layout::ChannelLayout::default(target_channels as i32),
+                    (target_sample_rate as u64 * NORMAL_RATE as u64 / rate as u64) as u32,
+                ).unwrap()
+            };
+            let mut resampler = resampler_for(&decoder, rate);
+
+            // Decode the audio packets, ends at shutdown (the demuxer keeps the channel open)
+            for message in track.packets.iter() {
+                if !running.load(Ordering::Acquire) {
+                    return;
+                }
 
-            let mut input_ctx = ffmpeg_next::format::input(&path)
-                .expect("Failed to open audio file");
+                let packet = match message {
+                    TrackMessage::Packet(packet) => packet,
+                    TrackMessage::Seek(next) => {
+                        decoder.flush();
+                        generation = next;
+                        // A rate change comes with a seek, the old samples were just thrown away
+                        let new_rate = playback_rate.load(Ordering::Acquire);
+                        if new_rate != rate {
+                            rate = new_rate;
+                            resampler = resampler_for(&decoder, rate);
+                        }
+                        continue;
+                    }
+                    TrackMessage::Eof => {
+                        // Drain decoder
+                        let _ = decoder.send_eof();
+                        let mut frame = ffmpeg_next::util::frame::Audio::empty();
+                        while decoder.receive_frame(&mut frame).is_ok() {
+                            let mut resampled = ffmpeg_next::util::frame::Audio::empty();
+                            if resampler.run(&frame, &mut resampled).is_ok() {
+                                let pts = frame.pts().unwrap_or(0) as f64 * f64::from(time_base);
+                                let sample_count = resampled.samples() * target_channels as usize;
+                                let bytes = resampled.data(0);
+
+                                if sample_count > 0 {
+                                    let samples: Vec<f32> = unsafe {
+                                        std::slice::from_raw_parts(
+                                            bytes.as_ptr() as *const f32,
+                                            sample_count
+                                        ).to_vec()
+                                    };
+                                    if !send_or_stop(&sender, AudioChunk { pts, samples, generation }, &running) {
+                                        return;
+                                    }
+                                }
+                            }
+                        }
+                        // Empty chunk, tells the filler the track is over
+                        let end = AudioChunk { pts: 0.0, samples: Vec::new(), generation };
+                        if !send_or_stop(&sender, end, &running) {
+                            return;
+                        }
+                        continue;
+                    }
+                };
 
-            let audio_stream = input_ctx
-                .streams()
-                .best(ffmpeg_next::media::Type::Audio)
-                .expect("No audio stream");
-
-            let audio_idx = audio_stream.index();
-            let time_base = audio_stream.time_base();
-
-            let ctx = ffmpeg_next::codec::context::Context::from_parameters(
-                audio_stream.parameters()
-            ).unwrap();
-            let mut decoder = ctx.decoder().audio().unwrap();
-
-            let mut resampler = ffmpeg_next::software::resampling::Context::get(
-                decoder.format(),
-                decoder.channel_layout(),
-                decoder.rate(),
-                ffmpeg_next::format::Sample::F32(ffmpeg_next::format::sample::Type::Packed),
-                ffmpeg_next::channel_layout::ChannelLayout::STEREO,
-                target_sample_rate,
-            ).unwrap();
-
-            // Demux and decode audio packets
-            for (stream, packet) in input_ctx.packets() {
-                if stream.index() != audio_idx {
+                // A seek is on its way, this packet is from the old position and not worth decoding
+                if generation != seek_generation.load(Ordering::Acquire) {
                     continue;
                 }
 
@@ -258,7 +951,7 @@ fn spawn_audio_decoder(
 
                     let pts = frame.pts().unwrap_or(0) as f64 * f64::from(time_base);
 
-                    let sample_count = resampled.samples() * 2; // Stereo
+                    let sample_count = resampled.samples() * target_channels as usize; // Interleaved
                     let bytes = resampled.data(0);
 
                     if sample_count == 0 {
@@ -272,51 +965,120 @@ fn spawn_audio_decoder(
                         ).to_vec()
                     };
 
-                    // This blocks if channel is full (backpressure)
-                    if sender.send(AudioChunk { pts, samples }).is_err() {
-                        return; // Receiver dropped
+                    // Waits while the channel is full (backpressure), see send_or_stop
+                    if !send_or_stop(&sender, AudioChunk { pts, samples, generation }, &running) {
+                        return;
                     }
                 }
             }
+        })
+        .expect("Failed to spawn audio decoder thread");
+}
 
-            // Drain decoder
-            let _ = decoder.send_eof();
-            let mut frame = ffmpeg_next::util::frame::Audio::empty();
-            while decoder.receive_frame(&mut frame).is_ok() {
-                let mut resampled = ffmpeg_next::util::frame::Audio::empty();
-                if resampler.run(&frame, &mut resampled).is_ok() {
-                    let pts = frame.pts().unwrap_or(0) as f64 * f64::from(time_base);
-                    let sample_count = resampled.samples() * 2;
-                    let bytes = resampled.data(0);
+// Decodes the packets of a text subtitle stream into cues for the window
+// Cue times are media times, so unlike the audio and video they dont need a seek generation,
+// the window finds the one to show from the clock. Ends when the demuxer drops the route
+fn spawn_subtitle_decoder(
+    mut decoder: ffmpeg_next::decoder::Subtitle,
+    time_base: ffmpeg_next::Rational,
+    packets: Receiver<TrackMessage>,
+    sender: Sender<SubtitleCue>,
+    running: Arc<AtomicBool>,
+) {
+    thread::Builder::new()
+        .name("subtitle-decoder".to_string())
+        .spawn(move || {
+            let time_base = f64::from(time_base);
+            for message in packets.iter() {
+                if !running.load(Ordering::Acquire) {
+                    return;
+                }
 
-                    if sample_count > 0 {
-                        let samples: Vec<f32> = unsafe {
-                            std::slice::from_raw_parts(
-                                bytes.as_ptr() as *const f32,
-                                sample_count
-                            ).to_vec()
-                        };
-                        let _ = sender.send(AudioChunk { pts, samples });
+                let packet = match message {
+                    TrackMessage::Packet(packet) => packet,
+                    TrackMessage::Seek(_) => {
+                        decoder.flush();
+                        continue;
                     }
+                    TrackMessage::Eof => continue,
+                };
+
+                let mut subtitle = ffmpeg_next::Subtitle::new();
+                if !matches!(decoder.decode(&packet, &mut subtitle), Ok(true)) {
+                    continue;
+                }
+                let start_s = packet.pts().unwrap_or(0) as f64 * time_base;
+                let duration_s = packet.duration() as f64 * time_base;
+                // The window dropping the receiver is fine, this thread ends with the route instead
+                if let Some(cue) = subtitles::embedded::cue_from_subtitle(&subtitle, start_s, duration_s) {
+                    let _ = sender.send(cue);
                 }
             }
         })
-        .expect("Failed to spawn audio decoder thread");
+        .expect("Failed to spawn subtitle decoder thread");
 }
 
 // Thread that fills ring buffer from decoded audio chunks
 fn spawn_audio_buffer_filler(
     receiver: Receiver<AudioChunk>,
     ring_buffer: Arc<Mutex<AudioRingBuffer>>,
+    clock: Arc<AudioClock>,
+    seek_generation: Arc<AtomicU64>,
+    running: Arc<AtomicBool>,
+    audio_done: Arc<AtomicBool>,
 ) {
     thread::Builder::new()
         .name("audio-filler".to_string())
         .spawn(move || {
+            // Generation whose first samples already went into the buffer
+            let mut started_generation = 0;
+
+            // recv fails once the decoder is gone (shutdown)
             while let Ok(chunk) = receiver.recv() {
+                let generation = seek_generation.load(Ordering::Acquire);
+                // Decoded before the last seek
+                if chunk.generation != generation {
+                    continue;
+                }
+                // Everything decoded is in the ring buffer now
+                if chunk.samples.is_empty() {
+                    audio_done.store(true, Ordering::Release);
+                    continue;
+                }
+
+                // After a seek the audio starts on a keyframe before the target, but the clock
+                // (and so the video) is already at the target. Drop the samples in between so the
+                // first one in the buffer plays at the clock time. If the audio starts later
+                // instead (the seek failed) the clock moves forward to it.
+                let mut start = 0;
+                if started_generation != generation {
+                    let now = clock.current_time();
+                    if chunk.pts >= now {
+                        clock.set_time(chunk.pts);
+                    } else {
+                        start = clock.interleaved_samples(now - chunk.pts);
+                        if start >= chunk.samples.len() {
+                            continue; // All of it is before the target
+                        }
+                    }
+                    started_generation = generation;
+                }
+
                 // Write to ring buffer (will write as much as fits)
-                let mut written = 0;
+                let mut written = start;
                 while written < chunk.samples.len() {
+                    // Once the audio stream is gone nothing drains the buffer, without this
+                    // check we would wait for free space forever
+                    if !running.load(Ordering::Acquire) {
+                        return;
+                    }
+
                     if let Ok(mut buffer) = ring_buffer.lock() {
+                        // Checked under the lock App::seek clears the buffer with, so no piece
+                        // of the old position lands in the buffer after the clear
+                        if seek_generation.load(Ordering::Acquire) != generation {
+                            break;
+                        }
                         let n = buffer.write(&chunk.samples[written..]);
                         written += n;
 
@@ -332,6 +1094,118 @@ fn spawn_audio_buffer_filler(
         .expect("Failed to spawn audio filler thread");
 }
 
+// Downscale to max_height keeping the aspect ratio, never upscale
+fn scaled_size(width: u32, height: u32, max_height: Option<u32>) -> (u32, u32) {
+    match max_height {
+        Some(max_height) if height > max_height => {
+            let scaled_width = (width as u64 * max_height as u64 / height as u64) as u32;
+            // Keep it even, chroma subsampled formats dont like odd sizes
+            ((scaled_width & !1).max(2), max_height)
+        }
+        _ => (width, height),
+    }
+}
+
+// Parse the command line: an optional input (file, network url or -), --max-height <pixels>
+// --channels <n> and --threads <n|auto> (or --flag=<value>), --no-video, --hw-decode and --protocols
+// --thumbnail with --at <seconds>, --output <path.png> and --thumbnail-every <seconds>
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut args = args.skip(1);
+    let mut options = Options {
+        source: Source::parse(DEFAULT_INPUT),
+        max_height: None,
+        no_video: false,
+        channels: None,
+        hw_decode: false,
+        threads: None,
+        list_protocols: false,
+        thumbnail: None,
+    };
+    let mut source = None;
+    // Only used with --thumbnail, collected first since the flags can come in any order
+    let mut thumbnail = false;
+    let mut thumbnail_options = ThumbnailOptions::default();
+    let mut thumbnail_flag = None;
+
+    while let Some(arg) = args.next() {
+        if arg == "--no-video" {
+            options.no_video = true;
+            continue;
+        }
+        if arg == "--hw-decode" {
+            options.hw_decode = true;
+            continue;
+        }
+        if arg == "--protocols" {
+            options.list_protocols = true;
+            continue;
+        }
+        if arg == "--thumbnail" {
+            thumbnail = true;
+            continue;
+        }
+        // Anything that isnt a flag is the input, "-" alone is stdin and not a flag
+        if arg == "-" || !arg.starts_with('-') {
+            if source.is_some() {
+                return Err(format!("Only one input can be played, got another: {}", arg));
+            }
+            source = Some(Source::parse(&arg));
+            continue;
+        }
+
+        // Flags with a value, as the next argument or after =
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        if !matches!(flag, "--max-height" | "--channels" | "--threads" | "--at" | "--output" | "--thumbnail-every") {
+            return Err(format!("Unknown argument: {}", arg));
+        }
+        let value = match inline_value {
+            Some(value) => value,
+            None => args.next().ok_or(format!("{} needs a value", flag))?,
+        };
+
+        match flag {
+            "--max-height" => match value.parse::<u32>() {
+                Ok(height) if height >= 2 => options.max_height = Some(height),
+                _ => return Err(format!("Invalid --max-height value: {}", value)),
+            },
+            "--channels" => match value.parse::<u16>() {
+                Ok(channels) if channels >= 1 => options.channels = Some(channels),
+                _ => return Err(format!("Invalid --channels value: {}", value)),
+            },
+            "--threads" => match value.parse::<usize>() {
+                _ if value == "auto" => options.threads = None,
+                Ok(threads) if (1..=MAX_DECODE_THREADS).contains(&threads) => options.threads = Some(threads),
+                _ => return Err(format!("Invalid --threads value: {} (1 to {} or auto)", value, MAX_DECODE_THREADS)),
+            },
+            "--at" => match value.parse::<f64>() {
+                Ok(secs) if secs >= 0.0 => thumbnail_options.at_secs = secs,
+                _ => return Err(format!("Invalid --at value: {}", value)),
+            },
+            "--thumbnail-every" => match value.parse::<f64>() {
+                Ok(secs) if secs > 0.0 => thumbnail_options.every_secs = Some(secs),
+                _ => return Err(format!("Invalid --thumbnail-every value: {}", value)),
+            },
+            _ => thumbnail_options.output = PathBuf::from(value),
+        }
+        if matches!(flag, "--at" | "--output" | "--thumbnail-every") {
+            thumbnail_flag = Some(flag.to_string());
+        }
+    }
+
+    if let Some(source) = source {
+        options.source = source;
+    }
+    if thumbnail {
+        options.thumbnail = Some(thumbnail_options);
+    } else if let Some(flag) = thumbnail_flag {
+        return Err(format!("{} only works together with --thumbnail", flag));
+    }
+    Ok(options)
+}
+
 fn extract_rgba_data(frame: &ffmpeg_next::util::frame::Video, width: u32, height: u32) -> Vec<u8> {
     let stride = frame.stride(0);
     let src = frame.data(0);
@@ -360,6 +1234,50 @@ struct App {
     // Audio state
     audio_stream: Option<cpal::Stream>,
     audio_clock: Arc<AudioClock>,
+    ring_buffer: Option<Arc<Mutex<AudioRingBuffer>>>,
+    // Set by the filler thread once the whole audio track went into the ring buffer
+    audio_done: Arc<AtomicBool>,
+    // Every audio stream of the input, Digit1 to Digit9 pick one, see select_audio_track
+    audio_tracks: Vec<AudioTrackInfo>,
+    selected_audio_track: usize,
+    // Kept so a new audio decoder can feed the filler thread
+    audio_chunk_sender: Option<Sender<AudioChunk>>,
+    // Tells the demuxer about new audio and subtitle decoders, see RouteCmd
+    route_sender: Option<Sender<RouteCmd>>,
+
+    // Seeking, see SeekCmd. None until the demux thread runs
+    seek_sender: Option<Sender<SeekCmd>>,
+    seek_generation: Arc<AtomicU64>,
+    // Text shown over the video and until when, the seek target or the volume
+    osd: Option<(String, std::time::Instant)>,
+    // 0..=VOLUME_MAX, shared with the cpal callback which scales every sample by it
+    volume: Arc<AtomicU32>,
+    // The callback outputs silence while set, it still consumes samples and advances the clock
+    is_muted: Arc<AtomicBool>,
+    // RMS of what the callback played last as f32 bits, see osd/level_meter.rs. V shows it
+    audio_level: Arc<AtomicU32>,
+    show_level_meter: bool,
+    // One of PLAYBACK_RATES, read by the audio decoder and the clock
+    playback_rate: Arc<AtomicU32>,
+    // From the .srt next to the video, empty when there is none
+    subtitles: Vec<SubtitleCue>,
+    // Text subtitle streams of the input, S cycles through them, the .srt and off
+    subtitle_tracks: Vec<SubtitleTrackInfo>,
+    subtitle_selection: SubtitleSelection,
+    // Cues of the selected stream as its decoder thread finds them, see cycle_subtitles
+    subtitle_receiver: Option<Receiver<SubtitleCue>>,
+    embedded_cues: Vec<SubtitleCue>,
+    loop_mode: LoopMode,
+    // The window opens borderless fullscreen, F or a double click switches
+    is_fullscreen: bool,
+    last_click_time: Option<std::time::Instant>,
+    // Moving the mouse over the window brings the progress bar back
+    last_mouse_move: std::time::Instant,
+    // Digital zoom, MIN_ZOOM to MAX_ZOOM of zoom.rs. Moving the mouse with Ctrl held moves the
+    // center (0 to 1 across the frame)
+    zoom_level: f32,
+    zoom_center: (f32, f32),
+    modifiers: ModifiersState,
 
     // Dimensions
     width: u32,
@@ -367,10 +1285,24 @@ struct App {
 
     // Playback time
     duration_secs: f64,
+    playback_state: PlaybackState,
+
+    // Shutdown flag shared with the Ctrl-C handler, set for the whole run
+    running: Arc<AtomicBool>,
+    // Stop flag the threads of the current input check, see play_track
+    // Cleared on shutdown too, so every thread only has to watch this one
+    track_running: Arc<AtomicBool>,
+
+    // Inputs of the .m3u from the command line, empty when playing a single input
+    playlist: Vec<PathBuf>,
+    current_index: usize,
+
+    options: Options,
 }
 
 impl App {
-    fn new() -> Self {
+    fn new(running: Arc<AtomicBool>, options: Options, playlist: Vec<PathBuf>) -> Self {
+        let playback_rate = Arc::new(AtomicU32::new(NORMAL_RATE));
         Self {
             window: None,
             pixels: None,
@@ -378,22 +1310,546 @@ impl App {
             video_buffer: VecDeque::with_capacity(VIDEO_BUFFER_FRAMES),
             current_frame: Vec::new(),
             audio_stream: None,
-            audio_clock: Arc::new(AudioClock::new(48000)),
+            audio_clock: Arc::new(AudioClock::new(48000, 2, Arc::clone(&playback_rate))),
+            ring_buffer: None,
+            audio_done: Arc::new(AtomicBool::new(false)),
+            audio_tracks: Vec::new(),
+            selected_audio_track: 0,
+            audio_chunk_sender: None,
+            route_sender: None,
+            seek_sender: None,
+            seek_generation: Arc::new(AtomicU64::new(0)),
+            osd: None,
+            volume: Arc::new(AtomicU32::new(VOLUME_MAX)),
+            is_muted: Arc::new(AtomicBool::new(false)),
+            audio_level: Arc::new(AtomicU32::new(0)),
+            show_level_meter: false,
+            playback_rate,
+            subtitles: Vec::new(),
+            subtitle_tracks: Vec::new(),
+            subtitle_selection: SubtitleSelection::Off,
+            subtitle_receiver: None,
+            embedded_cues: Vec::new(),
+            loop_mode: LoopMode::NoLoop,
+            is_fullscreen: true,
+            last_click_time: None,
+            last_mouse_move: std::time::Instant::now(),
+            zoom_level: zoom::MIN_ZOOM,
+            zoom_center: (0.5, 0.5),
+            modifiers: ModifiersState::default(),
             width: 0,
             height: 0,
             duration_secs: 0.0,
+            playback_state: PlaybackState::default(),
+            running,
+            track_running: Arc::new(AtomicBool::new(true)),
+            playlist,
+            current_index: 0,
+            options,
         }
     }
 
+    // Decode thread, filler thread and cpal stream for the audio track
+    // Independent of the window so audio only mode can use it alone
+    fn start_audio(&mut self, track: Track) {
+        let host = cpal::default_host();
+        let device = host.default_output_device().expect("No audio device");
+
+        let config = device.default_output_config().expect("No output config");
+        let sample_rate = config.sample_rate();
+        let sample_format = config.sample_format();
+        let channels = output_channels(self.options.channels, config.channels());
+
+        self.audio_clock = Arc::new(AudioClock::new(sample_rate, channels, Arc::clone(&self.playback_rate)));
+
+        // Create ring buffer (2 seconds of audio)
+        let ring_capacity = sample_rate as usize * channels as usize * 2;
+        let ring_buffer = Arc::new(Mutex::new(AudioRingBuffer::new(ring_capacity)));
+
+        // Bounded for backpressure, see the video channel in can_create_surfaces
+        let (audio_tx, audio_rx) = bounded(AUDIO_CHANNEL_SIZE);
+
+        // A clone stays here for the decoder of another audio track
+        self.audio_chunk_sender = Some(audio_tx.clone());
+        spawn_audio_decoder(
+            track,
+            audio_tx,
+            sample_rate,
+            channels,
+            Arc::clone(&self.playback_rate),
+            Arc::clone(&self.seek_generation),
+            Arc::clone(&self.track_running),
+        );
+        spawn_audio_buffer_filler(
+            audio_rx,
+            Arc::clone(&ring_buffer),
+            Arc::clone(&self.audio_clock),
+            Arc::clone(&self.seek_generation),
+            Arc::clone(&self.track_running),
+            Arc::clone(&self.audio_done),
+        );
+
+        // Build audio stream
+        let stream = build_audio_stream(
+            &device,
+            &config.into(),
+            sample_format,
+            channels,
+            Arc::clone(&ring_buffer),
+            Arc::clone(&self.audio_clock),
+            Arc::clone(&self.volume),
+            Arc::clone(&self.is_muted),
+            Arc::clone(&self.audio_level),
+        );
+
+        stream.play().expect("Failed to play audio");
+
+        self.audio_stream = Some(stream);
+        self.ring_buffer = Some(ring_buffer);
+    }
+
+    // Demux and decode threads and the audio stream for options.source
+    // The window is not touched, it is made once in can_create_surfaces and kept between inputs
+    fn open_input(&mut self) -> Result<(), ffmpeg_next::Error> {
+        // Opened once, the demux thread reads it for both decoders (see Source::open)
+        ffmpeg_next::init().ok();
+        let input_ctx = self.options.source.open()?;
+
+        let duration = input_ctx.duration();
+
+        if duration > 0 {
+            self.duration_secs = duration as f64 / ffmpeg_next::ffi::AV_TIME_BASE as f64;
+        } else {
+            self.duration_secs = 0.0;
+        }
+
+        let audio_stream = input_ctx
+            .streams()
+            .best(ffmpeg_next::media::Type::Audio)
+            .ok_or(ffmpeg_next::Error::StreamNotFound)?;
+        let (audio_track, audio_route) = open_track(&audio_stream);
+        let mut routes = vec![audio_route];
+
+        // The best one plays first, the others can be picked with the digit keys
+        self.audio_tracks = input_ctx
+            .streams()
+            .filter(|stream| stream.parameters().medium() == ffmpeg_next::media::Type::Audio)
+            .map(|stream| AudioTrackInfo::new(&stream))
+            .collect();
+        self.selected_audio_track = self
+            .audio_tracks
+            .iter()
+            .position(|track| track.stream_index == audio_stream.index())
+            .unwrap_or(0);
+        if self.audio_tracks.len() > 1 {
+            for (i, track) in self.audio_tracks.iter().enumerate() {
+                println!("Audio track {}: {} ({})", i + 1, track.language, track.codec);
+            }
+        }
+        // Nothing decodes them until S picks one
+        self.subtitle_tracks = input_ctx.streams().filter_map(|stream| SubtitleTrackInfo::new(&stream)).collect();
+        for (i, track) in self.subtitle_tracks.iter().enumerate() {
+            println!("Subtitle track {}: {} ({})", i + 1, track.language, track.codec);
+        }
+
+        // Audio only, no window and no video decoding at all, the demuxer drops the video packets
+        let video_track = if self.options.no_video {
+            None
+        } else {
+            let video_stream = input_ctx
+                .streams()
+                .best(ffmpeg_next::media::Type::Video)
+                .ok_or(ffmpeg_next::Error::StreamNotFound)?;
+
+            // Errors instead of panics, play_track skips a playlist entry it cant open
+            let params = video_stream.parameters();
+            let ctx = ffmpeg_next::codec::context::Context::from_parameters(params)?;
+            let decoder = ctx.decoder().video()?;
+
+            // Decode threads scale straight to this size, so a smaller one means less work for the
+            // scaler and smaller frames to copy around, the window and Pixels buffer use it too
+            (self.width, self.height) = scaled_size(decoder.width(), decoder.height(), self.options.max_height);
+
+            let (video_track, video_route) = open_track(&video_stream);
+            routes.push(video_route);
+            Some(video_track)
+        };
+
+        let (seek_tx, seek_rx) = unbounded();
+        let (route_tx, route_rx) = unbounded();
+        spawn_demuxer(input_ctx, self.options.source.clone(), routes, seek_rx, route_rx, Arc::clone(&self.track_running));
+        self.seek_sender = Some(seek_tx);
+        self.route_sender = Some(route_tx);
+
+        // Setup audio
+        self.start_audio(audio_track);
+
+        let Some(video_track) = video_track else {
+            return Ok(());
+        };
+
+        // Setup channels for multithreading allowing us to communicate between threads
+        // Making the channels bounded provides backpressure to avoid excessive memory usage
+        // Its an important safety for no memory leaks or OOM crashes
+        let (video_tx, video_rx) = bounded(VIDEO_BUFFER_FRAMES);
+
+        // Start decoder thread
+        spawn_video_decoder(
+            video_track,
+            video_tx,
+            self.width,
+            self.height,
+            VideoDecodeSetup { hw_decode: self.options.hw_decode, threads: self.options.threads },
+            Arc::clone(&self.seek_generation),
+            Arc::clone(&self.track_running),
+        );
+
+        self.video_receiver = Some(video_rx);
+        self.current_frame = vec![0; (self.width * self.height * 4) as usize];
+
+        // Only a file has a folder to look for the .srt in
+        if let Source::File(path) = &self.options.source {
+            self.subtitles = subtitles::load_for_video(path);
+            if !self.subtitles.is_empty() {
+                println!("Loaded {} subtitles", self.subtitles.len());
+                self.subtitle_selection = SubtitleSelection::File;
+            }
+        }
+        Ok(())
+    }
+
+    // Stop the threads of the current input and start the playlist entry at index
+    // The old threads see track_running cleared within SEND_POLL_INTERVAL and exit, dropping
+    // their ends of the channels. They keep the old Arcs of the generation and audio_done, so
+    // whatever they still do cant touch the new input. An entry that cant be opened is skipped
+    // False when no entry from index on could be opened
+    fn play_track(&mut self, index: usize) -> bool {
+        self.track_running.store(false, Ordering::Release);
+        self.seek_sender = None;
+        self.route_sender = None;
+        self.audio_chunk_sender = None; // Or the filler thread would wait for chunks forever
+        self.video_receiver = None;
+        self.audio_stream = None; // Dropping the cpal stream stops the callback
+        self.ring_buffer = None;
+        self.video_buffer.clear();
+        self.subtitles.clear();
+        self.subtitle_selection = SubtitleSelection::Off;
+        self.subtitle_receiver = None;
+        self.embedded_cues.clear();
+
+        for index in index..self.playlist.len() {
+            self.track_running = Arc::new(AtomicBool::new(true));
+            self.seek_generation = Arc::new(AtomicU64::new(0));
+            self.audio_done = Arc::new(AtomicBool::new(false));
+            self.playback_state = PlaybackState::default();
+            self.current_index = index;
+            self.options.source = Source::File(self.playlist[index].clone());
+
+            if let Err(e) = self.open_input() {
+                eprintln!("Skipping {}: {}", self.playlist[index].display(), e);
+                continue;
+            }
+            // Another input can have another size, the window stays and pixels scales the buffer to it
+            if let Some(pixels) = &mut self.pixels
+                && let Err(e) = pixels.resize_buffer(self.width, self.height)
+            {
+                eprintln!("Failed to resize the frame buffer: {}", e);
+            }
+            let name = self.playlist[index].file_name().unwrap_or_default().to_string_lossy();
+            let text = format!("{}/{} {}", index + 1, self.playlist.len(), name);
+            self.show_osd(text, VOLUME_OSD_DURATION);
+            return true;
+        }
+        eprintln!("No playable input left in the playlist");
+        false
+    }
+
+    // Next (step 1) or previous (step -1) playlist entry, stops at both ends
+    fn change_track(&mut self, step: isize) {
+        let last = self.playlist.len().saturating_sub(1);
+        let index = self.current_index.saturating_add_signed(step).min(last);
+        if !self.playlist.is_empty() && index != self.current_index {
+            self.play_track(index);
+        }
+    }
+
+    // Audio is over once the filler is done and the stream played everything left in the buffer
+    fn audio_finished(&self) -> bool {
+        let buffer_empty = self.ring_buffer.as_ref()
+            .and_then(|buffer| buffer.lock().ok().map(|buffer| buffer.available() == 0))
+            .unwrap_or(true);
+        self.audio_done.load(Ordering::Acquire) && buffer_empty
+    }
+
+    // Stop everything before leaving the event loop
+    // Dropping the cpal stream releases the audio device, the flag makes decoders waiting on a
+    // full channel give up (see send_or_stop) and stops the loops of the other threads
+    fn shutdown(&mut self, event_loop: &dyn ActiveEventLoop) {
+        self.running.store(false, Ordering::Release);
+        self.track_running.store(false, Ordering::Release);
+
+        if let Some(stream) = self.audio_stream.take() {
+            let _ = stream.pause();
+        }
+        self.video_receiver = None;
+
+        event_loop.exit();
+    }
+
+    fn toggle_pause(&mut self) {
+        let state = &mut self.playback_state;
+        if state.is_playing {
+            state.paused_at_audio_time = self.audio_clock.current_time();
+            if let Some(stream) = &self.audio_stream {
+                let _ = stream.pause();
+            }
+        } else {
+            self.audio_clock.set_time(state.paused_at_audio_time);
+            if let Some(stream) = &self.audio_stream {
+                let _ = stream.play();
+            }
+        }
+        state.is_playing = !state.is_playing;
+    }
+
+    // Jump to target_seconds, the demux thread does the actual seek
+    // Here everything already buffered is thrown away and the clock moves to the target
+    fn seek(&mut self, target_seconds: f64) {
+        // A pipe cant go back, and going forward would mean reading everything in between
+        if self.options.source == Source::Stdin {
+            return;
+        }
+        let Some(seek_sender) = &self.seek_sender else {
+            return;
+        };
+        let mut target = target_seconds.max(0.0);
+        if self.duration_secs > 0.0 {
+            target = target.min(self.duration_secs);
+        }
+
+        // New generation under the ring buffer lock, see spawn_audio_buffer_filler
+        let ring_buffer = self.ring_buffer.as_ref().and_then(|buffer| buffer.lock().ok());
+        let generation = self.seek_generation.fetch_add(1, Ordering::AcqRel) + 1;
+        if let Some(mut buffer) = ring_buffer {
+            buffer.clear();
+        }
+        self.video_buffer.clear();
+        self.audio_clock.set_time(target);
+        // A seek while paused stays paused at the target
+        self.playback_state.paused_at_audio_time = target;
+        self.audio_done.store(false, Ordering::Release);
+
+        let _ = seek_sender.send(SeekCmd { target_secs: target, generation });
+        self.show_osd(format!("{} / {}", format_time(target), format_time(self.duration_secs)), OSD_DURATION);
+    }
+
+    // Clamped to 0..=VOLUME_MAX, the audio callback picks it up on its next buffer
+    fn set_volume(&mut self, volume: i32) {
+        let volume = volume.clamp(0, VOLUME_MAX as i32) as u32;
+        self.volume.store(volume, Ordering::Relaxed);
+        self.show_osd(format!("VOL {}%", volume * 100 / VOLUME_MAX), VOLUME_OSD_DURATION);
+    }
+
+    fn change_volume(&mut self, delta: i32) {
+        self.set_volume(self.volume.load(Ordering::Relaxed) as i32 + delta);
+    }
+
+    // Next slower (step -1) or faster (step 1) rate of PLAYBACK_RATES, stops at both ends
+    // Samples already decoded and buffered are at the old rate, so this seeks to where we are
+    // to throw them away and restart the audio at the new one (a pipe cant do that, see seek)
+    fn change_rate(&mut self, step: isize) {
+        if self.options.source == Source::Stdin || self.seek_sender.is_none() {
+            return;
+        }
+        let rate = self.playback_rate.load(Ordering::Acquire);
+        let index = PLAYBACK_RATES.iter().position(|&r| r == rate).unwrap_or(2);
+        let new_rate = PLAYBACK_RATES[index.saturating_add_signed(step).min(PLAYBACK_RATES.len() - 1)];
+        if new_rate == rate {
+            return;
+        }
+
+        // Read at the old rate, seek sets the clock again once the new one is stored
+        let now = self.current_time_secs();
+        self.playback_rate.store(new_rate, Ordering::Release);
+        self.seek(now);
+        self.show_osd(format!("SPEED {}X", new_rate as f64 / NORMAL_RATE as f64), OSD_DURATION);
+    }
+
+    // Switch to the audio track at index (Digit1 is 0)
+    // A new decoder for the stream starts on its own channel and the demuxer swaps the route,
+    // dropping the old sender ends the old decoder. It feeds the same filler thread and cpal
+    // stream, the seek to where we are empties the ring buffer and restarts the demuxer there
+    // so the new track picks up in sync. Like change_rate a pipe cant do that
+    fn select_audio_track(&mut self, index: usize) {
+        if self.options.source == Source::Stdin || index == self.selected_audio_track {
+            return;
+        }
+        let (Some(info), Some(chunk_sender)) = (self.audio_tracks.get(index), &self.audio_chunk_sender) else {
+            return;
+        };
+        let (track, route) = info.open();
+        let chunk_sender = chunk_sender.clone();
+
+        // Seek first, the route then starts at the generation of this seek
+        self.seek(self.current_time_secs());
+        let old_stream = Some(self.audio_tracks[self.selected_audio_track].stream_index);
+        let generation = self.seek_generation.load(Ordering::Acquire);
+        let Some(route_sender) = &self.route_sender else {
+            return;
+        };
+        if route_sender.send(RouteCmd { old_stream, route: Some(route), generation }).is_err() {
+            return;
+        }
+        spawn_audio_decoder(
+            track,
+            chunk_sender,
+            self.audio_clock.sample_rate,
+            self.audio_clock.channels,
+            Arc::clone(&self.playback_rate),
+            Arc::clone(&self.seek_generation),
+            Arc::clone(&self.track_running),
+        );
+        self.selected_audio_track = index;
+
+        let language = self.audio_tracks[index].language.to_uppercase();
+        self.show_osd(format!("AUDIO {}/{} {}", index + 1, self.audio_tracks.len(), language), OSD_DURATION);
+    }
+
+    // Next of off, the .srt file and the subtitle streams of the input, see SubtitleSelection
+    // A stream gets its own decoder thread and route from the demuxer, the one before it ends
+    // when the demuxer drops its route. The seek to where we are makes the demuxer read the
+    // packets around the current time again, so a cue that is already on screen shows up too
+    fn cycle_subtitles(&mut self) {
+        if self.route_sender.is_none() {
+            return;
+        }
+        let mut next = self.subtitle_selection.next(!self.subtitles.is_empty(), self.subtitle_tracks.len());
+
+        let mut route = None;
+        self.subtitle_receiver = None;
+        self.embedded_cues.clear();
+        if let SubtitleSelection::Embedded(index) = next {
+            let info = &self.subtitle_tracks[index];
+            let (track, new_route) = open_route(info.parameters.clone(), info.time_base, info.stream_index);
+            // Opened here, a decoder thread that gives up would close its route and stop the demuxer
+            match track.codec.decoder().subtitle() {
+                Ok(decoder) => {
+                    let (cue_tx, cue_rx) = unbounded();
+                    spawn_subtitle_decoder(decoder, track.time_base, track.packets, cue_tx, Arc::clone(&self.track_running));
+                    self.subtitle_receiver = Some(cue_rx);
+                    route = Some(new_route);
+                }
+                Err(e) => {
+                    eprintln!("Cant decode subtitle track {}: {}", index + 1, e);
+                    next = SubtitleSelection::Off;
+                }
+            }
+        }
+
+        let old_stream = match self.subtitle_selection {
+            SubtitleSelection::Embedded(index) => Some(self.subtitle_tracks[index].stream_index),
+            _ => None,
+        };
+        let added = route.is_some();
+        // Seek first like select_audio_track, the new route starts at its generation
+        if added {
+            self.seek(self.current_time_secs());
+        }
+        let generation = self.seek_generation.load(Ordering::Acquire);
+        if (old_stream.is_some() || added)
+            && let Some(route_sender) = &self.route_sender
+            && route_sender.send(RouteCmd { old_stream, route, generation }).is_err()
+        {
+            return;
+        }
+        self.subtitle_selection = next;
+
+        let text = match next {
+            SubtitleSelection::Off => "SUBS OFF".to_string(),
+            SubtitleSelection::File => "SUBS FILE".to_string(),
+            SubtitleSelection::Embedded(index) => {
+                let language = self.subtitle_tracks[index].language.to_uppercase();
+                format!("SUBS {}/{} {}", index + 1, self.subtitle_tracks.len(), language)
+            }
+        };
+        self.show_osd(text, OSD_DURATION);
+    }
+
+    // Stdin cant seek back, so it cant loop either and stays at NoLoop
+    fn cycle_loop_mode(&mut self) {
+        if self.options.source == Source::Stdin {
+            return;
+        }
+        self.loop_mode = self.loop_mode.next();
+        self.show_osd(self.loop_mode.label().to_string(), OSD_DURATION);
+    }
+
+    // fetch_xor flips it, there is no fade so the sound is back on the very next buffer
+    fn toggle_mute(&mut self) {
+        self.is_muted.fetch_xor(true, Ordering::Relaxed);
+    }
+
+    // Multiply the zoom by factor, back at 1x the center resets so the next zoom starts in the middle
+    fn change_zoom(&mut self, factor: f32) {
+        self.zoom_level = (self.zoom_level * factor).clamp(zoom::MIN_ZOOM, zoom::MAX_ZOOM);
+        if self.zoom_level == zoom::MIN_ZOOM {
+            self.zoom_center = (0.5, 0.5);
+        }
+        self.show_osd(format!("ZOOM {:.1}X", self.zoom_level), OSD_DURATION);
+    }
+
+    fn toggle_level_meter(&mut self) {
+        self.show_level_meter = !self.show_level_meter;
+        let text = if self.show_level_meter { "LEVEL ON" } else { "LEVEL OFF" };
+        self.show_osd(text.to_string(), OSD_DURATION);
+    }
+
+    // The window stays, the SurfaceResized that follows resizes the pixels surface to it
+    // Windowed gets the decorations back, a borderless window couldnt be moved or resized
+    fn toggle_fullscreen(&mut self) {
+        let Some(window) = &self.window else {
+            return;
+        };
+        let fullscreen = !self.is_fullscreen;
+        window.set_fullscreen(fullscreen.then_some(Fullscreen::Borderless(None)));
+        window.set_decorations(!fullscreen);
+        self.is_fullscreen = fullscreen;
+        let text = if fullscreen { "FULLSCREEN" } else { "WINDOWED" };
+        self.show_osd(text.to_string(), OSD_DURATION);
+    }
+
+    // Only the second click of a pair counts, a third one starts a new pair
+    fn handle_click(&mut self) {
+        let now = std::time::Instant::now();
+        match self.last_click_time {
+            Some(last) if now.duration_since(last) <= DOUBLE_CLICK_INTERVAL => {
+                self.last_click_time = None;
+                self.toggle_fullscreen();
+            }
+            _ => self.last_click_time = Some(now),
+        }
+    }
+
+    fn show_osd(&mut self, text: String, duration: std::time::Duration) {
+        self.osd = Some((text, std::time::Instant::now() + duration));
+    }
+
     fn process_next_frame(&mut self) {
+        // Paused: keep showing the last frame, the decoded ones wait in the buffer and channel
+        if !self.playback_state.is_playing {
+            return;
+        }
+
         let video_receiver = match self.video_receiver.as_ref() {
             Some(r) => r,
             None => return,
         };
 
-        // Refill buffer from decoder
+        // Refill buffer from decoder, frames decoded before the last seek are dropped
+        let generation = self.seek_generation.load(Ordering::Acquire);
         while self.video_buffer.len() < VIDEO_BUFFER_FRAMES {
             match video_receiver.try_recv() {
+                Ok(frame) if frame.generation != generation => {}
                 Ok(frame) => self.video_buffer.push_back(frame),
                 Err(_) => break,
             }
@@ -427,6 +1883,31 @@ impl App {
         progress.clamp(0.0, 1.0)
     }
 
+    // Text in the 3x5 OSD font (see glyph), each font pixel is a scale x scale square
+    fn draw_text(
+        frame: &mut [u8],
+        frame_width: u32,
+        frame_height: u32,
+        x: u32,
+        y: u32,
+        text: &str,
+        scale: u32,
+        color: [u8; 4],
+    ) {
+        for (i, c) in text.chars().enumerate() {
+            // 3 columns and 1 of spacing
+            let left = x + i as u32 * 4 * scale;
+            for (row, bits) in glyph(c.to_ascii_uppercase()).iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (0b100 >> column) != 0 {
+                        let (px, py) = (left + column * scale, y + row as u32 * scale);
+                        Self::draw_rect(frame, frame_width, frame_height, px, py, scale, scale, color);
+                    }
+                }
+            }
+        }
+    }
+
     fn draw_rect(
         frame: &mut [u8],
         frame_width: u32,
@@ -459,76 +1940,50 @@ impl ApplicationHandler for App {
         }
     }
 
-    // Create window and initialize video/audio
-    fn can_create_surfaces(&mut self, event_loop: &dyn ActiveEventLoop) {
-        let video_path = Path::new("sample_video.mp4");
-
-        // Get video metadata
-        ffmpeg_next::init().ok();
-        let input_ctx = ffmpeg_next::format::input(video_path)
-            .expect("Failed to open video");
-
-        let duration = input_ctx.duration();
-
-        if duration > 0 {
-            self.duration_secs = duration as f64 / ffmpeg_next::ffi::AV_TIME_BASE as f64;
-        } else {
-            self.duration_secs = 0.0;
+    // Ctrl-C only flips the flag from the signal handler thread, we notice it here on the next
+    // loop iteration (ControlFlow::Poll keeps us spinning) and tear down from the event loop
+    fn about_to_wait(&mut self, event_loop: &dyn ActiveEventLoop) {
+        if !self.running.load(Ordering::Acquire) {
+            self.shutdown(event_loop);
+            return;
         }
 
-        let video_stream = input_ctx
-            .streams()
-            .best(ffmpeg_next::media::Type::Video)
-            .expect("No video stream");
-
-        let params = video_stream.parameters();
-        let ctx = ffmpeg_next::codec::context::Context::from_parameters(params).unwrap();
-        let decoder = ctx.decoder().video().unwrap();
-
-        self.width = decoder.width();
-        self.height = decoder.height();
-
-        // Setup audio
-        let host = cpal::default_host();
-        let device = host.default_output_device().expect("No audio device");
-
-        let config = device.default_output_config().expect("No output config");
-        let sample_rate = config.sample_rate();
-        let sample_format = config.sample_format();
-
-        self.audio_clock = Arc::new(AudioClock::new(sample_rate));
-
-        // Create ring buffer (2 seconds of stereo audio)
-        let ring_capacity = sample_rate as usize * 2 * 2;
-        let ring_buffer = Arc::new(Mutex::new(AudioRingBuffer::new(ring_capacity)));
-
-        // Setup channels for multithreading allowing us to communicate between threads
-        // Making the channels bounded provides backpressure to avoid excessive memory usage
-        // Its an important safety for no memory leaks or OOM crashes
-        let (video_tx, video_rx) = bounded(VIDEO_BUFFER_FRAMES);
-        let (audio_tx, audio_rx) = bounded(AUDIO_CHANNEL_SIZE);
-
-        // Start decoder threads
-        spawn_video_decoder(video_path, video_tx, self.width, self.height);
-        spawn_audio_decoder(video_path, audio_tx, sample_rate);
-
-        // Start audio buffer filler
-        spawn_audio_buffer_filler(audio_rx, Arc::clone(&ring_buffer));
+        // Everything played: back to the start, or on to the next input of the playlist
+        // The check runs again only after the seek or the new input cleared audio_done, so this
+        // happens once per end of a track
+        if self.audio_finished() {
+            if self.loop_mode == LoopMode::LoopFile {
+                self.seek(0.0);
+            } else if self.current_index + 1 < self.playlist.len() {
+                self.play_track(self.current_index + 1);
+            }
+        }
 
-        // Build audio stream
-        let stream = build_audio_stream(
-            &device,
-            &config.into(),
-            sample_format,
-            Arc::clone(&ring_buffer),
-            Arc::clone(&self.audio_clock),
-        );
+        // No window means no redraws, a timer keeps us checking progress and the end of the track
+        if self.options.no_video {
+            if self.audio_finished() {
+                println!("Playback finished");
+                self.shutdown(event_loop);
+                return;
+            }
 
-        stream.play().expect("Failed to play audio");
+            println!("Playback progress: {:.2}%", self.playback_progress() * 100.0);
+            event_loop.set_control_flow(ControlFlow::WaitUntil(std::time::Instant::now() + AUDIO_ONLY_TICK));
+        }
+    }
 
-        self.video_receiver = Some(video_rx);
-        self.audio_stream = Some(stream);
-        self.current_frame = vec![0; (self.width * self.height * 4) as usize];
+    // Create window and initialize video/audio
+    fn can_create_surfaces(&mut self, event_loop: &dyn ActiveEventLoop) {
+        // A playlist starts at the first entry that opens, the same skipping as N and P
+        if self.playlist.is_empty() {
+            self.open_input().expect("Failed to open input");
+        } else if !self.play_track(0) {
+            event_loop.exit();
+            return;
+        }
+        if self.options.no_video {
+            return;
+        }
 
         // Create window
         let attrs = WindowAttributes::default()
@@ -556,7 +2011,60 @@ impl ApplicationHandler for App {
     ) {
         match event {
             WindowEvent::CloseRequested => {
-                event_loop.exit();
+                self.shutdown(event_loop);
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                if event.state != ElementState::Pressed {
+                    return;
+                }
+                match event.physical_key {
+                    // Holding space would toggle on every key repeat
+                    PhysicalKey::Code(KeyCode::Space) if !event.repeat => self.toggle_pause(),
+                    // Holding an arrow keeps seeking, like most players
+                    PhysicalKey::Code(KeyCode::ArrowLeft) => self.seek(self.current_time_secs() - SEEK_STEP_SECS),
+                    PhysicalKey::Code(KeyCode::ArrowRight) => self.seek(self.current_time_secs() + SEEK_STEP_SECS),
+                    PhysicalKey::Code(KeyCode::ArrowUp) => self.change_volume(VOLUME_STEP),
+                    PhysicalKey::Code(KeyCode::ArrowDown) => self.change_volume(-VOLUME_STEP),
+                    PhysicalKey::Code(KeyCode::KeyM) if !event.repeat => self.toggle_mute(),
+                    PhysicalKey::Code(KeyCode::KeyL) if !event.repeat => self.cycle_loop_mode(),
+                    PhysicalKey::Code(KeyCode::KeyN) if !event.repeat => self.change_track(1),
+                    PhysicalKey::Code(KeyCode::KeyP) if !event.repeat => self.change_track(-1),
+                    PhysicalKey::Code(KeyCode::BracketLeft) if !event.repeat => self.change_rate(-1),
+                    PhysicalKey::Code(KeyCode::BracketRight) if !event.repeat => self.change_rate(1),
+                    PhysicalKey::Code(KeyCode::KeyF) if !event.repeat => self.toggle_fullscreen(),
+                    PhysicalKey::Code(KeyCode::KeyS) if !event.repeat => self.cycle_subtitles(),
+                    PhysicalKey::Code(KeyCode::KeyV) if !event.repeat => self.toggle_level_meter(),
+                    // + shares its key with = on most layouts, holding keeps zooming
+                    PhysicalKey::Code(KeyCode::Equal | KeyCode::NumpadAdd) => self.change_zoom(ZOOM_STEP),
+                    PhysicalKey::Code(KeyCode::Minus | KeyCode::NumpadSubtract) => self.change_zoom(1.0 / ZOOM_STEP),
+                    // 1 to 9 pick the audio track, see select_audio_track
+                    PhysicalKey::Code(code) if !event.repeat => {
+                        if let Some(index) = audio_track_key(code) {
+                            self.select_audio_track(index);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            WindowEvent::PointerButton {
+                state: ElementState::Pressed,
+                button: ButtonSource::Mouse(MouseButton::Left),
+                ..
+            } => self.handle_click(),
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            WindowEvent::PointerMoved { position, .. } => {
+                self.last_mouse_move = std::time::Instant::now();
+                // Ctrl held: the zoomed in part follows the mouse
+                if self.modifiers.control_key()
+                    && let Some(window) = &self.window
+                {
+                    let size = window.surface_size();
+                    if size.width > 0 && size.height > 0 {
+                        let x = (position.x / size.width as f64).clamp(0.0, 1.0) as f32;
+                        let y = (position.y / size.height as f64).clamp(0.0, 1.0) as f32;
+                        self.zoom_center = (x, y);
+                    }
+                }
             }
             WindowEvent::SurfaceResized(new_size) => {
                 if let Some(pixels) = self.pixels.as_mut() {
@@ -568,30 +2076,97 @@ impl ApplicationHandler for App {
                 self.process_next_frame();
 
                 let progress = self.playback_progress();
+                // A seek makes the decoder find the same cues again
+                if let Some(receiver) = &self.subtitle_receiver {
+                    for cue in receiver.try_iter() {
+                        if !self.embedded_cues.contains(&cue) {
+                            self.embedded_cues.push(cue);
+                        }
+                    }
+                }
+                let cues: &[SubtitleCue] = match self.subtitle_selection {
+                    SubtitleSelection::Off => &[][..],
+                    SubtitleSelection::File => &self.subtitles[..],
+                    SubtitleSelection::Embedded(_) => &self.embedded_cues[..],
+                };
+                let subtitle = subtitles::active_cue(cues, self.current_time_secs());
                 println!("Playback progress: {:.2}%", progress * 100.0);
 
                 // Get dimensions
                 let w = self.width;
                 let h = self.height;
-                let bar_height: u32 = 8;
-                let y = h.saturating_sub(bar_height);
-                let filled_width = (w as f64 * progress) as u32;
-
-                if let Some(pixels) = self.pixels.as_mut() {
+                // Subtitles stay above the bar even while it is hidden, so they dont jump
+                let y = h.saturating_sub(progress_bar::BAR_HEIGHT);
+                let show_progress_bar = self.last_mouse_move.elapsed() < PROGRESS_BAR_HIDE_DELAY;
+                let elapsed = self.current_time_secs();
+
+                // After a failed resize_buffer (see play_track) the buffer still has the old size and
+                // nothing drawn for w x h fits, the window keeps the last frame instead
+                if let Some(pixels) = self.pixels.as_mut()
+                    && pixels.frame().len() == w as usize * h as usize * 4
+                {
                     let frame = pixels.frame_mut();
 
-                    // Copy the video frame
-                    if !self.current_frame.is_empty() {
-                        frame.copy_from_slice(&self.current_frame);
+                    // Copy the video frame, or the zoomed in part of it stretched over the whole buffer
+                    // A frame of the track before, at its size, is left out until the new one comes
+                    if self.current_frame.len() == frame.len() {
+                        if self.zoom_level > zoom::MIN_ZOOM {
+                            zoom::scale_crop(&self.current_frame, frame, w, h, self.zoom_level, self.zoom_center);
+                        } else {
+                            frame.copy_from_slice(&self.current_frame);
+                        }
                     }
 
                     // Draw the progress bar on top
-                    Self::draw_rect(frame, w, h, 0, y, w, bar_height, [50, 50, 50, 255]);
-                    Self::draw_rect(frame, w, h, 0, y, filled_width, bar_height, [0, 200, 0, 255]);
+                    if show_progress_bar {
+                        ProgressBar::draw(frame, w, h, progress as f32, elapsed, self.duration_secs);
+                    }
+
+                    // Level bar on the right, above the progress bar like the subtitles
+                    if self.show_level_meter {
+                        LevelMeter::draw(frame, w, h, y, level_meter::load_level(&self.audio_level));
+                    }
+
+                    // Subtitle lines centered above the progress bar, last line at the bottom
+                    // A black copy one font pixel down and right keeps white text readable on bright frames
+                    if let Some(cue) = subtitle {
+                        let scale = (h / 100).max(2);
+                        let line_height = scale * 7;
+                        let mut line_y = y.saturating_sub(scale * 3 + line_height * cue.lines.len() as u32);
+                        for line in &cue.lines {
+                            let text_width = (line.chars().count() as u32 * 4 * scale).saturating_sub(scale);
+                            let line_x = w.saturating_sub(text_width) / 2;
+                            Self::draw_text(frame, w, h, line_x + scale, line_y + scale, line, scale, [0, 0, 0, 255]);
+                            Self::draw_text(frame, w, h, line_x, line_y, line, scale, [255, 255, 255, 255]);
+                            line_y += line_height;
+                        }
+                    }
+
+                    // Seek target or volume in the top left corner, on a dark box so it reads on any frame
+                    if let Some((text, hide_at)) = &self.osd
+                        && std::time::Instant::now() < *hide_at
+                    {
+                        let scale = (h / 120).max(2);
+                        let margin = scale * 4;
+                        let text_width = text.chars().count() as u32 * 4 * scale;
+                        Self::draw_rect(frame, w, h, margin, margin, text_width + scale * 3, scale * 7, [0, 0, 0, 255]);
+                        Self::draw_text(frame, w, h, margin + scale * 2, margin + scale, text, scale, [255, 255, 255, 255]);
+                    }
+
+                    // Mute indicator in the top right corner, stays as long as the sound is off
+                    if self.is_muted.load(Ordering::Relaxed) {
+                        let text = "MUTE";
+                        let scale = (h / 120).max(2);
+                        let margin = scale * 4;
+                        let box_width = text.len() as u32 * 4 * scale + scale * 3;
+                        let x = w.saturating_sub(margin + box_width);
+                        Self::draw_rect(frame, w, h, x, margin, box_width, scale * 7, [0, 0, 0, 255]);
+                        Self::draw_text(frame, w, h, x + scale * 2, margin + scale, text, scale, [255, 80, 80, 255]);
+                    }
 
                     // Render to screen
                     if pixels.render().is_err() {
-                        event_loop.exit();
+                        self.shutdown(event_loop);
                         return;
                     }
                 }
@@ -605,14 +2180,110 @@ impl ApplicationHandler for App {
     }
 }
 
+// 3x5 pixel glyphs for the OSD and subtitles, one row per entry, bit 2 is the left column
+// Digits, capitals and common punctuation, draw_text shows lowercase as capitals
+// Anything else (accents, other scripts) is blank
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b111, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b111, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        // Rounder than the zero so the two can be told apart
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '?' => [0b110, 0b001, 0b010, 0b000, 0b010],
+        '\'' => [0b010, 0b010, 0b000, 0b000, 0b000],
+        '"' => [0b101, 0b101, 0b000, 0b000, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        _ => [0; 5],
+    }
+}
+
+// m:ss, or h:mm:ss from one hour on
+fn format_time(secs: f64) -> String {
+    let total = secs.max(0.0) as u64;
+    let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+// How many channels the decoder resamples to for a device with device_channels
+// ffmpeg does the downmix, a mono one mixes left and right instead of dropping one of them
+fn output_channels(requested: Option<u16>, device_channels: u16) -> u16 {
+    match requested {
+        Some(channels) if channels > device_channels => {
+            eprintln!(
+                "Warning: the audio device only has {} channel(s), playing {} instead of {}",
+                device_channels, device_channels, channels
+            );
+            device_channels
+        }
+        Some(channels) => channels,
+        None => device_channels.min(2),
+    }
+}
+
+// Sample of one frame for output channel ch. Mono goes to every speaker, with more channels
+// the output ones repeat them in order (stereo on 4 speakers: L R L R)
+fn output_sample(source: &[f32], ch: usize) -> f32 {
+    source[ch % source.len()]
+}
+
 fn build_audio_stream(
     device: &cpal::Device,
     config: &cpal::StreamConfig,
     format: cpal::SampleFormat,
+    source_channels: u16,
     ring_buffer: Arc<Mutex<AudioRingBuffer>>,
     clock: Arc<AudioClock>,
+    volume: Arc<AtomicU32>,
+    is_muted: Arc<AtomicBool>,
+    level: Arc<AtomicU32>,
 ) -> cpal::Stream {
     let channels = config.channels as usize;
+    let source_channels = source_channels as usize;
     let err_fn = |err| eprintln!("Audio error: {}", err);
 
     match format {
@@ -621,22 +2292,28 @@ fn build_audio_stream(
                 config,
                 move |data: &mut [f32], _| {
                     let frames = data.len() / channels;
-                    let mut stereo_data = vec![0.0f32; frames * 2];
+                    let mut source_data = vec![0.0f32; frames * source_channels];
 
                     if let Ok(mut buffer) = ring_buffer.lock() {
-                        buffer.read(&mut stereo_data);
+                        buffer.read(&mut source_data);
                     }
+                    // Relaxed is enough, a change showing up one buffer later is not audible
+                    // Muted still reads the ring buffer so playback keeps going, only silently
+                    let gain = if is_muted.load(Ordering::Relaxed) {
+                        0.0
+                    } else {
+                        volume.load(Ordering::Relaxed) as f32 / VOLUME_MAX as f32
+                    };
 
-                    // Convert stereo to output channels
+                    // Spread the decoded channels over the output ones, see output_sample
                     for frame in 0..frames {
-                        let l = stereo_data[frame * 2];
-                        let r = stereo_data[frame * 2 + 1];
-
+                        let source = &source_data[frame * source_channels..(frame + 1) * source_channels];
                         for ch in 0..channels {
-                            data[frame * channels + ch] = if ch % 2 == 0 { l } else { r };
+                            data[frame * channels + ch] = output_sample(source, ch) * gain;
                         }
                     }
 
+                    level_meter::store_level(&level, &source_data, gain);
                     clock.advance(frames as u64);
                 },
                 err_fn,
@@ -648,23 +2325,27 @@ fn build_audio_stream(
                 config,
                 move |data: &mut [i32], _| {
                     let frames = data.len() / channels;
-                    let mut stereo_data = vec![0.0f32; frames * 2];
+                    let mut source_data = vec![0.0f32; frames * source_channels];
 
                     if let Ok(mut buffer) = ring_buffer.lock() {
-                        buffer.read(&mut stereo_data);
+                        buffer.read(&mut source_data);
                     }
+                    let gain = if is_muted.load(Ordering::Relaxed) {
+                        0.0
+                    } else {
+                        volume.load(Ordering::Relaxed) as f32 / VOLUME_MAX as f32
+                    };
 
                     for frame in 0..frames {
-                        let l = stereo_data[frame * 2];
-                        let r = stereo_data[frame * 2 + 1];
-
+                        let source = &source_data[frame * source_channels..(frame + 1) * source_channels];
                         for ch in 0..channels {
-                            let sample = if ch % 2 == 0 { l } else { r };
+                            let sample = output_sample(source, ch) * gain;
                             data[frame * channels + ch] =
                                 (sample.clamp(-1.0, 1.0) * i32::MAX as f32) as i32;
                         }
                     }
 
+                    level_meter::store_level(&level, &source_data, gain);
                     clock.advance(frames as u64);
                 },
                 err_fn,
@@ -676,10 +2357,66 @@ fn build_audio_stream(
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // e.g. --max-height 720 to watch a 4K file at 720p, --no-video to only listen, --channels 1 for mono,
+    // --hw-decode to let the GPU decode the video
+    // The input can be a file, an http(s), rtsp or hls url or - to read a piped stream from stdin
+    let mut options = parse_args(std::env::args())?;
+
+    if options.list_protocols {
+        println!("Input protocols this ffmpeg was built with:");
+        for protocol in input_protocols() {
+            println!("  {}", protocol);
+        }
+        return Ok(());
+    }
+    // Check now instead of failing later with a vague error from format::input
+    if let Some(protocol) = options.source.protocol()
+        && !input_protocols().contains(&protocol)
+    {
+        return Err(format!("This ffmpeg was built without {} support, see --protocols", protocol).into());
+    }
+
+    // Frames to PNG and exit, before anything of the player (window, audio) is made
+    // Exit code 1 when no thumbnail could be saved, so scripts can check
+    if let Some(thumbnail_options) = &options.thumbnail {
+        ffmpeg_next::init().ok();
+        let result = options
+            .source
+            .open()
+            .map_err(|e| format!("Cant open the input: {}", e))
+            .and_then(|input_ctx| thumbnail::extract(input_ctx, thumbnail_options));
+        if let Err(e) = result {
+            eprintln!("Thumbnail failed: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let event_loop = EventLoop::new()?;
     event_loop.set_control_flow(ControlFlow::Poll);
 
-    let app = App::new();
+    // Without a handler SIGINT kills the process right away and the audio stream is never
+    // dropped, which can leave the device claimed
+    let running = Arc::new(AtomicBool::new(true));
+    let handler_running = Arc::clone(&running);
+    ctrlc::set_handler(move || {
+        handler_running.store(false, Ordering::Release);
+    })?;
+
+    // A playlist plays its first entry, App moves on to the others (see play_track)
+    let mut playlist = Vec::new();
+    if let Source::File(path) = &options.source
+        && playlist::is_playlist(path)
+    {
+        playlist = playlist::m3u::M3uParser::parse(path)
+            .map_err(|e| format!("Failed to read playlist {}: {}", path.display(), e))?;
+        let Some(first) = playlist.first() else {
+            return Err(format!("Playlist {} has no entries", path.display()).into());
+        };
+        options.source = Source::File(first.clone());
+    }
+
+    let app = App::new(running, options, playlist);
     event_loop.run_app(app)?;
 
     Ok(())
@@ -0,0 +1,16 @@
+// Overlays drawn straight into the pixels frame buffer, on top of the video frame
+// The seek and volume text still uses the small 3x5 font in main.rs
+
+pub mod font;
+pub mod level_meter;
+pub mod progress_bar;
+
+// Solid rectangle, the parts outside the frame are cut off
+fn fill(frame: &mut [u8], width: u32, height: u32, x: u32, y: u32, rect_width: u32, rect_height: u32, color: [u8; 4]) {
+    for yy in y..(y + rect_height).min(height) {
+        for xx in x..(x + rect_width).min(width) {
+            let idx = (yy as usize * width as usize + xx as usize) * 4;
+            frame[idx..idx + 4].copy_from_slice(&color);
+        }
+    }
+}
@@ -0,0 +1,45 @@
+// 8x8 bitmap font for the progress bar times, one byte per row, bit 7 is the left column
+// Only what a time needs: digits, ':' , '-' and '/'. Anything else is blank
+
+pub const GLYPH_SIZE: u32 = 8;
+
+pub fn glyph(c: char) -> [u8; 8] {
+    match c {
+        '0' => [0x3C, 0x66, 0x6E, 0x76, 0x66, 0x66, 0x3C, 0x00],
+        '1' => [0x18, 0x38, 0x18, 0x18, 0x18, 0x18, 0x7E, 0x00],
+        '2' => [0x3C, 0x66, 0x06, 0x0C, 0x30, 0x60, 0x7E, 0x00],
+        '3' => [0x3C, 0x66, 0x06, 0x1C, 0x06, 0x66, 0x3C, 0x00],
+        '4' => [0x0C, 0x1C, 0x3C, 0x6C, 0x7E, 0x0C, 0x0C, 0x00],
+        '5' => [0x7E, 0x60, 0x7C, 0x06, 0x06, 0x66, 0x3C, 0x00],
+        '6' => [0x3C, 0x60, 0x7C, 0x66, 0x66, 0x66, 0x3C, 0x00],
+        '7' => [0x7E, 0x06, 0x0C, 0x18, 0x30, 0x30, 0x30, 0x00],
+        '8' => [0x3C, 0x66, 0x66, 0x3C, 0x66, 0x66, 0x3C, 0x00],
+        '9' => [0x3C, 0x66, 0x66, 0x3E, 0x06, 0x0C, 0x38, 0x00],
+        ':' => [0x00, 0x18, 0x18, 0x00, 0x00, 0x18, 0x18, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x7E, 0x00, 0x00, 0x00, 0x00],
+        '/' => [0x02, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x40, 0x00],
+        _ => [0; 8],
+    }
+}
+
+// Width of text in pixels, the glyphs have their spacing built in
+pub fn text_width(text: &str) -> u32 {
+    text.chars().count() as u32 * GLYPH_SIZE
+}
+
+// Text with its top left corner at x, y, pixels outside the frame are skipped
+pub fn draw_text(frame: &mut [u8], width: u32, height: u32, x: u32, y: u32, text: &str, color: [u8; 4]) {
+    for (i, c) in text.chars().enumerate() {
+        let left = x + i as u32 * GLYPH_SIZE;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            let py = y + row as u32;
+            for column in 0..GLYPH_SIZE {
+                let px = left + column;
+                if bits & (0x80 >> column) != 0 && px < width && py < height {
+                    let idx = (py as usize * width as usize + px as usize) * 4;
+                    frame[idx..idx + 4].copy_from_slice(&color);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,83 @@
+// Audio level bar on the right edge of the frame, V turns it on and off
+//
+//   _
+//  |_|  red above -3 dB
+//  |_|  yellow above -12 dB
+//  |#|
+//  |#|  green, filled up to the current level
+//  |#|
+//
+// The level comes from the cpal callback, a realtime thread that must never wait for the window.
+// So no Mutex: the callback stores the level as the bits of an f32 in an AtomicU32 and the
+// redraw loads it. Only the callback writes, a plain load and store is enough, no compare and swap.
+
+use super::fill;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+const METER_WIDTH: u32 = 8;
+const METER_HEIGHT: u32 = 120;
+// Space to the right edge and to the progress bar below
+const PADDING: u32 = 12;
+// Quietest level that still lights up the bar, everything below shows as empty
+const FLOOR_DB: f32 = -60.0;
+const YELLOW_DB: f32 = -12.0;
+const RED_DB: f32 = -3.0;
+// Share of the last level kept per callback, a loud peak falls over a few hundred milliseconds
+// instead of the bar flickering with every buffer
+const FALL: f32 = 0.9;
+const BACKGROUND_COLOR: [u8; 4] = [30, 30, 30, 255];
+const GREEN: [u8; 4] = [0, 200, 0, 255];
+const YELLOW: [u8; 4] = [230, 200, 0, 255];
+const RED: [u8; 4] = [230, 40, 40, 255];
+
+// For the audio callback: RMS of the samples it just played, gain is the volume (0 when muted)
+// The new level is that or the falling last one, whichever is louder
+pub fn store_level(level: &AtomicU32, samples: &[f32], gain: f32) {
+    if samples.is_empty() {
+        return;
+    }
+    let sum: f32 = samples.iter().map(|sample| sample * sample).sum();
+    let rms = (sum / samples.len() as f32).sqrt() * gain;
+    let falling = f32::from_bits(level.load(Ordering::Relaxed)) * FALL;
+    level.store(rms.max(falling).to_bits(), Ordering::Relaxed);
+}
+
+pub fn load_level(level: &AtomicU32) -> f32 {
+    f32::from_bits(level.load(Ordering::Relaxed))
+}
+
+// 0.0 to 1.0 of the bar for an RMS level, in dB since that is closer to how loud it sounds
+fn fraction(level_db: f32) -> f32 {
+    ((level_db - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0)
+}
+
+fn to_db(rms: f32) -> f32 {
+    20.0 * rms.max(1e-6).log10()
+}
+
+pub struct LevelMeter;
+
+impl LevelMeter {
+    // bottom is the row the bar ends at, the top of the progress bar area
+    pub fn draw(frame: &mut [u8], width: u32, height: u32, bottom: u32, rms: f32) {
+        let x = width.saturating_sub(PADDING + METER_WIDTH);
+        let top = bottom.saturating_sub(PADDING + METER_HEIGHT);
+        fill(frame, width, height, x, top, METER_WIDTH, METER_HEIGHT, BACKGROUND_COLOR);
+
+        let filled = (METER_HEIGHT as f32 * fraction(to_db(rms))) as u32;
+        let yellow_from = (METER_HEIGHT as f32 * fraction(YELLOW_DB)) as u32;
+        let red_from = (METER_HEIGHT as f32 * fraction(RED_DB)) as u32;
+        // Row by row from the bottom, so each zone keeps its color while the bar moves
+        for row in 0..filled {
+            let color = if row >= red_from {
+                RED
+            } else if row >= yellow_from {
+                YELLOW
+            } else {
+                GREEN
+            };
+            let y = top + METER_HEIGHT - 1 - row;
+            fill(frame, width, height, x + 1, y, METER_WIDTH - 2, 1, color);
+        }
+    }
+}
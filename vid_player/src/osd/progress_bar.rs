@@ -0,0 +1,55 @@
+// Seek bar along the bottom of the window, redrawn over every video frame
+//
+//  ______________________________________________________
+// | 1:23  [==========--------------------------]  -2:34 |  36 pixels, darkened video behind
+//
+// Elapsed time on the left, time left on the right, both in the 8x8 font
+
+use super::{fill, font};
+
+// Rows at the bottom of the frame the bar covers, subtitles sit above it
+pub const BAR_HEIGHT: u32 = 36;
+// Space between the frame edge, the times and the track
+const PADDING: u32 = 12;
+const TRACK_HEIGHT: u32 = 6;
+// How much of the video shows through the background, out of 255
+const BACKGROUND_ALPHA: u32 = 160;
+const TRACK_COLOR: [u8; 4] = [90, 90, 90, 255];
+const FILL_COLOR: [u8; 4] = [0, 200, 0, 255];
+const TEXT_COLOR: [u8; 4] = [255, 255, 255, 255];
+
+pub struct ProgressBar;
+
+impl ProgressBar {
+    // frame is the RGBA pixels buffer, width x height, progress is 0.0 to 1.0
+    pub fn draw(frame: &mut [u8], width: u32, height: u32, progress: f32, elapsed: f64, total: f64) {
+        let top = height.saturating_sub(BAR_HEIGHT);
+        darken(frame, width, height, top);
+
+        let elapsed_text = crate::format_time(elapsed);
+        let remaining_text = format!("-{}", crate::format_time(total - elapsed));
+        let text_y = top + BAR_HEIGHT.saturating_sub(font::GLYPH_SIZE) / 2;
+        font::draw_text(frame, width, height, PADDING, text_y, &elapsed_text, TEXT_COLOR);
+        let remaining_x = width.saturating_sub(PADDING + font::text_width(&remaining_text));
+        font::draw_text(frame, width, height, remaining_x, text_y, &remaining_text, TEXT_COLOR);
+
+        // Track between the two times, the filled part is the played share of it
+        let track_x = PADDING * 2 + font::text_width(&elapsed_text);
+        let track_width = remaining_x.saturating_sub(track_x + PADDING);
+        let track_y = top + BAR_HEIGHT.saturating_sub(TRACK_HEIGHT) / 2;
+        let filled_width = (track_width as f32 * progress.clamp(0.0, 1.0)) as u32;
+        fill(frame, width, height, track_x, track_y, track_width, TRACK_HEIGHT, TRACK_COLOR);
+        fill(frame, width, height, track_x, track_y, filled_width, TRACK_HEIGHT, FILL_COLOR);
+    }
+}
+
+// Blend every row from top down towards black, the video stays visible behind the bar
+fn darken(frame: &mut [u8], width: u32, height: u32, top: u32) {
+    let start = top as usize * width as usize * 4;
+    let end = (height as usize * width as usize * 4).min(frame.len());
+    for pixel in frame[start.min(end)..end].chunks_exact_mut(4) {
+        for channel in &mut pixel[..3] {
+            *channel = (*channel as u32 * (255 - BACKGROUND_ALPHA) / 255) as u8;
+        }
+    }
+}
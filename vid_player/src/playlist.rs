@@ -0,0 +1,13 @@
+// Several inputs played one after the other, loaded from a playlist file
+// Only M3U (.m3u and .m3u8) for now, other formats would get their own parser module here
+
+pub mod m3u;
+
+use std::path::Path;
+
+// Is this input a playlist instead of a video, decided by the extension like the .srt lookup
+pub fn is_playlist(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("m3u") || extension.eq_ignore_ascii_case("m3u8"))
+}
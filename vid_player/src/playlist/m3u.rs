@@ -0,0 +1,77 @@
+// M3U files list one input per line, everything starting with # is a comment or a directive:
+//
+// #EXTM3U
+// #EXTINF:123,Artist - Title
+// videos/first.mp4
+// /home/me/second.mkv
+//
+// The #EXTINF info (duration and title) isnt needed, the player reads both from the file itself.
+// Relative paths are relative to the folder of the playlist, not to where the player was started.
+
+use std::path::{Path, PathBuf};
+
+pub struct M3uParser;
+
+impl M3uParser {
+    // Local files in playlist order, entries are not checked for existence here, a missing one
+    // fails when its turn comes like any other input that cant be opened
+    pub fn parse(path: &Path) -> std::io::Result<Vec<PathBuf>> {
+        let bytes = std::fs::read(path)?;
+        // .m3u8 is UTF-8, plain .m3u often is too. Other encodings get replacement characters
+        let text = String::from_utf8_lossy(&bytes);
+        let folder = path.parent().unwrap_or(Path::new(""));
+        Ok(Self::parse_entries(&text, folder))
+    }
+
+    fn parse_entries(text: &str, folder: &Path) -> Vec<PathBuf> {
+        // Files saved on Windows often start with a byte order mark
+        let text = text.trim_start_matches('\u{feff}');
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            // Streams in a playlist would need the url handling of Source, only files for now
+            .filter(|line| !line.contains("://"))
+            // join keeps an absolute entry as it is
+            .map(|line| folder.join(line))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(text: &str) -> Vec<PathBuf> {
+        M3uParser::parse_entries(text, Path::new("/playlists"))
+    }
+
+    #[test]
+    fn test_comments_and_extinf_are_skipped() {
+        let text = "#EXTM3U\n#EXTINF:123,Artist - Title\nfirst.mp4\n# a comment\n#EXTINF:-1,Second\nsecond.mkv\n";
+        assert_eq!(entries(text), vec![PathBuf::from("/playlists/first.mp4"), PathBuf::from("/playlists/second.mkv")]);
+    }
+
+    #[test]
+    fn test_blank_lines_and_whitespace_are_ignored() {
+        let text = "\n  first.mp4  \r\n\r\n\t\nsecond.mkv\n\n";
+        assert_eq!(entries(text), vec![PathBuf::from("/playlists/first.mp4"), PathBuf::from("/playlists/second.mkv")]);
+    }
+
+    #[test]
+    fn test_relative_paths_join_the_playlist_folder() {
+        let text = "videos/first.mp4\n/home/me/second.mkv\n../third.webm\n";
+        assert_eq!(entries(text), vec![
+            PathBuf::from("/playlists/videos/first.mp4"),
+            PathBuf::from("/home/me/second.mkv"),
+            PathBuf::from("/playlists/../third.webm"),
+        ]);
+    }
+
+    #[test]
+    fn test_bom_and_urls() {
+        let text = "\u{feff}#EXTM3U\nfirst.mp4\nhttps://example.com/stream.m3u8\n";
+        assert_eq!(entries(text), vec![PathBuf::from("/playlists/first.mp4")]);
+        // Without the trim the BOM would stick to the first entry
+        assert_eq!(entries("\u{feff}first.mp4"), vec![PathBuf::from("/playlists/first.mp4")]);
+    }
+}
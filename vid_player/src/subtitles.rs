@@ -0,0 +1,24 @@
+// Subtitles shown over the video, loaded from a file next to it or decoded from the video itself
+// Only SubRip (.srt) files for now, other formats would get their own parser module here
+
+pub mod embedded;
+pub mod srt;
+
+use std::path::Path;
+use srt::{SrtParser, SubtitleCue};
+
+// video.srt for video.mp4, nothing when there is no such file
+// Not every .srt is UTF-8, invalid bytes become replacement characters instead of failing
+pub fn load_for_video(video: &Path) -> Vec<SubtitleCue> {
+    match std::fs::read(video.with_extension("srt")) {
+        Ok(bytes) => SrtParser::parse(&String::from_utf8_lossy(&bytes)),
+        Err(_) => Vec::new(),
+    }
+}
+
+// Cue on screen at time_s, when cues overlap the one that started last wins
+pub fn active_cue(cues: &[SubtitleCue], time_s: f64) -> Option<&SubtitleCue> {
+    cues.iter()
+        .filter(|cue| cue.start_s <= time_s && time_s < cue.end_s)
+        .max_by(|a, b| a.start_s.total_cmp(&b.start_s))
+}
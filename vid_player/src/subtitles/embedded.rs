@@ -0,0 +1,96 @@
+// Subtitle streams inside the video file (mkv and mp4 often carry several languages)
+// ffmpeg decodes every text format (SubRip, ASS, WebVTT, mov_text) to ASS dialogue lines, so
+// one function turns them into the same SubtitleCue the .srt parser makes. Picture based
+// subtitles (DVD, Blu-ray) would need their bitmaps drawn, those streams are left out.
+
+use super::srt::SubtitleCue;
+use ffmpeg_next::codec::Id;
+
+// How long a cue stays when neither the packet nor the decoder says
+const DEFAULT_CUE_SECS: f64 = 5.0;
+
+// One text subtitle stream of the input, S cycles through them
+pub struct SubtitleTrackInfo {
+    pub stream_index: usize,
+    pub language: String, // From the stream metadata, "und" when the file doesnt say
+    pub codec: String,
+    // Copies, so a decoder can be made after the demux thread took the input
+    pub parameters: ffmpeg_next::codec::Parameters,
+    pub time_base: ffmpeg_next::Rational,
+}
+
+impl SubtitleTrackInfo {
+    // None for streams that arent subtitles or not text
+    pub fn new(stream: &ffmpeg_next::Stream) -> Option<Self> {
+        let parameters = stream.parameters();
+        if !is_text_codec(parameters.id()) {
+            return None;
+        }
+        Some(Self {
+            stream_index: stream.index(),
+            language: stream.metadata().get("language").unwrap_or("und").to_string(),
+            codec: parameters.id().name().to_string(),
+            parameters: parameters.clone(),
+            time_base: stream.time_base(),
+        })
+    }
+}
+
+fn is_text_codec(id: Id) -> bool {
+    matches!(
+        id,
+        Id::SUBRIP | Id::SRT | Id::ASS | Id::SSA | Id::WEBVTT | Id::MOV_TEXT | Id::TEXT
+    )
+}
+
+// The cue of one decoded subtitle, times in seconds of the media
+// start_s is the packet time, the duration comes from the packet or else the decoder
+pub fn cue_from_subtitle(subtitle: &ffmpeg_next::Subtitle, start_s: f64, duration_s: f64) -> Option<SubtitleCue> {
+    let mut lines = Vec::new();
+    for rect in subtitle.rects() {
+        match rect {
+            ffmpeg_next::subtitle::Rect::Ass(ass) => lines.extend(ass_lines(ass.get())),
+            ffmpeg_next::subtitle::Rect::Text(text) => lines.extend(text.get().lines().map(str::to_string)),
+            ffmpeg_next::subtitle::Rect::Bitmap(_) => {}
+        }
+    }
+    lines.retain(|line| !line.trim().is_empty());
+    if lines.is_empty() {
+        return None;
+    }
+
+    let start_s = start_s + subtitle.start() as f64 / 1000.0;
+    let duration_s = if duration_s > 0.0 {
+        duration_s
+    } else if subtitle.end() > subtitle.start() {
+        (subtitle.end() - subtitle.start()) as f64 / 1000.0
+    } else {
+        DEFAULT_CUE_SECS
+    };
+    Some(SubtitleCue { start_s, end_s: start_s + duration_s, lines })
+}
+
+// "ReadOrder,Layer,Style,Name,MarginL,MarginR,MarginV,Effect,Text", the text can have commas
+// itself so only the first 8 split. {\i1} style overrides are dropped, \N and \n break the line
+// and \h is a space that doesnt break
+fn ass_lines(dialogue: &str) -> Vec<String> {
+    let text = dialogue.splitn(9, ',').nth(8).unwrap_or(dialogue);
+
+    let mut plain = String::new();
+    let mut in_override = false;
+    for c in text.chars() {
+        match c {
+            '{' => in_override = true,
+            '}' if in_override => in_override = false,
+            _ if !in_override => plain.push(c),
+            _ => {}
+        }
+    }
+
+    plain
+        .replace("\\h", " ")
+        .split("\\N")
+        .flat_map(|part| part.split("\\n"))
+        .map(|line| line.trim().to_string())
+        .collect()
+}
@@ -0,0 +1,128 @@
+// SubRip files are blocks separated by empty lines:
+//
+// 1
+// 00:00:01,000 --> 00:00:04,500
+// First line
+// Second line
+//
+// The counter is ignored, some files number wrong or not at all. Blocks without a valid timing
+// line are skipped instead of failing the whole file.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubtitleCue {
+    pub start_s: f64,
+    pub end_s: f64,
+    pub lines: Vec<String>,
+}
+
+pub struct SrtParser;
+
+impl SrtParser {
+    // Cues come back sorted by start time
+    pub fn parse(text: &str) -> Vec<SubtitleCue> {
+        // Files saved on Windows often start with a byte order mark
+        let text = text.trim_start_matches('\u{feff}');
+        let mut cues = Vec::new();
+        let mut lines = text.lines().map(|line| line.trim_end());
+
+        while let Some(line) = lines.next() {
+            let Some((start_s, end_s)) = parse_timing(line) else {
+                continue; // Counter, blank line or junk
+            };
+            let text_lines = lines.by_ref()
+                .take_while(|line| !line.is_empty())
+                .map(strip_tags)
+                .collect::<Vec<_>>();
+            if end_s > start_s {
+                cues.push(SubtitleCue { start_s, end_s, lines: text_lines });
+            }
+        }
+
+        cues.sort_by(|a, b| a.start_s.total_cmp(&b.start_s));
+        cues
+    }
+}
+
+// "00:00:01,000 --> 00:00:04,500", anything after the end time (position hints) is ignored
+fn parse_timing(line: &str) -> Option<(f64, f64)> {
+    let (start, end) = line.split_once("-->")?;
+    let end = end.split_whitespace().next()?;
+    Some((parse_timestamp(start.trim())?, parse_timestamp(end)?))
+}
+
+// hh:mm:ss,mmm, a dot instead of the comma shows up too
+fn parse_timestamp(timestamp: &str) -> Option<f64> {
+    let (clock, millis) = timestamp.split_once([',', '.']).unwrap_or((timestamp, "0"));
+    let mut parts = clock.split(':').map(|part| part.parse::<u32>().ok());
+    let (hours, minutes, seconds) = (parts.next()??, parts.next()??, parts.next()??);
+    if parts.next().is_some() {
+        return None;
+    }
+    let millis = millis.parse::<u32>().ok()?;
+    Some(hours as f64 * 3600.0 + minutes as f64 * 60.0 + seconds as f64 + millis as f64 / 1000.0)
+}
+
+// Drop <i>, <b>, <font ...> and friends, the bitmap font has no styles
+fn strip_tags(line: &str) -> String {
+    let mut text = String::with_capacity(line.len());
+    let mut in_tag = false;
+    for c in line.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_cues_after_a_bom() {
+        let text = "\u{feff}1\n00:00:01,000 --> 00:00:04,500\nFirst line\nSecond line\n\n2\n00:01:02,250 --> 00:01:03,000\nNext\n";
+        let cues = SrtParser::parse(text);
+        assert_eq!(cues, vec![
+            SubtitleCue { start_s: 1.0, end_s: 4.5, lines: vec!["First line".to_string(), "Second line".to_string()] },
+            SubtitleCue { start_s: 62.25, end_s: 63.0, lines: vec!["Next".to_string()] },
+        ]);
+    }
+
+    #[test]
+    fn test_timestamps_take_a_comma_or_a_dot() {
+        assert_eq!(parse_timestamp("01:02:03,456"), Some(3723.456));
+        assert_eq!(parse_timestamp("01:02:03.456"), Some(3723.456));
+        assert_eq!(parse_timestamp("00:00:07"), Some(7.0));
+        assert_eq!(parse_timestamp("00:07,000"), None);
+        assert_eq!(parse_timestamp("00:00:xx,000"), None);
+        // Position hints after the end time are ignored
+        assert_eq!(parse_timing("00:00:01.000 --> 00:00:02.500 X1:10 X2:20"), Some((1.0, 2.5)));
+        assert_eq!(parse_timing("00:00:01,000 -> 00:00:02,000"), None);
+    }
+
+    #[test]
+    fn test_junk_and_backwards_cues_are_skipped() {
+        let text = "1\nnot a timing line\nlost text\n\n2\n00:00:05,000 --> 00:00:05,000\nZero length\n\n3\n00:00:09,000 --> 00:00:08,000\nBackwards\n\n4\n00:00:10,000 --> 00:00:11,000\nKept\n";
+        let cues = SrtParser::parse(text);
+        assert_eq!(cues.len(), 1);
+        assert_eq!(cues[0].lines, vec!["Kept".to_string()]);
+    }
+
+    #[test]
+    fn test_tags_are_stripped() {
+        assert_eq!(strip_tags("<i>Hello</i> <font color=\"red\">world</font>"), "Hello world");
+        assert_eq!(strip_tags("1 > 0"), "1 > 0");
+        let cues = SrtParser::parse("1\n00:00:01,000 --> 00:00:02,000\n<b>Bold</b>\n");
+        assert_eq!(cues[0].lines, vec!["Bold".to_string()]);
+    }
+
+    #[test]
+    fn test_cues_are_sorted_by_start() {
+        let text = "2\n00:00:10,000 --> 00:00:12,000\nSecond\n\n1\n00:00:01,000 --> 00:00:02,000\nFirst\n";
+        let starts: Vec<f64> = SrtParser::parse(text).iter().map(|cue| cue.start_s).collect();
+        assert_eq!(starts, vec![1.0, 10.0]);
+    }
+}
@@ -0,0 +1,151 @@
+// Thumbnail mode, saves frames of the video as PNG without opening a window
+//   vid_player video.mp4 --thumbnail --at 12.5 --output cover.png
+//   vid_player video.mp4 --thumbnail --thumbnail-every 10 --output thumbs/frame.png
+// The second one writes frame_0001.png, frame_0002.png, ... one every 10 seconds from --at to the end.
+// No event loop, Pixels surface or audio stream, only ffmpeg: seek, decode until the frame at the
+// time, scale it to RGBA at the size of the video and write it with the image crate.
+
+use std::path::{Path, PathBuf};
+
+const DEFAULT_OUTPUT: &str = "thumbnail.png";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThumbnailOptions {
+    pub at_secs: f64, // --at <seconds>, the first (or only) frame
+    pub every_secs: Option<f64>, // --thumbnail-every <seconds>, one frame per step until the end
+    pub output: PathBuf, // --output <path.png>, numbered when there are several
+}
+
+impl Default for ThumbnailOptions {
+    fn default() -> Self {
+        Self { at_secs: 0.0, every_secs: None, output: PathBuf::from(DEFAULT_OUTPUT) }
+    }
+}
+
+// Saves the thumbnails, the paths that were written in order
+pub fn extract(
+    mut input_ctx: ffmpeg_next::format::context::Input,
+    options: &ThumbnailOptions,
+) -> Result<Vec<PathBuf>, String> {
+    let stream = input_ctx.streams().best(ffmpeg_next::media::Type::Video).ok_or("No video stream")?;
+    let stream_index = stream.index();
+    let time_base = f64::from(stream.time_base());
+    let context = ffmpeg_next::codec::context::Context::from_parameters(stream.parameters())
+        .map_err(|e| format!("Cant read the video codec parameters: {}", e))?;
+    let mut decoder = context.decoder().video().map_err(|e| format!("Cant open the video decoder: {}", e))?;
+
+    // Unknown for some streams (a live url), then the batch goes on until a frame cant be found
+    let duration_secs = match input_ctx.duration() {
+        duration if duration > 0 => Some(duration as f64 / ffmpeg_next::ffi::AV_TIME_BASE as f64),
+        _ => None,
+    };
+
+    let mut written = Vec::new();
+    // Only a single --at falls back to the last frame, a batch without a duration would never end
+    let take_last = options.every_secs.is_none();
+    for (i, target_secs) in target_times(options, duration_secs).enumerate() {
+        let frame = match decode_frame_at(&mut input_ctx, &mut decoder, stream_index, time_base, target_secs, take_last) {
+            Ok(Some(frame)) => frame,
+            // Past the last frame, the batch is done
+            Ok(None) => break,
+            // Some demuxers refuse to seek past the end instead
+            Err(_) if options.every_secs.is_some() && !written.is_empty() => break,
+            Err(e) => return Err(e),
+        };
+        let path = match options.every_secs {
+            Some(_) => numbered_path(&options.output, i + 1),
+            None => options.output.clone(),
+        };
+        save_png(&frame, &path)?;
+        println!("Saved {:.2}s to {}", target_secs, path.display());
+        written.push(path);
+    }
+    if written.is_empty() {
+        return Err(format!("--at {:.2}s is past the end of the video", options.at_secs));
+    }
+    Ok(written)
+}
+
+// at, at + every, at + 2 * every, ... before the end, only at without --thumbnail-every
+fn target_times(options: &ThumbnailOptions, duration_secs: Option<f64>) -> impl Iterator<Item = f64> {
+    let at_secs = options.at_secs;
+    let (count, step, end) = match options.every_secs {
+        Some(step) => (usize::MAX, step, duration_secs.unwrap_or(f64::INFINITY)),
+        // A single --at past the end still gets the last frame, see decode_frame_at
+        None => (1, 0.0, f64::INFINITY),
+    };
+    (0..count).map(move |i| at_secs + i as f64 * step).take_while(move |time| *time < end)
+}
+
+// frame.png to frame_0007.png, so a batch sorts by time in a file manager
+fn numbered_path(output: &Path, number: usize) -> PathBuf {
+    let stem = output.file_stem().and_then(|stem| stem.to_str()).unwrap_or("thumbnail");
+    output.with_file_name(format!("{}_{:04}.png", stem, number))
+}
+
+// First frame at or after target_secs. The seek lands on the keyframe before it, the frames in
+// between are decoded and dropped. When the input ends before target_secs it is None, or with
+// take_last the last frame, so a time a bit past the end still gives the final frame.
+fn decode_frame_at(
+    input_ctx: &mut ffmpeg_next::format::context::Input,
+    decoder: &mut ffmpeg_next::decoder::Video,
+    stream_index: usize,
+    time_base: f64,
+    target_secs: f64,
+    take_last: bool,
+) -> Result<Option<ffmpeg_next::util::frame::Video>, String> {
+    let ts = (target_secs * ffmpeg_next::ffi::AV_TIME_BASE as f64) as i64;
+    input_ctx.seek(ts, ..ts).map_err(|e| format!("Cant seek to {:.2}s: {}", target_secs, e))?;
+    decoder.flush();
+
+    let mut frame = ffmpeg_next::util::frame::Video::empty();
+    let mut last = None;
+    let mut at_target = |decoder: &mut ffmpeg_next::decoder::Video, last: &mut Option<ffmpeg_next::util::frame::Video>| {
+        while decoder.receive_frame(&mut frame).is_ok() {
+            let pts_secs = frame.pts().unwrap_or(0) as f64 * time_base;
+            if pts_secs + 0.001 >= target_secs {
+                return Some(frame.clone());
+            }
+            *last = Some(frame.clone());
+        }
+        None
+    };
+
+    for (stream, packet) in input_ctx.packets() {
+        if stream.index() != stream_index || decoder.send_packet(&packet).is_err() {
+            continue;
+        }
+        if let Some(frame) = at_target(decoder, &mut last) {
+            return Ok(Some(frame));
+        }
+    }
+    let _ = decoder.send_eof();
+    if let Some(frame) = at_target(decoder, &mut last) {
+        return Ok(Some(frame));
+    }
+    if !take_last {
+        return Ok(None);
+    }
+    last.map(Some).ok_or(format!("No video frame at {:.2}s", target_secs))
+}
+
+// Software scaler to RGBA at the size of the frame, the same conversion playback does
+fn save_png(frame: &ffmpeg_next::util::frame::Video, path: &Path) -> Result<(), String> {
+    let (width, height) = (frame.width(), frame.height());
+    let mut scaler = ffmpeg_next::software::scaling::Context::get(
+        frame.format(),
+        width,
+        height,
+        ffmpeg_next::format::Pixel::RGBA,
+        width,
+        height,
+        ffmpeg_next::software::scaling::flag::Flags::BILINEAR,
+    )
+    .map_err(|e| format!("Cant convert the frame to RGBA: {}", e))?;
+    let mut rgba_frame = ffmpeg_next::util::frame::Video::empty();
+    scaler.run(frame, &mut rgba_frame).map_err(|e| format!("Cant convert the frame to RGBA: {}", e))?;
+
+    let data = super::extract_rgba_data(&rgba_frame, width, height);
+    image::save_buffer(path, &data, width, height, image::ColorType::Rgba8)
+        .map_err(|e| format!("Cant write {}: {}", path.display(), e))
+}
@@ -0,0 +1,97 @@
+// Digital zoom: a smaller piece of the decoded frame is stretched over the whole frame buffer
+// There are no more pixels than the video has, so this only makes them bigger. Bilinear
+// interpolation blends the 4 closest source pixels, so the result looks soft instead of blocky.
+// Pixels then scales the frame buffer to the window like it does without zoom.
+
+pub const MIN_ZOOM: f32 = 1.0;
+pub const MAX_ZOOM: f32 = 10.0;
+
+// Part of the frame shown at zoom, x, y, width and height in source pixels
+// Centered on center (0 to 1 across the frame), pushed back inside when that would cross an edge
+pub fn crop_rect(width: u32, height: u32, zoom: f32, center: (f32, f32)) -> (f32, f32, f32, f32) {
+    let zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+    let (crop_width, crop_height) = (width as f32 / zoom, height as f32 / zoom);
+    let x = (center.0 * width as f32 - crop_width / 2.0).clamp(0.0, width as f32 - crop_width);
+    let y = (center.1 * height as f32 - crop_height / 2.0).clamp(0.0, height as f32 - crop_height);
+    (x, y, crop_width, crop_height)
+}
+
+// Write the crop of src scaled to width x height into dst, both RGBA frames of that size
+pub fn scale_crop(src: &[u8], dst: &mut [u8], width: u32, height: u32, zoom: f32, center: (f32, f32)) {
+    let (crop_x, crop_y, crop_width, crop_height) = crop_rect(width, height, zoom, center);
+    let (scale_x, scale_y) = (crop_width / width as f32, crop_height / height as f32);
+    let (max_x, max_y) = (width as f32 - 1.0, height as f32 - 1.0);
+
+    for dy in 0..height as usize {
+        // Pixel centers line up, hence the half pixel on both sides
+        let sy = (crop_y + (dy as f32 + 0.5) * scale_y - 0.5).clamp(0.0, max_y);
+        let (y0, fy) = (sy as usize, sy.fract());
+        let y1 = (y0 + 1).min(height as usize - 1);
+
+        for dx in 0..width as usize {
+            let sx = (crop_x + (dx as f32 + 0.5) * scale_x - 0.5).clamp(0.0, max_x);
+            let (x0, fx) = (sx as usize, sx.fract());
+            let x1 = (x0 + 1).min(width as usize - 1);
+
+            let at = |x: usize, y: usize| (y * width as usize + x) * 4;
+            let (top_left, top_right) = (at(x0, y0), at(x1, y0));
+            let (bottom_left, bottom_right) = (at(x0, y1), at(x1, y1));
+            let out = at(dx, dy);
+            for channel in 0..4 {
+                let top = src[top_left + channel] as f32 * (1.0 - fx) + src[top_right + channel] as f32 * fx;
+                let bottom = src[bottom_left + channel] as f32 * (1.0 - fx) + src[bottom_right + channel] as f32 * fx;
+                dst[out + channel] = (top * (1.0 - fy) + bottom * fy).round() as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Solid RGBA frame, with one pixel of another color at (x, y)
+    fn frame_with_dot(width: u32, height: u32, dot: (u32, u32)) -> Vec<u8> {
+        let mut frame = vec![0, 0, 0, 255].repeat((width * height) as usize);
+        let at = ((dot.1 * width + dot.0) * 4) as usize;
+        frame[at..at + 4].copy_from_slice(&[255, 0, 0, 255]);
+        frame
+    }
+
+    #[test]
+    fn test_zoom_one_is_the_whole_frame() {
+        assert_eq!(crop_rect(100, 50, 1.0, (0.5, 0.5)), (0.0, 0.0, 100.0, 50.0));
+        // The center doesnt matter when the crop is the frame
+        assert_eq!(crop_rect(100, 50, 1.0, (0.9, 0.1)), (0.0, 0.0, 100.0, 50.0));
+        // Below the minimum counts as no zoom
+        assert_eq!(crop_rect(100, 50, 0.5, (0.5, 0.5)), (0.0, 0.0, 100.0, 50.0));
+
+        let src = frame_with_dot(8, 6, (3, 2));
+        let mut dst = vec![0; src.len()];
+        scale_crop(&src, &mut dst, 8, 6, 1.0, (0.5, 0.5));
+        assert_eq!(dst, src);
+    }
+
+    #[test]
+    fn test_zoom_ten_is_a_tenth_around_the_center() {
+        assert_eq!(crop_rect(100, 50, 10.0, (0.5, 0.5)), (45.0, 22.5, 10.0, 5.0));
+        // Above the maximum is held at it
+        assert_eq!(crop_rect(100, 50, 20.0, (0.5, 0.5)), (45.0, 22.5, 10.0, 5.0));
+    }
+
+    #[test]
+    fn test_center_at_the_edges_stays_inside() {
+        assert_eq!(crop_rect(100, 50, 10.0, (0.0, 0.0)), (0.0, 0.0, 10.0, 5.0));
+        assert_eq!(crop_rect(100, 50, 10.0, (1.0, 1.0)), (90.0, 45.0, 10.0, 5.0));
+        assert_eq!(crop_rect(100, 50, 2.0, (-1.0, 2.0)), (0.0, 25.0, 50.0, 25.0));
+
+        // Zoomed into the top left corner, its pixel fills the top left of the buffer
+        // up to the middle, where blending with its neighbours starts
+        let src = frame_with_dot(10, 10, (0, 0));
+        let mut dst = vec![0; src.len()];
+        scale_crop(&src, &mut dst, 10, 10, 10.0, (0.0, 0.0));
+        assert_eq!(dst[..4], [255, 0, 0, 255]);
+        let middle = (4 * 10 + 4) * 4;
+        assert_eq!(dst[middle..middle + 4], [255, 0, 0, 255]);
+    }
+}
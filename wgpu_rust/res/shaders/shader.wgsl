@@ -0,0 +1,259 @@
+// Defines how GPU should read the instance buffer and map those bytes into the shader
+// A struct in a shader tells the GPU: For every instances i draw, look at the current memory address
+// and extract these four vectors
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+    @location(9) color: vec4<f32>, // Per instance tint
+}
+
+struct RenderModeUniform {
+    mode: u32, // 0 normal, 1 depth, 2 grayscale, 3 UV, see graphics/render_mode.rs
+    selected_instance: u32, // Instance picked with the mouse
+    padding1: u32,
+    padding2: u32,
+};
+
+const NO_SELECTION: u32 = 0xffffffffu;
+
+@group(3) @binding(0)
+var<uniform> render_mode: RenderModeUniform;
+
+// Shared clock and screen size, see graphics/globals.rs
+struct GlobalsUniform {
+    time: f32, // Seconds since start
+    delta: f32, // Seconds since the last frame
+    resolution: vec2<f32>, // Surface size in pixels
+}
+@group(3) @binding(1)
+var<uniform> globals: GlobalsUniform;
+
+@group(2) @binding(0)
+var depth_tex: texture_depth_2d;
+@group(2) @binding(1)
+var depth_sampler: sampler;
+
+// Vertex shader (positioning)
+// Logic performed for each vertex
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>, // View-projection matrix for transforming vertices
+    view_position: vec4<f32>, // Camera eye position, needed for the reflection direction
+}
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform; // Uniform buffer for camera data
+
+// Data comes from vertex buffer
+struct VertexInput {
+    // Location means the layout location of the attribute in the vertex buffer
+    @location(0) position: vec3<f32>, // Input attribute for vertex position
+    @location(1) tex_coords: vec2<f32>,  // Input attribute for texture coordinates
+    @location(2) normal: vec3<f32>, // Input attribute for vertex normal (for lighting calculations)
+}
+
+// Data into rasterizer and fragment shader
+struct VertexOutput {
+    // Vertex position in clip space(meaning inside our viewport)
+    // builtin position is in framebuffer coordinates, meaning (0,0) is bottom-left
+    // while clip space is normalized device coordinates where (-1,-1) is bottom-left
+    @builtin(position) clip_position: vec4<f32>, // Tells GPU about clip space position of vertex
+    @location(0) tex_coords: vec2<f32>, // Pass texture coordinates to fragment shader
+    @location(1) world_normal: vec3<f32>, // Pass normal to fragment shader for lighting calculations
+    @location(2) world_position: vec3<f32>, // Pass world position to fragment shader for lighting calculations
+    // Integers cant be interpolated between vertices, flat means use the value as is
+    @location(3) @interpolate(flat) instance_index: u32,
+    @location(4) color: vec4<f32>,
+    // CLIP_DISTANCES_OUTPUT
+};
+
+// What the fragment shader reads of VertexOutput
+// Same fields, but clip distances are vertex only, so they cant be in the fragment input
+struct FragmentInput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) world_position: vec3<f32>,
+    @location(3) @interpolate(flat) instance_index: u32,
+    @location(4) color: vec4<f32>,
+};
+
+// Need the light position data in this shader to actually do light calculations based on its position and color
+struct Light {
+    position: vec3<f32>,
+    color: vec3<f32>,
+}
+@group(4) @binding(0)
+var<uniform> light: Light;
+
+// Distance fog, see FogUniform in graphics/light.rs
+struct Fog {
+    color: vec4<f32>, // Usually the clear color
+    start: f32, // No fog closer than this
+    end: f32, // Only fog from here on
+    enabled: u32,
+}
+@group(4) @binding(1)
+var<uniform> fog: Fog;
+
+// Model transform, uniform scale applied to the local vertex position
+struct TransformUniform {
+    scale: f32,
+}
+@group(5) @binding(0)
+var<uniform> transform: TransformUniform;
+
+// Environment cubemap captured by the closest light probe
+// strength is 0 when no probe is close enough, so nothing gets reflected
+struct ProbeUniform {
+    strength: f32,
+}
+@group(6) @binding(0)
+var env_map: texture_cube<f32>;
+@group(6) @binding(1)
+var env_sampler: sampler;
+@group(6) @binding(2)
+var<uniform> probe: ProbeUniform;
+
+// Optional clip plane for cross sections, see graphics/clip.rs
+struct ClipUniform {
+    plane: vec4<f32>, // xyz normal, w distance from the origin
+    enabled: u32,
+}
+@group(7) @binding(0)
+var<uniform> clip: ClipUniform;
+
+// Negative on the side of the plane that gets cut away
+fn clip_distance(world_position: vec3<f32>) -> f32 {
+    if (clip.enabled == 0u) {
+        return 1.0;
+    }
+    return dot(clip.plane.xyz, world_position) - clip.plane.w;
+}
+
+@vertex // Signals its an entry point for the vertex shader
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    // WGPU cant handle mat4x4 as input, so we reconstruct it here from 4 vec4s
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    var out: VertexOutput;
+    // Passing data from vertex shader to fragment shader so it can do texturing and lighting calculations
+    out.tex_coords = model.tex_coords;
+    out.world_normal = model.normal;
+    out.instance_index = instance_index;
+    out.color = instance.color;
+
+    // Converting to World Space (Model position is relative to itself, bringing model matrix moves vertex to the world)
+    // Scale happens in local space first, so every instance grows around its own center
+    var world_position: vec4<f32> = model_matrix * vec4<f32>(model.position * transform.scale, 1.0);
+    out.world_position = world_position.xyz;
+
+    // Converting to Clip Space (this is where the Camera happens)
+    out.clip_position = camera.view_proj * world_position;
+    // Hardware clip plane, only in the CLIP_DISTANCES variant of this shader
+    // CLIP_DISTANCES_WRITE
+    return out;
+}
+
+// Fragment shader (coloring) fragment shaders runs per pixel
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>; // 2D texture bound to group 0 binding 0
+@group(0) @binding(1)
+var s_diffuse: sampler; // Sampler bound to group 0 binding 1
+
+// Per material parameters next to the texture, MaterialUniform in model.rs
+struct Material {
+    base_color: vec4<f32>, // Multiplies the texture, Kd in the mtl file
+    metallic: f32,
+    roughness: f32, // 0 is a mirror like highlight, 1 no highlight at all
+}
+@group(0) @binding(2)
+var<uniform> material: Material;
+
+@fragment
+fn fs_main(in: FragmentInput) -> @location(0) vec4<f32> {
+    // Software clip plane, does nothing when the rasterizer already clipped
+    if (clip_distance(in.world_position) < 0.0) {
+        discard;
+    }
+
+    // If render mode is 1, visualize the depth buffer instead of the texture
+    if (render_mode.mode == 1u) {
+        let coords = vec2<i32>(in.clip_position.xy);
+            let d = textureLoad(depth_tex, coords, 0);
+
+            // If d is 0.99, this becomes 0.01.
+            // If d is 1.0 (background), this becomes 0.0.
+            let visualize = 1.0 - d;
+
+            // Multiply by a huge number to force it to show up
+            return vec4<f32>(vec3<f32>(visualize * 100.0), 1.0);
+    }
+
+    // Mode 3, texture coordinates as colors: u is red, v is green
+    if (render_mode.mode == 3u) {
+        return vec4<f32>(fract(in.tex_coords), 0.0, 1.0);
+    }
+
+    // normal textured rendering
+    // Tinted by the instance color, alpha included so an instance can be see through too
+    let object_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords) * material.base_color * in.color;
+
+    // Simple ambient light
+    let ambient_strenght = 0.1;
+    let ambient_color = light.color * ambient_strenght;
+
+    // Diffuse light
+    let light_dir = normalize(light.position - in.world_position);
+    let diffuse_strenght = max(dot(in.world_normal, light_dir), 0.0);
+    let diffuse_color = light.color * diffuse_strenght;
+
+    // Metals have no diffuse part, their color is all in the highlight
+    var result = (ambient_color + diffuse_color * (1.0 - material.metallic)) * object_color.xyz;
+
+    // Blinn-Phong specular, the exponent from the roughness the same way resources.rs turns the
+    // mtl Ns into roughness. Rough surfaces get a wide and weak highlight, at 1 there is none
+    let view_dir = normalize(in.world_position - camera.view_position.xyz);
+    let half_dir = normalize(light_dir - view_dir);
+    let roughness = clamp(material.roughness, 0.05, 1.0);
+    let shininess = max(2.0 / (roughness * roughness) - 2.0, 1.0);
+    let specular_strength = pow(max(dot(normalize(in.world_normal), half_dir), 0.0), shininess) * (1.0 - roughness);
+    let specular_color = mix(light.color, light.color * object_color.rgb, material.metallic);
+    result += specular_color * specular_strength;
+
+    // Reflection, bounce the view direction off the surface and look it up in the cubemap
+    let reflect_dir = reflect(view_dir, normalize(in.world_normal));
+    let reflection = textureSample(env_map, env_sampler, reflect_dir).rgb;
+    result = mix(result, reflection, probe.strength);
+
+    // Tint the instance selected with mouse picking
+    if (render_mode.selected_instance != NO_SELECTION && in.instance_index == render_mode.selected_instance) {
+        // Pulses between 20% and 50% tint so the selection stands out
+        let pulse = 0.35 + 0.15 * sin(globals.time * 4.0);
+        result = mix(result, vec3<f32>(1.0, 0.6, 0.1), pulse);
+    }
+
+    // Fog by distance to the camera instead of the depth along the view direction,
+    // that way it doesnt move around when the camera only turns
+    if (fog.enabled != 0u) {
+        let distance = length(in.world_position - camera.view_position.xyz);
+        let amount = clamp((distance - fog.start) / (fog.end - fog.start), 0.0, 1.0);
+        result = mix(result, fog.color.rgb, amount);
+    }
+
+    // Mode 2, grayscale of the finished color, weighted like the eye sees brightness (Rec. 709)
+    if (render_mode.mode == 2u) {
+        result = vec3<f32>(dot(result, vec3<f32>(0.2126, 0.7152, 0.0722)));
+    }
+
+    return vec4<f32>(result, object_color.a);
+}
\ No newline at end of file
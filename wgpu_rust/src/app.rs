@@ -1,23 +1,156 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 use winit::{
     application::ApplicationHandler, event::*, event_loop::{ActiveEventLoop},
-    keyboard::PhysicalKey, window::Window
+    keyboard::PhysicalKey, window::WindowId,
 };
 
 use crate::{state::State, input::InputHandler};
-use crate::input::InputAction;
+use crate::input::{GamepadHandler, InputAction, MOUSE_COLOR_MAPPING};
+use crate::graphics::present_mode::PresentModePreference;
+use crate::config::AppConfig;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::config::WindowConfig;
 
 // THE ORCHESTRATOR
 // Manages OS lifecycle. Speaks to winit to create windows, handle events, etc
 // Does not care about rendering, but that there is a window to render to
 pub struct App {
-    state: Option<State>,
+    // One State per window, each with its own surface, camera and device
+    // Events are routed by the id of the window they happened in
+    states: HashMap<WindowId, State>,
+    // The window created at startup, closing it ends the app, its size is the one saved
+    main_window: Option<WindowId>,
+    // Last cursor position in physical pixels, MouseInput events dont carry a position
+    cursor_position: (f64, f64),
+    // We only try to rebuild State once after losing the device, if it happens again we give up
+    recovered_from_device_loss: bool,
+    // CSV file for the GPU pass timings, from --trace-file
+    trace_file: Option<PathBuf>,
+    // From WGPU_PRESENT_MODE or vsync in the config, F9 changes the mode of the running State
+    present_mode_preference: PresentModePreference,
+    // Title, size and the rest of how the window is created, see config.rs
+    config: AppConfig,
+    // Sticks move the camera of the window being redrawn, like the keys of the focused one
+    gamepad: GamepadHandler,
+    // The browser cant block on State::new, it is built in the background and sent back through
+    // the event loop as a user event
+    #[cfg(target_arch = "wasm32")]
+    proxy: Option<winit::event_loop::EventLoopProxy<State>>,
 }
 
 impl App  {
-    pub fn new() -> Self {
+    pub fn new(
+        trace_file: Option<PathBuf>,
+        config: AppConfig,
+        #[cfg(target_arch = "wasm32")] event_loop: &winit::event_loop::EventLoop<State>,
+    ) -> Self {
+        // The env var is the quicker one to change, so it wins over the file
+        let present_mode_preference = match PresentModePreference::from_env() {
+            PresentModePreference::Auto => config.present_mode_preference(),
+            preference => preference,
+        };
+        if config.msaa > 1 {
+            log::warn!("msaa = {} in the config, the renderer has no MSAA yet and draws without it", config.msaa);
+        }
         Self {
-            state: None,
+            states: HashMap::new(),
+            main_window: None,
+            cursor_position: (0.0, 0.0),
+            recovered_from_device_loss: false,
+            trace_file,
+            present_mode_preference,
+            config,
+            gamepad: GamepadHandler::new(),
+            #[cfg(target_arch = "wasm32")]
+            proxy: Some(event_loop.create_proxy()),
+        }
+    }
+
+    // A freshly built State, from pollster on native or from the user event on the web
+    // The first one belongs to the main window, only that one writes the GPU trace
+    fn set_state(&mut self, mut state: State) {
+        let id = state.window().id();
+        if self.main_window.is_none() {
+            self.main_window = Some(id);
+            if let Some(path) = &self.trace_file {
+                state.start_gpu_trace(path);
+            }
+        }
+        Self::update_title(&state, &self.config.title);
+        self.states.insert(id, state);
+    }
+
+    // Another window on the same scene, looking at the instance grid from above
+    // Its State is built from scratch like the first one, nothing is shared between the two
+    fn open_window(&mut self, event_loop: &ActiveEventLoop) {
+        #[cfg(target_arch = "wasm32")]
+        {
+            let _ = event_loop;
+            log::warn!("The web version only has the page canvas, no second window");
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let window_attributes = self.config.window_attributes()
+                .with_title(format!("{} - top view", self.config.title));
+            let window = match event_loop.create_window(window_attributes) {
+                Ok(window) => Arc::new(window),
+                Err(e) => {
+                    log::error!("Unable to open another window: {}", e);
+                    return;
+                }
+            };
+            match pollster::block_on(State::new(window, self.present_mode_preference)) {
+                Ok(mut state) => {
+                    state.set_camera_view((0.0, 25.0, 20.0).into(), (0.0, 0.0, 0.0).into());
+                    state.window().request_redraw();
+                    self.set_state(state);
+                    log::info!("Opened window {}", self.states.len());
+                }
+                Err(e) => log::error!("Unable to create the renderer for the new window: {:#}", e),
+            }
+        }
+    }
+
+    // Present mode in the title, so the effect of F9 can be checked against the frame rate
+    // The drawn model is in there too, Space changes it
+    fn update_title(state: &State, title: &str) {
+        let model = state.active_model_name().unwrap_or("no model");
+        state.window().set_title(&format!("{} - {} - {:?}", title, model, state.present_mode()));
+    }
+
+    // Everything in State (buffers, pipelines, textures) belongs to the lost device, so we throw
+    // it all away and build a new State on the same window
+    fn recover_from_device_loss(&mut self, event_loop: &ActiveEventLoop, window_id: WindowId) {
+        let Some(old_state) = self.states.remove(&window_id) else {
+            return;
+        };
+
+        if self.recovered_from_device_loss {
+            log::error!("Device lost again after rebuilding, giving up");
+            event_loop.exit();
+            return;
+        }
+        self.recovered_from_device_loss = true;
+
+        let window = old_state.window().clone();
+        let present_mode_preference = PresentModePreference::from(old_state.present_mode());
+        drop(old_state);
+
+        match pollster::block_on(State::new(window, present_mode_preference)) {
+            Ok(mut state) => {
+                log::warn!("Device lost, rebuilt the renderer");
+                let size = state.window().inner_size();
+                state.resize(size.width, size.height);
+                state.window().request_redraw();
+                self.states.insert(window_id, state);
+            }
+            Err(e) => {
+                log::error!("Unable to rebuild the renderer after device loss: {:#}", e);
+                event_loop.exit();
+            }
         }
     }
 }
@@ -28,14 +161,81 @@ impl App  {
 // Servers as the controller that tells the WGPU engine when to update and render and redraw
 impl ApplicationHandler<State> for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        #[allow(unused_mut)] // To avoid warnings on non-wasm32 targets
-        let mut window_attributes = Window::default_attributes();
+        // Coming back from suspended, the windows and States are still there, only the surfaces
+        // were dropped. Desktop only calls resumed once at startup, so it never gets here
+        if !self.states.is_empty() {
+            for state in self.states.values_mut() {
+                match state.resume() {
+                    Ok(()) => state.window().request_redraw(),
+                    Err(e) => {
+                        log::error!("Unable to recreate the surface after resuming: {:#}", e);
+                        event_loop.exit();
+                    }
+                }
+            }
+            return;
+        }
+
+        let mut window_attributes = self.config.window_attributes();
+
+        // Same size as when the app was last closed, the size from AppConfig the first time
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(config) = WindowConfig::load() {
+            window_attributes = window_attributes
+                .with_inner_size(winit::dpi::PhysicalSize::new(config.width, config.height));
+        }
+
+        // On the web the window draws into the canvas of assets/index.html
+        #[cfg(target_arch = "wasm32")]
+        {
+            use wasm_bindgen::JsCast;
+            use winit::platform::web::WindowAttributesExtWebSys;
+
+            let canvas = web_sys::window()
+                .and_then(|window| window.document())
+                .and_then(|document| document.get_element_by_id("canvas"))
+                .and_then(|element| element.dyn_into::<web_sys::HtmlCanvasElement>().ok());
+            window_attributes = window_attributes.with_canvas(canvas);
+        }
 
         // Create the window
         let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
 
         // If we are not on web use pollster
-        self.state = Some(pollster::block_on(State::new(window)).unwrap());
+        #[cfg(not(target_arch = "wasm32"))]
+        match pollster::block_on(State::new(window, self.present_mode_preference)) {
+            Ok(state) => self.set_state(state),
+            Err(e) => {
+                log::error!("Unable to create the renderer: {:#}", e);
+                event_loop.exit();
+            }
+        }
+
+        // On the web State::new runs on the browser's executor, user_event picks up the result
+        // The proxy is taken so a second resumed doesnt build another State
+        #[cfg(target_arch = "wasm32")]
+        if let Some(proxy) = self.proxy.take() {
+            let present_mode_preference = self.present_mode_preference;
+            wasm_bindgen_futures::spawn_local(async move {
+                match State::new(window, present_mode_preference).await {
+                    Ok(state) => {
+                        if proxy.send_event(state).is_err() {
+                            log::error!("Event loop closed before the renderer was created");
+                        }
+                    }
+                    Err(e) => log::error!("Unable to create the renderer: {:#}", e),
+                }
+            });
+        }
+    }
+
+    // The State built in resumed on the web
+    // The canvas may have been resized while it was loading, the surface still has the old size
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, mut state: State) {
+        let size = state.window().inner_size();
+        state.resize(size.width, size.height);
+        state.window().request_redraw();
+        self.set_state(state);
     }
 
     // Handle window events like resize, close, redraw, keyboard input
@@ -43,34 +243,76 @@ impl ApplicationHandler<State> for App {
     fn window_event(
         &mut self,
         event_loop: &ActiveEventLoop,
-        _window_id: winit::window::WindowId,
+        window_id: WindowId,
         event: WindowEvent,
     ) {
-        let state = match &mut self.state {
+        // Rebuild before touching anything of the old device
+        if matches!(event, WindowEvent::RedrawRequested)
+            && self.states.get(&window_id).is_some_and(State::is_device_lost)
+        {
+            self.recover_from_device_loss(event_loop, window_id);
+            return;
+        }
+
+        // Closing another window only drops its State, the main one takes the app with it
+        if matches!(event, WindowEvent::CloseRequested) && self.main_window != Some(window_id) {
+            self.states.remove(&window_id);
+            return;
+        }
+
+        let state = match self.states.get_mut(&window_id) {
             Some(canvas) => canvas,
             None => return,
         };
 
+        // Overlay gets the event first, the scene only sees what egui doesnt want
+        #[cfg(feature = "gui")]
+        if state.gui_wants_event(&event) {
+            return;
+        }
+
         match event {
             WindowEvent::CloseRequested => event_loop.exit(),
             WindowEvent::Resized(size) => state.resize(size.width, size.height),
+            // The new physical size is configured right away, so the frame drawn before the
+            // Resized event that follows isnt stretched over the bigger (or smaller) window
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                state.set_scale_factor(scale_factor);
+                let size = state.window().inner_size();
+                state.resize(size.width, size.height);
+            }
+            WindowEvent::Occluded(occluded) => state.set_occluded(occluded),
             WindowEvent::RedrawRequested => {
+                if let Some((left, right)) = self.gamepad.poll() {
+                    state.camera_controller.handle_sticks(left, right);
+                }
                 state.update();
                 match state.render() {
                     Ok(_) => {}
                     // Reconfigure surface if lost
                     Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
-                        let size = state.window.inner_size();
+                        let size = state.window().inner_size();
                         state.resize(size.width, size.height);
                     }
+                    // Nothing we can free on our side, keep going would just log this every frame
+                    Err(wgpu::SurfaceError::OutOfMemory) => {
+                        log::error!("Out of memory while getting the next frame, exiting");
+                        event_loop.exit();
+                    }
+                    // Frame took too long to be ready, skip it and try again next redraw
+                    Err(wgpu::SurfaceError::Timeout) => {
+                        log::warn!("Timed out getting the next frame, skipping it");
+                    }
                     Err(e) => {
                         log::error!("Unable to render {}", e);
                     }
                 }
             }
             WindowEvent::CursorMoved {position, ..} => {
+                self.cursor_position = (position.x, position.y);
                 let config = state.config();
-                let color = InputHandler::calculate_color_from_mouse(
+                let color = InputHandler::color_from_mouse(
+                    MOUSE_COLOR_MAPPING,
                     position.x,
                     position.y,
                     config.width,
@@ -78,6 +320,14 @@ impl ApplicationHandler<State> for App {
                 );
                 state.set_clear_color(color);
             }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => {
+                let (x, y) = self.cursor_position;
+                state.pick_instance(x, y);
+            }
             WindowEvent::KeyboardInput {
                 event:
                 KeyEvent {
@@ -91,16 +341,85 @@ impl ApplicationHandler<State> for App {
                 // Handle application-level input
                 let action = InputHandler::handle_key(event_loop, code, key_state.is_pressed());
                 match action {
-                    InputAction::ToggleShape => state.toggle_shape(),
-                    InputAction::ToggleDepthVisualization => state.toggle_depth_visualization(),
+                    InputAction::CycleModel => {
+                        state.cycle_model();
+                        Self::update_title(state, &self.config.title);
+                    }
+                    InputAction::CycleRenderMode => state.cycle_render_mode(),
+                    InputAction::ToggleComputeAnimation => state.toggle_compute_animation(),
+                    InputAction::ToggleSkinning => state.toggle_skinning(),
+                    InputAction::ScaleUp => state.adjust_scale(1.0),
+                    InputAction::ScaleDown => state.adjust_scale(-1.0),
+                    InputAction::BakeProbes => state.bake_light_probes(),
+                    InputAction::ToggleCulling => state.toggle_culling(),
+                    InputAction::ToggleDebugLines => state.toggle_debug_lines(),
+                    InputAction::ShowBoundingBoxes(show) => state.set_show_bounding_boxes(show),
+                    InputAction::ToggleXray => state.toggle_xray(),
+                    InputAction::ToggleMultiview => state.toggle_multiview(),
+                    InputAction::ReloadModel => state.reload_model(),
+                    InputAction::ReloadShader => state.reload_shader(),
+                    InputAction::ToggleClipPlane => state.toggle_clip_plane(),
+                    InputAction::ToggleTransparentQuads => state.toggle_transparent_quads(),
+                    InputAction::ToggleMotionVectors => state.toggle_motion_vectors(),
+                    InputAction::ToggleTaa => state.toggle_taa(),
+                    InputAction::FrameScene => state.frame_scene(),
+                    InputAction::ToggleSubsurfaceScattering => state.set_sss_enabled(!state.is_sss_enabled()),
+                    InputAction::ToggleCloth => state.toggle_cloth(),
+                    InputAction::ToggleSplitScreen => state.toggle_split_screen(),
+                    InputAction::ToggleWater => state.toggle_water(),
+                    InputAction::ToggleFog => state.toggle_fog(),
+                    InputAction::ToggleLodDebug => state.toggle_lod_debug(),
+                    InputAction::CyclePresentMode => {
+                        state.cycle_present_mode();
+                        Self::update_title(state, &self.config.title);
+                    }
                     InputAction::Exit => event_loop.exit(),
                     _ => {}
                 }
                 
                 // Handle camera movement input
                 state.camera_controller.handle_key(code, is_pressed);
+
+                // Goes through App like the OS events would, state is not borrowed anymore here
+                match action {
+                    InputAction::SimulateSuspendResume => {
+                        log::info!("Simulating a suspend/resume cycle");
+                        self.suspended(event_loop);
+                        self.resumed(event_loop);
+                    }
+                    InputAction::OpenWindow => self.open_window(event_loop),
+                    _ => {}
+                }
             }
             _ => {}
         }
     }
+
+    // Android destroys the native window when the app goes to the background, the surface
+    // made from it is dropped here and resumed makes a new one
+    fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
+        for state in self.states.values_mut() {
+            state.suspend();
+        }
+    }
+
+    // Last callback before the event loop stops, the trace file is flushed here
+    // Every way out (close button, Esc) ends up here, so it is also where the window size is saved
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        for state in self.states.values_mut() {
+            state.finalize_profiler();
+            state.report_gpu_errors();
+        }
+
+        // Only the main window, the others open at the configured size every time
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(state) = self.main_window.and_then(|id| self.states.get(&id)) {
+            let size = state.window().inner_size();
+            if let Some(config) = WindowConfig::new(size.width, size.height)
+                && let Err(e) = config.save()
+            {
+                log::warn!("Unable to save the window size: {:#}", e);
+            }
+        }
+    }
 }
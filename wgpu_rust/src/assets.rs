@@ -0,0 +1,5 @@
+// Asset streaming and caching, loading files without blocking the render loop and keeping
+// one GPU copy of each
+
+pub(crate) mod loader;
+pub(crate) mod manager;
@@ -0,0 +1,182 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use crate::resources::{self, ModelData};
+
+// Background asset loading
+// Reading files from disk (and parsing obj files) can take long enough to drop frames if it
+// happens inside update/render. Instead we send a request to a loader thread and keep
+// rendering. The thread does the I/O and CPU work and sends the result back, the render loop
+// picks it up in State::update and does the GPU upload, which has to happen there anyway.
+//
+//   render thread --LoadRequest--> loader thread (disk, parsing)
+//   render thread <--LoadResult--- loader thread
+//
+// The thread stops by itself when the AssetLoader is dropped, recv fails once the sender is gone.
+
+// Identifies one request, so results can be matched with whoever asked for them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AssetHandle(u64);
+
+pub enum LoadRequest {
+    Texture(AssetHandle, String),
+    Model(AssetHandle, String),
+}
+
+pub enum LoadResult {
+    // Encoded image bytes, decoding happens with the upload
+    Texture(AssetHandle, anyhow::Result<Vec<u8>>),
+    Model(AssetHandle, anyhow::Result<ModelData>),
+}
+
+// Where a streamed asset is at, T is the GPU side type (Texture, Model)
+pub enum AssetState<T> {
+    Loading,
+    Loaded(T),
+    Failed(String),
+}
+
+pub struct WatchedAsset<T> {
+    pub handle: AssetHandle,
+    pub state: AssetState<T>,
+}
+
+impl<T> WatchedAsset<T> {
+    pub fn new(handle: AssetHandle) -> Self {
+        Self {
+            handle,
+            state: AssetState::Loading,
+        }
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        match &self.state {
+            AssetState::Loaded(asset) => Some(asset),
+            _ => None,
+        }
+    }
+}
+
+pub struct AssetLoader {
+    sender: Sender<LoadRequest>,
+    receiver: Receiver<LoadResult>,
+    next_handle: u64,
+}
+
+impl AssetLoader {
+    pub fn new() -> Self {
+        let (request_sender, request_receiver) = mpsc::channel::<LoadRequest>();
+        let (result_sender, result_receiver) = mpsc::channel::<LoadResult>();
+
+        thread::Builder::new()
+            .name("asset-loader".to_string())
+            .spawn(move || {
+                while let Ok(request) = request_receiver.recv() {
+                    // Render side is gone, nobody wants the result
+                    if result_sender.send(load(request)).is_err() {
+                        return;
+                    }
+                }
+            })
+            .expect("Failed to spawn asset loader thread");
+
+        Self {
+            sender: request_sender,
+            receiver: result_receiver,
+            next_handle: 0,
+        }
+    }
+
+    // File names are relative to res/, same as resources.rs
+    pub fn request_texture(&mut self, file_name: &str) -> AssetHandle {
+        let handle = self.next_handle();
+        self.send(LoadRequest::Texture(handle, file_name.to_string()));
+        handle
+    }
+
+    pub fn request_model(&mut self, file_name: &str) -> AssetHandle {
+        let handle = self.next_handle();
+        self.send(LoadRequest::Model(handle, file_name.to_string()));
+        handle
+    }
+
+    // Everything that finished since the last call, never waits
+    pub fn finished(&self) -> Vec<LoadResult> {
+        self.receiver.try_iter().collect()
+    }
+
+    fn next_handle(&mut self) -> AssetHandle {
+        self.next_handle += 1;
+        AssetHandle(self.next_handle)
+    }
+
+    fn send(&self, request: LoadRequest) {
+        // Only fails if the thread died (panicked while loading), nothing will ever come back
+        if self.sender.send(request).is_err() {
+            log::error!("Asset loader thread is not running, request dropped");
+        }
+    }
+}
+
+// Runs on the loader thread, the resources functions are async only for the web version so
+// blocking on them here is fine
+fn load(request: LoadRequest) -> LoadResult {
+    match request {
+        LoadRequest::Texture(handle, file_name) => {
+            LoadResult::Texture(handle, pollster::block_on(resources::load_bytes(&file_name)))
+        }
+        LoadRequest::Model(handle, file_name) => {
+            LoadResult::Model(handle, pollster::block_on(resources::read_model(&file_name)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    // Wait for n results, the loader thread does real file I/O
+    fn wait_for(loader: &AssetLoader, n: usize) -> Vec<LoadResult> {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut results = Vec::new();
+        while results.len() < n && Instant::now() < deadline {
+            results.extend(loader.finished());
+            thread::sleep(Duration::from_millis(5));
+        }
+        results
+    }
+
+    #[test]
+    fn test_loads_model_and_texture_in_background() {
+        let mut loader = AssetLoader::new();
+        let model = loader.request_model("cube.obj");
+        let texture = loader.request_texture("cube-diffuse.jpg");
+        assert_ne!(model, texture);
+
+        let results = wait_for(&loader, 2);
+        assert_eq!(results.len(), 2);
+        for result in results {
+            match result {
+                LoadResult::Model(handle, data) => {
+                    assert_eq!(handle, model);
+                    let data = data.unwrap();
+                    assert!(!data.meshes.is_empty());
+                    assert!(!data.materials[0].diffuse_bytes.is_empty());
+                }
+                LoadResult::Texture(handle, bytes) => {
+                    assert_eq!(handle, texture);
+                    assert!(!bytes.unwrap().is_empty());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_missing_file_reports_error() {
+        let mut loader = AssetLoader::new();
+        loader.request_texture("does-not-exist.png");
+
+        let results = wait_for(&loader, 1);
+        assert!(matches!(results.as_slice(), [LoadResult::Texture(_, Err(_))]));
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::graphics::texture::Texture;
+
+// Resource manager, one GPU copy of every texture and shader module
+// Several materials can point at the same image file and several pipelines use the same
+// shader, without a cache each of them uploads (or compiles) its own copy.
+// Entries are shared through Arc and never evicted, they live as long as the manager.
+//
+// On native we usually know the file path. On the web (and for include_bytes! assets) there is
+// no file system, the bytes come from somewhere else, so those are keyed by a name instead.
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ResourceKey {
+    Path(PathBuf),
+    Name(String),
+}
+
+impl ResourceKey {
+    pub fn label(&self) -> String {
+        match self {
+            ResourceKey::Path(path) => path.display().to_string(),
+            ResourceKey::Name(name) => name.clone(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ResourceManager {
+    textures: HashMap<ResourceKey, Arc<Texture>>,
+    shaders: HashMap<ResourceKey, Arc<wgpu::ShaderModule>>,
+}
+
+impl ResourceManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Reads and uploads the file the first time, later calls return the same texture
+    #[allow(dead_code)] // Models hand over bytes read on the asset thread, see get_or_create_texture
+    pub fn get_or_load_texture(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: &Path,
+    ) -> anyhow::Result<Arc<Texture>> {
+        self.get_or_insert_texture(ResourceKey::Path(path.to_path_buf()), |key| {
+            let bytes = std::fs::read(path)?;
+            Texture::from_bytes(device, queue, &bytes, &key.label())
+        })
+    }
+
+    // Same but with the bytes already in memory, they are only decoded on a cache miss
+    pub fn get_or_create_texture(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        key: ResourceKey,
+        bytes: &[u8],
+    ) -> anyhow::Result<Arc<Texture>> {
+        self.get_or_insert_texture(key, |key| Texture::from_bytes(device, queue, bytes, &key.label()))
+    }
+
+    #[allow(dead_code)] // Every shader is include_str! for now, see get_or_create_shader
+    pub fn get_or_load_shader(&mut self, device: &wgpu::Device, path: &Path) -> anyhow::Result<Arc<wgpu::ShaderModule>> {
+        let key = ResourceKey::Path(path.to_path_buf());
+        if let Some(shader) = self.shaders.get(&key) {
+            return Ok(Arc::clone(shader));
+        }
+        let source = std::fs::read_to_string(path)?;
+        Ok(self.get_or_create_shader(device, key, &source))
+    }
+
+    pub fn get_or_create_shader(&mut self, device: &wgpu::Device, key: ResourceKey, source: &str) -> Arc<wgpu::ShaderModule> {
+        let shader = self.shaders.entry(key).or_insert_with_key(|key| {
+            Arc::new(device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(&key.label()),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            }))
+        });
+        Arc::clone(shader)
+    }
+
+    // Swap in a rebuilt module (shader hot reload), later lookups get the new one
+    pub fn replace_shader(&mut self, key: ResourceKey, shader: Arc<wgpu::ShaderModule>) {
+        self.shaders.insert(key, shader);
+    }
+
+    // GPU memory of every cached texture, for the render stats (graphics/render_stats.rs)
+    pub fn texture_bytes(&self) -> u64 {
+        self.textures.values().map(|texture| texture.memory_size()).sum()
+    }
+
+    // Any other way of making the texture, load only runs on a cache miss
+    // Failed loads are not cached, the next call tries again
+    pub fn get_or_insert_texture(
+        &mut self,
+        key: ResourceKey,
+        load: impl FnOnce(&ResourceKey) -> anyhow::Result<Texture>,
+    ) -> anyhow::Result<Arc<Texture>> {
+        if let Some(texture) = self.textures.get(&key) {
+            return Ok(Arc::clone(texture));
+        }
+        let texture = Arc::new(load(&key)?);
+        self.textures.insert(key, Arc::clone(&texture));
+        Ok(texture)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn res_path(file_name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("res").join(file_name)
+    }
+
+    // None when the machine has no adapter at all (no GPU and no software fallback)
+    fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())).ok()?;
+        pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).ok()
+    }
+
+    #[test]
+    fn test_repeated_texture_loads_share_one_upload() {
+        let Some((device, queue)) = device() else {
+            eprintln!("No adapter available, skipping texture cache test");
+            return;
+        };
+        let mut manager = ResourceManager::new();
+
+        let first = manager.get_or_load_texture(&device, &queue, &res_path("cube-diffuse.jpg")).unwrap();
+        let second = manager.get_or_load_texture(&device, &queue, &res_path("cube-diffuse.jpg")).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let other = manager.get_or_load_texture(&device, &queue, &res_path("cube-normal.png")).unwrap();
+        assert!(!Arc::ptr_eq(&first, &other));
+
+        // Bytes keyed by name, a cache hit doesnt even look at them
+        let bytes = std::fs::read(res_path("cube-normal.png")).unwrap();
+        let named = manager.get_or_create_texture(&device, &queue, ResourceKey::Name("normal".into()), &bytes).unwrap();
+        let named_again = manager.get_or_create_texture(&device, &queue, ResourceKey::Name("normal".into()), &[]).unwrap();
+        assert!(Arc::ptr_eq(&named, &named_again));
+        assert!(!Arc::ptr_eq(&named, &other));
+
+        // Shared entries count once
+        let expected = first.memory_size() + other.memory_size() + named.memory_size();
+        assert_eq!(manager.texture_bytes(), expected);
+    }
+
+    #[test]
+    fn test_failed_texture_load_is_not_cached() {
+        let Some((device, queue)) = device() else {
+            eprintln!("No adapter available, skipping texture cache test");
+            return;
+        };
+        let mut manager = ResourceManager::new();
+
+        assert!(manager.get_or_create_texture(&device, &queue, ResourceKey::Name("bad".into()), &[1, 2, 3]).is_err());
+        let bytes = std::fs::read(res_path("cube-normal.png")).unwrap();
+        assert!(manager.get_or_create_texture(&device, &queue, ResourceKey::Name("bad".into()), &bytes).is_ok());
+    }
+
+    #[test]
+    fn test_repeated_shader_loads_share_one_module() {
+        let Some((device, _queue)) = device() else {
+            eprintln!("No adapter available, skipping shader cache test");
+            return;
+        };
+        let mut manager = ResourceManager::new();
+        let shader_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/graphics/shaders/light.wgsl");
+
+        let first = manager.get_or_load_shader(&device, &shader_path).unwrap();
+        let second = manager.get_or_load_shader(&device, &shader_path).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        // Same source under a name is a separate entry, keys are never compared by content
+        let source = include_str!("../graphics/shaders/light.wgsl");
+        let named = manager.get_or_create_shader(&device, ResourceKey::Name("light.wgsl".into()), source);
+        assert!(!Arc::ptr_eq(&first, &named));
+    }
+}
@@ -0,0 +1,216 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use crate::state::State;
+
+// Benchmark mode, renders the scene without a window and prints how long it took
+//   wgpu_rust --bench 500                          500 frames, results on stdout
+//   wgpu_rust --bench 500 --bench-out results.json same, and written as JSON for comparing runs
+//   wgpu_rust --bench 500 --bench-write-buffer     uniforms with queue.write_buffer instead of the staging belt
+// Frames are the same ones the window shows (State::render_offscreen), at a fixed size so runs
+// on different monitors compare. Any GPU error during the frames makes the run fail, a broken
+// frame is not a fast one.
+
+const BENCH_WIDTH: u32 = 1280;
+const BENCH_HEIGHT: u32 = 720;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchOptions {
+    pub frames: u32,
+    pub out: Option<PathBuf>,
+    pub uniform_staging: bool, // False with --bench-write-buffer, see graphics/uniform_writer.rs
+}
+
+impl BenchOptions {
+    // None without --bench, an error for a frame count that isnt a positive number
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> anyhow::Result<Option<Self>> {
+        let mut frames = None;
+        let mut out = None;
+        let mut uniform_staging = true;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--bench" => frames = Some(args.next().unwrap_or_default()),
+                "--bench-out" => out = args.next().map(PathBuf::from),
+                "--bench-write-buffer" => uniform_staging = false,
+                _ => {}
+            }
+        }
+        let Some(frames) = frames else {
+            return Ok(None);
+        };
+        match frames.parse::<u32>() {
+            Ok(frames) if frames > 0 => Ok(Some(Self { frames, out, uniform_staging })),
+            _ => anyhow::bail!("--bench needs a number of frames, got {:?}", frames),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchResult {
+    pub frames: u32,
+    pub total: Duration,
+    // Upper bound, see State::scene_triangle_count
+    pub triangles_per_frame: u64,
+    pub gpu_errors: usize,
+    pub uniform_staging: bool,
+}
+
+impl BenchResult {
+    pub fn ms_per_frame(&self) -> f64 {
+        self.total.as_secs_f64() * 1000.0 / self.frames as f64
+    }
+
+    fn uniform_uploads(&self) -> &'static str {
+        if self.uniform_staging { "staging_belt" } else { "write_buffer" }
+    }
+
+    pub fn triangles_per_sec(&self) -> f64 {
+        let seconds = self.total.as_secs_f64();
+        if seconds > 0.0 {
+            self.triangles_per_frame as f64 * self.frames as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    // Written by hand like the config files, there is no serde in this crate
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\n  \"frames\": {},\n  \"width\": {},\n  \"height\": {},\n  \"total_ms\": {:.3},\n  \"ms_per_frame\": {:.3},\n  \"triangles_per_frame\": {},\n  \"triangles_per_sec\": {:.0},\n  \"gpu_errors\": {},\n  \"uniform_uploads\": \"{}\"\n}}\n",
+            self.frames,
+            BENCH_WIDTH,
+            BENCH_HEIGHT,
+            self.total.as_secs_f64() * 1000.0,
+            self.ms_per_frame(),
+            self.triangles_per_frame,
+            self.triangles_per_sec(),
+            self.gpu_errors,
+            self.uniform_uploads(),
+        )
+    }
+
+    pub fn print(&self) {
+        println!("Rendered {} frames at {}x{}", self.frames, BENCH_WIDTH, BENCH_HEIGHT);
+        println!("Total: {:.1} ms", self.total.as_secs_f64() * 1000.0);
+        println!("Per frame: {:.3} ms", self.ms_per_frame());
+        println!("Triangles: {} per frame, ~{:.2} M/s", self.triangles_per_frame, self.triangles_per_sec() / 1e6);
+        println!("Uniform uploads: {}", self.uniform_uploads());
+    }
+}
+
+// Run the benchmark and report it, errors when a frame had a GPU error so the exit code says so
+pub fn run(options: &BenchOptions) -> anyhow::Result<()> {
+    let result = pollster::block_on(render_frames(options.frames, options.uniform_staging))?;
+    result.print();
+    if let Some(path) = &options.out {
+        write_results(path, &result)?;
+        println!("Results written to {}", path.display());
+    }
+    if result.gpu_errors > 0 {
+        anyhow::bail!("{} GPU error(s) while rendering the benchmark frames", result.gpu_errors);
+    }
+    Ok(())
+}
+
+fn write_results(path: &Path, result: &BenchResult) -> anyhow::Result<()> {
+    std::fs::write(path, result.to_json())
+        .map_err(|e| anyhow::anyhow!("Unable to write the benchmark results to {}: {}", path.display(), e))
+}
+
+async fn render_frames(frames: u32, uniform_staging: bool) -> anyhow::Result<BenchResult> {
+    let mut state = State::new_headless(BENCH_WIDTH, BENCH_HEIGHT).await?;
+    state.set_uniform_staging(uniform_staging);
+    let target = state.device().create_texture(&wgpu::TextureDescriptor {
+        label: Some("Bench Target"),
+        size: wgpu::Extent3d { width: BENCH_WIDTH, height: BENCH_HEIGHT, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: state.config().format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+    // Setup uploads (textures, buffers) finish before the clock starts
+    state.device().poll(wgpu::PollType::wait_indefinitely())?;
+    let errors_before = state.gpu_errors().errors().len();
+
+    let start = Instant::now();
+    for _ in 0..frames {
+        state.update();
+        state.render_offscreen(&view);
+    }
+    // Submitting only queues the work, the frames are done once the queue is empty
+    state.device().poll(wgpu::PollType::wait_indefinitely())?;
+    let total = start.elapsed();
+
+    Ok(BenchResult {
+        frames,
+        total,
+        triangles_per_frame: state.scene_triangle_count(),
+        gpu_errors: state.gpu_errors().errors().len() - errors_before,
+        uniform_staging: state.uniform_staging(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_bench_options_from_args() {
+        assert_eq!(BenchOptions::from_args(args(&["wgpu_rust"])).unwrap(), None);
+        assert_eq!(
+            BenchOptions::from_args(args(&["wgpu_rust", "--bench", "100"])).unwrap(),
+            Some(BenchOptions { frames: 100, out: None, uniform_staging: true })
+        );
+        assert_eq!(
+            BenchOptions::from_args(args(&["wgpu_rust", "--bench-out", "out.json", "--bench", "5"])).unwrap(),
+            Some(BenchOptions { frames: 5, out: Some(PathBuf::from("out.json")), uniform_staging: true })
+        );
+        assert_eq!(
+            BenchOptions::from_args(args(&["wgpu_rust", "--bench", "5", "--bench-write-buffer"])).unwrap(),
+            Some(BenchOptions { frames: 5, out: None, uniform_staging: false })
+        );
+        assert!(BenchOptions::from_args(args(&["wgpu_rust", "--bench", "0"])).is_err());
+        assert!(BenchOptions::from_args(args(&["wgpu_rust", "--bench"])).is_err());
+    }
+
+    #[test]
+    fn test_bench_result_rates_and_json() {
+        let result = BenchResult {
+            frames: 100,
+            total: Duration::from_millis(500),
+            triangles_per_frame: 1000,
+            gpu_errors: 0,
+            uniform_staging: true,
+        };
+        assert!((result.ms_per_frame() - 5.0).abs() < 1e-9);
+        assert!((result.triangles_per_sec() - 200_000.0).abs() < 1e-6);
+        let json = result.to_json();
+        assert!(json.contains("\"frames\": 100,"));
+        assert!(json.contains("\"ms_per_frame\": 5.000,"));
+        assert!(json.contains("\"triangles_per_sec\": 200000,"));
+        assert!(json.contains("\"uniform_uploads\": \"staging_belt\""));
+    }
+
+    // Full scene without a window, skips without an adapter for the backends the app uses
+    // (the software GL one of a CI machine cant compile the scene shader)
+    #[test]
+    fn test_headless_frames_render_without_errors() {
+        let backends = crate::graphics::adapter::backends_from_env();
+        if pollster::block_on(crate::graphics::headless::request_adapter(backends, false)).is_err() {
+            eprintln!("No adapter available, skipping bench test");
+            return;
+        }
+        let result = pollster::block_on(render_frames(2, true)).unwrap();
+        assert_eq!(result.frames, 2);
+        assert_eq!(result.gpu_errors, 0);
+        assert!(result.triangles_per_frame > 0);
+    }
+}
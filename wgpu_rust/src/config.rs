@@ -0,0 +1,289 @@
+use std::path::{Path, PathBuf};
+use crate::graphics::present_mode::PresentModePreference;
+
+// Settings read from small TOML files, kept to flat key = value lines so they are read by hand
+// instead of pulling in serde and toml:
+// - AppConfig, how the window is created. wgpu_rust.toml next to the binary, or --config <path>
+// - WindowConfig, the size the window had when it was last closed, in the home directory
+// A missing file or a bad value is never an error, it is logged and the default is used.
+
+const APP_CONFIG_FILE: &str = "wgpu_rust.toml";
+const WINDOW_CONFIG_FILE: &str = ".wgpu_rust.toml";
+// Biggest texture wgpu guarantees, a bigger surface couldnt be configured anyway
+const MAX_WINDOW_SIZE: u32 = 8192;
+// Sample counts every adapter supports for the common color formats
+const MSAA_SAMPLE_COUNTS: [u32; 2] = [1, 4];
+
+// Example wgpu_rust.toml, every key is optional:
+//   title = "My scene"
+//   width = 1280
+//   height = 720
+//   min_size = [640, 360]
+//   resizable = true
+//   vsync = false
+//   msaa = 4
+//   icon_path = "res/icon.png"
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppConfig {
+    pub title: String,
+    // Logical pixels, the saved WindowConfig size wins over these
+    pub width: u32,
+    pub height: u32,
+    pub min_size: Option<(u32, u32)>,
+    pub resizable: bool,
+    // None leaves the present mode to WGPU_PRESENT_MODE or the surface, see present_mode.rs
+    pub vsync: Option<bool>,
+    pub msaa: u32,
+    pub icon_path: Option<PathBuf>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            title: "wgpu_rust".to_string(),
+            width: 800,
+            height: 600,
+            min_size: None,
+            resizable: true,
+            vsync: None,
+            msaa: 1,
+            icon_path: None,
+        }
+    }
+}
+
+impl AppConfig {
+    // Every key is checked on its own, a bad one warns and keeps its default, the rest still apply
+    pub fn parse(text: &str) -> Self {
+        let mut config = Self::default();
+        for (key, value) in entries(text) {
+            let valid = match key {
+                "title" => parse_string(value).map(|title| config.title = title).is_some(),
+                "width" => parse_window_size(value).map(|width| config.width = width).is_some(),
+                "height" => parse_window_size(value).map(|height| config.height = height).is_some(),
+                "min_size" => parse_size_pair(value).map(|size| config.min_size = Some(size)).is_some(),
+                "resizable" => value.parse().map(|resizable| config.resizable = resizable).is_ok(),
+                "vsync" => value.parse().map(|vsync| config.vsync = Some(vsync)).is_ok(),
+                "msaa" => value.parse()
+                    .ok()
+                    .filter(|samples| MSAA_SAMPLE_COUNTS.contains(samples))
+                    .map(|samples| config.msaa = samples)
+                    .is_some(),
+                "icon_path" => parse_string(value).map(|path| config.icon_path = Some(path.into())).is_some(),
+                _ => {
+                    log::warn!("Unknown config key {:?}, ignoring it", key);
+                    continue;
+                }
+            };
+            if !valid {
+                log::warn!("Invalid config value {} = {}, using the default", key, value);
+            }
+        }
+        config
+    }
+
+    // The given file, or wgpu_rust.toml next to the binary when there is none
+    // Only a file that was asked for explicitly is worth a warning when it is missing
+    pub fn load(path: Option<&Path>) -> Self {
+        let (path, explicit) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match std::env::current_exe() {
+                Ok(exe) => (exe.with_file_name(APP_CONFIG_FILE), false),
+                Err(_) => return Self::default(),
+            },
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                log::info!("Using config {}", path.display());
+                Self::parse(&text)
+            }
+            Err(e) => {
+                if explicit {
+                    log::warn!("Unable to read config {}: {}, using the defaults", path.display(), e);
+                }
+                Self::default()
+            }
+        }
+    }
+
+    pub fn present_mode_preference(&self) -> PresentModePreference {
+        match self.vsync {
+            Some(true) => PresentModePreference::Vsync,
+            Some(false) => PresentModePreference::MaxFps,
+            None => PresentModePreference::Auto,
+        }
+    }
+
+    pub fn window_attributes(&self) -> winit::window::WindowAttributes {
+        let mut attributes = winit::window::Window::default_attributes()
+            .with_title(&self.title)
+            .with_inner_size(winit::dpi::LogicalSize::new(self.width, self.height))
+            .with_resizable(self.resizable);
+        if let Some((width, height)) = self.min_size {
+            attributes = attributes.with_min_inner_size(winit::dpi::LogicalSize::new(width, height));
+        }
+        if let Some(path) = &self.icon_path {
+            match load_icon(path) {
+                Ok(icon) => attributes = attributes.with_window_icon(Some(icon)),
+                Err(e) => log::warn!("Unable to load the window icon {}: {:#}", path.display(), e),
+            }
+        }
+        attributes
+    }
+}
+
+fn load_icon(path: &Path) -> anyhow::Result<winit::window::Icon> {
+    let image = image::open(path)?.into_rgba8();
+    let (width, height) = image.dimensions();
+    Ok(winit::window::Icon::from_rgba(image.into_raw(), width, height)?)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))] // The browser decides the canvas size
+pub struct WindowConfig {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+impl WindowConfig {
+    // Physical pixels, the same as window.inner_size()
+    pub fn new(width: u32, height: u32) -> Option<Self> {
+        // A minimized window reports 0x0, that size is not worth remembering
+        let valid = |size| (1..=MAX_WINDOW_SIZE).contains(&size);
+        (valid(width) && valid(height)).then_some(Self { width, height })
+    }
+
+    // width = 1280 and height = 720 lines, anything else in the file is ignored
+    pub fn parse(text: &str) -> Option<Self> {
+        let (mut width, mut height) = (None, None);
+        for (key, value) in entries(text) {
+            let value = value.parse::<u32>().ok();
+            match key {
+                "width" => width = value,
+                "height" => height = value,
+                _ => {}
+            }
+        }
+        Self::new(width?, height?)
+    }
+
+    pub fn to_toml(self) -> String {
+        format!("[window]\nwidth = {}\nheight = {}\n", self.width, self.height)
+    }
+
+    pub fn load() -> Option<Self> {
+        let path = window_config_path()?;
+        let text = std::fs::read_to_string(&path).ok()?;
+        let config = Self::parse(&text);
+        if config.is_none() {
+            log::warn!("Ignoring {}, it has no valid window size", path.display());
+        }
+        config
+    }
+
+    pub fn save(self) -> anyhow::Result<()> {
+        let path = window_config_path()
+            .ok_or_else(|| anyhow::anyhow!("No home directory to save {} in", WINDOW_CONFIG_FILE))?;
+        std::fs::write(path, self.to_toml())?;
+        Ok(())
+    }
+}
+
+// HOME on Linux and macOS, USERPROFILE on Windows
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+fn window_config_path() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    Some(PathBuf::from(home).join(WINDOW_CONFIG_FILE))
+}
+
+// key = value lines, [sections], comments and blank lines are skipped
+// The value is trimmed and loses a trailing # comment, unless it is a quoted string
+fn entries(text: &str) -> impl Iterator<Item = (&str, &str)> {
+    text.lines().filter_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        let key = key.trim();
+        if key.is_empty() || key.starts_with('#') {
+            return None;
+        }
+        let value = value.trim();
+        let value = if value.starts_with('"') {
+            value
+        } else {
+            value.split('#').next().unwrap_or_default().trim()
+        };
+        Some((key, value))
+    })
+}
+
+// "text", without escape sequences
+fn parse_string(value: &str) -> Option<String> {
+    let rest = value.strip_prefix('"')?;
+    let end = rest.find('"')?;
+    Some(rest[..end].to_string())
+}
+
+fn parse_window_size(value: &str) -> Option<u32> {
+    value.parse().ok().filter(|size| (1..=MAX_WINDOW_SIZE).contains(size))
+}
+
+// [width, height]
+fn parse_size_pair(value: &str) -> Option<(u32, u32)> {
+    let inner = value.strip_prefix('[')?.strip_suffix(']')?;
+    let (width, height) = inner.split_once(',')?;
+    Some((parse_window_size(width.trim())?, parse_window_size(height.trim())?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_config_round_trip() {
+        let config = WindowConfig::new(1280, 720).unwrap();
+        assert_eq!(WindowConfig::parse(&config.to_toml()), Some(config));
+    }
+
+    #[test]
+    fn test_broken_window_config_falls_back() {
+        assert_eq!(WindowConfig::parse(""), None);
+        assert_eq!(WindowConfig::parse("width = 1280"), None);
+        assert_eq!(WindowConfig::parse("width = wide\nheight = 720"), None);
+        assert_eq!(WindowConfig::parse("width = 0\nheight = 720"), None);
+        assert_eq!(WindowConfig::parse("width = 100000\nheight = 720"), None);
+        // Unknown keys and junk lines dont matter as long as both sizes are there
+        assert_eq!(
+            WindowConfig::parse("# comment\n[window]\nheight=600\nfullscreen = true\nwidth= 800"),
+            WindowConfig::new(800, 600),
+        );
+    }
+
+    #[test]
+    fn test_app_config_parses_every_key() {
+        let config = AppConfig::parse(
+            "title = \"My # scene\"\nwidth = 1280 # wide\nheight = 720\nmin_size = [640, 360]\n\
+             resizable = false\nvsync = true\nmsaa = 4\nicon_path = \"res/icon.png\"",
+        );
+        assert_eq!(config, AppConfig {
+            title: "My # scene".to_string(),
+            width: 1280,
+            height: 720,
+            min_size: Some((640, 360)),
+            resizable: false,
+            vsync: Some(true),
+            msaa: 4,
+            icon_path: Some(PathBuf::from("res/icon.png")),
+        });
+        assert_eq!(config.present_mode_preference(), PresentModePreference::Vsync);
+    }
+
+    #[test]
+    fn test_invalid_app_config_values_keep_defaults() {
+        let config = AppConfig::parse(
+            "title = untitled\nwidth = 0\nheight = 700\nmin_size = [640]\nresizable = maybe\nmsaa = 3\nfoo = 1",
+        );
+        // Only the valid height applied
+        assert_eq!(config, AppConfig { height: 700, ..AppConfig::default() });
+        assert_eq!(config.present_mode_preference(), PresentModePreference::Auto);
+    }
+}
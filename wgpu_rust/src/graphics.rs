@@ -7,4 +7,39 @@ pub(crate) mod texture;
 pub mod camera;
 pub(crate) mod camera_controller;
 pub(crate) mod instance;
-pub mod light;
\ No newline at end of file
+pub mod light;
+pub(crate) mod compute;
+pub(crate) mod cloth;
+pub(crate) mod skinning;
+pub(crate) mod picking;
+pub(crate) mod outline;
+pub(crate) mod shadow;
+pub(crate) mod transform;
+pub(crate) mod light_probe;
+pub(crate) mod text;
+pub(crate) mod sdf;
+pub(crate) mod sprite;
+pub(crate) mod debug_lines;
+pub(crate) mod curves;
+pub(crate) mod bounds;
+pub(crate) mod lod;
+pub(crate) mod multiview;
+pub(crate) mod split_screen;
+pub(crate) mod adapter;
+pub(crate) mod headless;
+pub(crate) mod present_mode;
+pub(crate) mod profiler;
+pub(crate) mod clip;
+pub(crate) mod transparency;
+pub(crate) mod motion_vectors;
+pub(crate) mod taa;
+pub(crate) mod post_process;
+pub(crate) mod globals;
+pub(crate) mod water;
+pub(crate) mod pipeline_cache;
+pub(crate) mod error_log;
+pub(crate) mod render_mode;
+pub(crate) mod uniform_writer;
+pub(crate) mod render_stats;
+#[cfg(feature = "gui")]
+pub(crate) mod egui_renderer;
\ No newline at end of file
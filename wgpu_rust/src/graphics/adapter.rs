@@ -0,0 +1,155 @@
+// Picking which GPU and which graphics API to use
+// By default wgpu picks for us (request_adapter), but sometimes we want to force one:
+// Vulkan vs DX12 to compare drivers, or the integrated GPU of a laptop to save battery.
+//   WGPU_BACKEND=vulkan            comma separated list, same names wgpu uses (vk, dx12, metal, gl)
+//   WGPU_ADAPTER_NAME=intel        case insensitive part of the adapter name
+// Anything unset or not matching falls back to the default choice.
+
+const BACKEND_VAR: &str = "WGPU_BACKEND";
+const ADAPTER_NAME_VAR: &str = "WGPU_ADAPTER_NAME";
+
+// Backends the Instance should load, PRIMARY (Vulkan, Metal, DX12, WebGPU) when not forced
+pub fn backends_from_env() -> wgpu::Backends {
+    match wgpu::Backends::from_env() {
+        Some(backends) if !backends.is_empty() => backends,
+        Some(_) => {
+            log::warn!("{} has no known backend names, using the default backends", BACKEND_VAR);
+            wgpu::Backends::PRIMARY
+        }
+        None => wgpu::Backends::PRIMARY,
+    }
+}
+
+// Device and queue with the features and limits the renderer needs, shared by State and the
+// headless context (graphics/headless.rs) so both can build the same pipelines
+pub async fn request_device(adapter: &wgpu::Adapter) -> Result<(wgpu::Device, wgpu::Queue), wgpu::RequestDeviceError> {
+    adapter
+        .request_device(&wgpu::DeviceDescriptor {
+            label: None,
+            // Only where supported, pipeline statistics are a debugging extra
+            // Clip distances make the clip plane exact, without them the shader discards pixels
+            required_features: adapter.features()
+                & (wgpu::Features::PIPELINE_STATISTICS_QUERY
+                    | wgpu::Features::TIMESTAMP_QUERY
+                    | wgpu::Features::CLIP_DISTANCES),
+            experimental_features: wgpu::ExperimentalFeatures::disabled(),
+            required_limits: wgpu::Limits {
+                max_bind_groups: 8,
+                ..wgpu::Limits::default()
+            },
+            memory_hints: Default::default(),
+            trace: wgpu::Trace::Off,
+        })
+        .await
+}
+
+// Adapter named in WGPU_ADAPTER_NAME if there is one that can draw to the surface,
+// otherwise whatever request_adapter gives us
+pub async fn select_adapter(
+    instance: &wgpu::Instance,
+    surface: &wgpu::Surface<'_>,
+    backends: wgpu::Backends,
+) -> anyhow::Result<wgpu::Adapter> {
+    if let Ok(name) = std::env::var(ADAPTER_NAME_VAR) {
+        let adapters: Vec<wgpu::Adapter> = instance
+            .enumerate_adapters(backends)
+            .await
+            .into_iter()
+            .filter(|adapter| adapter.is_surface_supported(surface))
+            .collect();
+        let infos: Vec<wgpu::AdapterInfo> = adapters.iter().map(|adapter| adapter.get_info()).collect();
+
+        match find_adapter_by_name(&infos, &name) {
+            Some(index) => {
+                let adapter = adapters.into_iter().nth(index).unwrap();
+                log_adapter(&adapter);
+                return Ok(adapter);
+            }
+            None => {
+                let names: Vec<&str> = infos.iter().map(|info| info.name.as_str()).collect();
+                log::warn!(
+                    "No adapter matching {}={:?} among {:?}, using the default one",
+                    ADAPTER_NAME_VAR,
+                    name,
+                    names,
+                );
+            }
+        }
+    }
+
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: Some(surface), // Find adapter compatible with our surface
+            force_fallback_adapter: false, // If true will use software rendering
+        })
+        .await?;
+    log_adapter(&adapter);
+    Ok(adapter)
+}
+
+// Index of the first adapter whose name contains name, ignoring case
+pub fn find_adapter_by_name(infos: &[wgpu::AdapterInfo], name: &str) -> Option<usize> {
+    let name = name.trim().to_lowercase();
+    if name.is_empty() {
+        return None;
+    }
+    infos.iter().position(|info| info.name.to_lowercase().contains(&name))
+}
+
+fn log_adapter(adapter: &wgpu::Adapter) {
+    let info = adapter.get_info();
+    log::info!("Using adapter {} ({:?}, {:?})", info.name, info.backend, info.device_type);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake_info(name: &str, backend: wgpu::Backend, device_type: wgpu::DeviceType) -> wgpu::AdapterInfo {
+        wgpu::AdapterInfo {
+            name: name.to_string(),
+            vendor: 0,
+            device: 0,
+            device_type,
+            device_pci_bus_id: String::new(),
+            driver: String::new(),
+            driver_info: String::new(),
+            backend,
+            subgroup_min_size: 0,
+            subgroup_max_size: 0,
+            transient_saves_memory: false,
+        }
+    }
+
+    fn fake_infos() -> Vec<wgpu::AdapterInfo> {
+        vec![
+            fake_info("NVIDIA GeForce RTX 3070", wgpu::Backend::Vulkan, wgpu::DeviceType::DiscreteGpu),
+            fake_info("Intel(R) UHD Graphics 630", wgpu::Backend::Vulkan, wgpu::DeviceType::IntegratedGpu),
+            fake_info("Intel(R) UHD Graphics 630", wgpu::Backend::Dx12, wgpu::DeviceType::IntegratedGpu),
+            fake_info("llvmpipe (LLVM 17.0.6, 256 bits)", wgpu::Backend::Gl, wgpu::DeviceType::Cpu),
+        ]
+    }
+
+    #[test]
+    fn test_name_match_is_case_insensitive_substring() {
+        let infos = fake_infos();
+        assert_eq!(find_adapter_by_name(&infos, "geforce"), Some(0));
+        assert_eq!(find_adapter_by_name(&infos, "UHD"), Some(1));
+        assert_eq!(find_adapter_by_name(&infos, "  LLVMpipe "), Some(3));
+    }
+
+    #[test]
+    fn test_first_match_wins() {
+        // Same GPU listed once per backend, the first one in enumeration order is picked
+        assert_eq!(find_adapter_by_name(&fake_infos(), "intel"), Some(1));
+    }
+
+    #[test]
+    fn test_no_match_or_empty_name() {
+        let infos = fake_infos();
+        assert_eq!(find_adapter_by_name(&infos, "radeon"), None);
+        assert_eq!(find_adapter_by_name(&infos, ""), None);
+        assert_eq!(find_adapter_by_name(&[], "intel"), None);
+    }
+}
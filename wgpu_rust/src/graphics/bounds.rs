@@ -0,0 +1,172 @@
+use cgmath::{EuclideanSpace, InnerSpace, Transform};
+
+// Axis aligned bounding box, the smallest box with edges along x, y and z around some points
+// Used for the debug boxes and to frame the camera on the whole scene (Home key)
+
+// Half size of the box framed around a single point, so the camera ends up close but not inside it
+const MIN_FRAME_RADIUS: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: cgmath::Point3<f32>,
+    pub max: cgmath::Point3<f32>,
+}
+
+impl Aabb {
+    // Inverted box, extending it with any point gives a box around just that point
+    pub const EMPTY: Aabb = Aabb {
+        min: cgmath::Point3::new(f32::MAX, f32::MAX, f32::MAX),
+        max: cgmath::Point3::new(f32::MIN, f32::MIN, f32::MIN),
+    };
+
+    pub fn from_points(points: impl IntoIterator<Item = cgmath::Point3<f32>>) -> Self {
+        points.into_iter().fold(Self::EMPTY, |aabb, point| aabb.extend(point))
+    }
+
+    // Nothing was added to it yet
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn extend(&self, point: cgmath::Point3<f32>) -> Self {
+        Self {
+            min: cgmath::Point3::new(self.min.x.min(point.x), self.min.y.min(point.y), self.min.z.min(point.z)),
+            max: cgmath::Point3::new(self.max.x.max(point.x), self.max.y.max(point.y), self.max.z.max(point.z)),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        if other.is_empty() {
+            return *self;
+        }
+        self.extend(other.min).extend(other.max)
+    }
+
+    pub fn scaled(&self, scale: f32) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+        Self::from_points([self.min * scale, self.max * scale])
+    }
+
+    // 8 corners ordered by bits: x = bit 0, y = bit 1, z = bit 2 (same order as DebugLines::box_corners)
+    pub fn corners(&self) -> [cgmath::Point3<f32>; 8] {
+        std::array::from_fn(|i| cgmath::Point3::new(
+            if i & 1 == 0 { self.min.x } else { self.max.x },
+            if i & 2 == 0 { self.min.y } else { self.max.y },
+            if i & 4 == 0 { self.min.z } else { self.max.z },
+        ))
+    }
+
+    // Box around the transformed corners, a rotated box needs a bigger axis aligned one
+    pub fn transformed(&self, matrix: &cgmath::Matrix4<f32>) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+        Self::from_points(self.corners().map(|corner| matrix.transform_point(corner)))
+    }
+
+    pub fn center(&self) -> cgmath::Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    // Radius of the sphere through the corners
+    pub fn radius(&self) -> f32 {
+        (self.max - self.min).magnitude() * 0.5
+    }
+}
+
+// Eye and target that fit the whole box in view, looking along view_dir
+// fovy in degrees. The narrower of the vertical and horizontal field of view decides the distance,
+// distance = radius / tan(fov / 2), with margin 1.0 the bounding sphere touches the edges.
+// None for an empty box, a zero size box is framed like a small one so the result is never NaN
+pub fn frame_bounds(
+    bounds: &Aabb,
+    fovy: f32,
+    aspect: f32,
+    view_dir: cgmath::Vector3<f32>,
+    margin: f32,
+) -> Option<(cgmath::Point3<f32>, cgmath::Point3<f32>)> {
+    if bounds.is_empty() || !bounds.radius().is_finite() {
+        return None;
+    }
+    let radius = bounds.radius().max(MIN_FRAME_RADIUS) * margin;
+
+    let aspect = if aspect.is_finite() && aspect > 0.0 { aspect } else { 1.0 };
+    let half_fovy = cgmath::Rad::from(cgmath::Deg(fovy)).0 * 0.5;
+    // Same tangent scaled by the aspect ratio gives the horizontal field of view
+    let half_fovx = (half_fovy.tan() * aspect).atan();
+    let half_fov = half_fovy.min(half_fovx);
+    let distance = radius / half_fov.tan();
+
+    // Keep looking the same way, only a camera sitting on its target has no direction
+    let view_dir = if view_dir.magnitude2() > f32::EPSILON {
+        view_dir.normalize()
+    } else {
+        -cgmath::Vector3::unit_z()
+    };
+    let target = bounds.center();
+    Some((target - view_dir * distance, target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f32, y: f32, z: f32) -> cgmath::Point3<f32> {
+        cgmath::Point3::new(x, y, z)
+    }
+
+    #[test]
+    fn test_from_points_and_union() {
+        let a = Aabb::from_points([point(0.0, 1.0, 2.0), point(-1.0, 3.0, 0.0)]);
+        assert_eq!(a, Aabb { min: point(-1.0, 1.0, 0.0), max: point(0.0, 3.0, 2.0) });
+        assert!(Aabb::EMPTY.is_empty() && !a.is_empty());
+        assert_eq!(Aabb::EMPTY.union(&a), a);
+        assert_eq!(a.union(&Aabb::EMPTY), a);
+    }
+
+    #[test]
+    fn test_transformed_box_grows_when_rotated() {
+        let unit = Aabb::from_points([point(-1.0, -1.0, -1.0), point(1.0, 1.0, 1.0)]);
+        let rotation = cgmath::Matrix4::from_angle_y(cgmath::Deg(45.0));
+        let moved = unit.transformed(&(cgmath::Matrix4::from_translation((5.0, 0.0, 0.0).into()) * rotation));
+        let half_diagonal = 2.0_f32.sqrt();
+        assert!((moved.max.x - (5.0 + half_diagonal)).abs() < 1e-5);
+        assert!((moved.min.z + half_diagonal).abs() < 1e-5);
+        assert!((moved.max.y - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_frame_distance_matches_fov() {
+        // Radius sqrt(3) box, 90 degree vertical fov on a square screen: distance = radius / tan(45)
+        let bounds = Aabb::from_points([point(-1.0, -1.0, -1.0), point(1.0, 1.0, 1.0)]);
+        let (eye, target) = frame_bounds(&bounds, 90.0, 1.0, -cgmath::Vector3::unit_z(), 1.0).unwrap();
+        assert_eq!(target, point(0.0, 0.0, 0.0));
+        assert!((eye.z - 3.0_f32.sqrt()).abs() < 1e-5);
+
+        // A tall narrow screen has less horizontal room, so the camera backs off further
+        let (narrow_eye, _) = frame_bounds(&bounds, 90.0, 0.5, -cgmath::Vector3::unit_z(), 1.0).unwrap();
+        assert!((narrow_eye.z - 3.0_f32.sqrt() / 0.5).abs() < 1e-4);
+        // A wide screen is limited by the vertical fov, same as the square one
+        let (wide_eye, _) = frame_bounds(&bounds, 90.0, 2.0, -cgmath::Vector3::unit_z(), 1.0).unwrap();
+        assert!((wide_eye.z - eye.z).abs() < 1e-5);
+
+        // Margin moves the camera back by the same factor, the view direction is kept
+        let (margin_eye, _) = frame_bounds(&bounds, 90.0, 1.0, cgmath::Vector3::new(0.0, 0.0, -4.0), 1.2).unwrap();
+        assert!((margin_eye.z - 3.0_f32.sqrt() * 1.2).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_degenerate_bounds_dont_produce_nan() {
+        assert_eq!(frame_bounds(&Aabb::EMPTY, 45.0, 1.0, -cgmath::Vector3::unit_z(), 1.1), None);
+
+        let single = Aabb::from_points([point(2.0, 0.0, 0.0)]);
+        for (aspect, view_dir) in [(1.0, cgmath::Vector3::new(0.0, 0.0, 0.0)), (0.0, -cgmath::Vector3::unit_z()), (f32::NAN, cgmath::Vector3::unit_x())] {
+            let (eye, target) = frame_bounds(&single, 45.0, aspect, view_dir, 1.1).unwrap();
+            assert_eq!(target, point(2.0, 0.0, 0.0));
+            assert!(eye.x.is_finite() && eye.y.is_finite() && eye.z.is_finite());
+            assert!((eye - target).magnitude() > 0.0);
+        }
+    }
+}
@@ -26,25 +26,53 @@ pub fn create_model_vertex_buffer(device: &wgpu::Device, vertices: &[crate::mode
 }
 
 // Index buffer holds indices that define how vertices are connected to form triangles
-pub fn create_index_buffer(device: &wgpu::Device, indices: &[u16])
-    -> wgpu::Buffer {
-    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+// Indices can be 16 or 32 bit, the buffer remembers which so draw calls bind it with the right format
+pub struct IndexBuffer {
+    pub buffer: wgpu::Buffer,
+    pub format: wgpu::IndexFormat,
+    pub count: u32,
+}
+
+impl IndexBuffer {
+    pub fn from_u16(device: &wgpu::Device, indices: &[u16]) -> Self {
+        Self::create(device, bytemuck::cast_slice(indices), wgpu::IndexFormat::Uint16, indices.len())
+    }
+
+    pub fn from_u32(device: &wgpu::Device, indices: &[u32]) -> Self {
+        Self::create(device, bytemuck::cast_slice(indices), wgpu::IndexFormat::Uint32, indices.len())
+    }
+
+    // Half the memory when every index fits in 16 bits, which is most meshes under 65k vertices
+    pub fn from_u32_compact(device: &wgpu::Device, indices: &[u32]) -> Self {
+        match index_format_for(indices) {
+            wgpu::IndexFormat::Uint16 => {
+                let indices = indices.iter().map(|&index| index as u16).collect::<Vec<_>>();
+                Self::from_u16(device, &indices)
+            }
+            wgpu::IndexFormat::Uint32 => Self::from_u32(device, indices),
+        }
+    }
+
+    fn create(device: &wgpu::Device, contents: &[u8], format: wgpu::IndexFormat, count: usize) -> Self {
+        // Buffer sizes have to be a multiple of 4, an odd number of u16 indices is 2 bytes short
+        let mut padded = contents.to_vec();
+        padded.resize(contents.len().next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT as usize), 0);
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(indices),
+            contents: &padded,
             usage: wgpu::BufferUsages::INDEX,
-        }
-    )
+        });
+        Self { buffer, format, count: count as u32 }
+    }
 }
 
-// New implementation for model vertices struct used for the loading 3d models from obj files
-pub fn create_model_index_buffer(device: &wgpu::Device, indices: &[u32])
-    -> wgpu::Buffer {
-    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Model Index Buffer"),
-            contents: bytemuck::cast_slice(indices),
-            usage: wgpu::BufferUsages::INDEX,
-        }
-    )
+// Smallest index format that can hold every index
+pub fn index_format_for(indices: &[u32]) -> wgpu::IndexFormat {
+    if indices.iter().all(|&index| index <= u16::MAX as u32) {
+        wgpu::IndexFormat::Uint16
+    } else {
+        wgpu::IndexFormat::Uint32
+    }
 }
 
 // Uniform buffer holds data that remains constant for entire draw calls
@@ -69,7 +97,55 @@ pub fn create_instance_buffer(device: &wgpu::Device, instance_data: Vec<Instance
         &wgpu::util::BufferInitDescriptor {
             label: Some("Instance Buffer"),
             contents: bytemuck::cast_slice(&instance_data),
-            usage: wgpu::BufferUsages::VERTEX,
+            // COPY_DST so the previous frame matrices can be rewritten every frame
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
         }
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())).ok()?;
+        pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).ok()
+    }
+
+    // Triangle list over vertex_count vertices, the last triangle uses the highest index
+    fn synthetic_indices(vertex_count: u32) -> Vec<u32> {
+        (0..vertex_count - 2).flat_map(|i| [0, i + 1, i + 2]).collect()
+    }
+
+    #[test]
+    fn test_index_format_switches_above_u16_limit() {
+        assert_eq!(index_format_for(&synthetic_indices(u16::MAX as u32 + 1)), wgpu::IndexFormat::Uint16);
+        assert_eq!(index_format_for(&synthetic_indices(u16::MAX as u32 + 2)), wgpu::IndexFormat::Uint32);
+        assert_eq!(index_format_for(&[]), wgpu::IndexFormat::Uint16);
+    }
+
+    #[test]
+    fn test_compact_index_buffer_keeps_every_index() {
+        let Some((device, _queue)) = device() else {
+            eprintln!("No adapter available, skipping index buffer test");
+            return;
+        };
+
+        // One vertex past what 16 bits can address
+        let large = synthetic_indices(u16::MAX as u32 + 2);
+        let buffer = IndexBuffer::from_u32_compact(&device, &large);
+        assert_eq!(buffer.format, wgpu::IndexFormat::Uint32);
+        assert_eq!(buffer.count, large.len() as u32);
+        assert_eq!(buffer.buffer.size(), (large.len() * 4) as u64);
+
+        // Odd u16 count, padded up to the 4 byte buffer alignment
+        let small = IndexBuffer::from_u32_compact(&device, &[0, 1, 2]);
+        assert_eq!(small.format, wgpu::IndexFormat::Uint16);
+        assert_eq!(small.count, 3);
+        assert_eq!(small.buffer.size(), 8);
+    }
+}
@@ -52,7 +52,7 @@ impl Camera {
         self.eye
     }
 
-    fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+    pub(crate) fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
 
         // GPUs dont actually move the camera, instead we move and rotate the entire scene inversely to simulate camera movement
         // the view matrix offsets every vertex so that they are relative to the camera position and orientation
@@ -81,6 +81,8 @@ impl Camera {
 pub struct CameraUniform {
     // Cant use cgmath with bytemuck so we convert the Matrix4 into a 4x4 f32 array
     view_proj: [[f32; 4]; 4],
+    // Eye position for effects that need the view direction (reflections), vec4 for alignment
+    view_position: [f32; 4],
 }
 
 impl CameraUniform {
@@ -88,11 +90,19 @@ impl CameraUniform {
         use cgmath::SquareMatrix;
         Self {
             view_proj: cgmath::Matrix4::identity().into(),
+            view_position: [0.0; 4],
         }
     }
 
     pub fn update_view_proj(&mut self, camera: &Camera) {
         self.view_proj = camera.build_view_projection_matrix().into();
+        self.view_position = camera.eye.to_homogeneous().into();
+    }
+
+    // For cameras that are not described by a Camera struct (cubemap faces, etc)
+    pub fn set_view_proj(&mut self, view_proj: cgmath::Matrix4<f32>, position: cgmath::Point3<f32>) {
+        self.view_proj = view_proj.into();
+        self.view_position = position.to_homogeneous().into();
     }
 
     pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
@@ -100,7 +110,8 @@ impl CameraUniform {
             entries: &[
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
+                    // Fragment needs the view position for reflections
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -1,12 +1,22 @@
 use winit::keyboard::KeyCode;
 use crate::graphics::camera::Camera;
 
+// Sticks never rest exactly at 0, anything below this is treated as centered
+pub const STICK_DEADZONE: f32 = 0.15;
+// Wind the camera pushes into the scene (the cloth is blown by it), force per unit of mass
+pub const WIND_STRENGTH: f32 = 2.0;
+// Moving the camera blows harder
+const WIND_GUST: f32 = 3.0;
+
 pub struct CameraController {
     speed: f32,
     is_forward_pressed: bool,
     is_backward_pressed: bool,
     is_left_pressed: bool,
     is_right_pressed: bool,
+    // Analog input in -1..1, set by a gamepad. Left stick y moves, right stick orbits
+    move_axis: f32,
+    orbit_axes: (f32, f32),
 }
 
 impl CameraController {
@@ -17,9 +27,21 @@ impl CameraController {
             is_backward_pressed: false,
             is_left_pressed: false,
             is_right_pressed: false,
+            move_axis: 0.0,
+            orbit_axes: (0.0, 0.0),
         }
     }
 
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
     // We use booleans so the movement is smooth while key is held down
     pub fn handle_key(&mut self, code: KeyCode, is_pressed: bool) -> bool {
         match code {
@@ -43,6 +65,13 @@ impl CameraController {
         }
     }
 
+    // Same controls as the keys but analog, a half pushed stick moves at half speed
+    // Fed every frame by GamepadHandler (input.rs)
+    pub fn handle_sticks(&mut self, left: (f32, f32), right: (f32, f32)) {
+        self.move_axis = apply_deadzone(left.1);
+        self.orbit_axes = (apply_deadzone(right.0), apply_deadzone(right.1));
+    }
+
     pub fn update_camera(&self, camera: &mut Camera) {
         use cgmath::InnerSpace;
 
@@ -82,5 +111,119 @@ impl CameraController {
             // we add left/right vector to the forward vector before normalizing and scaling
             camera.eye = camera.target - (forward - right * self.speed).normalize() * forward_mag;
         }
+
+        // Sticks after the keys, forward is stick up
+        let forward = camera.target - camera.eye;
+        let forward_mag = forward.magnitude();
+        let step = self.move_axis * self.speed;
+        if step < 0.0 || forward_mag > step {
+            camera.eye += forward.normalize() * step;
+        }
+
+        // Orbit the same way as the keys, vertical orbit uses the camera up vector
+        // Stop short of the poles, past them look_at flips the view upside down
+        let forward = camera.target - camera.eye;
+        let forward_mag = forward.magnitude();
+        let (orbit_x, orbit_y) = self.orbit_axes;
+        if orbit_x != 0.0 {
+            camera.eye = camera.target - (forward + right * orbit_x * self.speed).normalize() * forward_mag;
+        }
+        let forward = camera.target - camera.eye;
+        let orbited = (forward - camera.up * orbit_y * self.speed).normalize();
+        if orbit_y != 0.0 && orbited.dot(camera.up.normalize()).abs() < 0.99 {
+            camera.eye = camera.target - orbited * forward_mag;
+        }
     }
-}
\ No newline at end of file
+}
+
+impl CameraController {
+    // Wind blows where the camera looks, stronger while it is moving
+    pub fn wind(&self, camera: &Camera) -> cgmath::Vector3<f32> {
+        use cgmath::InnerSpace;
+
+        let forward = camera.target - camera.eye;
+        if forward.magnitude2() == 0.0 {
+            return cgmath::Vector3::new(0.0, 0.0, 0.0);
+        }
+        let moving = self.is_forward_pressed
+            || self.is_backward_pressed
+            || self.is_left_pressed
+            || self.is_right_pressed
+            || self.move_axis != 0.0
+            || self.orbit_axes != (0.0, 0.0);
+        let strength = if moving { WIND_STRENGTH * WIND_GUST } else { WIND_STRENGTH };
+        forward.normalize() * strength
+    }
+}
+
+// Rescale so the output still starts at 0 right after the deadzone instead of jumping to 0.15
+fn apply_deadzone(value: f32) -> f32 {
+    let magnitude = value.abs();
+    if magnitude < STICK_DEADZONE {
+        return 0.0;
+    }
+    value.signum() * ((magnitude - STICK_DEADZONE) / (1.0 - STICK_DEADZONE)).min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::camera::CameraConfig;
+    use cgmath::InnerSpace;
+
+    fn test_camera() -> Camera {
+        Camera::new(CameraConfig {
+            eye: (0.0, 0.0, 5.0).into(),
+            target: (0.0, 0.0, 0.0).into(),
+            up: cgmath::Vector3::unit_y(),
+            aspect: 1.0,
+            fovy: 45.0,
+            znear: 0.1,
+            zfar: 100.0,
+        })
+    }
+
+    #[test]
+    fn test_deadzone() {
+        assert_eq!(apply_deadzone(0.1), 0.0);
+        assert_eq!(apply_deadzone(-0.1), 0.0);
+        assert_eq!(apply_deadzone(1.0), 1.0);
+        assert_eq!(apply_deadzone(-1.0), -1.0);
+        assert!(apply_deadzone(0.2) > 0.0 && apply_deadzone(0.2) < 0.1);
+    }
+
+    #[test]
+    fn test_sticks_move_and_orbit() {
+        let mut controller = CameraController::new(0.5);
+
+        // Full stick forward moves like holding W
+        let mut camera = test_camera();
+        controller.handle_sticks((0.0, 1.0), (0.0, 0.0));
+        controller.update_camera(&mut camera);
+        assert!((camera.eye.z - 4.5).abs() < 1e-5);
+
+        // Orbit keeps the distance to the target
+        let mut camera = test_camera();
+        controller.handle_sticks((0.0, 0.0), (1.0, 1.0));
+        controller.update_camera(&mut camera);
+        assert!(((camera.eye - camera.target).magnitude() - 5.0).abs() < 1e-4);
+        assert!(camera.eye.x.abs() > 0.1 && camera.eye.y.abs() > 0.1);
+
+        // Centered sticks leave the camera alone
+        let mut camera = test_camera();
+        controller.handle_sticks((0.05, -0.05), (0.1, 0.0));
+        controller.update_camera(&mut camera);
+        assert_eq!(camera.eye, test_camera().eye);
+    }
+
+    #[test]
+    fn test_wind_follows_the_camera() {
+        let mut controller = CameraController::new(0.5);
+        let camera = test_camera();
+        let calm = controller.wind(&camera);
+        assert!((calm - cgmath::Vector3::new(0.0, 0.0, -WIND_STRENGTH)).magnitude() < 1e-5);
+
+        controller.handle_key(KeyCode::KeyD, true);
+        assert!((controller.wind(&camera).magnitude() - WIND_STRENGTH * WIND_GUST).abs() < 1e-5);
+    }
+}
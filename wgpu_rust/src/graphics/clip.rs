@@ -0,0 +1,141 @@
+// User clip plane, cuts away everything on one side of a plane
+// Used for cross-section views: slide the plane through a model and look at its insides.
+// The plane keeps points where dot(normal, position) >= distance, in world space.
+//
+// With wgpu::Features::CLIP_DISTANCES the vertex shader writes @builtin(clip_distances) and the
+// rasterizer clips triangles exactly against the plane. WGSL has no #ifdef, so the shader has
+// placeholder comments that scene_shader_source fills in when the feature is on.
+// Without the feature the fragment shader discards pixels on the wrong side instead, same
+// picture but the fragment shader still runs for them. It stays in both variants, with
+// hardware clipping there is nothing left for it to discard.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipPlane {
+    pub normal: [f32; 3], // Points towards the side that stays visible
+    pub distance: f32, // Distance from the origin along the normal
+}
+
+impl ClipPlane {
+    // Positive on the visible side, negative on the clipped side, same as the shader
+    pub fn signed_distance(&self, point: [f32; 3]) -> f32 {
+        let [nx, ny, nz] = self.normal;
+        nx * point[0] + ny * point[1] + nz * point[2] - self.distance
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ClipUniform {
+    pub plane: [f32; 4], // xyz normal, w distance
+    pub enabled: u32,
+    pub _padding: [u32; 3], // Uniforms need 16 byte alignment
+}
+
+impl ClipUniform {
+    pub fn new(plane: Option<ClipPlane>) -> Self {
+        let (plane, enabled) = match plane {
+            Some(ClipPlane { normal: [x, y, z], distance }) => {
+                // Normalized so the clip distance is in world units, a zero normal clips nothing
+                let length = (x * x + y * y + z * z).sqrt();
+                if length > f32::EPSILON {
+                    ([x / length, y / length, z / length, distance / length], 1)
+                } else {
+                    ([0.0; 4], 0)
+                }
+            }
+            None => ([0.0; 4], 0),
+        };
+        Self {
+            plane,
+            enabled,
+            _padding: [0; 3],
+        }
+    }
+}
+
+pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Clip Bind Group Layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                // Vertex for the hardware clip distance, fragment for the discard fallback
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }
+        ],
+    })
+}
+
+pub fn create_bind_group(
+    device: &wgpu::Device,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    clip_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: clip_buffer.as_entire_binding(),
+            }
+        ],
+        label: Some("Clip Bind Group"),
+    })
+}
+
+// Scene shader with the clip distance output switched on when the device has the feature
+pub fn scene_shader_source(source: &str, clip_distances: bool) -> String {
+    if !clip_distances {
+        return source.to_string();
+    }
+    let source = source
+        .replace(
+            "// CLIP_DISTANCES_OUTPUT",
+            "@builtin(clip_distances) clip_distances: array<f32, 1>,",
+        )
+        .replace(
+            "// CLIP_DISTANCES_WRITE",
+            "out.clip_distances[0] = clip_distance(world_position.xyz);",
+        );
+    format!("enable clip_distances;\n{}", source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_distance_sides() {
+        let plane = ClipPlane { normal: [1.0, 0.0, 0.0], distance: 2.0 };
+        assert_eq!(plane.signed_distance([3.0, 5.0, -1.0]), 1.0);
+        assert_eq!(plane.signed_distance([2.0, 0.0, 0.0]), 0.0);
+        assert_eq!(plane.signed_distance([0.0, 0.0, 0.0]), -2.0);
+    }
+
+    #[test]
+    fn test_uniform_normalizes_plane() {
+        let uniform = ClipUniform::new(Some(ClipPlane { normal: [0.0, 2.0, 0.0], distance: 4.0 }));
+        assert_eq!(uniform.plane, [0.0, 1.0, 0.0, 2.0]);
+        assert_eq!(uniform.enabled, 1);
+
+        assert_eq!(ClipUniform::new(None).enabled, 0);
+        assert_eq!(ClipUniform::new(Some(ClipPlane { normal: [0.0; 3], distance: 1.0 })).enabled, 0);
+    }
+
+    #[test]
+    fn test_shader_variants() {
+        let source = include_str!("../../res/shaders/shader.wgsl");
+        assert_eq!(scene_shader_source(source, false), source);
+
+        let hardware = scene_shader_source(source, true);
+        assert!(hardware.starts_with("enable clip_distances;"));
+        assert!(hardware.contains("@builtin(clip_distances)"));
+        assert!(hardware.contains("out.clip_distances[0]"));
+    }
+}
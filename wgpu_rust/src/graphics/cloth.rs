@@ -0,0 +1,578 @@
+use std::ops::Range;
+use wgpu::util::DeviceExt;
+use crate::graphics::buffers;
+
+// Cloth simulated on the GPU with position based dynamics
+// The cloth is a grid of vertices joined by distance constraints (structural to the direct
+// neighbours, shear across the diagonals, bend to the vertex after next). Every frame one compute
+// pass moves the vertices with Verlet integration (gravity + wind) and then pulls every
+// constraint back to its rest length, ITERATIONS times. More iterations make stiffer cloth.
+// Constraints sharing a vertex cant run at the same time, they would both write it, so they are
+// split on the CPU into batches where every vertex shows up once and each batch is one dispatch.
+// The position buffer is also the vertex buffer of the draw, nothing is copied back to the CPU.
+
+const WORKGROUP_SIZE: u32 = 64; // Must match WORKGROUP_SIZE in cloth_sim.wgsl
+const ITERATIONS: usize = 8;
+// Vertices along each side and the distance between them
+const GRID_SIZE: u32 = 16;
+const SPACING: f32 = 0.08;
+// Top left corner, the cloth hangs down from there in the x/y plane
+const ORIGIN: [f32; 3] = [-0.6, 1.2, -1.0];
+const GRAVITY: [f32; 3] = [0.0, -9.8, 0.0];
+const DAMPING: f32 = 0.99;
+// Bigger steps make the constraints fight too hard, a slow frame simulates less time instead
+const MAX_DT: f32 = 1.0 / 30.0;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Constraint {
+    a: u32,
+    b: u32,
+    rest_length: f32,
+    _padding: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ClothParams {
+    gravity: [f32; 4],
+    wind: [f32; 4],
+    dt: f32,
+    damping: f32,
+    time: f32,
+    vertex_count: u32,
+    pin_count: u32,
+    _padding: [u32; 3], // Uniforms are sized in 16 byte steps
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct BatchUniform {
+    first: u32,
+    count: u32,
+    _padding: [u32; 2],
+}
+
+pub struct ClothSim {
+    // xyz position and the inverse mass in w
+    // STORAGE for the simulation, VERTEX for the draw, COPY_DST to reset it
+    pub position_buffer: wgpu::Buffer,
+    pub prev_position_buffer: wgpu::Buffer,
+    // Only read through the bind group on the GPU, kept here so they can be rewritten later
+    #[allow(dead_code)]
+    pub constraint_buffer: wgpu::Buffer,
+    // Indices of the vertices that dont move
+    #[allow(dead_code)]
+    pub pin_buffer: wgpu::Buffer,
+    // Verlet integration, one invocation per vertex
+    pub compute_pipeline: wgpu::ComputePipeline,
+    // Distance constraint projection, one invocation per constraint of the current batch
+    constraint_pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    batch_bind_group: wgpu::BindGroup,
+    batch_stride: u32,
+    batches: Vec<Range<u32>>,
+    params: ClothParams,
+    params_buffer: wgpu::Buffer,
+    render_pipeline: wgpu::RenderPipeline,
+    uv_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_count: u32,
+    rest_positions: Vec<[f32; 4]>,
+}
+
+impl ClothSim {
+    pub fn new(
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let rest_positions = grid_positions(GRID_SIZE, GRID_SIZE, SPACING, ORIGIN);
+        let (constraints, batches) = batch_constraints(&grid_constraints(GRID_SIZE, GRID_SIZE, SPACING), rest_positions.len());
+        // The two top corners, the cloth hangs from them like a sheet on a line
+        let pins = [0, GRID_SIZE - 1];
+
+        let position_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Position Buffer"),
+            contents: bytemuck::cast_slice(&rest_positions),
+            // COPY_SRC so the positions can be read back, the tests check where the cloth ended up
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::VERTEX
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+        });
+        // Same as the positions, the cloth starts at rest
+        let prev_position_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Previous Position Buffer"),
+            contents: bytemuck::cast_slice(&rest_positions),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let constraint_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Constraint Buffer"),
+            contents: bytemuck::cast_slice(&constraints),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let pin_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Pin Buffer"),
+            contents: bytemuck::cast_slice(&pins),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let params = ClothParams {
+            gravity: [GRAVITY[0], GRAVITY[1], GRAVITY[2], 0.0],
+            wind: [0.0; 4],
+            dt: 0.0,
+            damping: DAMPING,
+            time: 0.0,
+            vertex_count: rest_positions.len() as u32,
+            pin_count: pins.len() as u32,
+            _padding: [0; 3],
+        };
+        let params_buffer = buffers::create_uniform_buffer(device, &params);
+
+        // Every batch sits at its own aligned offset, the dynamic offset picks one per dispatch
+        let batch_stride = device.limits().min_uniform_buffer_offset_alignment
+            .max(std::mem::size_of::<BatchUniform>() as u32);
+        let mut batch_data = vec![0u8; batch_stride as usize * batches.len()];
+        for (i, batch) in batches.iter().enumerate() {
+            let uniform = BatchUniform { first: batch.start, count: batch.end - batch.start, _padding: [0; 2] };
+            let offset = i * batch_stride as usize;
+            batch_data[offset..offset + std::mem::size_of::<BatchUniform>()].copy_from_slice(bytemuck::bytes_of(&uniform));
+        }
+        let batch_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Batch Buffer"),
+            contents: &batch_data,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Cloth Bind Group Layout"),
+            entries: &[
+                storage_entry(0, false),
+                storage_entry(1, false),
+                storage_entry(2, true),
+                storage_entry(3, true),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Cloth Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: position_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: prev_position_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: constraint_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: pin_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 4, resource: params_buffer.as_entire_binding() },
+            ],
+        });
+        let batch_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Cloth Batch Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let batch_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Cloth Batch Bind Group"),
+            layout: &batch_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &batch_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<BatchUniform>() as u64),
+                }),
+            }],
+        });
+
+        // Both entry points share the layout, so the bind groups stay set between them
+        let compute_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Cloth Compute Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout, &batch_bind_group_layout],
+            immediate_size: 0,
+        });
+        let compute_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Cloth Simulation Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/cloth_sim.wgsl").into()),
+        });
+        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Cloth Integrate Pipeline"),
+            layout: Some(&compute_layout),
+            module: &compute_shader,
+            entry_point: Some("cs_integrate"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let constraint_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Cloth Constraint Pipeline"),
+            layout: Some(&compute_layout),
+            module: &compute_shader,
+            entry_point: Some("cs_constraint"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        // Drawing: simulated positions plus fixed uvs, the grid triangles never change
+        let uvs = grid_uvs(GRID_SIZE, GRID_SIZE);
+        let uv_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth UV Buffer"),
+            contents: bytemuck::cast_slice(&uvs),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let indices = grid_indices(GRID_SIZE, GRID_SIZE);
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cloth Index Buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let render_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Cloth Render Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout], // Same camera as the scene
+            immediate_size: 0,
+        });
+        let render_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Cloth Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/cloth.wgsl").into()),
+        });
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Cloth Render Pipeline"),
+            layout: Some(&render_layout),
+            vertex: wgpu::VertexState {
+                module: &render_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Float32x4],
+                    },
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![1 => Float32x2],
+                    },
+                ],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &render_shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                cull_mode: None, // Both sides of the cloth are visible
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        Self {
+            position_buffer,
+            prev_position_buffer,
+            constraint_buffer,
+            pin_buffer,
+            compute_pipeline,
+            constraint_pipeline,
+            bind_group,
+            batch_bind_group,
+            batch_stride,
+            batches,
+            params,
+            params_buffer,
+            render_pipeline,
+            uv_buffer,
+            index_buffer,
+            index_count: indices.len() as u32,
+            rest_positions,
+        }
+    }
+
+    // Back to the flat starting grid, at rest
+    pub fn reset(&mut self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.position_buffer, 0, bytemuck::cast_slice(&self.rest_positions));
+        queue.write_buffer(&self.prev_position_buffer, 0, bytemuck::cast_slice(&self.rest_positions));
+        self.params.time = 0.0;
+    }
+
+    // Step length and wind for the next dispatch, wind is a force per unit of mass in world space
+    pub fn update(&mut self, queue: &wgpu::Queue, delta_time: f32, wind: cgmath::Vector3<f32>) {
+        self.params.dt = delta_time.min(MAX_DT);
+        self.params.time += self.params.dt;
+        self.params.wind = [wind.x, wind.y, wind.z, 0.0];
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&self.params));
+    }
+
+    // Record the simulation step, must be encoded before the render pass that draws the cloth
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Cloth Simulation Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        // Integration doesnt read the batch, but the layout has it so it has to be set
+        compute_pass.set_bind_group(1, &self.batch_bind_group, &[0]);
+        compute_pass.set_pipeline(&self.compute_pipeline);
+        compute_pass.dispatch_workgroups(self.params.vertex_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+
+        // Each dispatch sees the writes of the one before, so batches go one after the other
+        compute_pass.set_pipeline(&self.constraint_pipeline);
+        for _ in 0..ITERATIONS {
+            for (i, batch) in self.batches.iter().enumerate() {
+                compute_pass.set_bind_group(1, &self.batch_bind_group, &[i as u32 * self.batch_stride]);
+                compute_pass.dispatch_workgroups((batch.end - batch.start).div_ceil(WORKGROUP_SIZE), 1, 1);
+            }
+        }
+    }
+
+    // Inside the scene pass, like the debug lines it changes the pipeline and reuses the camera
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass, camera_bind_group: &wgpu::BindGroup) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.position_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.uv_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.index_count, 0, 0..1);
+    }
+}
+
+// Row by row from the top left corner, x to the right and y down, inverse mass 1
+fn grid_positions(width: u32, height: u32, spacing: f32, origin: [f32; 3]) -> Vec<[f32; 4]> {
+    (0..width * height)
+        .map(|i| {
+            let (x, y) = ((i % width) as f32, (i / width) as f32);
+            [origin[0] + x * spacing, origin[1] - y * spacing, origin[2], 1.0]
+        })
+        .collect()
+}
+
+fn grid_uvs(width: u32, height: u32) -> Vec<[f32; 2]> {
+    (0..width * height)
+        .map(|i| [(i % width) as f32 / (width - 1) as f32, (i / width) as f32 / (height - 1) as f32])
+        .collect()
+}
+
+// Two triangles per cell
+fn grid_indices(width: u32, height: u32) -> Vec<u32> {
+    let mut indices = Vec::new();
+    for y in 0..height - 1 {
+        for x in 0..width - 1 {
+            let top_left = y * width + x;
+            let bottom_left = top_left + width;
+            indices.extend_from_slice(&[top_left, bottom_left, top_left + 1, top_left + 1, bottom_left, bottom_left + 1]);
+        }
+    }
+    indices
+}
+
+// Structural, shear and bend constraints, at the distances of the flat grid
+fn grid_constraints(width: u32, height: u32, spacing: f32) -> Vec<Constraint> {
+    // (dx, dy) to the other end, only pointing forward so every pair shows up once
+    let links: [(i32, i32, f32); 6] = [
+        (1, 0, 1.0), // Structural
+        (0, 1, 1.0),
+        (1, 1, std::f32::consts::SQRT_2), // Shear
+        (-1, 1, std::f32::consts::SQRT_2),
+        (2, 0, 2.0), // Bend
+        (0, 2, 2.0),
+    ];
+    let mut constraints = Vec::new();
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            for &(dx, dy, length) in &links {
+                let (other_x, other_y) = (x + dx, y + dy);
+                if other_x < 0 || other_x >= width as i32 || other_y >= height as i32 {
+                    continue;
+                }
+                constraints.push(Constraint {
+                    a: (y * width as i32 + x) as u32,
+                    b: (other_y * width as i32 + other_x) as u32,
+                    rest_length: length * spacing,
+                    _padding: 0,
+                });
+            }
+        }
+    }
+    constraints
+}
+
+// Greedy coloring: each constraint goes into the first batch that doesnt use either of its
+// vertices yet. Returns the constraints ordered by batch and the range of every batch
+fn batch_constraints(constraints: &[Constraint], vertex_count: usize) -> (Vec<Constraint>, Vec<Range<u32>>) {
+    let mut batches: Vec<(Vec<Constraint>, Vec<bool>)> = Vec::new();
+    for constraint in constraints {
+        let (a, b) = (constraint.a as usize, constraint.b as usize);
+        let batch = match batches.iter().position(|(_, used)| !used[a] && !used[b]) {
+            Some(batch) => batch,
+            None => {
+                batches.push((Vec::new(), vec![false; vertex_count]));
+                batches.len() - 1
+            }
+        };
+        let (batch_constraints, used) = &mut batches[batch];
+        batch_constraints.push(*constraint);
+        used[a] = true;
+        used[b] = true;
+    }
+
+    let mut ordered = Vec::with_capacity(constraints.len());
+    let mut ranges = Vec::with_capacity(batches.len());
+    for (batch_constraints, _) in batches {
+        let start = ordered.len() as u32;
+        ordered.extend(batch_constraints);
+        ranges.push(start..ordered.len() as u32);
+    }
+    (ordered, ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_constraints_count_and_rest_length() {
+        let constraints = grid_constraints(3, 3, 0.5);
+        // 12 structural, 8 shear, 6 bend
+        assert_eq!(constraints.len(), 26);
+        let diagonal = constraints.iter().find(|c| (c.a, c.b) == (0, 4)).unwrap();
+        assert!((diagonal.rest_length - 0.5 * std::f32::consts::SQRT_2).abs() < 1e-6);
+        let bend = constraints.iter().find(|c| (c.a, c.b) == (0, 2)).unwrap();
+        assert_eq!(bend.rest_length, 1.0);
+    }
+
+    #[test]
+    fn test_batches_never_share_a_vertex() {
+        let constraints = grid_constraints(GRID_SIZE, GRID_SIZE, SPACING);
+        let vertex_count = (GRID_SIZE * GRID_SIZE) as usize;
+        let (ordered, batches) = batch_constraints(&constraints, vertex_count);
+        assert_eq!(ordered.len(), constraints.len());
+        assert_eq!(batches.last().unwrap().end as usize, ordered.len());
+        for batch in &batches {
+            let mut used = vec![false; vertex_count];
+            for constraint in &ordered[batch.start as usize..batch.end as usize] {
+                assert!(!used[constraint.a as usize] && !used[constraint.b as usize]);
+                used[constraint.a as usize] = true;
+                used[constraint.b as usize] = true;
+            }
+        }
+        // A vertex has up to 12 constraints, greedy coloring stays close to that
+        assert!(batches.len() <= 16, "{}", batches.len());
+    }
+
+    #[test]
+    fn test_grid_indices_and_uvs() {
+        let indices = grid_indices(3, 2);
+        assert_eq!(indices, vec![0, 3, 1, 1, 3, 4, 1, 4, 2, 2, 4, 5]);
+        let uvs = grid_uvs(3, 2);
+        assert_eq!(uvs[0], [0.0, 0.0]);
+        assert_eq!(uvs[5], [1.0, 1.0]);
+    }
+
+    // Simulates until the cloth settles on the GPU and reads the positions back
+    // Skips when the machine has no adapter at all (no GPU and no software fallback)
+    #[test]
+    fn test_cloth_hangs_from_the_pins() {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let Ok(adapter) = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())) else {
+            eprintln!("No adapter available, skipping cloth simulation test");
+            return;
+        };
+        if !adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS) {
+            eprintln!("No compute shaders, skipping cloth simulation test");
+            return;
+        }
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).unwrap();
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let mut cloth = ClothSim::new(&device, wgpu::TextureFormat::Rgba8UnormSrgb, wgpu::TextureFormat::Depth32Float, &camera_layout);
+
+        // 20 simulated seconds, long enough for the damping to stop the swinging
+        for _ in 0..600 {
+            cloth.update(&queue, MAX_DT, cgmath::Vector3::new(0.0, 0.0, -2.0));
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            cloth.dispatch(&mut encoder);
+            queue.submit(std::iter::once(encoder.finish()));
+        }
+
+        let size = cloth.position_buffer.size();
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cloth Test Readback"),
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.copy_buffer_to_buffer(&cloth.position_buffer, 0, &readback, 0, size);
+        queue.submit(std::iter::once(encoder.finish()));
+        readback.slice(..).map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+        let data = readback.slice(..).get_mapped_range();
+        let positions: &[[f32; 4]] = bytemuck::cast_slice(&data);
+
+        // Pins stayed where they were, with no mass
+        assert_eq!(positions[0], [ORIGIN[0], ORIGIN[1], ORIGIN[2], 0.0]);
+        assert_eq!(positions[GRID_SIZE as usize - 1][3], 0.0);
+        // The rest fell and the wind blew it back, but the constraints held it together
+        let bottom_middle = positions[(GRID_SIZE * (GRID_SIZE - 1) + GRID_SIZE / 2) as usize];
+        assert!(bottom_middle.iter().all(|v| v.is_finite()));
+        assert!(bottom_middle[2] < ORIGIN[2], "{bottom_middle:?}");
+        let top_to_bottom = (ORIGIN[1] - bottom_middle[1]).abs();
+        let cloth_length = SPACING * (GRID_SIZE - 1) as f32;
+        assert!(top_to_bottom > cloth_length * 0.5 && top_to_bottom < cloth_length * 1.5, "{top_to_bottom}");
+    }
+}
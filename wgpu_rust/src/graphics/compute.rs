@@ -0,0 +1,173 @@
+use wgpu::util::DeviceExt;
+use crate::graphics::buffers;
+use crate::graphics::instance::InstanceRaw;
+use crate::graphics::profiler::{self, PipelineStatsQuery};
+
+// GPU side instance animation using a compute shader
+// Instead of recalculating every model matrix on the CPU and uploading it each frame,
+// we upload the rest transforms once and let the GPU write the animated ones
+// into a storage buffer. That same buffer is then bound as the instance vertex buffer.
+// The basic render path keeps using the static instance buffer when this is disabled.
+
+const WORKGROUP_SIZE: u32 = 64; // Must match @workgroup_size in compute.wgsl
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ComputeParams {
+    time: f32,
+    amplitude: f32,
+    instance_count: u32,
+    _padding: u32, // Uniforms need 16 byte alignment
+}
+
+pub struct InstanceAnimation {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    params: ComputeParams,
+    params_buffer: wgpu::Buffer,
+    // STORAGE so the compute shader can write to it, VERTEX so the render pass can read it
+    pub output_buffer: wgpu::Buffer,
+}
+
+impl InstanceAnimation {
+    pub fn new(device: &wgpu::Device, instance_data: &[InstanceRaw], amplitude: f32) -> Self {
+        // Rest transforms, only read by the shader
+        let rest_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Rest Instance Buffer"),
+            contents: bytemuck::cast_slice(instance_data),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        // Starts with the same data so the first frame is valid even before a dispatch
+        let output_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Animated Instance Buffer"),
+            contents: bytemuck::cast_slice(instance_data),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
+        });
+
+        let params = ComputeParams {
+            time: 0.0,
+            amplitude,
+            instance_count: instance_data.len() as u32,
+            _padding: 0,
+        };
+        let params_buffer = buffers::create_uniform_buffer(device, &params);
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Instance Animation Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Instance Animation Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: rest_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: output_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Instance Animation Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Compute Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/compute.wgsl").into()),
+        });
+
+        // Compute pipelines are much simpler than render pipelines, there is no
+        // fixed function state (rasterizer, depth, blending), only the shader entry point
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Instance Animation Pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            bind_group,
+            params,
+            params_buffer,
+            output_buffer,
+        }
+    }
+
+    // Advance the animation clock, uploaded to the GPU before the dispatch
+    pub fn update(&mut self, queue: &wgpu::Queue, delta_time: f32) {
+        self.params.time += delta_time;
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[self.params]));
+    }
+
+    // Record the compute pass, must be encoded before the render pass that reads output_buffer
+    // stats counts the invocations of this pass when pipeline statistics are being recorded
+    // timestamp_writes times the pass on the GPU, see profiler::GpuProfiler
+    pub fn dispatch(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        stats: Option<&PipelineStatsQuery>,
+        timestamp_writes: Option<wgpu::ComputePassTimestampWrites>,
+    ) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Instance Animation Pass"),
+            timestamp_writes,
+        });
+        if let Some(stats) = stats {
+            compute_pass.begin_pipeline_statistics_query(&stats.query_set, profiler::COMPUTE_QUERY);
+        }
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        // Round up so every instance gets an invocation
+        let workgroups = self.params.instance_count.div_ceil(WORKGROUP_SIZE);
+        compute_pass.dispatch_workgroups(workgroups, 1, 1);
+        if stats.is_some() {
+            compute_pass.end_pipeline_statistics_query();
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use crate::graphics::texture;
+
+// Bezier curves drawn as line strips
+// The curve is split in half (de Casteljau) over and over until every piece is flat enough,
+// so tight bends get many segments and straight stretches only a few.
+// Any number of control points works, 4 is the usual cubic curve.
+
+// Pieces are never split deeper than this, 2^16 segments is far past anything visible
+const MAX_DEPTH: u32 = 16;
+const INITIAL_CAPACITY: u64 = 1024; // Vertices
+
+pub struct BezierCurve {
+    pub control_points: Vec<[f32; 3]>,
+    pub color: [f32; 4],
+}
+
+impl BezierCurve {
+    // Points along the curve, first and last control point included
+    // max_error is the largest distance (world units) a segment may be away from the real curve
+    pub fn tessellate(&self, max_error: f32) -> Vec<[f32; 3]> {
+        let points: Vec<cgmath::Vector3<f32>> = self.control_points.iter().map(|&p| p.into()).collect();
+        let Some(&first) = points.first() else {
+            return Vec::new();
+        };
+        let mut out = vec![first.into()];
+        if points.len() > 1 {
+            subdivide(&points, max_error, MAX_DEPTH, &mut out);
+        }
+        out
+    }
+}
+
+// Appends the end of this piece, or of both halves when it is not flat yet
+fn subdivide(points: &[cgmath::Vector3<f32>], max_error: f32, depth: u32, out: &mut Vec<[f32; 3]>) {
+    if depth == 0 || chord_error(points) <= max_error {
+        out.push(points[points.len() - 1].into());
+        return;
+    }
+    let (left, right) = split_half(points);
+    subdivide(&left, max_error, depth - 1, out);
+    subdivide(&right, max_error, depth - 1, out);
+}
+
+// The curve stays inside its control polygon, so the farthest control point from the chord
+// (first to last point) is an upper bound on how far the chord is from the curve
+fn chord_error(points: &[cgmath::Vector3<f32>]) -> f32 {
+    use cgmath::InnerSpace;
+
+    let start = points[0];
+    let chord = points[points.len() - 1] - start;
+    let length2 = chord.magnitude2();
+    points[1..points.len() - 1]
+        .iter()
+        .map(|&p| {
+            let offset = p - start;
+            if length2 == 0.0 {
+                // Closed piece, the chord is a single point
+                return offset.magnitude();
+            }
+            // Distance to the closest point on the chord segment
+            let t = (offset.dot(chord) / length2).clamp(0.0, 1.0);
+            (offset - chord * t).magnitude()
+        })
+        .fold(0.0, f32::max)
+}
+
+// de Casteljau at t = 0.5: average neighbours until one point is left, the first point of
+// every round is a control point of the left half and the last one of the right half
+fn split_half(points: &[cgmath::Vector3<f32>]) -> (Vec<cgmath::Vector3<f32>>, Vec<cgmath::Vector3<f32>>) {
+    let mut left = Vec::with_capacity(points.len());
+    let mut right = Vec::with_capacity(points.len());
+    let mut round = points.to_vec();
+    loop {
+        left.push(round[0]);
+        right.push(round[round.len() - 1]);
+        if round.len() == 1 {
+            break;
+        }
+        round = round.windows(2).map(|pair| (pair[0] + pair[1]) * 0.5).collect();
+    }
+    right.reverse();
+    (left, right)
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct CurveVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+impl CurveVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<CurveVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+pub struct CurveRenderer {
+    pub vertex_buffer: wgpu::Buffer,
+    pub pipeline: wgpu::RenderPipeline,
+    vertex_capacity: u64,
+    max_error: f32,
+}
+
+impl CurveRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        max_error: f32,
+    ) -> Self {
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Curve Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout], // Same camera as the scene
+            immediate_size: 0,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Curve Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/curves.wgsl").into()),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Curve Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[CurveVertex::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING), // Color alpha fades the curve
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                // Every vertex continues the line from the previous one, one draw per curve
+                topology: wgpu::PrimitiveTopology::LineStrip,
+                cull_mode: None,
+                ..Default::default()
+            },
+            // Hidden behind the scene like the debug lines, but never hides anything itself
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        Self {
+            vertex_buffer: Self::create_vertex_buffer(device, INITIAL_CAPACITY),
+            pipeline,
+            vertex_capacity: INITIAL_CAPACITY,
+            max_error,
+        }
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, capacity: u64) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Curve Vertex Buffer"),
+            size: capacity * std::mem::size_of::<CurveVertex>() as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    // Tessellates and uploads the curves, then draws them on top of what is already in target
+    // The buffer is written through the queue, so call this once per frame
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        depth: &wgpu::TextureView,
+        camera_bind_group: &wgpu::BindGroup,
+        curves: &[BezierCurve],
+    ) {
+        // Every curve is its own strip, the ranges keep them from being joined together
+        let mut vertices = Vec::new();
+        let mut ranges = Vec::with_capacity(curves.len());
+        for curve in curves {
+            let start = vertices.len() as u32;
+            vertices.extend(curve.tessellate(self.max_error).into_iter()
+                .map(|position| CurveVertex { position, color: curve.color }));
+            ranges.push(start..vertices.len() as u32);
+        }
+        if vertices.is_empty() {
+            return;
+        }
+
+        // Grows to the next power of two like the debug lines, so it isnt reallocated every frame
+        let needed = vertices.len() as u64;
+        if needed > self.vertex_capacity {
+            self.vertex_capacity = needed.next_power_of_two();
+            self.vertex_buffer = Self::create_vertex_buffer(device, self.vertex_capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Curve Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load, // Keep the scene
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+            multiview_mask: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        for range in ranges.into_iter().filter(|range| range.len() > 1) {
+            render_pass.draw(range, 0..1);
+        }
+    }
+}
+
+// Circle on a horizontal plane from 4 cubic curves, each a quarter
+// The inner control points sit KAPPA * radius along the tangent, the classic circle approximation
+pub fn horizontal_circle(center: [f32; 3], radius: f32, color: [f32; 4]) -> Vec<BezierCurve> {
+    const KAPPA: f32 = 0.552_284_8;
+    let [x, y, z] = center;
+    let k = KAPPA * radius;
+    // Corner points around the circle and the direction to walk from each to the next
+    let quarters = [
+        ([radius, 0.0], [0.0, 1.0]),
+        ([0.0, radius], [-1.0, 0.0]),
+        ([-radius, 0.0], [0.0, -1.0]),
+        ([0.0, -radius], [1.0, 0.0]),
+    ];
+    (0..4)
+        .map(|i| {
+            let (start, start_dir) = quarters[i];
+            let (end, end_dir) = quarters[(i + 1) % 4];
+            BezierCurve {
+                control_points: vec![
+                    [x + start[0], y, z + start[1]],
+                    [x + start[0] + start_dir[0] * k, y, z + start[1] + start_dir[1] * k],
+                    [x + end[0] - end_dir[0] * k, y, z + end[1] - end_dir[1] * k],
+                    [x + end[0], y, z + end[1]],
+                ],
+                color,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distance_from_origin(p: [f32; 3]) -> f32 {
+        (p[0] * p[0] + p[1] * p[1] + p[2] * p[2]).sqrt()
+    }
+
+    #[test]
+    fn test_straight_curve_is_one_segment() {
+        let curve = BezierCurve {
+            control_points: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [2.0, 0.0, 0.0], [3.0, 0.0, 0.0]],
+            color: [1.0; 4],
+        };
+        assert_eq!(curve.tessellate(0.001), vec![[0.0, 0.0, 0.0], [3.0, 0.0, 0.0]]);
+    }
+
+    #[test]
+    fn test_split_half_matches_curve_midpoint() {
+        // Quadratic: B(0.5) = 0.25 p0 + 0.5 p1 + 0.25 p2
+        let points: Vec<cgmath::Vector3<f32>> = vec![(0.0, 0.0, 0.0).into(), (1.0, 2.0, 0.0).into(), (2.0, 0.0, 0.0).into()];
+        let (left, right) = split_half(&points);
+        assert_eq!(left.len(), 3);
+        assert_eq!(left[2], cgmath::Vector3::new(1.0, 1.0, 0.0));
+        assert_eq!(right[0], left[2]);
+        assert_eq!(right[2], points[2]);
+    }
+
+    #[test]
+    fn test_circle_stays_within_error() {
+        let radius = 10.0;
+        for max_error in [0.5, 0.05, 0.005] {
+            let mut segments = 0;
+            for curve in horizontal_circle([0.0; 3], radius, [1.0; 4]) {
+                let points = curve.tessellate(max_error);
+                segments += points.len() - 1;
+                assert_eq!(points.first(), curve.control_points.first());
+                assert_eq!(points.last(), curve.control_points.last());
+                for pair in points.windows(2) {
+                    let mid = [(pair[0][0] + pair[1][0]) * 0.5, 0.0, (pair[0][2] + pair[1][2]) * 0.5];
+                    // Sagitta of the chord, plus the small error of the 4 curve approximation itself
+                    assert!(radius - distance_from_origin(mid) <= max_error + radius * 0.0003);
+                }
+            }
+            // Tighter error needs more segments, but only as many as the bend asks for
+            assert!(segments >= 4 && segments < 4 * 2usize.pow(MAX_DEPTH / 2));
+        }
+    }
+
+    #[test]
+    fn test_degenerate_curves() {
+        let empty = BezierCurve { control_points: vec![], color: [1.0; 4] };
+        assert!(empty.tessellate(0.1).is_empty());
+        let single = BezierCurve { control_points: vec![[1.0, 2.0, 3.0]], color: [1.0; 4] };
+        assert_eq!(single.tessellate(0.1), vec![[1.0, 2.0, 3.0]]);
+    }
+}
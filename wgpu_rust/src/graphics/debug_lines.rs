@@ -0,0 +1,171 @@
+// Immediate mode debug drawing
+// Call line() (or the axes/grid/box helpers) every frame for whatever you want to see, then
+// prepare() uploads the whole batch into one vertex buffer and draw() renders it as a LineList
+// inside the main render pass, so lines are depth tested against the scene.
+// Nothing is kept between frames, if you stop calling line() the line disappears.
+
+const INITIAL_CAPACITY: u64 = 1024; // Vertices, 2 per line
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+impl LineVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+pub struct DebugLines {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    vertex_capacity: u64,
+    vertices: Vec<LineVertex>,
+    vertex_count: u32, // Vertices uploaded by the last prepare
+}
+
+impl DebugLines {
+    pub fn new(
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug Lines Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout], // Same camera as the scene
+            immediate_size: 0,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Debug Lines Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/debug_lines.wgsl").into()),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Debug Lines Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[LineVertex::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                // Every 2 vertices are one independent line
+                topology: wgpu::PrimitiveTopology::LineList,
+                cull_mode: None, // Lines have no faces
+                ..Default::default()
+            },
+            // Render pass has a depth attachment, so the format has to match even if we only test
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: false, // Gizmos shouldnt hide each other
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let vertex_buffer = Self::create_vertex_buffer(device, INITIAL_CAPACITY);
+
+        Self {
+            pipeline,
+            vertex_buffer,
+            vertex_capacity: INITIAL_CAPACITY,
+            vertices: Vec::new(),
+            vertex_count: 0,
+        }
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, capacity: u64) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Debug Lines Vertex Buffer"),
+            size: capacity * std::mem::size_of::<LineVertex>() as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    pub fn line(&mut self, a: cgmath::Point3<f32>, b: cgmath::Point3<f32>, color: [f32; 3]) {
+        self.vertices.push(LineVertex { position: a.into(), color });
+        self.vertices.push(LineVertex { position: b.into(), color });
+    }
+
+    // Red X, green Y, blue Z
+    pub fn axes(&mut self, origin: cgmath::Point3<f32>, length: f32) {
+        self.line(origin, origin + cgmath::Vector3::unit_x() * length, [1.0, 0.0, 0.0]);
+        self.line(origin, origin + cgmath::Vector3::unit_y() * length, [0.0, 1.0, 0.0]);
+        self.line(origin, origin + cgmath::Vector3::unit_z() * length, [0.0, 0.0, 1.0]);
+    }
+
+    // size x size cells on the y = 0 plane, centered on the origin
+    pub fn grid(&mut self, size: u32, spacing: f32, color: [f32; 3]) {
+        let half = size as f32 * spacing * 0.5;
+        for i in 0..=size {
+            let offset = i as f32 * spacing - half;
+            self.line((offset, 0.0, -half).into(), (offset, 0.0, half).into(), color);
+            self.line((-half, 0.0, offset).into(), (half, 0.0, offset).into(), color);
+        }
+    }
+
+    // 12 edges of a box given its 8 corners, ordered by bits: x = bit 0, y = bit 1, z = bit 2
+    pub fn box_corners(&mut self, corners: &[cgmath::Point3<f32>; 8], color: [f32; 3]) {
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                // Connect each corner to its neighbour along each axis once
+                if i & bit == 0 {
+                    self.line(corners[i], corners[i | bit], color);
+                }
+            }
+        }
+    }
+
+    // Upload this frame's lines, the CPU list is cleared for the next frame
+    // Buffer grows to the next power of two so we dont reallocate every frame
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let needed = self.vertices.len() as u64;
+        if needed > self.vertex_capacity {
+            self.vertex_capacity = needed.next_power_of_two();
+            self.vertex_buffer = Self::create_vertex_buffer(device, self.vertex_capacity);
+        }
+        if needed > 0 {
+            queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        }
+        self.vertex_count = needed as u32;
+        self.vertices.clear();
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass, camera_bind_group: &wgpu::BindGroup) {
+        if self.vertex_count == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
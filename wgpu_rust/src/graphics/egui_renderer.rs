@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+
+// Small egui painter on top of our wgpu version
+// egui gives us, every frame, a list of textures to create/update/free (its font atlas, images)
+// and a list of triangle meshes in points (logical pixels) with a clip rectangle each.
+// We upload all meshes into one vertex + index buffer and draw them one by one with a scissor
+// rect, premultiplied alpha blending and no depth, on top of the finished frame.
+
+pub struct EguiRenderer {
+    pipeline: wgpu::RenderPipeline,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    screen_buffer: wgpu::Buffer,
+    screen_bind_group: wgpu::BindGroup,
+    textures: HashMap<egui::TextureId, (wgpu::Texture, wgpu::BindGroup)>,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ScreenUniform {
+    size_in_points: [f32; 2],
+    _padding: [f32; 2],
+}
+
+impl EguiRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        layouts: &crate::graphics::texture::TextureLayoutCache,
+        color_format: wgpu::TextureFormat,
+    ) -> Self {
+        let screen_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Egui Screen Buffer"),
+            size: std::mem::size_of::<ScreenUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let screen_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Egui Screen Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let screen_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Egui Screen Bind Group"),
+            layout: &screen_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: screen_buffer.as_entire_binding(),
+            }],
+        });
+
+        // Same layout as our own textures, texture at binding 0 and sampler at binding 1
+        let texture_bind_group_layout = layouts.texture_bind_group_layout.clone();
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Egui Pipeline Layout"),
+            bind_group_layouts: &[&screen_bind_group_layout, &texture_bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Egui Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/egui.wgsl").into()),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Egui Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[wgpu::VertexBufferLayout {
+                    // egui::epaint::Vertex: pos, uv (points and 0..1) and a sRGBA color in 4 bytes
+                    array_stride: std::mem::size_of::<egui::epaint::Vertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Unorm8x4],
+                }],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    // egui colors come with premultiplied alpha
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(), // egui doesnt care about winding
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let vertex_buffer = Self::create_buffer(device, "Egui Vertex Buffer", wgpu::BufferUsages::VERTEX, 1024 * 1024);
+        let index_buffer = Self::create_buffer(device, "Egui Index Buffer", wgpu::BufferUsages::INDEX, 1024 * 1024);
+
+        Self {
+            pipeline,
+            texture_bind_group_layout,
+            screen_buffer,
+            screen_bind_group,
+            textures: HashMap::new(),
+            vertex_buffer,
+            index_buffer,
+        }
+    }
+
+    fn create_buffer(device: &wgpu::Device, label: &str, usage: wgpu::BufferUsages, size: u64) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size,
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    // Create or partially update the textures egui asked for
+    pub fn update_textures(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, delta: &egui::TexturesDelta) {
+        for (id, image_delta) in &delta.set {
+            let egui::ImageData::Color(image) = &image_delta.image;
+            let [width, height] = image.size;
+            let size = wgpu::Extent3d {
+                width: width as u32,
+                height: height as u32,
+                depth_or_array_layers: 1,
+            };
+
+            // Whole new texture, otherwise pos says where the patch goes in the existing one
+            if image_delta.pos.is_none() {
+                let texture = device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("Egui Texture"),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                    view_formats: &[],
+                });
+                let filter = |filter: egui::TextureFilter| match filter {
+                    egui::TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+                    egui::TextureFilter::Linear => wgpu::FilterMode::Linear,
+                };
+                let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                    label: Some("Egui Sampler"),
+                    mag_filter: filter(image_delta.options.magnification),
+                    min_filter: filter(image_delta.options.minification),
+                    ..Default::default()
+                });
+                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Egui Texture Bind Group"),
+                    layout: &self.texture_bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&sampler),
+                        },
+                    ],
+                });
+                self.textures.insert(*id, (texture, bind_group));
+            }
+
+            let Some((texture, _)) = self.textures.get(id) else {
+                continue;
+            };
+            let [x, y] = image_delta.pos.unwrap_or([0, 0]);
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x: x as u32, y: y as u32, z: 0 },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                bytemuck::cast_slice(&image.pixels),
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * width as u32),
+                    rows_per_image: Some(height as u32),
+                },
+                size,
+            );
+        }
+    }
+
+    // Textures egui no longer needs, called after the frame that last used them
+    pub fn free_textures(&mut self, delta: &egui::TexturesDelta) {
+        for id in &delta.free {
+            self.textures.remove(id);
+        }
+    }
+
+    // Draw the tessellated egui output over view, size_in_pixels is the size of the target
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        primitives: &[egui::ClippedPrimitive],
+        size_in_pixels: [u32; 2],
+        pixels_per_point: f32,
+    ) {
+        let screen = ScreenUniform {
+            size_in_points: [
+                size_in_pixels[0] as f32 / pixels_per_point,
+                size_in_pixels[1] as f32 / pixels_per_point,
+            ],
+            _padding: [0.0; 2],
+        };
+        queue.write_buffer(&self.screen_buffer, 0, bytemuck::cast_slice(&[screen]));
+
+        // Pack every mesh into the shared buffers, remembering where each one starts
+        let mut vertices: Vec<egui::epaint::Vertex> = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
+        let mut draws = Vec::new();
+        for egui::ClippedPrimitive { clip_rect, primitive } in primitives {
+            // Paint callbacks would need to run our own code inside the pass, not supported
+            let egui::epaint::Primitive::Mesh(mesh) = primitive else {
+                continue;
+            };
+            let index_range = indices.len() as u32..(indices.len() + mesh.indices.len()) as u32;
+            draws.push((*clip_rect, mesh.texture_id, index_range, vertices.len() as i32));
+            vertices.extend_from_slice(&mesh.vertices);
+            indices.extend_from_slice(&mesh.indices);
+        }
+        if draws.is_empty() {
+            return;
+        }
+
+        // Grow geometrically when a frame needs more space than we have
+        let vertex_bytes: &[u8] = bytemuck::cast_slice(&vertices);
+        let index_bytes: &[u8] = bytemuck::cast_slice(&indices);
+        if vertex_bytes.len() as u64 > self.vertex_buffer.size() {
+            let size = (vertex_bytes.len() as u64).next_power_of_two();
+            self.vertex_buffer = Self::create_buffer(device, "Egui Vertex Buffer", wgpu::BufferUsages::VERTEX, size);
+        }
+        if index_bytes.len() as u64 > self.index_buffer.size() {
+            let size = (index_bytes.len() as u64).next_power_of_two();
+            self.index_buffer = Self::create_buffer(device, "Egui Index Buffer", wgpu::BufferUsages::INDEX, size);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, vertex_bytes);
+        queue.write_buffer(&self.index_buffer, 0, index_bytes);
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Egui Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+            multiview_mask: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.screen_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+        for (clip_rect, texture_id, index_range, base_vertex) in draws {
+            let Some((_, bind_group)) = self.textures.get(&texture_id) else {
+                continue;
+            };
+
+            // Clip rect is in points, scissor is in pixels and must stay inside the target
+            let min_x = (clip_rect.min.x * pixels_per_point).round().clamp(0.0, size_in_pixels[0] as f32) as u32;
+            let min_y = (clip_rect.min.y * pixels_per_point).round().clamp(0.0, size_in_pixels[1] as f32) as u32;
+            let max_x = (clip_rect.max.x * pixels_per_point).round().clamp(min_x as f32, size_in_pixels[0] as f32) as u32;
+            let max_y = (clip_rect.max.y * pixels_per_point).round().clamp(min_y as f32, size_in_pixels[1] as f32) as u32;
+            if max_x == min_x || max_y == min_y {
+                continue;
+            }
+
+            render_pass.set_scissor_rect(min_x, min_y, max_x - min_x, max_y - min_y);
+            render_pass.set_bind_group(1, bind_group, &[]);
+            render_pass.draw_indexed(index_range, base_vertex, 0..1);
+        }
+    }
+}
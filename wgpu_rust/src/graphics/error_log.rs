@@ -0,0 +1,234 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+// Collecting wgpu validation errors instead of panicking on them
+// An invalid texture, pipeline or bind group normally goes to the uncaptured error handler, which
+// panics, and all we get is a backtrace pointing somewhere inside wgpu.
+// An error scope hands the errors of everything created between push and pop back to us instead,
+// so setup runs phase by phase in scopes with a label ("scene pipelines") and the errors end up in
+// a GpuErrorLog next to that label. Where we can keep going (a shader reload that doesnt compile
+// keeps the old pipelines) the error is only logged, the report is printed when the app exits.
+// How much is checked comes from the environment:
+//   WGPU_VALIDATION=0          API validation layers (Vulkan, DX12) off, on by default in debug builds
+//   WGPU_DEBUG=1               labels and debug markers for tools like RenderDoc, on in debug builds
+//   WGPU_PANIC_ON_ERROR=1      uncaptured errors panic like before, for the backtrace
+
+const PANIC_ON_ERROR_VAR: &str = "WGPU_PANIC_ON_ERROR";
+
+// Label of errors that happened outside any scope
+pub const UNCAPTURED_LABEL: &str = "uncaptured";
+
+// Flags for the Instance, the build defaults with WGPU_VALIDATION and WGPU_DEBUG applied on top
+pub fn instance_flags() -> wgpu::InstanceFlags {
+    wgpu::InstanceFlags::from_build_config().with_env()
+}
+
+fn panic_on_error() -> bool {
+    std::env::var(PANIC_ON_ERROR_VAR).is_ok_and(|value| is_enabled(&value))
+}
+
+fn is_enabled(value: &str) -> bool {
+    matches!(value.trim().to_lowercase().as_str(), "1" | "true" | "yes" | "on")
+}
+
+// What failed (the label of the phase) and the message wgpu gave
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpuError {
+    pub label: String,
+    pub message: String,
+}
+
+impl fmt::Display for GpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.label, self.message)
+    }
+}
+
+impl std::error::Error for GpuError {}
+
+// Shared between State and the uncaptured error handler, which runs on whatever thread wgpu
+// reports from, so the list sits behind an Arc<Mutex>
+#[derive(Clone, Default)]
+pub struct GpuErrorLog {
+    errors: Arc<Mutex<Vec<GpuError>>>,
+}
+
+impl GpuErrorLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, label: &str, error: &wgpu::Error) -> GpuError {
+        let error = GpuError { label: label.to_string(), message: error.to_string() };
+        log::error!("GPU error in {}", error);
+        self.errors.lock().unwrap().push(error.clone());
+        error
+    }
+
+    // Everything recorded so far, oldest first
+    pub fn errors(&self) -> Vec<GpuError> {
+        self.errors.lock().unwrap().clone()
+    }
+
+    // Run create inside a validation scope, the value only comes back when nothing in it failed
+    // Objects created by a failed phase are invalid, using them would only fail again later
+    pub async fn capture<T>(
+        &self,
+        device: &wgpu::Device,
+        label: &str,
+        create: impl FnOnce() -> T,
+    ) -> Result<T, GpuError> {
+        let scope = device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let value = create();
+        match scope.pop().await {
+            Some(error) => Err(self.record(label, &error)),
+            None => Ok(value),
+        }
+    }
+
+    // For the device's uncaptured error handler, see watch_device in state.rs
+    // WGPU_PANIC_ON_ERROR keeps the old behaviour for everything but running out of memory,
+    // which State recovers from by rebuilding the device
+    pub fn uncaptured(&self, error: wgpu::Error) {
+        let out_of_memory = matches!(error, wgpu::Error::OutOfMemory { .. });
+        let error = self.record(UNCAPTURED_LABEL, &error);
+        if !out_of_memory && panic_on_error() {
+            panic!("Uncaptured wgpu error: {}", error.message);
+        }
+    }
+
+    // One line per label with how many errors it had and the first message, in the order the
+    // labels first failed. None when nothing went wrong
+    pub fn report(&self) -> Option<String> {
+        let errors = self.errors.lock().unwrap();
+        if errors.is_empty() {
+            return None;
+        }
+
+        let mut labels: Vec<(&str, usize, &str)> = Vec::new();
+        for error in errors.iter() {
+            match labels.iter_mut().find(|(label, _, _)| *label == error.label) {
+                Some((_, count, _)) => *count += 1,
+                None => labels.push((&error.label, 1, &error.message)),
+            }
+        }
+
+        let mut report = format!("{} GPU error(s):", errors.len());
+        for (label, count, message) in labels {
+            // wgpu messages span several lines, the first one says what went wrong
+            let first_line = message.lines().next().unwrap_or_default();
+            report.push_str(&format!("\n  {} ({}x): {}", label, count, first_line));
+        }
+        Some(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::headless::HeadlessContext;
+
+    fn error(label: &str, message: &str) -> GpuError {
+        GpuError { label: label.to_string(), message: message.to_string() }
+    }
+
+    // A bind group with a buffer that is missing the UNIFORM usage the layout asks for
+    fn create_invalid_bind_group(device: &wgpu::Device) -> wgpu::BindGroup {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Uniform Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Vertex Only Buffer"),
+            size: 64,
+            usage: wgpu::BufferUsages::VERTEX,
+            mapped_at_creation: false,
+        });
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Invalid Bind Group"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: buffer.as_entire_binding() }],
+        })
+    }
+
+    #[test]
+    fn test_report_groups_errors_by_label() {
+        let log = GpuErrorLog::new();
+        assert_eq!(log.report(), None);
+
+        log.errors.lock().unwrap().extend([
+            error("scene pipelines", "Shader is invalid\n  more detail"),
+            error("diffuse texture", "Bad size"),
+            error("scene pipelines", "Layout mismatch"),
+        ]);
+        assert_eq!(
+            log.report().unwrap(),
+            "3 GPU error(s):\n  scene pipelines (2x): Shader is invalid\n  diffuse texture (1x): Bad size"
+        );
+    }
+
+    #[test]
+    fn test_is_enabled() {
+        assert!(is_enabled("1"));
+        assert!(is_enabled(" True "));
+        assert!(!is_enabled("0"));
+        assert!(!is_enabled(""));
+    }
+
+    #[test]
+    fn test_invalid_bind_group_is_captured_with_its_label() {
+        let Ok(context) = pollster::block_on(HeadlessContext::new()) else {
+            eprintln!("No adapter available, skipping error scope test");
+            return;
+        };
+        let log = GpuErrorLog::new();
+
+        let result = pollster::block_on(log.capture(&context.device, "test bind group", || {
+            create_invalid_bind_group(&context.device)
+        }));
+        let Err(error) = result else {
+            panic!("invalid bind group should be captured");
+        };
+        assert_eq!(error.label, "test bind group");
+        assert_eq!(log.errors(), vec![error]);
+
+        // Nothing invalid, nothing recorded
+        let result = pollster::block_on(log.capture(&context.device, "valid buffer", || {
+            context.device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size: 64,
+                usage: wgpu::BufferUsages::UNIFORM,
+                mapped_at_creation: false,
+            })
+        }));
+        assert!(result.is_ok());
+        assert_eq!(log.errors().len(), 1);
+    }
+
+    #[test]
+    fn test_uncaptured_error_goes_to_the_log() {
+        let Ok(context) = pollster::block_on(HeadlessContext::new()) else {
+            eprintln!("No adapter available, skipping uncaptured error test");
+            return;
+        };
+        let log = GpuErrorLog::new();
+        let handler_log = log.clone();
+        context.device.on_uncaptured_error(Arc::new(move |error| handler_log.uncaptured(error)));
+
+        create_invalid_bind_group(&context.device);
+        context.device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+
+        let errors = log.errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].label, UNCAPTURED_LABEL);
+    }
+}
@@ -0,0 +1,60 @@
+// Values every shader might want, uploaded once per frame from State::update
+// time drives animated effects, delta is the length of the last frame, resolution the surface size
+
+// Longest frame the animations will step over, a stall (window drag, breakpoint) would
+// otherwise make everything jump ahead at once
+pub const MAX_DELTA: f32 = 0.1;
+
+// 4 x 4 bytes, exactly one 16 byte block so no padding is needed
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GlobalsUniform {
+    pub time: f32, // Seconds since the app started
+    pub delta: f32, // Seconds since the previous frame
+    pub resolution: [f32; 2], // Surface width and height in pixels
+}
+
+impl GlobalsUniform {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            time: 0.0,
+            delta: 0.0,
+            resolution: [width as f32, height as f32],
+        }
+    }
+
+    // Move the clock to the new total time, delta is clamped to MAX_DELTA
+    pub fn advance(&mut self, time: f32) {
+        self.delta = (time - self.time).clamp(0.0, MAX_DELTA);
+        self.time = time;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Same order and offsets as the WGSL struct, and a multiple of 16 bytes for the uniform buffer
+    #[test]
+    fn test_globals_layout() {
+        assert_eq!(size_of::<GlobalsUniform>(), 16);
+        assert_eq!(size_of::<GlobalsUniform>() % 16, 0);
+        assert_eq!(std::mem::offset_of!(GlobalsUniform, time), 0);
+        assert_eq!(std::mem::offset_of!(GlobalsUniform, delta), 4);
+        assert_eq!(std::mem::offset_of!(GlobalsUniform, resolution), 8);
+
+        let globals = GlobalsUniform { time: 1.0, delta: 2.0, resolution: [3.0, 4.0] };
+        let floats: &[f32] = bytemuck::cast_slice(bytemuck::bytes_of(&globals));
+        assert_eq!(floats, &[1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn test_advance_clamps_delta() {
+        let mut globals = GlobalsUniform::new(800, 600);
+        globals.advance(0.016);
+        assert_eq!(globals.delta, 0.016);
+        globals.advance(5.0);
+        assert_eq!(globals.time, 5.0);
+        assert_eq!(globals.delta, MAX_DELTA);
+    }
+}
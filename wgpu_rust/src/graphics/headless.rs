@@ -0,0 +1,128 @@
+use crate::graphics::{adapter, error_log};
+
+// GPU without a window: device and queue for tests and offscreen tools
+// State needs a surface to pick its adapter, here there is nothing to present to, so any adapter
+// works. force_fallback_adapter asks for the software one (llvmpipe, WARP) first, which is what a
+// CI machine without a GPU has, and a real GPU is used when there is no software adapter.
+// The device comes from adapter::request_device, the same features and limits as State.
+
+pub struct HeadlessContext {
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+}
+
+impl HeadlessContext {
+    // Format of the render_to_image target, for pipelines drawn in its pass
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+    // Errors when the machine has no adapter at all, tests skip in that case
+    pub async fn new() -> anyhow::Result<Self> {
+        let adapter = match request_adapter(wgpu::Backends::all(), true).await {
+            Ok((_, adapter)) => adapter,
+            Err(_) => request_adapter(wgpu::Backends::all(), false).await?.1,
+        };
+        let (device, queue) = adapter::request_device(&adapter).await?;
+        Ok(Self { adapter, device, queue })
+    }
+
+    // Clear a width x height target, let draw record into the same pass and read the pixels back
+    // Rgba8Unorm so the bytes are exactly the clear color, no sRGB conversion, 4 bytes per pixel
+    // row by row from the top left
+    pub fn render_to_image(
+        &self,
+        width: u32,
+        height: u32,
+        clear_color: wgpu::Color,
+        draw: impl FnOnce(&mut wgpu::RenderPass),
+    ) -> Vec<u8> {
+        let size = wgpu::Extent3d { width, height, depth_or_array_layers: 1 };
+        let target = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Headless Target"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Headless Encoder"),
+        });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Headless Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(clear_color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+                multiview_mask: None,
+            });
+            draw(&mut render_pass);
+        }
+
+        // Buffer rows have to start on 256 bytes, so each row is padded and the padding cut off below
+        let row_bytes = width * 4;
+        let padded_row_bytes = row_bytes.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Headless Readback"),
+            size: (padded_row_bytes * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            target.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &readback,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: Some(height),
+                },
+            },
+            size,
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        readback.slice(..).map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        self.device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+        let padded = readback.slice(..).get_mapped_range();
+        padded
+            .chunks(padded_row_bytes as usize)
+            .flat_map(|row| &row[..row_bytes as usize])
+            .copied()
+            .collect()
+    }
+}
+
+// Instance and adapter for backends without a surface to support, the first step of
+// HeadlessContext::new and State::new_headless. Tests that need the adapter State::new_headless
+// gets probe with the same backends and skip on an error
+pub async fn request_adapter(
+    backends: wgpu::Backends,
+    force_fallback_adapter: bool,
+) -> anyhow::Result<(wgpu::Instance, wgpu::Adapter)> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends,
+        flags: error_log::instance_flags(),
+        ..Default::default()
+    });
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            force_fallback_adapter,
+            ..Default::default()
+        })
+        .await?;
+    Ok((instance, adapter))
+}
@@ -9,12 +9,15 @@
 pub struct Instance {
     pub position: cgmath::Vector3<f32>,
     pub rotation: cgmath::Quaternion<f32>, // Quaternion is a math representation for 3D rotations
+    pub color: [f32; 4], // Tint multiplied into the final color, white leaves the texture as is
 }
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct InstanceRaw {
     model: [[f32; 4]; 4], // 4x4 matrix for model transformation
+    color: [f32; 4], // RGBA tint
+    prev_model: [[f32; 4]; 4], // Model matrix of the previous frame, for motion vectors
 }
 
 impl Instance {
@@ -25,18 +28,30 @@ impl Instance {
     // Instead we give it a single model matrix that combines all transformations (Model Matrix = Translation * Rotation * Scale)
     // Then we need to translate our cgmath types into raw arrays of f32 that GPU understands
     pub fn to_raw(&self) -> InstanceRaw {
+        // Not moving yet, so the previous frame had the same matrix
+        self.to_raw_with_prev(self.model_matrix())
+    }
+
+    // Same as to_raw, with where the instance was last frame
+    pub fn to_raw_with_prev(&self, prev_model: cgmath::Matrix4<f32>) -> InstanceRaw {
         InstanceRaw {
-            model: (cgmath::Matrix4::from_translation(self.position) *
-                cgmath::Matrix4::from(self.rotation)).into(),
+            model: self.model_matrix().into(),
+            color: self.color,
+            prev_model: prev_model.into(),
         }
     }
+
+    pub fn model_matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from_translation(self.position) * cgmath::Matrix4::from(self.rotation)
+    }
 }
 
 impl InstanceRaw {
     // Descriptor methods are like the instruction manual for the GPU
     // Without this the GPU wouldnt know how to interpret the raw byte data in the buffer
     // Here we are telling the GPU that our InstanceRaw struct is made up of 4 vec4s (4 f32 arrays of length 4)
-    // And each vec4 corresponds to a row of the model matrix
+    // And each vec4 corresponds to a row of the model matrix, then one more vec4 for the color
+    // and 4 more for the previous model matrix
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
         use std::mem;
         wgpu::VertexBufferLayout {
@@ -68,7 +83,88 @@ impl InstanceRaw {
                     shader_location: 8,
                     format: wgpu::VertexFormat::Float32x4,
                 },
+                // Color right after the matrix
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 16]>() as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                // Previous frame matrix after the color, only the motion vector shader reads it
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 20]>() as wgpu::BufferAddress,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 24]>() as wgpu::BufferAddress,
+                    shader_location: 11,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 28]>() as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 32]>() as wgpu::BufferAddress,
+                    shader_location: 13,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
             ]
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // desc() offsets are written by hand, this catches them drifting from the struct
+    #[test]
+    fn test_desc_matches_instance_raw_layout() {
+        assert_eq!(size_of::<InstanceRaw>(), 144);
+
+        let layout = InstanceRaw::desc();
+        assert_eq!(layout.array_stride, size_of::<InstanceRaw>() as wgpu::BufferAddress);
+
+        let locations = layout.attributes.iter().map(|attribute| attribute.shader_location).collect::<Vec<_>>();
+        assert_eq!(locations, vec![5, 6, 7, 8, 9, 10, 11, 12, 13]);
+
+        let model_offset = std::mem::offset_of!(InstanceRaw, model) as wgpu::BufferAddress;
+        for (row, attribute) in layout.attributes[..4].iter().enumerate() {
+            assert_eq!(attribute.offset, model_offset + (row * size_of::<[f32; 4]>()) as wgpu::BufferAddress);
+        }
+        assert_eq!(layout.attributes[4].offset, std::mem::offset_of!(InstanceRaw, color) as wgpu::BufferAddress);
+
+        let prev_model_offset = std::mem::offset_of!(InstanceRaw, prev_model) as wgpu::BufferAddress;
+        for (row, attribute) in layout.attributes[5..].iter().enumerate() {
+            assert_eq!(attribute.offset, prev_model_offset + (row * size_of::<[f32; 4]>()) as wgpu::BufferAddress);
+        }
+    }
+
+    #[test]
+    fn test_to_raw_keeps_color() {
+        let instance = Instance {
+            position: cgmath::Vector3::new(1.0, 2.0, 3.0),
+            rotation: cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            color: [0.2, 0.4, 0.6, 1.0],
+        };
+        let raw = instance.to_raw();
+        assert_eq!(raw.color, [0.2, 0.4, 0.6, 1.0]);
+        assert_eq!(raw.model[3], [1.0, 2.0, 3.0, 1.0]);
+        assert_eq!(raw.prev_model, raw.model);
+    }
+
+    #[test]
+    fn test_to_raw_with_prev_keeps_previous_matrix() {
+        let instance = Instance {
+            position: cgmath::Vector3::new(1.0, 0.0, 0.0),
+            rotation: cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            color: [1.0; 4],
+        };
+        let prev = cgmath::Matrix4::from_translation(cgmath::Vector3::new(0.5, 0.0, 0.0));
+        let raw = instance.to_raw_with_prev(prev);
+        assert_eq!(raw.model[3], [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(raw.prev_model[3], [0.5, 0.0, 0.0, 1.0]);
+    }
+}
@@ -12,6 +12,44 @@ pub struct LightUniform {
     pub _padding2: u32, // Additional padding to ensure the struct size is a multiple of 16 bytes
 }
 
+// Distance fog, shares the light bind group (binding 1) since every lit shader already has it
+// Fragments fade from their lit color at start to exactly color at end, distances from the camera.
+// With color equal to the clear color the far end of the scene melts into the background.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FogUniform {
+    pub color: [f32; 4], // Linear, like wgpu::Color and the clear color
+    pub start: f32,
+    pub end: f32,
+    pub enabled: u32,
+    pub _pad: u32, // 16 + 4 * 4 bytes, a multiple of 16
+}
+
+impl FogUniform {
+    pub fn new(color: wgpu::Color, start: f32, end: f32) -> Self {
+        Self {
+            color: [color.r as f32, color.g as f32, color.b as f32, 1.0],
+            start,
+            // end == start would divide by zero in the shader
+            end: end.max(start + f32::EPSILON),
+            enabled: 1,
+            _pad: 0,
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Self { enabled: 0, ..Self::new(wgpu::Color::BLACK, 0.0, 1.0) }
+    }
+
+    // How much of the fog color a fragment this far away gets, same formula as shader.wgsl
+    #[allow(dead_code)] // Only the tests check the shader math against it
+    pub fn amount(&self, distance: f32) -> f32 {
+        if self.enabled == 0 {
+            return 0.0;
+        }
+        ((distance - self.start) / (self.end - self.start)).clamp(0.0, 1.0)
+    }
+}
 
 pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
     device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -26,6 +64,16 @@ pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout
                     min_binding_size: None,
                 },
                 count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1, // FogUniform, only the fragment shader blends it in
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
             }
         ],
     })
@@ -35,6 +83,7 @@ pub fn create_bind_group_from_light(
     device: &wgpu::Device,
     bind_group_layout: &wgpu::BindGroupLayout,
     light_buffer: &wgpu::Buffer,
+    fog_buffer: &wgpu::Buffer,
 ) -> wgpu::BindGroup {
     device.create_bind_group(&wgpu::BindGroupDescriptor {
         layout: bind_group_layout,
@@ -42,8 +91,46 @@ pub fn create_bind_group_from_light(
             wgpu::BindGroupEntry {
                 binding: 0,
                 resource: light_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: fog_buffer.as_entire_binding(),
             }
         ],
         label: Some("Light Bind Group"),
     })
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Same order and offsets as the WGSL Fog struct, and a multiple of 16 bytes for the uniform buffer
+    #[test]
+    fn test_fog_layout() {
+        assert_eq!(size_of::<FogUniform>(), 32);
+        assert_eq!(std::mem::offset_of!(FogUniform, color), 0);
+        assert_eq!(std::mem::offset_of!(FogUniform, start), 16);
+        assert_eq!(std::mem::offset_of!(FogUniform, end), 20);
+        assert_eq!(std::mem::offset_of!(FogUniform, enabled), 24);
+
+        let fog = FogUniform::new(wgpu::Color { r: 0.1, g: 0.2, b: 0.3, a: 0.5 }, 5.0, 25.0);
+        let words: &[u32] = bytemuck::cast_slice(bytemuck::bytes_of(&fog));
+        assert_eq!(f32::from_bits(words[4]), 5.0);
+        assert_eq!(f32::from_bits(words[5]), 25.0);
+        assert_eq!(words[6], 1);
+        // Fog is never see through, the alpha of the clear color doesnt matter
+        assert_eq!(fog.color[3], 1.0);
+    }
+
+    #[test]
+    fn test_fog_amount() {
+        let fog = FogUniform::new(wgpu::Color::WHITE, 5.0, 25.0);
+        assert_eq!(fog.amount(0.0), 0.0);
+        assert_eq!(fog.amount(15.0), 0.5);
+        assert_eq!(fog.amount(100.0), 1.0);
+        assert_eq!(FogUniform::disabled().amount(100.0), 0.0);
+        // Start and end at the same spot is a hard edge, not a NaN
+        assert_eq!(FogUniform::new(wgpu::Color::WHITE, 5.0, 5.0).amount(6.0), 1.0);
+    }
+}
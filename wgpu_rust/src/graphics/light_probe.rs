@@ -0,0 +1,336 @@
+use std::ops::Range;
+use cgmath::InnerSpace;
+use crate::graphics::camera::{CameraUniform, OPENGL_TO_WGPU_MATRIX};
+use crate::graphics::instance::InstanceRaw;
+use crate::graphics::{buffers, pipeline, texture};
+use crate::model::{self, Vertex};
+use crate::state::State;
+
+// Environment probes for local reflections
+// A probe is a camera with a 90 degree fov placed somewhere in the scene that renders the scene
+// 6 times, once for each direction (+X, -X, +Y, -Y, +Z, -Z), into the 6 faces of a cubemap.
+// Objects near the probe then sample that cubemap with their reflection vector to fake
+// reflections of their surroundings. Baking is expensive, so it only happens on demand.
+
+pub const PROBE_RESOLUTION: u32 = 128;
+pub const PROBE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+const PROBE_STRENGTH: f32 = 0.25; // How reflective objects using this probe look
+
+// Look direction and up vector for each cubemap face, in the order GPUs expect the layers
+const FACE_DIRECTIONS: [([f32; 3], [f32; 3]); 6] = [
+    ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),  // +X
+    ([-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]), // -X
+    ([0.0, 1.0, 0.0], [0.0, 0.0, -1.0]), // +Y
+    ([0.0, -1.0, 0.0], [0.0, 0.0, 1.0]), // -Y
+    ([0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),  // +Z
+    ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0]), // -Z
+];
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ProbeUniform {
+    strength: f32,
+    _padding: [f32; 3],
+}
+
+pub struct ProbeCapture {
+    pub cubemap: texture::Texture,
+    pub depth: texture::Texture,
+    pub pipeline: wgpu::RenderPipeline,
+    pub position: cgmath::Point3<f32>,
+    pub radius: f32, // Objects further than this dont use the probe
+    // Camera uniform buffer and bind group for each face
+    face_cameras: Vec<(wgpu::Buffer, wgpu::BindGroup)>,
+    // Bound at group 6 of the main pipeline when drawing objects assigned to this probe
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl ProbeCapture {
+    pub fn new(
+        device: &wgpu::Device,
+        pipeline: wgpu::RenderPipeline,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        environment_bind_group_layout: &wgpu::BindGroupLayout,
+        position: cgmath::Point3<f32>,
+        radius: f32,
+    ) -> Self {
+        let cubemap = create_cubemap(device, PROBE_RESOLUTION, "Probe Cubemap");
+        let depth = create_probe_depth(device);
+
+        let face_cameras = (0..6)
+            .map(|_| {
+                let buffer = buffers::create_uniform_buffer(device, &CameraUniform::new());
+                let bind_group = CameraUniform::create_bind_group(device, camera_bind_group_layout, &buffer);
+                (buffer, bind_group)
+            })
+            .collect();
+
+        let bind_group = create_environment_bind_group(
+            device,
+            environment_bind_group_layout,
+            &cubemap,
+            PROBE_STRENGTH,
+        );
+
+        Self {
+            cubemap,
+            depth,
+            pipeline,
+            position,
+            radius,
+            face_cameras,
+            bind_group,
+        }
+    }
+
+    // Pipeline shared by every probe, renders the scene with the simplified probe shader
+    pub fn create_pipeline(
+        device: &wgpu::Device,
+        material_bind_group_layout: &wgpu::BindGroupLayout,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        light_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::RenderPipeline {
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Probe Pipeline Layout"),
+            bind_group_layouts: &[material_bind_group_layout, camera_bind_group_layout, light_bind_group_layout],
+            immediate_size: 0,
+        });
+        let shader = wgpu::ShaderModuleDescriptor {
+            label: Some("Probe Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/probe.wgsl").into()),
+        };
+        pipeline::create_render_pipeline_with_culling(
+            device,
+            &layout,
+            PROBE_FORMAT,
+            Some(texture::Texture::DEPTH_FORMAT),
+            &[model::ModelVertex::desc(), InstanceRaw::desc()],
+            shader,
+            wgpu::BlendState::REPLACE,
+            // The face projection is mirrored on X (see face_view_proj), which flips the winding
+            wgpu::FrontFace::Cw,
+            Some(wgpu::Face::Back),
+        )
+    }
+
+    // Render the scene 6 times from probe_position into the cubemap faces
+    pub fn bake(&self, state: &State, encoder: &mut wgpu::CommandEncoder, probe_position: cgmath::Point3<f32>) {
+        for (face, (camera_buffer, camera_bind_group)) in self.face_cameras.iter().enumerate() {
+            let mut camera_uniform = CameraUniform::new();
+            camera_uniform.set_view_proj(face_view_proj(face, probe_position), probe_position);
+            state.queue.write_buffer(camera_buffer, 0, bytemuck::cast_slice(&[camera_uniform]));
+
+            // View of a single layer of the cubemap so we can render into it like a 2D texture
+            let face_view = self.cubemap.texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Probe Face View"),
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_array_layer: face as u32,
+                array_layer_count: Some(1),
+                ..Default::default()
+            });
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Probe Capture Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &face_view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(state.clear_color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth.texture_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard, // Depth is only needed during the capture
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+                multiview_mask: None,
+            });
+
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_vertex_buffer(1, state.instance_buffer.slice(..));
+            render_pass.set_bind_group(1, camera_bind_group, &[]);
+            render_pass.set_bind_group(2, &state.light_bind_group, &[]);
+            for mesh in &state.obj_model.meshes {
+                let material = &state.obj_model.materials[mesh.material];
+                render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(mesh.index_buffer.buffer.slice(..), mesh.index_buffer.format);
+                render_pass.set_bind_group(0, &material.bind_group, &[]);
+                render_pass.draw_indexed(0..mesh.index_buffer.count, 0, 0..state.instances.len() as u32);
+            }
+        }
+    }
+}
+
+// View projection for one cubemap face
+// Cubemaps are addressed as if seen from the inside, which is mirrored on X compared to a
+// normal camera looking in the same direction, so we flip X in clip space
+fn face_view_proj(face: usize, position: cgmath::Point3<f32>) -> cgmath::Matrix4<f32> {
+    let (direction, up) = FACE_DIRECTIONS[face];
+    let view = cgmath::Matrix4::look_to_rh(position, direction.into(), up.into());
+    let proj = cgmath::perspective(cgmath::Deg(90.0), 1.0, 0.1, 100.0);
+    let flip_x = cgmath::Matrix4::from_nonuniform_scale(-1.0, 1.0, 1.0);
+    OPENGL_TO_WGPU_MATRIX * flip_x * proj * view
+}
+
+fn create_cubemap(device: &wgpu::Device, size: u32, label: &str) -> texture::Texture {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 6, // One layer per face
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: PROBE_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    // Cube view lets the shader sample with a direction instead of uv coordinates
+    let texture_view = texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("Probe Cubemap View"),
+        dimension: Some(wgpu::TextureViewDimension::Cube),
+        ..Default::default()
+    });
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+    texture::Texture { texture, texture_view, sampler }
+}
+
+fn create_probe_depth(device: &wgpu::Device) -> texture::Texture {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Probe Depth"),
+        size: wgpu::Extent3d {
+            width: PROBE_RESOLUTION,
+            height: PROBE_RESOLUTION,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: texture::Texture::DEPTH_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+    texture::Texture { texture, texture_view, sampler }
+}
+
+pub fn create_environment_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Environment Bind Group Layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::Cube,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+    })
+}
+
+fn create_environment_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    cubemap: &texture::Texture,
+    strength: f32,
+) -> wgpu::BindGroup {
+    let uniform_buffer = buffers::create_uniform_buffer(device, &ProbeUniform {
+        strength,
+        _padding: [0.0; 3],
+    });
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Environment Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&cubemap.texture_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&cubemap.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: uniform_buffer.as_entire_binding(),
+            },
+        ],
+    })
+}
+
+// Bound for objects with no probe nearby (or before baking), strength 0 means no reflection
+pub fn create_default_environment_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+) -> wgpu::BindGroup {
+    let cubemap = create_cubemap(device, 1, "Default Environment Cubemap");
+    create_environment_bind_group(device, layout, &cubemap, 0.0)
+}
+
+// Split the instances into contiguous runs that use the same (closest, in range) probe
+// so each run can be drawn with one instanced draw call and one bind group switch
+pub fn assign_probes(
+    instance_positions: &[cgmath::Vector3<f32>],
+    probes: &[ProbeCapture],
+) -> Vec<(Range<u32>, Option<usize>)> {
+    let mut runs: Vec<(Range<u32>, Option<usize>)> = Vec::new();
+
+    for (index, position) in instance_positions.iter().enumerate() {
+        let closest = probes
+            .iter()
+            .enumerate()
+            .map(|(probe_index, probe)| {
+                let distance = (cgmath::Vector3::new(probe.position.x, probe.position.y, probe.position.z) - position).magnitude();
+                (probe_index, distance, probe.radius)
+            })
+            .filter(|(_, distance, radius)| distance <= radius)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(probe_index, _, _)| probe_index);
+
+        let index = index as u32;
+        match runs.last_mut() {
+            // Same probe as the previous instance, extend the run
+            Some((range, probe)) if *probe == closest => range.end = index + 1,
+            _ => runs.push((index..index + 1, closest)),
+        }
+    }
+
+    runs
+}
@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use wgpu::BindGroup;
+use crate::graphics::buffers;
+use crate::model::ModelVertex;
+
+// Level of detail (LOD)
+// Objects far from the camera cover a few pixels, drawing all their triangles is wasted work.
+// We build simpler copies of the mesh up front and pick one per instance based on distance.
+// Simplification is vertex clustering: space is cut into cells of `threshold` size and every
+// vertex falling in the same cell is merged into one, triangles that collapse are dropped.
+
+// How big the merge cells get compared to the distance the level starts at
+const MERGE_FACTOR: f32 = 0.02;
+// How far past a boundary (as a fraction of it) an instance has to move before it switches level
+// Without it an instance sitting right on the boundary swaps meshes every frame as the camera wobbles
+pub const LOD_HYSTERESIS: f32 = 0.1;
+
+// One simplified copy of the mesh and up to where it is used
+pub struct Lod {
+    pub max_distance: f32,
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: buffers::IndexBuffer,
+}
+
+pub struct LodMesh {
+    // Sorted from most to least detailed
+    pub levels: Vec<Lod>,
+}
+
+impl LodMesh {
+    // levels are the max distance of each level in increasing order, the first one is the
+    // original mesh and the last one is used for anything further than all of them
+    pub fn from_mesh(
+        device: &wgpu::Device,
+        vertices: &[ModelVertex],
+        indices: &[u32],
+        levels: &[f32],
+    ) -> Self {
+        let mut previous: (Vec<ModelVertex>, Vec<u32>) = (vertices.to_vec(), indices.to_vec());
+
+        let levels = levels.iter().enumerate()
+            .map(|(level, &max_distance)| {
+                // A level starts where the previous one ends
+                let min_distance = if level == 0 { 0.0 } else { levels[level - 1] };
+                let (level_vertices, level_indices) = simplify(vertices, indices, min_distance * MERGE_FACTOR);
+                // Over simplified meshes disappear, keep the last level that still had triangles
+                if !level_indices.is_empty() {
+                    previous = (level_vertices, level_indices);
+                }
+
+                Lod {
+                    max_distance,
+                    vertex_buffer: buffers::create_model_vertex_buffer(device, &previous.0),
+                    index_buffer: buffers::IndexBuffer::from_u32_compact(device, &previous.1),
+                }
+            })
+            .collect();
+
+        Self { levels }
+    }
+
+    pub fn max_distances(&self) -> Vec<f32> {
+        self.levels.iter().map(|lod| lod.max_distance).collect()
+    }
+}
+
+// First level whose max distance covers the distance, past all of them the last one
+pub fn level_for_distance(max_distances: &[f32], distance: f32) -> usize {
+    max_distances.iter()
+        .position(|max_distance| distance <= *max_distance)
+        .unwrap_or(max_distances.len().saturating_sub(1))
+}
+
+// Same as level_for_distance, but an instance keeps its previous level until it is more than
+// hysteresis (a fraction) past either boundary of that level
+pub fn level_with_hysteresis(max_distances: &[f32], distance: f32, previous: Option<usize>, hysteresis: f32) -> usize {
+    let level = level_for_distance(max_distances, distance);
+    let Some(previous) = previous.filter(|&previous| previous < max_distances.len()) else {
+        return level;
+    };
+    let near = if previous == 0 { 0.0 } else { max_distances[previous - 1] * (1.0 - hysteresis) };
+    // The last level has no far boundary, it covers everything beyond
+    let far = if previous + 1 == max_distances.len() { f32::INFINITY } else { max_distances[previous] * (1.0 + hysteresis) };
+    if (near..=far).contains(&distance) { previous } else { level }
+}
+
+// LOD level of every instance for this frame, previous holds the levels of the last frame
+// (empty on the first one, or when the instances changed)
+pub fn select_levels(max_distances: &[f32], distances: impl Iterator<Item = f32>, previous: &[usize]) -> Vec<usize> {
+    distances.enumerate()
+        .map(|(index, distance)| {
+            level_with_hysteresis(max_distances, distance, previous.get(index).copied(), LOD_HYSTERESIS)
+        })
+        .collect()
+}
+
+// Instances with the same level, one instanced draw each
+// A draw needs its instances next to each other in the instance buffer, so a bucket is a
+// contiguous range: the buffer keeps the grid order because picking and the probe runs index
+// into it, and the animated copy only exists on the GPU where it cant be sorted from here.
+// Neighbours in the grid are at similar distances, so a row splits into only a few ranges
+pub fn lod_runs(instances: Range<u32>, levels: &[usize]) -> Vec<(Range<u32>, usize)> {
+    let mut runs: Vec<(Range<u32>, usize)> = Vec::new();
+    for index in instances {
+        let level = levels.get(index as usize).copied().unwrap_or(0);
+        match runs.last_mut() {
+            Some((range, run_level)) if *run_level == level => range.end = index + 1,
+            _ => runs.push((index..index + 1, level)),
+        }
+    }
+    runs
+}
+
+// Vertex clustering simplification, threshold <= 0 returns the mesh unchanged
+// Vertices only merge when their normals point roughly the same way, so hard edges (like the
+// corners of a cube, where each face has its own copy of the vertex) survive
+pub fn simplify(vertices: &[ModelVertex], indices: &[u32], threshold: f32) -> (Vec<ModelVertex>, Vec<u32>) {
+    if threshold <= 0.0 {
+        return (vertices.to_vec(), indices.to_vec());
+    }
+
+    // Cell coordinates + quantized normal -> index of the merged vertex
+    let mut clusters: HashMap<[i32; 6], u32> = HashMap::new();
+    let mut merged: Vec<ModelVertex> = Vec::new();
+    let mut counts: Vec<f32> = Vec::new();
+    let mut remap: Vec<u32> = Vec::with_capacity(vertices.len());
+
+    for vertex in vertices {
+        let [x, y, z] = vertex.position;
+        let [nx, ny, nz] = vertex.normal;
+        let key = [
+            (x / threshold).floor() as i32,
+            (y / threshold).floor() as i32,
+            (z / threshold).floor() as i32,
+            (nx * 2.0).round() as i32,
+            (ny * 2.0).round() as i32,
+            (nz * 2.0).round() as i32,
+        ];
+
+        let index = *clusters.entry(key).or_insert_with(|| {
+            merged.push(ModelVertex { position: [0.0; 3], ..*vertex });
+            counts.push(0.0);
+            merged.len() as u32 - 1
+        });
+        // Accumulate the positions, divided by the count below to get the average
+        let cluster = &mut merged[index as usize];
+        for axis in 0..3 {
+            cluster.position[axis] += vertex.position[axis];
+        }
+        counts[index as usize] += 1.0;
+        remap.push(index);
+    }
+
+    for (vertex, count) in merged.iter_mut().zip(&counts) {
+        vertex.position = vertex.position.map(|value| value / count);
+    }
+
+    // Triangles with two corners in the same cluster have no area anymore
+    let indices = indices
+        .chunks_exact(3)
+        .map(|triangle| [remap[triangle[0] as usize], remap[triangle[1] as usize], remap[triangle[2] as usize]])
+        .filter(|[a, b, c]| a != b && b != c && a != c)
+        .flatten()
+        .collect();
+
+    (merged, indices)
+}
+
+pub trait DrawLod<'a> {
+    fn draw_lod_mesh_instanced(
+        &mut self,
+        lod_mesh: &'a LodMesh,
+        level: usize,
+        material_bind_group: &'a BindGroup,
+        instances: Range<u32>,
+        camera_bind_group: &'a BindGroup,
+        light_bind_group: &'a BindGroup,
+    );
+}
+
+impl<'a, 'b> DrawLod<'b> for wgpu::RenderPass<'a>
+where
+    'b: 'a,
+{
+    fn draw_lod_mesh_instanced(
+        &mut self,
+        lod_mesh: &'b LodMesh,
+        level: usize,
+        material_bind_group: &'b BindGroup,
+        instances: Range<u32>,
+        camera_bind_group: &'b BindGroup,
+        light_bind_group: &'b BindGroup,
+    ) {
+        let Lod { vertex_buffer, index_buffer, .. } = &lod_mesh.levels[level];
+        self.set_vertex_buffer(0, vertex_buffer.slice(..));
+        self.set_index_buffer(index_buffer.buffer.slice(..), index_buffer.format);
+        self.set_bind_group(0, material_bind_group, &[]);
+        self.set_bind_group(1, camera_bind_group, &[]);
+        self.set_bind_group(4, light_bind_group, &[]);
+        self.draw_indexed(0..index_buffer.count, 0, instances);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(position: [f32; 3]) -> ModelVertex {
+        ModelVertex {
+            position,
+            tex_coords: [0.0; 2],
+            normal: [0.0, 1.0, 0.0],
+        }
+    }
+
+    #[test]
+    fn test_zero_threshold_keeps_mesh() {
+        let vertices = [vertex([0.0, 0.0, 0.0]), vertex([1.0, 0.0, 0.0]), vertex([0.0, 0.0, 1.0])];
+        let (simplified, indices) = simplify(&vertices, &[0, 1, 2], 0.0);
+        assert_eq!(simplified.len(), 3);
+        assert_eq!(indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_close_vertices_merge_and_degenerate_triangles_drop() {
+        // Two triangles sharing an edge, the second one has a sliver corner next to vertex 1
+        let vertices = [
+            vertex([0.0, 0.0, 0.0]),
+            vertex([1.0, 0.0, 0.0]),
+            vertex([0.0, 0.0, 1.0]),
+            vertex([1.01, 0.0, 0.01]),
+        ];
+        let (simplified, indices) = simplify(&vertices, &[0, 1, 2, 1, 3, 2], 0.5);
+        assert_eq!(simplified.len(), 3);
+        assert_eq!(indices, vec![0, 1, 2]);
+        // Merged vertex sits between the two originals
+        assert!((simplified[1].position[0] - 1.005).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_level_for_distance() {
+        let max_distances = [10.0, 25.0, 50.0];
+        assert_eq!(level_for_distance(&max_distances, 0.0), 0);
+        assert_eq!(level_for_distance(&max_distances, 10.0), 0);
+        assert_eq!(level_for_distance(&max_distances, 10.5), 1);
+        assert_eq!(level_for_distance(&max_distances, 1000.0), 2);
+    }
+
+    #[test]
+    fn test_hysteresis_keeps_level_near_the_boundary() {
+        let max_distances = [10.0, 25.0, 50.0];
+        // Just past the boundary, both sides stay where they were
+        assert_eq!(level_with_hysteresis(&max_distances, 10.5, Some(0), 0.1), 0);
+        assert_eq!(level_with_hysteresis(&max_distances, 9.5, Some(1), 0.1), 1);
+        // Clearly past it they switch
+        assert_eq!(level_with_hysteresis(&max_distances, 11.5, Some(0), 0.1), 1);
+        assert_eq!(level_with_hysteresis(&max_distances, 8.5, Some(1), 0.1), 0);
+        // Jumps over several levels at once, no previous level or a stale one is plain selection
+        assert_eq!(level_with_hysteresis(&max_distances, 100.0, Some(0), 0.1), 2);
+        assert_eq!(level_with_hysteresis(&max_distances, 10.5, None, 0.1), 1);
+        assert_eq!(level_with_hysteresis(&max_distances, 10.5, Some(7), 0.1), 1);
+    }
+
+    #[test]
+    fn test_select_levels_doesnt_flicker_on_the_boundary() {
+        let max_distances = [10.0, 25.0];
+        let mut levels = Vec::new();
+        // Camera wobbling around the boundary, the instance stays at level 0 the whole time
+        for distance in [9.9, 10.1, 9.8, 10.4, 9.95, 10.9] {
+            levels = select_levels(&max_distances, std::iter::once(distance), &levels);
+            assert_eq!(levels, vec![0]);
+        }
+        levels = select_levels(&max_distances, std::iter::once(11.5), &levels);
+        assert_eq!(levels, vec![1]);
+    }
+
+    #[test]
+    fn test_lod_runs_bucket_neighbours() {
+        let levels = [0, 0, 1, 1, 1, 0, 2];
+        assert_eq!(lod_runs(0..7, &levels), vec![(0..2, 0), (2..5, 1), (5..6, 0), (6..7, 2)]);
+        // Sub range of the instances, like one probe run
+        assert_eq!(lod_runs(3..6, &levels), vec![(3..5, 1), (5..6, 0)]);
+        // Missing levels (no LOD meshes) draw the full mesh
+        assert_eq!(lod_runs(0..3, &[]), vec![(0..3, 0)]);
+    }
+
+    #[test]
+    fn test_opposite_normals_dont_merge() {
+        let mut flipped = vertex([0.0, 0.0, 0.0]);
+        flipped.normal = [0.0, -1.0, 0.0];
+        let (simplified, _) = simplify(&[vertex([0.0, 0.0, 0.0]), flipped], &[], 1.0);
+        assert_eq!(simplified.len(), 2);
+    }
+}
@@ -0,0 +1,219 @@
+use cgmath::SquareMatrix;
+use crate::graphics::instance::InstanceRaw;
+use crate::graphics::{buffers, texture};
+use crate::model::{self, Vertex};
+
+// Motion vectors: for every pixel, how far its surface moved on screen since the last frame
+// The scene is drawn a second time with a tiny shader that projects each vertex with this
+// frame's and last frame's matrices (camera and instance) and writes the difference.
+// Temporal effects (TAA, motion blur) read the velocity texture to find last frame's pixel.
+// Skinning and LOD are ignored here, the full rest mesh is close enough for the velocity.
+
+// Two 16 bit floats are plenty for screen space offsets and half the size of Rg32Float
+pub const VELOCITY_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct MotionUniform {
+    view_proj: [[f32; 4]; 4],
+    prev_view_proj: [[f32; 4]; 4],
+}
+
+impl MotionUniform {
+    // The matrix of this frame becomes the previous one of the next frame
+    fn advance(&mut self, view_proj: cgmath::Matrix4<f32>) {
+        self.prev_view_proj = self.view_proj;
+        self.view_proj = view_proj.into();
+    }
+}
+
+pub struct MotionVectorPass {
+    pub motion_vector_pipeline: wgpu::RenderPipeline,
+    pub velocity_texture: texture::Texture,
+    // Own depth buffer so only the closest surface writes its velocity
+    depth_texture: texture::Texture,
+    uniform: MotionUniform,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl MotionVectorPass {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        transform_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let identity: [[f32; 4]; 4] = cgmath::Matrix4::<f32>::identity().into();
+        let uniform = MotionUniform {
+            view_proj: identity,
+            prev_view_proj: identity,
+        };
+        let uniform_buffer = buffers::create_uniform_buffer(device, &uniform);
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Motion Vector Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Motion Vector Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Motion Vector Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout, transform_bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Motion Vector Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/motion_vectors.wgsl").into()),
+        });
+
+        let motion_vector_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Motion Vector Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[model::ModelVertex::desc(), InstanceRaw::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: VELOCITY_FORMAT,
+                    blend: None, // Velocity is data, nothing to blend
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let (velocity_texture, depth_texture) = Self::create_targets(device, config);
+
+        Self {
+            motion_vector_pipeline,
+            velocity_texture,
+            depth_texture,
+            uniform,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    fn create_targets(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> (texture::Texture, texture::Texture) {
+        (
+            texture::Texture::create_render_target(device, config, "Velocity Texture", VELOCITY_FORMAT),
+            texture::Texture::create_depth_texture(device, config, "Motion Vector Depth Texture"),
+        )
+    }
+
+    // Both targets are screen sized
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        (self.velocity_texture, self.depth_texture) = Self::create_targets(device, config);
+    }
+
+    // Call once per frame, last frame's camera is kept as the previous one
+    pub fn update(&mut self, queue: &wgpu::Queue, view_proj: cgmath::Matrix4<f32>) {
+        self.uniform.advance(view_proj);
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
+
+    // Own render pass, the velocity is cleared to zero where nothing was drawn
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        obj_model: &model::Model,
+        instance_buffer: &wgpu::Buffer,
+        instances: std::ops::Range<u32>,
+        transform_bind_group: &wgpu::BindGroup,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Motion Vector Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.velocity_texture.texture_view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_texture.texture_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Discard, // Only needed while this pass runs
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+            multiview_mask: None,
+        });
+        render_pass.set_pipeline(&self.motion_vector_pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_bind_group(1, transform_bind_group, &[]);
+        render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+        for mesh in &obj_model.meshes {
+            render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(mesh.index_buffer.buffer.slice(..), mesh.index_buffer.format);
+            render_pass.draw_indexed(0..mesh.index_buffer.count, 0, instances.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advance_keeps_last_frame_as_previous() {
+        let identity: [[f32; 4]; 4] = cgmath::Matrix4::<f32>::identity().into();
+        let mut uniform = MotionUniform {
+            view_proj: identity,
+            prev_view_proj: identity,
+        };
+        let first = cgmath::Matrix4::from_scale(2.0);
+        let second = cgmath::Matrix4::from_scale(3.0);
+
+        uniform.advance(first);
+        uniform.advance(second);
+        let first: [[f32; 4]; 4] = first.into();
+        let second: [[f32; 4]; 4] = second.into();
+        assert_eq!(uniform.prev_view_proj, first);
+        assert_eq!(uniform.view_proj, second);
+    }
+}
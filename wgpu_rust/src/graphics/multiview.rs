@@ -0,0 +1,294 @@
+use cgmath::{InnerSpace, Matrix4};
+use crate::graphics::camera::{Camera, CameraUniform, OPENGL_TO_WGPU_MATRIX};
+use crate::graphics::{buffers, pipeline, texture};
+use crate::state::State;
+
+// Stereo rendering for VR headsets
+// A headset shows a slightly different image to each eye. We draw the whole scene once per
+// eye, each time with that eye's camera, into the left and right halves of one wide texture.
+// The headset runtime (or our side by side preview) then takes the halves from there.
+//
+// GPUs with wgpu::Features::MULTIVIEW can draw both eyes in a single pass: the render pass gets
+// a multiview_mask, the target is a 2 layer texture array and the shaders read
+// @builtin(view_index) to pick the eye matrix. That needs a view_index variant of every scene
+// shader, so for now this is a stub: we only report whether the adapter could do it and always
+// use the two pass path below.
+
+// Distance between the eyes in world units, used when the eyes follow the desktop camera
+const EYE_SEPARATION: f32 = 0.064;
+
+pub struct MultiviewState {
+    pub left_eye_matrix: Matrix4<f32>,
+    pub right_eye_matrix: Matrix4<f32>,
+    // Twice as wide as one eye, left eye on the left half
+    pub target: texture::Texture,
+    depth: texture::Texture,
+    eye_size: (u32, u32),
+    // Camera uniform buffer and bind group for each eye, left then right
+    eye_cameras: [(wgpu::Buffer, wgpu::BindGroup); 2],
+    // Set once a VR runtime gives us matrices, from then on we stop following the desktop camera
+    external_matrices: bool,
+    pub supports_single_pass: bool,
+
+    // Draws the wide target over the whole surface so we can see both eyes on a monitor
+    preview_pipeline: wgpu::RenderPipeline,
+    preview_bind_group_layout: wgpu::BindGroupLayout,
+    preview_bind_group: wgpu::BindGroup,
+}
+
+impl MultiviewState {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        supports_single_pass: bool,
+    ) -> Self {
+        use cgmath::SquareMatrix;
+
+        let eye_size = eye_size(device, config);
+        let (target, depth) = create_targets(device, config.format, eye_size);
+
+        let eye_cameras = [0, 1].map(|_| {
+            let buffer = buffers::create_uniform_buffer(device, &CameraUniform::new());
+            let bind_group = CameraUniform::create_bind_group(device, camera_bind_group_layout, &buffer);
+            (buffer, bind_group)
+        });
+
+        let preview_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Multiview Preview Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let preview_bind_group = create_preview_bind_group(device, &preview_bind_group_layout, &target);
+
+        let preview_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Multiview Preview Pipeline Layout"),
+            bind_group_layouts: &[&preview_bind_group_layout],
+            immediate_size: 0,
+        });
+        // The fullscreen triangle is clockwise, no culling so its winding doesnt matter
+        let preview_pipeline = pipeline::create_render_pipeline_with_culling(
+            device,
+            &preview_layout,
+            config.format,
+            None,
+            &[],
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Multiview Preview Shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("shaders/multiview_preview.wgsl").into()),
+            },
+            wgpu::BlendState::REPLACE,
+            wgpu::FrontFace::Ccw,
+            None,
+        );
+
+        Self {
+            left_eye_matrix: Matrix4::identity(),
+            right_eye_matrix: Matrix4::identity(),
+            target,
+            depth,
+            eye_size,
+            eye_cameras,
+            external_matrices: false,
+            supports_single_pass,
+            preview_pipeline,
+            preview_bind_group_layout,
+            preview_bind_group,
+        }
+    }
+
+    // Targets follow the window size, each eye gets a full window worth of pixels
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.eye_size = eye_size(device, config);
+        (self.target, self.depth) = create_targets(device, config.format, self.eye_size);
+        self.preview_bind_group = create_preview_bind_group(device, &self.preview_bind_group_layout, &self.target);
+    }
+
+    // View projection matrices coming from the headset
+    pub fn set_eye_matrices(&mut self, left: Matrix4<f32>, right: Matrix4<f32>) {
+        self.left_eye_matrix = left;
+        self.right_eye_matrix = right;
+        self.external_matrices = true;
+    }
+
+    // Without a headset the eyes sit next to the desktop camera, half the separation to each side
+    pub fn follow_camera(&mut self, camera: &Camera) {
+        if self.external_matrices {
+            return;
+        }
+
+        let forward = (camera.target - camera.eye).normalize();
+        let right = forward.cross(camera.up).normalize();
+        let eye_matrix = |side: f32| {
+            let offset = right * (side * EYE_SEPARATION * 0.5);
+            let view = Matrix4::look_at_rh(camera.eye + offset, camera.target + offset, camera.up);
+            let proj = cgmath::perspective(cgmath::Deg(camera.fovy), camera.aspect, camera.znear, camera.zfar);
+            OPENGL_TO_WGPU_MATRIX * proj * view
+        };
+        self.left_eye_matrix = eye_matrix(-1.0);
+        self.right_eye_matrix = eye_matrix(1.0);
+    }
+
+    // Two passes over the wide target, the viewport keeps each eye in its half
+    // eye_position is only used for reflections, both eyes share it
+    pub fn render(
+        &self,
+        state: &State,
+        encoder: &mut wgpu::CommandEncoder,
+        eye_position: cgmath::Point3<f32>,
+        instance_lods: &[usize],
+    ) {
+        let (eye_width, eye_height) = self.eye_size;
+        let eyes = [self.left_eye_matrix, self.right_eye_matrix];
+
+        for (eye, ((camera_buffer, camera_bind_group), view_proj)) in self.eye_cameras.iter().zip(eyes).enumerate() {
+            let mut camera_uniform = CameraUniform::new();
+            camera_uniform.set_view_proj(view_proj, eye_position);
+            state.queue.write_buffer(camera_buffer, 0, bytemuck::cast_slice(&[camera_uniform]));
+
+            // Clear clears the whole texture, so only the first eye can use it
+            let color_load = if eye == 0 {
+                wgpu::LoadOp::Clear(state.clear_color)
+            } else {
+                wgpu::LoadOp::Load
+            };
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Multiview Eye Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.target.texture_view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: color_load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth.texture_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+                multiview_mask: None,
+            });
+            render_pass.set_viewport(
+                (eye * eye_width as usize) as f32,
+                0.0,
+                eye_width as f32,
+                eye_height as f32,
+                0.0,
+                1.0,
+            );
+            state.draw_scene(&mut render_pass, camera_bind_group, instance_lods);
+        }
+    }
+
+    // Stretch both eyes over view, each half gets squeezed horizontally but it is only a preview
+    pub fn draw_preview(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Multiview Preview Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+            multiview_mask: None,
+        });
+        render_pass.set_pipeline(&self.preview_pipeline);
+        render_pass.set_bind_group(0, &self.preview_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+// One eye is the window size, narrower if two of them dont fit in the max texture width
+fn eye_size(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> (u32, u32) {
+    let max_width = device.limits().max_texture_dimension_2d / 2;
+    (config.width.clamp(1, max_width), config.height.max(1))
+}
+
+fn create_targets(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    (eye_width, eye_height): (u32, u32),
+) -> (texture::Texture, texture::Texture) {
+    let size = wgpu::Extent3d {
+        width: eye_width * 2,
+        height: eye_height,
+        depth_or_array_layers: 1,
+    };
+    let create = |label: &str, format: wgpu::TextureFormat| {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            // Sampled by the preview, COPY_SRC so a VR runtime can copy the eyes out
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        texture::Texture { texture, texture_view, sampler }
+    };
+
+    (
+        create("Multiview Target", format),
+        create("Multiview Depth", texture::Texture::DEPTH_FORMAT),
+    )
+}
+
+fn create_preview_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    target: &texture::Texture,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Multiview Preview Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&target.texture_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&target.sampler),
+            },
+        ],
+    })
+}
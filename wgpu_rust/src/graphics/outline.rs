@@ -0,0 +1,148 @@
+use crate::graphics::buffers;
+use crate::graphics::pipeline::{self, StencilTestPipeline};
+use crate::model;
+
+// Outline around the picked instance, drawn with the stencil buffer in two passes:
+// 1. The instance goes into the stencil mask (State::begin_stencil_mask), every pixel it covers
+//    gets the mask value. The mask pass clears the stencil first, so nothing of an earlier
+//    selection is left over.
+// 2. The same instance slightly scaled up and in a flat color, with a stencil test that only
+//    passes outside the mask. What is left is a rim of the outline color around the instance.
+
+pub const DEFAULT_COLOR: [f32; 4] = [1.0, 0.6, 0.0, 1.0];
+pub const DEFAULT_THICKNESS: f32 = 0.06;
+// Past this the copy is so much bigger it stops looking like an outline
+pub const MAX_THICKNESS: f32 = 0.5;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct OutlineUniform {
+    color: [f32; 4],
+    thickness: f32,
+    _padding: [f32; 3], // Uniforms need 16 byte alignment
+}
+
+impl OutlineUniform {
+    fn new(color: [f32; 4], thickness: f32) -> Self {
+        Self {
+            color,
+            thickness: thickness.clamp(0.0, MAX_THICKNESS),
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+pub struct OutlinePass {
+    pipeline: StencilTestPipeline,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl OutlinePass {
+    // mask_ref is the stencil value the mask pass leaves on the instance
+    pub fn new(
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        transform_bind_group_layout: &wgpu::BindGroupLayout,
+        mask_ref: u8,
+    ) -> Self {
+        let uniform_buffer = buffers::create_uniform_buffer(device, &OutlineUniform::new(DEFAULT_COLOR, DEFAULT_THICKNESS));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Outline Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT, // Thickness and color
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Outline Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Outline Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, transform_bind_group_layout, &bind_group_layout],
+            immediate_size: 0,
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Outline Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/outline.wgsl").into()),
+        });
+        let pipeline = pipeline::create_outline_pipeline(device, &layout, &shader, color_format, mask_ref);
+
+        Self {
+            pipeline,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    // Thickness is extra scale (0.06 = 6% bigger), clamped to 0..MAX_THICKNESS
+    pub fn set_params(&self, queue: &wgpu::Queue, color: [f32; 4], thickness: f32) {
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[OutlineUniform::new(color, thickness)]));
+    }
+
+    // Draw into a pass whose depth stencil attachment holds the mask of the same instance
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        obj_model: &'a model::Model,
+        instance_buffer: &'a wgpu::Buffer,
+        instance: u32,
+        camera_bind_group: &'a wgpu::BindGroup,
+        transform_bind_group: &'a wgpu::BindGroup,
+    ) {
+        self.pipeline.apply(render_pass);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, transform_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+        for mesh in &obj_model.meshes {
+            render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(mesh.index_buffer.buffer.slice(..), mesh.index_buffer.format);
+            render_pass.draw_indexed(0..mesh.index_buffer.count, 0, instance..instance + 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::camera::CameraUniform;
+    use crate::graphics::headless::HeadlessContext;
+    use crate::graphics::transform;
+
+    #[test]
+    fn test_outline_uniform_layout_and_clamp() {
+        assert_eq!(std::mem::size_of::<OutlineUniform>(), 32);
+        assert_eq!(OutlineUniform::new(DEFAULT_COLOR, 2.0).thickness, MAX_THICKNESS);
+        assert_eq!(OutlineUniform::new(DEFAULT_COLOR, -1.0).thickness, 0.0);
+    }
+
+    // Shader and bind group layouts have to agree, a mismatch fails pipeline creation
+    #[test]
+    fn test_outline_pipeline_builds() {
+        let Ok(context) = pollster::block_on(HeadlessContext::new()) else {
+            eprintln!("No adapter available, skipping outline test");
+            return;
+        };
+        let device = &context.device;
+        let camera_layout = CameraUniform::create_bind_group_layout(device);
+        let transform_layout = transform::create_bind_group_layout(device);
+        let outline = OutlinePass::new(device, HeadlessContext::FORMAT, &camera_layout, &transform_layout, 1);
+        outline.set_params(&context.queue, [0.0, 1.0, 0.0, 1.0], 0.1);
+        device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+    }
+}
@@ -0,0 +1,199 @@
+use cgmath::{EuclideanSpace, InnerSpace, SquareMatrix};
+use crate::model::ModelVertex;
+
+// CPU side mouse picking
+// The camera takes a point in the world and flattens it onto the screen (view projection matrix)
+// Picking is the opposite: take a pixel on the screen and turn it back into a ray in the world.
+// With the inverted view projection matrix we unproject the pixel at the near plane and at the
+// far plane, the line between both points is the ray. Then we test the ray against a bounding
+// sphere around each instance and keep the closest hit.
+
+pub struct Ray {
+    pub origin: cgmath::Point3<f32>,
+    pub direction: cgmath::Vector3<f32>, // Always normalized
+}
+
+// Turn a cursor position in physical pixels into a world space ray
+// Returns None if the view projection matrix can not be inverted (degenerate camera)
+pub fn screen_to_ray(
+    x: f64,
+    y: f64,
+    width: u32,
+    height: u32,
+    view_proj: cgmath::Matrix4<f32>,
+) -> Option<Ray> {
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    // Pixel coordinates to normalized device coordinates
+    // X goes from -1 (left) to 1 (right), Y from 1 (top) to -1 (bottom) because pixels grow downwards
+    let ndc_x = (2.0 * x / width as f64 - 1.0) as f32;
+    let ndc_y = (1.0 - 2.0 * y / height as f64) as f32;
+
+    let inverse = view_proj.invert()?;
+
+    // WGPU depth goes from 0 (near plane) to 1 (far plane)
+    let unproject = |depth: f32| {
+        let point = inverse * cgmath::Vector4::new(ndc_x, ndc_y, depth, 1.0);
+        // Perspective divide to go back from homogeneous coordinates
+        cgmath::Point3::new(point.x / point.w, point.y / point.w, point.z / point.w)
+    };
+
+    let near = unproject(0.0);
+    let far = unproject(1.0);
+
+    Some(Ray {
+        origin: near,
+        direction: (far - near).normalize(),
+    })
+}
+
+// Distance along the ray to the first hit with the sphere, None if the ray misses it
+// Solves |origin + t * direction - center|^2 = radius^2 for t
+pub fn intersect_sphere(ray: &Ray, center: cgmath::Point3<f32>, radius: f32) -> Option<f32> {
+    let to_origin = ray.origin - center;
+    // Direction is normalized so the quadratic "a" term is 1
+    let b = to_origin.dot(ray.direction);
+    let c = to_origin.magnitude2() - radius * radius;
+    let discriminant = b * b - c;
+
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let sqrt_d = discriminant.sqrt();
+    let t_near = -b - sqrt_d;
+    let t_far = -b + sqrt_d;
+
+    // If the near hit is behind the origin we are inside the sphere, use the far one
+    if t_near >= 0.0 {
+        Some(t_near)
+    } else if t_far >= 0.0 {
+        Some(t_far)
+    } else {
+        None
+    }
+}
+
+// Index of the closest sphere hit by the ray
+pub fn pick_closest(ray: &Ray, spheres: &[(cgmath::Point3<f32>, f32)]) -> Option<usize> {
+    spheres
+        .iter()
+        .enumerate()
+        .filter_map(|(index, (center, radius))| {
+            intersect_sphere(ray, *center, *radius).map(|t| (index, t))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(index, _)| index)
+}
+
+// Sphere around the mesh in its local space, centered on the middle of its bounding box
+pub fn bounding_sphere(vertices: &[ModelVertex]) -> (cgmath::Point3<f32>, f32) {
+    if vertices.is_empty() {
+        return (cgmath::Point3::origin(), 0.0);
+    }
+
+    let mut min = cgmath::Vector3::new(f32::MAX, f32::MAX, f32::MAX);
+    let mut max = cgmath::Vector3::new(f32::MIN, f32::MIN, f32::MIN);
+    for v in vertices {
+        min.x = min.x.min(v.position[0]);
+        min.y = min.y.min(v.position[1]);
+        min.z = min.z.min(v.position[2]);
+        max.x = max.x.max(v.position[0]);
+        max.y = max.y.max(v.position[1]);
+        max.z = max.z.max(v.position[2]);
+    }
+    let center = cgmath::Point3::from_vec((min + max) * 0.5);
+
+    let radius = vertices
+        .iter()
+        .map(|v| (cgmath::Point3::from(v.position) - center).magnitude())
+        .fold(0.0, f32::max);
+
+    (center, radius)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::camera::{Camera, CameraConfig};
+
+    fn test_camera() -> Camera {
+        Camera::new(CameraConfig {
+            eye: (0.0, 0.0, 5.0).into(),
+            target: (0.0, 0.0, 0.0).into(),
+            up: cgmath::Vector3::unit_y(),
+            aspect: 1.0,
+            fovy: 90.0,
+            znear: 0.1,
+            zfar: 100.0,
+        })
+    }
+
+    fn approx(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn test_center_of_screen_unprojects_along_view_direction() {
+        let camera = test_camera();
+        let ray = screen_to_ray(50.0, 50.0, 100, 100, camera.build_view_projection_matrix()).unwrap();
+
+        // Origin sits on the near plane in front of the eye
+        assert!(approx(ray.origin.x, 0.0));
+        assert!(approx(ray.origin.y, 0.0));
+        assert!(approx(ray.origin.z, 4.9));
+        // Looking down -Z towards the target
+        assert!(approx(ray.direction.x, 0.0));
+        assert!(approx(ray.direction.y, 0.0));
+        assert!(approx(ray.direction.z, -1.0));
+    }
+
+    #[test]
+    fn test_screen_corner_unprojects_to_frustum_edge() {
+        let camera = test_camera();
+        // Top right corner with a 90 degree fov is 45 degrees off in both axis
+        let ray = screen_to_ray(100.0, 0.0, 100, 100, camera.build_view_projection_matrix()).unwrap();
+        let expected = cgmath::Vector3::new(1.0f32, 1.0, -1.0).normalize();
+        assert!(approx(ray.direction.x, expected.x));
+        assert!(approx(ray.direction.y, expected.y));
+        assert!(approx(ray.direction.z, expected.z));
+    }
+
+    #[test]
+    fn test_sphere_hit_distance() {
+        let ray = Ray {
+            origin: (0.0, 0.0, 5.0).into(),
+            direction: (0.0, 0.0, -1.0).into(),
+        };
+        let t = intersect_sphere(&ray, (0.0, 0.0, 0.0).into(), 1.0).unwrap();
+        assert!(approx(t, 4.0));
+    }
+
+    #[test]
+    fn test_sphere_miss_and_behind() {
+        let ray = Ray {
+            origin: (0.0, 0.0, 5.0).into(),
+            direction: (0.0, 0.0, -1.0).into(),
+        };
+        // Off to the side
+        assert!(intersect_sphere(&ray, (3.0, 0.0, 0.0).into(), 1.0).is_none());
+        // Behind the ray origin
+        assert!(intersect_sphere(&ray, (0.0, 0.0, 10.0).into(), 1.0).is_none());
+    }
+
+    #[test]
+    fn test_pick_closest_prefers_nearest_hit() {
+        let ray = Ray {
+            origin: (0.0, 0.0, 5.0).into(),
+            direction: (0.0, 0.0, -1.0).into(),
+        };
+        let spheres = [
+            ((0.0, 0.0, -5.0).into(), 1.0),
+            ((0.0, 0.0, 0.0).into(), 1.0),
+            ((5.0, 0.0, 0.0).into(), 1.0),
+        ];
+        assert_eq!(pick_closest(&ray, &spheres), Some(1));
+    }
+}
@@ -1,10 +1,14 @@
+use std::sync::Arc;
 use crate::graphics::instance::InstanceRaw;
+use crate::graphics::pipeline_cache::{PipelineCache, PipelineKey, VertexLayouts};
 use crate::graphics::texture;
 use crate::model;
 use crate::model::Vertex;
 
 
 
+// Simplest way to one pipeline, State gets its pipelines from the PipelineCache now
+#[allow(dead_code)]
 pub fn create_render_pipeline(
     device: &wgpu::Device,
     layout: &wgpu::PipelineLayout,
@@ -12,27 +16,81 @@ pub fn create_render_pipeline(
     depth_format: Option<wgpu::TextureFormat>,
     vertex_layouts: &[wgpu::VertexBufferLayout],
     shader: wgpu::ShaderModuleDescriptor,
+    blend: wgpu::BlendState,
+) -> wgpu::RenderPipeline {
+    create_render_pipeline_with_culling(
+        device,
+        layout,
+        color_format,
+        depth_format,
+        vertex_layouts,
+        shader,
+        blend,
+        wgpu::FrontFace::Ccw,
+        Some(wgpu::Face::Back),
+    )
+}
+
+// Same as create_render_pipeline but lets the caller pick the winding order of front faces
+// and which faces get culled (None draws both sides)
+// Needed when the projection mirrors the image (like cubemap faces), which flips the winding,
+// or to debug meshes with inconsistent winding
+#[allow(clippy::too_many_arguments)]
+pub fn create_render_pipeline_with_culling(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    color_format: wgpu::TextureFormat,
+    depth_format: Option<wgpu::TextureFormat>,
+    vertex_layouts: &[wgpu::VertexBufferLayout],
+    shader: wgpu::ShaderModuleDescriptor,
+    blend: wgpu::BlendState,
+    front_face: wgpu::FrontFace,
+    cull_mode: Option<wgpu::Face>,
 ) -> wgpu::RenderPipeline {
     let shader = device.create_shader_module(shader);
+    create_render_pipeline_from_module(
+        device,
+        layout,
+        color_format,
+        depth_format,
+        vertex_layouts,
+        &shader,
+        blend,
+        front_face,
+        cull_mode,
+    )
+}
 
+// Same again with an already compiled shader, so pipelines sharing a shader compile it once
+// (see assets/manager.rs)
+#[allow(clippy::too_many_arguments)]
+pub fn create_render_pipeline_from_module(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    color_format: wgpu::TextureFormat,
+    depth_format: Option<wgpu::TextureFormat>,
+    vertex_layouts: &[wgpu::VertexBufferLayout],
+    shader: &wgpu::ShaderModule,
+    blend: wgpu::BlendState,
+    front_face: wgpu::FrontFace,
+    cull_mode: Option<wgpu::Face>,
+) -> wgpu::RenderPipeline {
     device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: Some("Render Pipeline"),
         layout: Some(layout),
         vertex: wgpu::VertexState {
-            module: &shader,
+            module: shader,
             entry_point: Some("vs_main"),
             buffers: vertex_layouts,
             compilation_options: Default::default(),
         },
         fragment: Some(wgpu::FragmentState {
-            module: &shader,
+            module: shader,
             entry_point: Some("fs_main"),
             targets: &[Some(wgpu::ColorTargetState {
                 format: color_format,
-                blend: Some(wgpu::BlendState {
-                    alpha: wgpu::BlendComponent::REPLACE,
-                    color: wgpu::BlendComponent::REPLACE,
-                }),
+                // REPLACE for opaque geometry, ALPHA_BLENDING for transparent (see transparency.rs)
+                blend: Some(blend),
                 write_mask: wgpu::ColorWrites::ALL,
             })],
             compilation_options: Default::default(),
@@ -40,8 +98,8 @@ pub fn create_render_pipeline(
         primitive: wgpu::PrimitiveState {
             topology: wgpu::PrimitiveTopology::TriangleList,
             strip_index_format: None,
-            front_face: wgpu::FrontFace::Ccw,
-            cull_mode: Some(wgpu::Face::Back),
+            front_face,
+            cull_mode,
             // Setting this to other than fill requires Features::NON_FILL_POLYGON_MODE
             polygon_mode: wgpu::PolygonMode::Fill,
             // Requires Features::DEPTH_CLIP_CONTROL
@@ -51,7 +109,9 @@ pub fn create_render_pipeline(
         },
         depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
             format,
-            depth_write_enabled: true,
+            // Blended surfaces still test against depth but dont write it, otherwise a transparent
+            // surface would hide whatever behind it gets drawn after it
+            depth_write_enabled: blend == wgpu::BlendState::REPLACE,
             depth_compare: wgpu::CompareFunction::Less,
             stencil: wgpu::StencilState::default(),
             bias: wgpu::DepthBiasState::default(),
@@ -78,6 +138,245 @@ pub fn create_render_pipeline(
 
 
 
+// Stencil buffer is an extra 8 bits per pixel next to the depth value. Pipelines can write to it
+// and compare against it, so one set of geometry can define a shape (the mask) and later draws
+// only show up inside (or outside) that shape. Portals, UI panels, x-ray views...
+pub const STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+
+// Writes the mask: every pixel covered by the geometry gets its stencil value incremented
+// (0 -> 1 after a clear), no color output at all and the depth buffer is left alone
+// Uses the main scene shader vertex stage, so the layout must be the main render pipeline layout
+pub fn create_stencil_mask_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+) -> wgpu::RenderPipeline {
+
+    let write_stencil = wgpu::StencilFaceState {
+        compare: wgpu::CompareFunction::Always,
+        fail_op: wgpu::StencilOperation::Keep,
+        depth_fail_op: wgpu::StencilOperation::Keep,
+        pass_op: wgpu::StencilOperation::IncrementClamp,
+    };
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Stencil Mask Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers: &[model::ModelVertex::desc(), InstanceRaw::desc()],
+            compilation_options: Default::default(),
+        },
+        fragment: None, // Only the stencil buffer is written
+        primitive: wgpu::PrimitiveState {
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: STENCIL_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState {
+                front: write_stencil,
+                back: write_stencil,
+                read_mask: 0xff,
+                write_mask: 0xff,
+            },
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview_mask: None,
+        cache: None,
+    })
+}
+
+// Stencil reference is dynamic state in wgpu (set on the render pass, not baked in the pipeline)
+// so we keep the value together with the pipeline and set both at once
+pub struct StencilTestPipeline {
+    pub pipeline: wgpu::RenderPipeline,
+    pub ref_value: u8,
+}
+
+impl StencilTestPipeline {
+    pub fn apply<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_stencil_reference(self.ref_value as u32);
+    }
+}
+
+// Normal scene rendering, but pixels only pass where the stencil value equals ref_value
+// Also the main scene shader, passed in so it isnt compiled again
+pub fn create_stencil_test_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    color_format: wgpu::TextureFormat,
+    ref_value: u8,
+) -> StencilTestPipeline {
+
+    let test_stencil = wgpu::StencilFaceState {
+        compare: wgpu::CompareFunction::Equal,
+        fail_op: wgpu::StencilOperation::Keep,
+        depth_fail_op: wgpu::StencilOperation::Keep,
+        pass_op: wgpu::StencilOperation::Keep,
+    };
+
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Stencil Test Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers: &[model::ModelVertex::desc(), InstanceRaw::desc()],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: color_format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: STENCIL_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState {
+                front: test_stencil,
+                back: test_stencil,
+                read_mask: 0xff,
+                write_mask: 0x00, // Only read the mask, never change it
+            },
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview_mask: None,
+        cache: None,
+    });
+
+    StencilTestPipeline { pipeline, ref_value }
+}
+
+// Flat colored outline around a stencil mask: pixels only pass where the stencil value is NOT
+// ref_value, so a slightly bigger copy of the masked mesh only shows up as a rim around it
+// No depth test, the outline of a selection stays visible behind other objects
+// Layout and shader come from graphics/outline.rs, vertex buffers are the same as the scene
+pub fn create_outline_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    color_format: wgpu::TextureFormat,
+    ref_value: u8,
+) -> StencilTestPipeline {
+
+    let outside_mask = wgpu::StencilFaceState {
+        compare: wgpu::CompareFunction::NotEqual,
+        fail_op: wgpu::StencilOperation::Keep,
+        depth_fail_op: wgpu::StencilOperation::Keep,
+        pass_op: wgpu::StencilOperation::Keep,
+    };
+
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Outline Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers: &[model::ModelVertex::desc(), InstanceRaw::desc()],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: color_format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING), // Outline color can be see through
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: STENCIL_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState {
+                front: outside_mask,
+                back: outside_mask,
+                read_mask: 0xff,
+                write_mask: 0x00, // Only read the mask, never change it
+            },
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview_mask: None,
+        cache: None,
+    });
+
+    StencilTestPipeline { pipeline, ref_value }
+}
+
+// Name of the scene shader in the ResourceManager and in PipelineKeys
+pub const SCENE_SHADER_ID: &str = "shader.wgsl";
+
+// Every pipeline built from the scene shader, rebuilt together when the shader is reloaded
+// The plain variants come from the PipelineCache, the stencil ones need stencil state it doesnt cover
+pub struct ScenePipelines {
+    pub render: Arc<wgpu::RenderPipeline>,
+    pub no_cull: Arc<wgpu::RenderPipeline>,
+    pub transparent: Arc<wgpu::RenderPipeline>,
+    pub stencil_mask: wgpu::RenderPipeline,
+    pub stencil_test: StencilTestPipeline,
+}
+
+pub fn create_scene_pipelines(
+    device: &wgpu::Device,
+    cache: &mut PipelineCache,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    color_format: wgpu::TextureFormat,
+    stencil_ref: u8,
+) -> ScenePipelines {
+    let key = PipelineKey::new(SCENE_SHADER_ID, color_format, VertexLayouts::ModelInstanced);
+    ScenePipelines {
+        render: cache.get_or_create(device, key, layout, shader),
+        // Debug copy with culling off, swapped in with the cull toggle key
+        // Built up front so toggling doesnt stall on shader compilation
+        no_cull: cache.get_or_create(device, PipelineKey { cull_mode: None, ..key }, layout, shader),
+        // Transparent materials: same shader, blended, no depth write and both sides visible
+        transparent: cache.get_or_create(
+            device,
+            PipelineKey { blend: wgpu::BlendState::ALPHA_BLENDING, cull_mode: None, ..key },
+            layout,
+            shader,
+        ),
+        stencil_mask: create_stencil_mask_pipeline(device, layout, shader),
+        stencil_test: create_stencil_test_pipeline(device, layout, shader, color_format, stencil_ref),
+    }
+}
+
+
+
+
+
+
+
+
+
+
+
+
 // UNUSED. KEPT FOR REFERENCE.
 pub fn old_create_render_pipeline(
     device: &wgpu::Device,
@@ -92,7 +391,7 @@ pub fn old_create_render_pipeline(
     // Takes the shader file and sends it to GPU driver
     let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
         label: Some("Shader"),
-        source: wgpu::ShaderSource::Wgsl(include_str!("shaders/shader.wgsl").into()),
+        source: wgpu::ShaderSource::Wgsl(include_str!("../../res/shaders/shader.wgsl").into()),
     });
 
     // What extra data can the shader access (external buffers, textures, etc)
@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::graphics::camera::CameraUniform;
+use crate::graphics::instance::InstanceRaw;
+use crate::graphics::{light, texture};
+use crate::model::{self, Vertex};
+
+// Caches for pipelines and bind group layouts, built the first time they are asked for
+// Every variant of a pipeline (other blend, fill mode, sample count...) is a whole new pipeline
+// object, building all combinations up front in State::new doesnt scale. Here a small key
+// describes the variant and the pipeline is only compiled the first time that key shows up.
+// Entries are shared through Arc like in assets/manager.rs, and stay until invalidated.
+
+// The bind group layouts most pipelines share
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LayoutKind {
+    Texture,
+    Material,
+    Depth,
+    Camera,
+    Light,
+}
+
+impl LayoutKind {
+    fn create(self, device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        match self {
+            LayoutKind::Texture => texture::create_texture_bind_group_layout(device),
+            LayoutKind::Material => texture::create_material_bind_group_layout(device),
+            LayoutKind::Depth => texture::create_depth_bind_group_layout(device),
+            LayoutKind::Camera => CameraUniform::create_bind_group_layout(device),
+            LayoutKind::Light => light::create_bind_group_layout(device),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct LayoutCache {
+    layouts: HashMap<LayoutKind, Arc<wgpu::BindGroupLayout>>,
+}
+
+impl LayoutCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&mut self, device: &wgpu::Device, kind: LayoutKind) -> Arc<wgpu::BindGroupLayout> {
+        let layout = self.layouts.entry(kind).or_insert_with(|| Arc::new(kind.create(device)));
+        Arc::clone(layout)
+    }
+}
+
+// Vertex buffer layouts cant be hashed (they hold slices), so the key names the combination
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VertexLayouts {
+    Model,          // Just the mesh, like the light cube
+    ModelInstanced, // Mesh in slot 0, InstanceRaw in slot 1
+}
+
+impl VertexLayouts {
+    fn buffers(self) -> Vec<wgpu::VertexBufferLayout<'static>> {
+        match self {
+            VertexLayouts::Model => vec![model::ModelVertex::desc()],
+            VertexLayouts::ModelInstanced => vec![model::ModelVertex::desc(), InstanceRaw::desc()],
+        }
+    }
+}
+
+// Everything that makes two pipelines from the same shader different
+// The pipeline layout is not part of it, a shader only fits one layout anyway
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PipelineKey {
+    pub shader: &'static str, // Same name the shader has in the ResourceManager
+    pub color_format: wgpu::TextureFormat,
+    pub depth_format: Option<wgpu::TextureFormat>,
+    pub blend: wgpu::BlendState,
+    pub polygon_mode: wgpu::PolygonMode,
+    pub cull_mode: Option<wgpu::Face>,
+    pub sample_count: u32,
+    pub vertex_layouts: VertexLayouts,
+}
+
+impl PipelineKey {
+    // Opaque, filled, back faces culled, no MSAA, against the main depth buffer
+    // Other variants change single fields: PipelineKey { cull_mode: None, ..key }
+    pub fn new(shader: &'static str, color_format: wgpu::TextureFormat, vertex_layouts: VertexLayouts) -> Self {
+        Self {
+            shader,
+            color_format,
+            depth_format: Some(texture::Texture::DEPTH_FORMAT),
+            blend: wgpu::BlendState::REPLACE,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            cull_mode: Some(wgpu::Face::Back),
+            sample_count: 1,
+            vertex_layouts,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct PipelineCache {
+    pipelines: HashMap<PipelineKey, Arc<wgpu::RenderPipeline>>,
+}
+
+impl PipelineCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // layout and shader are only used on a miss, they have to be the ones the key stands for
+    pub fn get_or_create(
+        &mut self,
+        device: &wgpu::Device,
+        key: PipelineKey,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+    ) -> Arc<wgpu::RenderPipeline> {
+        let pipeline = self.pipelines
+            .entry(key)
+            .or_insert_with(|| Arc::new(create_pipeline(device, &key, layout, shader)));
+        Arc::clone(pipeline)
+    }
+
+    // After the surface format changed, only pipelines drawing to the old format are dropped
+    // Offscreen ones with their own format stay cached. Returns how many were removed
+    #[allow(dead_code)] // The surface keeps the format it was configured with for now
+    pub fn invalidate_color_format(&mut self, format: wgpu::TextureFormat) -> usize {
+        self.invalidate(|key| key.color_format == format)
+    }
+
+    // Shader hot reload, the next lookup builds from the new module
+    pub fn invalidate_shader(&mut self, shader: &str) -> usize {
+        self.invalidate(|key| key.shader == shader)
+    }
+
+    fn invalidate(&mut self, matches: impl Fn(&PipelineKey) -> bool) -> usize {
+        let before = self.pipelines.len();
+        self.pipelines.retain(|key, _| !matches(key));
+        before - self.pipelines.len()
+    }
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    key: &PipelineKey,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+) -> wgpu::RenderPipeline {
+    let vertex_buffers = key.vertex_layouts.buffers();
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(key.shader),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers: &vertex_buffers,
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: key.color_format,
+                blend: Some(key.blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: key.cull_mode,
+            // Line and Point need Features::NON_FILL_POLYGON_MODE
+            polygon_mode: key.polygon_mode,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: key.depth_format.map(|format| wgpu::DepthStencilState {
+            format,
+            // Same rule as create_render_pipeline_from_module, blended surfaces dont write depth
+            depth_write_enabled: key.blend == wgpu::BlendState::REPLACE,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: key.sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview_mask: None,
+        cache: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::headless::HeadlessContext;
+
+    fn light_pipeline_parts(device: &wgpu::Device, layouts: &mut LayoutCache) -> (wgpu::PipelineLayout, wgpu::ShaderModule) {
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Test Pipeline Layout"),
+            bind_group_layouts: &[&layouts.get(device, LayoutKind::Camera), &layouts.get(device, LayoutKind::Light)],
+            immediate_size: 0,
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Light Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/light.wgsl").into()),
+        });
+        (layout, shader)
+    }
+
+    #[test]
+    fn test_layout_cache_hits_share_one_layout() {
+        let Ok(context) = pollster::block_on(HeadlessContext::new()) else {
+            eprintln!("No adapter available, skipping layout cache test");
+            return;
+        };
+        let mut layouts = LayoutCache::new();
+        let first = layouts.get(&context.device, LayoutKind::Camera);
+        let second = layouts.get(&context.device, LayoutKind::Camera);
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!Arc::ptr_eq(&first, &layouts.get(&context.device, LayoutKind::Light)));
+    }
+
+    #[test]
+    fn test_pipeline_cache_hits_share_one_pipeline() {
+        let Ok(context) = pollster::block_on(HeadlessContext::new()) else {
+            eprintln!("No adapter available, skipping pipeline cache test");
+            return;
+        };
+        let device = &context.device;
+        let (layout, shader) = light_pipeline_parts(device, &mut LayoutCache::new());
+        let mut cache = PipelineCache::new();
+
+        let key = PipelineKey::new("light.wgsl", wgpu::TextureFormat::Rgba8Unorm, VertexLayouts::Model);
+        let first = cache.get_or_create(device, key, &layout, &shader);
+        let second = cache.get_or_create(device, key, &layout, &shader);
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let no_cull = cache.get_or_create(device, PipelineKey { cull_mode: None, ..key }, &layout, &shader);
+        assert!(!Arc::ptr_eq(&first, &no_cull));
+    }
+
+    #[test]
+    fn test_format_change_only_rebuilds_that_format() {
+        let Ok(context) = pollster::block_on(HeadlessContext::new()) else {
+            eprintln!("No adapter available, skipping pipeline cache test");
+            return;
+        };
+        let device = &context.device;
+        let (layout, shader) = light_pipeline_parts(device, &mut LayoutCache::new());
+        let mut cache = PipelineCache::new();
+
+        let surface_key = PipelineKey::new("light.wgsl", wgpu::TextureFormat::Bgra8UnormSrgb, VertexLayouts::Model);
+        let offscreen_key = PipelineKey::new("light.wgsl", wgpu::TextureFormat::Rgba16Float, VertexLayouts::Model);
+        let surface = cache.get_or_create(device, surface_key, &layout, &shader);
+        let offscreen = cache.get_or_create(device, offscreen_key, &layout, &shader);
+
+        assert_eq!(cache.invalidate_color_format(wgpu::TextureFormat::Bgra8UnormSrgb), 1);
+        assert!(Arc::ptr_eq(&offscreen, &cache.get_or_create(device, offscreen_key, &layout, &shader)));
+        assert!(!Arc::ptr_eq(&surface, &cache.get_or_create(device, surface_key, &layout, &shader)));
+
+        assert_eq!(cache.invalidate_shader("light.wgsl"), 2);
+    }
+}
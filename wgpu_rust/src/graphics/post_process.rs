@@ -0,0 +1,901 @@
+use cgmath::SquareMatrix;
+use crate::graphics::instance::InstanceRaw;
+use crate::graphics::{buffers, texture};
+use crate::model::{self, Vertex};
+
+// Screen space subsurface scattering
+// Light that enters skin or leaves comes out again a bit further away, tinted by what it went
+// through. We fake it by blurring the lit frame, but only over pixels whose material scatters.
+// There is no G-buffer in this forward renderer, so the SSS channel is its own screen target
+// written by a small pre-pass (same idea as the velocity texture): rgb is how much each color
+// channel scatters (sss_color * sss_strength) and alpha the view depth of the pixel.
+// The blur is a separable gaussian, one horizontal pass into blur_texture and one vertical pass
+// onto the output. Samples on a different depth than the center (the edge of an ear in front of
+// the cheek) count less, so the blur doesnt leak across silhouettes.
+// Skinning and LOD are ignored here, the full rest mesh is close enough for a blur mask.
+
+// Amount per color channel and the view depth, 16 bit float keeps the depth usable
+pub const SSS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+// Center tap plus this many minus one on each side
+const KERNEL_TAPS: usize = 8;
+// Blur radius in pixels for a material with sss_strength 1
+const MAX_RADIUS: f32 = 12.0;
+// How fast samples fade with depth difference, per view space unit
+const DEPTH_FALLOFF: f32 = 8.0;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SssCameraUniform {
+    view_proj: [[f32; 4]; 4],
+}
+
+// One per material, read with a dynamic offset
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SssMaterialUniform {
+    amount: [f32; 4], // rgb = sss_color * sss_strength, w unused
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct BlurUniform {
+    direction: [f32; 2], // (1, 0) for the horizontal pass, (0, 1) for the vertical one
+    max_radius: f32,
+    depth_falloff: f32,
+    weights: [[f32; 4]; KERNEL_TAPS / 4], // Packed in vec4s, uniform arrays need 16 byte elements
+}
+
+impl BlurUniform {
+    fn new(direction: [f32; 2]) -> Self {
+        let kernel = gaussian_kernel();
+        Self {
+            direction,
+            max_radius: MAX_RADIUS,
+            depth_falloff: DEPTH_FALLOFF,
+            weights: std::array::from_fn(|i| std::array::from_fn(|j| kernel[i * 4 + j])),
+        }
+    }
+}
+
+pub struct SubsurfaceScatteringPass {
+    // Scene target while SSS is on, the blur reads it as the lit frame
+    pub scene_texture: texture::Texture,
+    pub sss_texture: texture::Texture,
+    // Result of the horizontal pass, read by the vertical one
+    blur_texture: texture::Texture,
+    depth_texture: texture::Texture,
+    mask_pipeline: wgpu::RenderPipeline,
+    blur_pipeline: wgpu::RenderPipeline,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    material_bind_group_layout: wgpu::BindGroupLayout,
+    material_buffer: wgpu::Buffer,
+    material_bind_group: wgpu::BindGroup,
+    material_stride: u32,
+    material_count: usize,
+    horizontal_buffer: wgpu::Buffer,
+    vertical_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    blur_bind_group_layout: wgpu::BindGroupLayout,
+    horizontal_bind_group: wgpu::BindGroup,
+    vertical_bind_group: wgpu::BindGroup,
+}
+
+impl SubsurfaceScatteringPass {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        config: &wgpu::SurfaceConfiguration,
+        transform_bind_group_layout: &wgpu::BindGroupLayout,
+        materials: &[model::Material],
+    ) -> Self {
+        let uniform_entry = |binding, visibility, has_dynamic_offset| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        // Mask pre-pass: scene geometry writing the SSS channel
+        let camera_buffer = buffers::create_uniform_buffer(device, &SssCameraUniform {
+            view_proj: cgmath::Matrix4::<f32>::identity().into(),
+        });
+        let camera_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("SSS Camera Bind Group Layout"),
+            entries: &[uniform_entry(0, wgpu::ShaderStages::VERTEX, false)],
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("SSS Camera Bind Group"),
+            layout: &camera_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        });
+        let material_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("SSS Material Bind Group Layout"),
+            entries: &[uniform_entry(0, wgpu::ShaderStages::FRAGMENT, true)],
+        });
+        // Every dynamic offset has to be a multiple of this, usually 256 bytes
+        let material_stride = device.limits().min_uniform_buffer_offset_alignment
+            .max(std::mem::size_of::<SssMaterialUniform>() as u32);
+        let (material_buffer, material_bind_group) =
+            Self::create_materials(device, &material_bind_group_layout, material_stride, materials.len().max(1));
+
+        let mask_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("SSS Mask Pipeline Layout"),
+            bind_group_layouts: &[&camera_bind_group_layout, transform_bind_group_layout, &material_bind_group_layout],
+            immediate_size: 0,
+        });
+        let mask_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("SSS Mask Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/sss_mask.wgsl").into()),
+        });
+        let mask_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("SSS Mask Pipeline"),
+            layout: Some(&mask_layout),
+            vertex: wgpu::VertexState {
+                module: &mask_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[model::ModelVertex::desc(), InstanceRaw::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &mask_shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: SSS_FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        // Blur passes: full screen triangle reading the lit frame and the SSS channel
+        let horizontal_buffer = buffers::create_uniform_buffer(device, &BlurUniform::new([1.0, 0.0]));
+        let vertical_buffer = buffers::create_uniform_buffer(device, &BlurUniform::new([0.0, 1.0]));
+        // Linear, the taps land between pixels once the radius is scaled by the strength
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("SSS Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::MipmapFilterMode::Nearest,
+            ..Default::default()
+        });
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let blur_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("SSS Blur Bind Group Layout"),
+            entries: &[
+                texture_entry(0), // Color to blur
+                texture_entry(1), // SSS channel
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                uniform_entry(3, wgpu::ShaderStages::FRAGMENT, false),
+            ],
+        });
+        let blur_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("SSS Blur Pipeline Layout"),
+            bind_group_layouts: &[&blur_bind_group_layout],
+            immediate_size: 0,
+        });
+        let blur_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("SSS Blur Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/sss_blur.wgsl").into()),
+        });
+        // Both passes write a color target of the surface format, the blur texture included
+        let blur_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("SSS Blur Pipeline"),
+            layout: Some(&blur_layout),
+            vertex: wgpu::VertexState {
+                module: &blur_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[], // Full screen triangle is generated from the vertex index
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &blur_shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: None, // Every pixel is written, the ones that dont scatter are copied
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let (scene_texture, sss_texture, blur_texture, depth_texture) = Self::create_targets(device, config);
+        let (horizontal_bind_group, vertical_bind_group) = Self::create_blur_bind_groups(
+            device,
+            &blur_bind_group_layout,
+            [&scene_texture, &sss_texture, &blur_texture],
+            &sampler,
+            [&horizontal_buffer, &vertical_buffer],
+        );
+
+        let mut pass = Self {
+            scene_texture,
+            sss_texture,
+            blur_texture,
+            depth_texture,
+            mask_pipeline,
+            blur_pipeline,
+            camera_buffer,
+            camera_bind_group,
+            material_bind_group_layout,
+            material_buffer,
+            material_bind_group,
+            material_stride,
+            material_count: 0,
+            horizontal_buffer,
+            vertical_buffer,
+            sampler,
+            blur_bind_group_layout,
+            horizontal_bind_group,
+            vertical_bind_group,
+        };
+        pass.write_materials(device, queue, materials);
+        pass
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> (texture::Texture, texture::Texture, texture::Texture, texture::Texture) {
+        (
+            texture::Texture::create_render_target(device, config, "SSS Scene Texture", config.format),
+            texture::Texture::create_render_target(device, config, "SSS Texture", SSS_FORMAT),
+            texture::Texture::create_render_target(device, config, "SSS Blur Texture", config.format),
+            texture::Texture::create_depth_texture(device, config, "SSS Depth Texture"),
+        )
+    }
+
+    fn create_materials(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        stride: u32,
+        count: usize,
+    ) -> (wgpu::Buffer, wgpu::BindGroup) {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("SSS Material Buffer"),
+            size: stride as u64 * count as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("SSS Material Bind Group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                // Only one material is visible per draw, the dynamic offset picks which
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<SssMaterialUniform>() as u64),
+                }),
+            }],
+        });
+        (buffer, bind_group)
+    }
+
+    // Horizontal reads the scene into the blur texture, vertical reads the blur texture
+    fn create_blur_bind_groups(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        [scene_texture, sss_texture, blur_texture]: [&texture::Texture; 3],
+        sampler: &wgpu::Sampler,
+        [horizontal_buffer, vertical_buffer]: [&wgpu::Buffer; 2],
+    ) -> (wgpu::BindGroup, wgpu::BindGroup) {
+        let bind_group = |label, color: &texture::Texture, buffer: &wgpu::Buffer| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&color.texture_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&sss_texture.texture_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: buffer.as_entire_binding(),
+                    },
+                ],
+            })
+        };
+        (
+            bind_group("SSS Horizontal Bind Group", scene_texture, horizontal_buffer),
+            bind_group("SSS Vertical Bind Group", blur_texture, vertical_buffer),
+        )
+    }
+
+    // Every target is screen sized
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        (self.scene_texture, self.sss_texture, self.blur_texture, self.depth_texture) = Self::create_targets(device, config);
+        (self.horizontal_bind_group, self.vertical_bind_group) = Self::create_blur_bind_groups(
+            device,
+            &self.blur_bind_group_layout,
+            [&self.scene_texture, &self.sss_texture, &self.blur_texture],
+            &self.sampler,
+            [&self.horizontal_buffer, &self.vertical_buffer],
+        );
+    }
+
+    // Upload the scattering of every material, call again when the model changes
+    pub fn write_materials(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, materials: &[model::Material]) {
+        let count = materials.len().max(1);
+        if count > self.material_count {
+            (self.material_buffer, self.material_bind_group) =
+                Self::create_materials(device, &self.material_bind_group_layout, self.material_stride, count);
+        }
+        self.material_count = count;
+
+        // Each entry sits at its own aligned offset, the gaps stay zero
+        let mut data = vec![0u8; self.material_stride as usize * count];
+        for (i, material) in materials.iter().enumerate() {
+            let uniform = SssMaterialUniform { amount: sss_amount(material.sss_strength, material.sss_color) };
+            let offset = i * self.material_stride as usize;
+            data[offset..offset + std::mem::size_of::<SssMaterialUniform>()].copy_from_slice(bytemuck::bytes_of(&uniform));
+        }
+        queue.write_buffer(&self.material_buffer, 0, &data);
+    }
+
+    // Same camera as the scene (jitter included), so the mask lines up with the lit pixels
+    pub fn update(&self, queue: &wgpu::Queue, view_proj: cgmath::Matrix4<f32>) {
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[SssCameraUniform { view_proj: view_proj.into() }]));
+    }
+
+    // Write the SSS channel, then blur scene_texture into output_view where it is not zero
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        obj_model: &model::Model,
+        instance_buffer: &wgpu::Buffer,
+        instances: std::ops::Range<u32>,
+        transform_bind_group: &wgpu::BindGroup,
+        output_view: &wgpu::TextureView,
+    ) {
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("SSS Mask Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.sss_texture.texture_view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT), // Nothing scatters
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture.texture_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard, // Only needed while this pass runs
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+                multiview_mask: None,
+            });
+            render_pass.set_pipeline(&self.mask_pipeline);
+            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            render_pass.set_bind_group(1, transform_bind_group, &[]);
+            render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+            // Meshes that dont scatter are drawn too, they hide what is behind them
+            for mesh in &obj_model.meshes {
+                let offset = mesh.material.min(self.material_count - 1) as u32 * self.material_stride;
+                render_pass.set_bind_group(2, &self.material_bind_group, &[offset]);
+                render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(mesh.index_buffer.buffer.slice(..), mesh.index_buffer.format);
+                render_pass.draw_indexed(0..mesh.index_buffer.count, 0, instances.clone());
+            }
+        }
+
+        self.blur(encoder, "SSS Horizontal Pass", &self.blur_texture.texture_view, &self.horizontal_bind_group);
+        self.blur(encoder, "SSS Vertical Pass", output_view, &self.vertical_bind_group);
+    }
+
+    fn blur(&self, encoder: &mut wgpu::CommandEncoder, label: &str, view: &wgpu::TextureView, bind_group: &wgpu::BindGroup) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), // Every pixel is overwritten
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+            multiview_mask: None,
+        });
+        render_pass.set_pipeline(&self.blur_pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+// How much each color channel scatters, red usually travels the furthest in skin
+fn sss_amount(strength: f32, color: [f32; 3]) -> [f32; 4] {
+    let strength = strength.clamp(0.0, 1.0);
+    [color[0] * strength, color[1] * strength, color[2] * strength, 0.0]
+}
+
+// Normalized weights of one side of the gaussian, index 0 is the center tap
+// Sigma is a third of the taps so the last one is almost zero, the two sides together sum to 1
+fn gaussian_kernel() -> [f32; KERNEL_TAPS] {
+    let sigma = KERNEL_TAPS as f32 / 3.0;
+    let mut kernel: [f32; KERNEL_TAPS] = std::array::from_fn(|i| (-((i * i) as f32) / (2.0 * sigma * sigma)).exp());
+    // Center once, every other tap on both sides
+    let total = kernel[0] + 2.0 * kernel[1..].iter().sum::<f32>();
+    for weight in &mut kernel {
+        *weight /= total;
+    }
+    kernel
+}
+
+// Vignette and chromatic aberration
+// Both are a single full screen pass over the finished frame: the frame is drawn into the
+// input texture of the pass instead of the screen, and the pass writes it changed onto the next
+// target. They share everything but the shader and the uniform, that part is FullScreenEffect.
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct VignetteUniform {
+    strength: f32,
+    power: f32,
+    _padding: [f32; 2], // Uniform buffers are sized in 16 byte steps
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ChromaticAberrationUniform {
+    offset: f32,
+    _padding: [f32; 3],
+}
+
+struct FullScreenEffect {
+    label: &'static str,
+    input_texture: texture::Texture,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+}
+
+impl FullScreenEffect {
+    fn new<T: bytemuck::Pod>(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        label: &'static str,
+        shader_source: &str,
+        uniform: &T,
+    ) -> Self {
+        let uniform_buffer = buffers::create_uniform_buffer(device, uniform);
+        // Linear, the aberration samples land between pixels
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(label),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::MipmapFilterMode::Nearest,
+            ..Default::default()
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(label),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(shader_source.into()),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[], // Full screen triangle is generated from the vertex index
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: None, // Every pixel is written
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let input_texture = texture::Texture::create_render_target(device, config, label, config.format);
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, &input_texture, &sampler, &uniform_buffer, label);
+        Self {
+            label,
+            input_texture,
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            uniform_buffer,
+            sampler,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        input_texture: &texture::Texture,
+        sampler: &wgpu::Sampler,
+        uniform_buffer: &wgpu::Buffer,
+        label: &str,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(label),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&input_texture.texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.input_texture = texture::Texture::create_render_target(device, config, self.label, config.format);
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.input_texture,
+            &self.sampler,
+            &self.uniform_buffer,
+            self.label,
+        );
+    }
+
+    fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        output_view: &wgpu::TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(self.label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output_view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), // Every pixel is overwritten
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes,
+            multiview_mask: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+// Darkens the frame towards the corners by 1 - (distance from the center * strength) ^ power
+pub struct VignettePass {
+    effect: FullScreenEffect,
+}
+
+impl VignettePass {
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        Self {
+            effect: FullScreenEffect::new(
+                device,
+                config,
+                "Vignette Pass",
+                include_str!("shaders/vignette.wgsl"),
+                &vignette_uniform(0.0, 1.0),
+            ),
+        }
+    }
+
+    // Where the frame has to be drawn for the vignette to read it
+    pub fn input_view(&self) -> &wgpu::TextureView {
+        &self.effect.input_texture.texture_view
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.effect.resize(device, config);
+    }
+
+    pub fn set_params(&self, queue: &wgpu::Queue, strength: f32, power: f32) {
+        queue.write_buffer(&self.effect.uniform_buffer, 0, bytemuck::bytes_of(&vignette_uniform(strength, power)));
+    }
+
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        output_view: &wgpu::TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        self.effect.render(encoder, output_view, timestamp_writes);
+    }
+}
+
+// Splits the color channels towards the edges, red outwards and blue inwards
+pub struct ChromaticAberrationPass {
+    effect: FullScreenEffect,
+}
+
+impl ChromaticAberrationPass {
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        Self {
+            effect: FullScreenEffect::new(
+                device,
+                config,
+                "Chromatic Aberration Pass",
+                include_str!("shaders/chromatic_aberration.wgsl"),
+                &chromatic_aberration_uniform(0.0),
+            ),
+        }
+    }
+
+    // Where the frame has to be drawn for the aberration to read it
+    pub fn input_view(&self) -> &wgpu::TextureView {
+        &self.effect.input_texture.texture_view
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.effect.resize(device, config);
+    }
+
+    pub fn set_offset(&self, queue: &wgpu::Queue, offset: f32) {
+        queue.write_buffer(&self.effect.uniform_buffer, 0, bytemuck::bytes_of(&chromatic_aberration_uniform(offset)));
+    }
+
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        output_view: &wgpu::TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        self.effect.render(encoder, output_view, timestamp_writes);
+    }
+}
+
+// pow of a negative base is undefined in WGSL, both values are kept at 0 or above
+fn vignette_uniform(strength: f32, power: f32) -> VignetteUniform {
+    VignetteUniform { strength: strength.max(0.0), power: power.max(0.0), _padding: [0.0; 2] }
+}
+
+fn chromatic_aberration_uniform(offset: f32) -> ChromaticAberrationUniform {
+    ChromaticAberrationUniform { offset, _padding: [0.0; 3] }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gaussian_kernel_is_normalized_and_decreasing() {
+        let kernel = gaussian_kernel();
+        let total = kernel[0] + 2.0 * kernel[1..].iter().sum::<f32>();
+        assert!((total - 1.0).abs() < 1e-5);
+        assert!(kernel.windows(2).all(|pair| pair[0] > pair[1]));
+
+        // Packed into the uniform in order
+        let uniform = BlurUniform::new([1.0, 0.0]);
+        assert_eq!(uniform.weights[0][0], kernel[0]);
+        assert_eq!(uniform.weights[1][3], kernel[KERNEL_TAPS - 1]);
+    }
+
+    #[test]
+    fn test_sss_amount() {
+        assert_eq!(sss_amount(0.0, [1.0, 0.5, 0.2]), [0.0; 4]);
+        assert_eq!(sss_amount(0.5, [1.0, 0.5, 0.2]), [0.5, 0.25, 0.1, 0.0]);
+        // Strength above 1 would blur further than the kernel reaches
+        assert_eq!(sss_amount(3.0, [1.0, 1.0, 1.0]), [1.0, 1.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_vignette_uniform_keeps_pow_defined() {
+        let uniform = vignette_uniform(-1.0, -2.0);
+        assert_eq!((uniform.strength, uniform.power), (0.0, 0.0));
+        assert_eq!(std::mem::size_of::<VignetteUniform>() % 16, 0);
+        assert_eq!(std::mem::size_of::<ChromaticAberrationUniform>() % 16, 0);
+    }
+
+    // Runs one effect over a 64x64 input and reads back the output, None when there is no adapter
+    fn render_effect(
+        input: impl Fn(u32, u32) -> [u8; 4],
+        effect: impl Fn(&wgpu::Device, &wgpu::Queue, &wgpu::SurfaceConfiguration) -> FullScreenEffect,
+    ) -> Option<Vec<[u8; 4]>> {
+        const SIZE: u32 = 64;
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())).ok()?;
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).unwrap();
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            width: SIZE,
+            height: SIZE,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        let effect = effect(&device, &queue, &config);
+        let pixels: Vec<u8> = (0..SIZE * SIZE).flat_map(|i| input(i % SIZE, i / SIZE)).collect();
+        queue.write_texture(
+            effect.input_texture.texture.as_image_copy(),
+            &pixels,
+            wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(SIZE * 4), rows_per_image: Some(SIZE) },
+            effect.input_texture.texture.size(),
+        );
+
+        let output = texture::Texture::create_render_target(&device, &config, "Effect Test Output", config.format);
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Effect Test Readback"),
+            size: (SIZE * SIZE * 4) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        effect.render(&mut encoder, &output.texture_view, None);
+        // 256 bytes per row, exactly the alignment copies need
+        encoder.copy_texture_to_buffer(
+            output.texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &readback,
+                layout: wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(SIZE * 4), rows_per_image: Some(SIZE) },
+            },
+            output.texture.size(),
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+        readback.slice(..).map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+        let data = readback.slice(..).get_mapped_range();
+        Some(bytemuck::cast_slice::<u8, [u8; 4]>(&data).to_vec())
+    }
+
+    #[test]
+    fn test_vignette_darkens_towards_the_corners() {
+        let Some(output) = render_effect(|_, _| [255; 4], |device, queue, config| {
+            let pass = VignettePass::new(device, config);
+            pass.set_params(queue, 1.0, 2.0);
+            pass.effect
+        }) else {
+            eprintln!("No adapter available, skipping vignette test");
+            return;
+        };
+        let center = output[32 * 64 + 32][0] as f32 / 255.0;
+        let corner = output[0][0] as f32 / 255.0;
+        assert!(center > 0.99, "{center}");
+        // 1 - (sqrt(0.5) * 1) ^ 2 at the very corner, a bit less far out at the first pixel center
+        assert!((corner - 0.5).abs() < 0.05, "{corner}");
+    }
+
+    #[test]
+    fn test_chromatic_aberration_splits_the_channels() {
+        // White only along the right edge
+        let Some(output) = render_effect(|x, _| if x >= 60 { [255; 4] } else { [0, 0, 0, 255] }, |device, queue, config| {
+            let pass = ChromaticAberrationPass::new(device, config);
+            pass.set_offset(queue, 0.2);
+            pass.effect
+        }) else {
+            eprintln!("No adapter available, skipping chromatic aberration test");
+            return;
+        };
+        // A few pixels left of the white, red already reads from it, green and blue dont
+        assert_eq!(output[32 * 64 + 56], [255, 0, 0, 255]);
+        // Inside the white, blue reads from further in and is black
+        assert_eq!(output[32 * 64 + 61], [255, 255, 0, 255]);
+    }
+}
@@ -0,0 +1,143 @@
+// Choosing how finished frames are shown on the screen
+//   WGPU_PRESENT_MODE=fifo         vsync, waits for the display refresh, never tears (always supported)
+//   WGPU_PRESENT_MODE=mailbox      no tearing, newest frame replaces a waiting one, lower latency
+//   WGPU_PRESENT_MODE=immediate    shows frames right away, lowest latency but can tear
+// Unset keeps whatever the surface lists first. A mode the surface doesnt support falls back to fifo.
+// F9 cycles through the supported ones while running.
+
+const PRESENT_MODE_VAR: &str = "WGPU_PRESENT_MODE";
+
+// Order F9 goes through, modes the surface doesnt support are skipped
+const CYCLE_ORDER: [wgpu::PresentMode; 3] = [
+    wgpu::PresentMode::Fifo,
+    wgpu::PresentMode::Mailbox,
+    wgpu::PresentMode::Immediate,
+];
+
+// What State::new should pick, the actual mode depends on what the surface supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PresentModePreference {
+    #[default]
+    Auto, // First mode the surface lists
+    Vsync, // Fifo
+    LowLatency, // Mailbox
+    MaxFps, // Immediate
+}
+
+impl PresentModePreference {
+    fn mode(self) -> Option<wgpu::PresentMode> {
+        match self {
+            Self::Auto => None,
+            Self::Vsync => Some(wgpu::PresentMode::Fifo),
+            Self::LowLatency => Some(wgpu::PresentMode::Mailbox),
+            Self::MaxFps => Some(wgpu::PresentMode::Immediate),
+        }
+    }
+
+    // Preference from WGPU_PRESENT_MODE, unknown names ask for vsync like an unsupported mode would
+    pub fn from_env() -> Self {
+        match std::env::var(PRESENT_MODE_VAR) {
+            Ok(name) => parse_present_mode(&name).unwrap_or_else(|| {
+                log::warn!("{}={:?} is not fifo, mailbox or immediate, using Fifo", PRESENT_MODE_VAR, name);
+                Self::Vsync
+            }),
+            Err(_) => Self::Auto,
+        }
+    }
+}
+
+// A running mode as a preference, so a rebuilt State (device loss) keeps what F9 picked
+impl From<wgpu::PresentMode> for PresentModePreference {
+    fn from(mode: wgpu::PresentMode) -> Self {
+        match mode {
+            wgpu::PresentMode::Fifo => Self::Vsync,
+            wgpu::PresentMode::Mailbox => Self::LowLatency,
+            wgpu::PresentMode::Immediate => Self::MaxFps,
+            _ => Self::Auto,
+        }
+    }
+}
+
+// Name from the env var to a preference, None for unknown names
+pub fn parse_present_mode(name: &str) -> Option<PresentModePreference> {
+    match name.trim().to_lowercase().as_str() {
+        "fifo" | "vsync" => Some(PresentModePreference::Vsync),
+        "mailbox" => Some(PresentModePreference::LowLatency),
+        "immediate" => Some(PresentModePreference::MaxFps),
+        _ => None,
+    }
+}
+
+// preferred if the surface supports it, fifo if not, the first supported mode without a preference
+pub fn choose_present_mode(preference: PresentModePreference, available: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+    let mode = match preference.mode() {
+        Some(mode) if available.contains(&mode) => mode,
+        Some(mode) => {
+            log::warn!("Present mode {:?} is not supported (available: {:?}), using Fifo", mode, available);
+            wgpu::PresentMode::Fifo
+        }
+        None => available.first().copied().unwrap_or(wgpu::PresentMode::Fifo),
+    };
+    log::info!("Present mode: {:?}", mode);
+    mode
+}
+
+// Mode after current in CYCLE_ORDER that the surface supports, wraps around to the start
+// A current mode outside the cycle (FifoRelaxed, AutoVsync) starts over at the first supported one
+pub fn next_present_mode(current: wgpu::PresentMode, available: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+    let supported: Vec<_> = CYCLE_ORDER.into_iter().filter(|mode| available.contains(mode)).collect();
+    match supported.iter().position(|&mode| mode == current) {
+        Some(index) => supported[(index + 1) % supported.len()],
+        None => supported.first().copied().unwrap_or(current),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_present_mode_names() {
+        assert_eq!(parse_present_mode("fifo"), Some(PresentModePreference::Vsync));
+        assert_eq!(parse_present_mode("VSync"), Some(PresentModePreference::Vsync));
+        assert_eq!(parse_present_mode(" Mailbox "), Some(PresentModePreference::LowLatency));
+        assert_eq!(parse_present_mode("immediate"), Some(PresentModePreference::MaxFps));
+        assert_eq!(parse_present_mode("fast"), None);
+    }
+
+    #[test]
+    fn test_supported_preference_is_used() {
+        let available = [wgpu::PresentMode::Fifo, wgpu::PresentMode::Mailbox];
+        assert_eq!(choose_present_mode(PresentModePreference::LowLatency, &available), wgpu::PresentMode::Mailbox);
+    }
+
+    #[test]
+    fn test_unsupported_preference_falls_back_to_fifo() {
+        let available = [wgpu::PresentMode::FifoRelaxed, wgpu::PresentMode::Fifo];
+        assert_eq!(choose_present_mode(PresentModePreference::MaxFps, &available), wgpu::PresentMode::Fifo);
+    }
+
+    #[test]
+    fn test_no_preference_keeps_first_available() {
+        let available = [wgpu::PresentMode::FifoRelaxed, wgpu::PresentMode::Fifo];
+        assert_eq!(choose_present_mode(PresentModePreference::Auto, &available), wgpu::PresentMode::FifoRelaxed);
+        assert_eq!(choose_present_mode(PresentModePreference::Auto, &[]), wgpu::PresentMode::Fifo);
+    }
+
+    #[test]
+    fn test_cycle_skips_unsupported_modes() {
+        let available = [wgpu::PresentMode::Fifo, wgpu::PresentMode::Immediate, wgpu::PresentMode::FifoRelaxed];
+        assert_eq!(next_present_mode(wgpu::PresentMode::Fifo, &available), wgpu::PresentMode::Immediate);
+        assert_eq!(next_present_mode(wgpu::PresentMode::Immediate, &available), wgpu::PresentMode::Fifo);
+        assert_eq!(next_present_mode(wgpu::PresentMode::FifoRelaxed, &available), wgpu::PresentMode::Fifo);
+        assert_eq!(next_present_mode(wgpu::PresentMode::Fifo, &[wgpu::PresentMode::Fifo]), wgpu::PresentMode::Fifo);
+    }
+
+    #[test]
+    fn test_running_mode_round_trips_as_preference() {
+        for mode in CYCLE_ORDER {
+            let preference = PresentModePreference::from(mode);
+            assert_eq!(choose_present_mode(preference, &CYCLE_ORDER), mode);
+        }
+    }
+}
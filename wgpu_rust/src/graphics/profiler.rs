@@ -0,0 +1,545 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Pipeline statistics queries
+// The GPU counts how many times each stage ran while a query is open: vertex shader
+// invocations, primitives going through the clipper, fragment shader invocations and compute
+// invocations. Handy to see what LOD, culling or overdraw actually change.
+// Needs Features::PIPELINE_STATISTICS_QUERY (mostly Vulkan and DX12), without it State simply
+// doesnt create this and the stats stay at zero.
+//
+// Results arrive a frame or more late: we resolve the queries into a buffer, copy it to a
+// mappable one and map it after submitting. While that copy is mapped we skip recording.
+
+// Query slots, the main render pass and the compute animation pass
+pub const RENDER_QUERY: u32 = 0;
+pub const COMPUTE_QUERY: u32 = 1;
+const QUERY_COUNT: u32 = 2;
+// One u64 per statistic, in the bit order of PipelineStatisticsTypes
+const STATS_PER_QUERY: usize = 5;
+const QUERY_BYTES: u64 = (STATS_PER_QUERY * std::mem::size_of::<u64>()) as u64;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PipelineStats {
+    pub vertex_invocations: u64,
+    pub primitives: u64, // Primitives assembled from the vertex shader output
+    pub visible_primitives: u64, // Primitives left after clipping
+    pub fragment_invocations: u64,
+    pub compute_invocations: u64,
+}
+
+impl PipelineStats {
+    // render and compute are the raw values of each query, compute is None when no compute
+    // pass ran that frame
+    fn from_results(render: &[u64], compute: Option<&[u64]>) -> Self {
+        Self {
+            vertex_invocations: render[0],
+            primitives: render[1],
+            visible_primitives: render[2],
+            fragment_invocations: render[3],
+            compute_invocations: compute.map(|compute| compute[4]).unwrap_or(0),
+        }
+    }
+}
+
+pub struct PipelineStatsQuery {
+    pub query_set: wgpu::QuerySet,
+    // Resolved query results, GPU only
+    pub result_buffer: wgpu::Buffer,
+    // Copy of result_buffer we can map and read on the CPU
+    readback_buffer: wgpu::Buffer,
+    // Set by the map_async callback once readback_buffer can be read
+    mapped: Arc<AtomicBool>,
+    // Some while a copy is waiting to be read, true if the compute query was written too
+    pending: Option<bool>,
+    map_requested: bool,
+    latest: PipelineStats,
+}
+
+impl PipelineStatsQuery {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Pipeline Statistics Query Set"),
+            ty: wgpu::QueryType::PipelineStatistics(wgpu::PipelineStatisticsTypes::all()),
+            count: QUERY_COUNT,
+        });
+        let result_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pipeline Statistics Result Buffer"),
+            size: QUERY_BYTES * QUERY_COUNT as u64,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pipeline Statistics Readback Buffer"),
+            size: QUERY_BYTES * QUERY_COUNT as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            query_set,
+            result_buffer,
+            readback_buffer,
+            mapped: Arc::new(AtomicBool::new(false)),
+            pending: None,
+            map_requested: false,
+            latest: PipelineStats::default(),
+        }
+    }
+
+    // False while the previous results are still on their way back
+    pub fn can_record(&self) -> bool {
+        self.pending.is_none()
+    }
+
+    // Resolve the queries written this frame and copy them to the readback buffer
+    // compute_recorded tells if the compute query was opened, unwritten queries cant be resolved
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder, compute_recorded: bool) {
+        let count = if compute_recorded { QUERY_COUNT } else { 1 };
+        encoder.resolve_query_set(&self.query_set, 0..count, &self.result_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.result_buffer, 0, &self.readback_buffer, 0, QUERY_BYTES * count as u64);
+        self.pending = Some(compute_recorded);
+    }
+
+    // Call after the commands from resolve were submitted
+    pub fn start_readback(&mut self) {
+        if self.pending.is_none() || self.map_requested {
+            return;
+        }
+        self.map_requested = true;
+        let mapped = Arc::clone(&self.mapped);
+        self.readback_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            if result.is_ok() {
+                mapped.store(true, Ordering::Release);
+            }
+        });
+    }
+
+    // Pick up the results if the mapping finished, doesnt wait for it
+    pub fn poll_results(&mut self, device: &wgpu::Device) {
+        let Some(compute_recorded) = self.pending else {
+            return;
+        };
+        let _ = device.poll(wgpu::PollType::Poll);
+        if !self.mapped.swap(false, Ordering::Acquire) {
+            return;
+        }
+
+        {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            let values: &[u64] = bytemuck::cast_slice(&data);
+            let (render, compute) = values.split_at(STATS_PER_QUERY);
+            self.latest = PipelineStats::from_results(render, compute_recorded.then_some(compute));
+        }
+        self.readback_buffer.unmap();
+        self.pending = None;
+        self.map_requested = false;
+    }
+
+    pub fn latest(&self) -> PipelineStats {
+        self.latest
+    }
+}
+
+// GPU timestamps
+// The GPU writes its clock into a query at the start and end of a pass, the difference is how
+// long the pass took on the GPU (CPU timers only see how long it took to record the commands).
+// Needs Features::TIMESTAMP_QUERY, read back the same way as the pipeline statistics above.
+// With --trace-file <path> every frame that made it back is appended to a CSV file.
+
+// Timed passes, each one uses a begin and an end query
+pub const COMPUTE_PASS: usize = 0;
+pub const SCENE_PASS: usize = 1;
+pub const TAA_PASS: usize = 2;
+pub const SHADOW_PASS: usize = 3; // Contact shadows
+pub const POST_PASS: usize = 4; // Lens effects, from the first one that runs to the last
+const PASS_NAMES: [&str; 5] = ["compute", "scene", "taa", "shadow", "post"];
+const TIMESTAMP_BYTES: u64 = std::mem::size_of::<u64>() as u64;
+// Resolves have to start at a multiple of 256 bytes, every pass gets a slot that big in the
+// result buffer. The readback copy packs them again, 2 timestamps per pass
+const RESOLVE_SLOT_BYTES: u64 = wgpu::QUERY_RESOLVE_BUFFER_ALIGNMENT;
+// The trace is written to disk once per this many frames, BufWriter holds the rest
+const TRACE_FLUSH_FRAMES: u32 = 60;
+
+pub struct GpuProfiler {
+    query_set: wgpu::QuerySet,
+    result_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    mapped: Arc<AtomicBool>,
+    // Nanoseconds per timestamp tick
+    period: f32,
+    frame: u64,
+    // Passes timed in the frame being recorded
+    recording: Vec<usize>,
+    // Frame number and passes of the copy waiting to be read
+    pending: Option<(u64, Vec<usize>)>,
+    map_requested: bool,
+    latest: Vec<(&'static str, f32)>,
+    trace: Option<TraceWriter<BufWriter<File>>>,
+}
+
+impl GpuProfiler {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let count = PASS_NAMES.len() as u32 * 2;
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Timestamp Query Set"),
+            ty: wgpu::QueryType::Timestamp,
+            count,
+        });
+        let result_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timestamp Result Buffer"),
+            size: RESOLVE_SLOT_BYTES * PASS_NAMES.len() as u64,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timestamp Readback Buffer"),
+            size: TIMESTAMP_BYTES * count as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            query_set,
+            result_buffer,
+            readback_buffer,
+            mapped: Arc::new(AtomicBool::new(false)),
+            period: queue.get_timestamp_period(),
+            frame: 0,
+            recording: Vec::new(),
+            pending: None,
+            map_requested: false,
+            latest: Vec::new(),
+            trace: None,
+        }
+    }
+
+    // Start writing every measured frame to a CSV file, replaces an existing file
+    pub fn start_trace(&mut self, path: &Path) -> std::io::Result<()> {
+        self.trace = Some(TraceWriter::new(BufWriter::new(File::create(path)?))?);
+        log::info!("Writing GPU pass timings to {}", path.display());
+        Ok(())
+    }
+
+    // Choose the passes timed this frame, nothing is timed while the last results are still
+    // on their way back. Call once per frame before recording any of them
+    pub fn begin_frame(&mut self, passes: &[usize]) {
+        self.frame += 1;
+        self.recording.clear();
+        if self.pending.is_none() {
+            self.recording.extend_from_slice(passes);
+        }
+    }
+
+    // Timestamp writes for a render pass, None if the pass isnt timed this frame
+    pub fn render_pass_writes(&self, pass: usize) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        self.render_pass_span_writes(pass, true, true)
+    }
+
+    // For a timed pass made of several render passes: the first one writes the begin query and
+    // the last one the end query, so the time between them is counted too
+    pub fn render_pass_span_writes(&self, pass: usize, first: bool, last: bool) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        self.recording.contains(&pass).then(|| wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: first.then_some(pass as u32 * 2),
+            end_of_pass_write_index: last.then_some(pass as u32 * 2 + 1),
+        })
+    }
+
+    pub fn compute_pass_writes(&self, pass: usize) -> Option<wgpu::ComputePassTimestampWrites<'_>> {
+        self.recording.contains(&pass).then(|| wgpu::ComputePassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(pass as u32 * 2),
+            end_of_pass_write_index: Some(pass as u32 * 2 + 1),
+        })
+    }
+
+    // Resolve the passes timed this frame, unwritten queries cant be resolved so one pair at a time
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if self.recording.is_empty() {
+            return;
+        }
+        for &pass in &self.recording {
+            let first = pass as u32 * 2;
+            let slot = pass as u64 * RESOLVE_SLOT_BYTES;
+            encoder.resolve_query_set(&self.query_set, first..first + 2, &self.result_buffer, slot);
+            let offset = first as u64 * TIMESTAMP_BYTES;
+            encoder.copy_buffer_to_buffer(&self.result_buffer, slot, &self.readback_buffer, offset, TIMESTAMP_BYTES * 2);
+        }
+        self.pending = Some((self.frame, std::mem::take(&mut self.recording)));
+    }
+
+    // Call after the commands from resolve were submitted
+    pub fn start_readback(&mut self) {
+        if self.pending.is_none() || self.map_requested {
+            return;
+        }
+        self.map_requested = true;
+        let mapped = Arc::clone(&self.mapped);
+        self.readback_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            if result.is_ok() {
+                mapped.store(true, Ordering::Release);
+            }
+        });
+    }
+
+    // Pick up the timings if the mapping finished and add them to the trace, doesnt wait
+    pub fn poll_results(&mut self, device: &wgpu::Device) {
+        if self.pending.is_none() {
+            return;
+        }
+        let _ = device.poll(wgpu::PollType::Poll);
+        if !self.mapped.swap(false, Ordering::Acquire) {
+            return;
+        }
+        let Some((frame, passes)) = self.pending.take() else {
+            return;
+        };
+
+        {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            let values: &[u64] = bytemuck::cast_slice(&data);
+            self.latest = passes.iter()
+                .map(|&pass| (PASS_NAMES[pass], pass_milliseconds(values[pass * 2], values[pass * 2 + 1], self.period)))
+                .collect();
+        }
+        self.readback_buffer.unmap();
+        self.map_requested = false;
+
+        if let Some(trace) = &mut self.trace
+            && let Err(e) = trace.write_frame(frame, &self.latest)
+        {
+            log::error!("Unable to write the GPU trace, stopping it: {}", e);
+            self.trace = None;
+        }
+    }
+
+    // Pass names and milliseconds of the last frame that made it back
+    pub fn latest(&self) -> &[(&'static str, f32)] {
+        &self.latest
+    }
+
+    // Flush whatever is still buffered and close the trace file
+    pub fn finalize(&mut self) {
+        if let Some(mut trace) = self.trace.take()
+            && let Err(e) = trace.flush()
+        {
+            log::error!("Unable to flush the GPU trace: {}", e);
+        }
+    }
+}
+
+// Everything the HUD and the gui show about recent frames, see State::frame_stats
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrameStats {
+    pub fps: f32,
+    // All zero without Features::PIPELINE_STATISTICS_QUERY
+    pub pipeline: PipelineStats,
+    // GPU milliseconds per timed pass, empty without Features::TIMESTAMP_QUERY
+    pub gpu_passes: Vec<(&'static str, f32)>,
+}
+
+impl FrameStats {
+    // One "GPU <pass>: <ms> ms" line per pass and the total, empty when nothing was timed
+    pub fn gpu_timing_lines(&self) -> Vec<String> {
+        if self.gpu_passes.is_empty() {
+            return Vec::new();
+        }
+        let total = self.gpu_passes.iter().map(|(_, milliseconds)| milliseconds).sum::<f32>();
+        self.gpu_passes.iter()
+            .map(|(pass, milliseconds)| format!("GPU {}: {:.2} ms", pass, milliseconds))
+            .chain(std::iter::once(format!("GPU total: {:.2} ms", total)))
+            .collect()
+    }
+}
+
+// Ticks between begin and end in milliseconds, 0 if the clock went backwards (some drivers do)
+fn pass_milliseconds(begin: u64, end: u64, period: f32) -> f32 {
+    (end.saturating_sub(begin) as f64 * period as f64 / 1_000_000.0) as f32
+}
+
+// CSV with one row per timed pass: frame,pass,duration_ms
+struct TraceWriter<W: Write> {
+    writer: W,
+    frames_since_flush: u32,
+}
+
+impl<W: Write> TraceWriter<W> {
+    fn new(mut writer: W) -> std::io::Result<Self> {
+        writeln!(writer, "frame,pass,duration_ms")?;
+        Ok(Self { writer, frames_since_flush: 0 })
+    }
+
+    fn write_frame(&mut self, frame: u64, timings: &[(&str, f32)]) -> std::io::Result<()> {
+        for (pass, milliseconds) in timings {
+            writeln!(self.writer, "{},{},{:.4}", frame, pass, milliseconds)?;
+        }
+        self.frames_since_flush += 1;
+        if self.frames_since_flush >= TRACE_FLUSH_FRAMES {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.frames_since_flush = 0;
+        self.writer.flush()
+    }
+}
+
+// Path after --trace-file (or --trace-file=path) in the command line arguments
+pub fn trace_file_from_args(args: impl IntoIterator<Item = String>) -> Option<PathBuf> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--trace-file" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--trace-file=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_read_from_query_slots() {
+        let render = [300, 100, 80, 5000, 0];
+        let compute = [0, 0, 0, 0, 128];
+
+        let stats = PipelineStats::from_results(&render, Some(&compute));
+        assert_eq!(stats, PipelineStats {
+            vertex_invocations: 300,
+            primitives: 100,
+            visible_primitives: 80,
+            fragment_invocations: 5000,
+            compute_invocations: 128,
+        });
+
+        // No compute pass that frame
+        assert_eq!(PipelineStats::from_results(&render, None).compute_invocations, 0);
+    }
+
+    #[test]
+    fn test_pass_milliseconds() {
+        assert_eq!(pass_milliseconds(1_000, 3_000_000, 1.0), 2.999);
+        // 2 ticks of 500ns
+        assert_eq!(pass_milliseconds(10, 12, 500.0), 0.001);
+        assert_eq!(pass_milliseconds(5, 3, 1.0), 0.0);
+    }
+
+    #[test]
+    fn test_trace_rows_and_flush_interval() {
+        let mut trace = TraceWriter::new(BufWriter::new(Vec::new())).unwrap();
+        trace.write_frame(1, &[("compute", 0.25), ("scene", 1.5)]).unwrap();
+        // Still in the BufWriter
+        assert!(trace.writer.get_ref().is_empty());
+
+        for frame in 2..=TRACE_FLUSH_FRAMES as u64 {
+            trace.write_frame(frame, &[("scene", 1.0)]).unwrap();
+        }
+        let csv = String::from_utf8(trace.writer.get_ref().clone()).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "frame,pass,duration_ms");
+        assert_eq!(lines[1], "1,compute,0.2500");
+        assert_eq!(lines[2], "1,scene,1.5000");
+        assert_eq!(lines.last(), Some(&"60,scene,1.0000"));
+    }
+
+    #[test]
+    fn test_trace_file_argument() {
+        let args = |list: &[&str]| list.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(trace_file_from_args(args(&["app", "--trace-file", "out.csv"])), Some(PathBuf::from("out.csv")));
+        assert_eq!(trace_file_from_args(args(&["app", "--trace-file=out.csv"])), Some(PathBuf::from("out.csv")));
+        assert_eq!(trace_file_from_args(args(&["app", "--trace-file"])), None);
+        assert_eq!(trace_file_from_args(args(&["app"])), None);
+    }
+
+    #[test]
+    fn test_gpu_timing_lines() {
+        assert!(FrameStats::default().gpu_timing_lines().is_empty());
+
+        let stats = FrameStats { gpu_passes: vec![("scene", 1.25), ("post", 0.5)], ..Default::default() };
+        assert_eq!(stats.gpu_timing_lines(), vec!["GPU scene: 1.25 ms", "GPU post: 0.50 ms", "GPU total: 1.75 ms"]);
+    }
+
+    // Times two passes in a row on a real device and checks the raw timestamps came back in order
+    // Skips without an adapter or without timestamp queries
+    #[test]
+    fn test_timestamps_are_monotonic() {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let Ok(adapter) = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())) else {
+            eprintln!("No adapter available, skipping timestamp test");
+            return;
+        };
+        if !adapter.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            eprintln!("No timestamp queries, skipping timestamp test");
+            return;
+        }
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            required_features: wgpu::Features::TIMESTAMP_QUERY,
+            ..Default::default()
+        })).unwrap();
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d { width: 4, height: 4, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = target.create_view(&Default::default());
+
+        let mut profiler = GpuProfiler::new(&device, &queue);
+        profiler.begin_frame(&[SCENE_PASS, POST_PASS]);
+        let mut encoder = device.create_command_encoder(&Default::default());
+        let spans = [(SCENE_PASS, true, true), (POST_PASS, true, false), (POST_PASS, false, true)];
+        for (pass, first, last) in spans {
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: profiler.render_pass_span_writes(pass, first, last),
+                multiview_mask: None,
+            });
+        }
+        profiler.resolve(&mut encoder);
+        queue.submit(std::iter::once(encoder.finish()));
+        profiler.start_readback();
+        device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+
+        {
+            let data = profiler.readback_buffer.slice(..).get_mapped_range();
+            let values: &[u64] = bytemuck::cast_slice(&data);
+            let written = [
+                values[SCENE_PASS * 2],
+                values[SCENE_PASS * 2 + 1],
+                values[POST_PASS * 2],
+                values[POST_PASS * 2 + 1],
+            ];
+            assert!(written.windows(2).all(|pair| pair[0] <= pair[1]), "{written:?}");
+        }
+
+        profiler.poll_results(&device);
+        let passes = profiler.latest().iter().map(|(pass, _)| *pass).collect::<Vec<_>>();
+        assert_eq!(passes, vec!["scene", "post"]);
+        assert!(profiler.latest().iter().all(|(_, milliseconds)| *milliseconds >= 0.0));
+    }
+}
@@ -0,0 +1,50 @@
+// What the scene shader outputs, V steps through them
+// The mode goes to fs_main in RenderModeUniform (group 3 binding 0), the shader picks the branch,
+// so switching is one small buffer write and no pipeline has to be rebuilt
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderMode {
+    #[default]
+    Normal, // Textured and lit
+    Depth, // Depth buffer, close is bright
+    Grayscale, // Lit result without the color, shows the lighting and texture detail alone
+    Uv, // Texture coordinates as red and green, to spot seams and stretched mappings
+}
+
+impl RenderMode {
+    // Same numbers as the mode checks in fs_main (shader.wgsl)
+    pub fn id(self) -> u32 {
+        match self {
+            Self::Normal => 0,
+            Self::Depth => 1,
+            Self::Grayscale => 2,
+            Self::Uv => 3,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Normal => Self::Depth,
+            Self::Depth => Self::Grayscale,
+            Self::Grayscale => Self::Uv,
+            Self::Uv => Self::Normal,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_visits_every_mode_and_wraps() {
+        let mut mode = RenderMode::default();
+        let mut ids = Vec::new();
+        for _ in 0..4 {
+            ids.push(mode.id());
+            mode = mode.next();
+        }
+        assert_eq!(ids, vec![0, 1, 2, 3]);
+        assert_eq!(mode, RenderMode::Normal);
+    }
+}
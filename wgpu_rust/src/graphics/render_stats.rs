@@ -0,0 +1,117 @@
+// What a frame asked the GPU to do, counted on the CPU while the commands are recorded
+// Unlike the pipeline statistics (profiler.rs) this works on every adapter and is ready the same
+// frame, but it only knows what we submitted: a triangle behind the camera still counts.
+// Good enough to see whether culling, LODs or batching cut the work down.
+//
+// Counted: the scene geometry (light cube, opaque and transparent meshes) of every scene pass,
+// so split screen, multiview and the water reflection passes add up. Debug lines, cloth, water,
+// post effects and the HUD are not. bytes_written is what State writes itself (the uniforms of
+// UniformWriter, instances, render mode, transform and clip plane), texture_bytes is the
+// ResourceManager cache.
+
+use std::cell::Cell;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    pub instances: u64,
+    pub triangles: u64,
+    // set_bind_group calls, setting the group that is already bound counts too
+    pub bind_group_switches: u32,
+    pub bytes_written: u64,
+    pub texture_bytes: u64, // Everything allocated so far, not only this frame
+}
+
+impl RenderStats {
+    // One draw_indexed of index_count indices (a triangle list) for every instance in instances
+    pub fn record_draw(&mut self, index_count: u32, instances: Range<u32>) {
+        let instance_count = instances.len() as u64;
+        self.draw_calls += 1;
+        self.instances += instance_count;
+        self.triangles += index_count as u64 / 3 * instance_count;
+    }
+
+    // For the HUD, "Draws: 5 Instances: 401 Triangles: 4.8k" and the memory line
+    pub fn hud_lines(&self) -> [String; 2] {
+        [
+            format!(
+                "Draws: {} Instances: {} Triangles: {} Binds: {}",
+                self.draw_calls,
+                self.instances,
+                short_count(self.triangles),
+                self.bind_group_switches,
+            ),
+            format!("Uploads: {} Textures: {}", short_bytes(self.bytes_written), short_bytes(self.texture_bytes)),
+        ]
+    }
+}
+
+// The draw functions only borrow State, so counting goes through a Cell
+#[derive(Default)]
+pub struct RenderStatsCounter {
+    current: Cell<RenderStats>,
+}
+
+impl RenderStatsCounter {
+    pub fn record(&self, count: impl FnOnce(&mut RenderStats)) {
+        let mut stats = self.current.get();
+        count(&mut stats);
+        self.current.set(stats);
+    }
+
+    // The frame is done, hands out its counts and starts the next one from zero
+    pub fn finish(&self, texture_bytes: u64) -> RenderStats {
+        let mut stats = self.current.take();
+        stats.texture_bytes = texture_bytes;
+        stats
+    }
+}
+
+fn short_count(count: u64) -> String {
+    match count {
+        0..1_000 => count.to_string(),
+        1_000..1_000_000 => format!("{:.1}k", count as f64 / 1e3),
+        _ => format!("{:.1}M", count as f64 / 1e6),
+    }
+}
+
+fn short_bytes(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draws_add_up_and_finish_resets() {
+        let counter = RenderStatsCounter::default();
+        counter.record(|stats| {
+            stats.record_draw(36, 0..100); // Cube for 100 instances
+            stats.record_draw(36, 5..6);
+            stats.bind_group_switches += 3;
+            stats.bytes_written += 64;
+        });
+
+        let stats = counter.finish(4096);
+        assert_eq!(stats.draw_calls, 2);
+        assert_eq!(stats.instances, 101);
+        assert_eq!(stats.triangles, 12 * 101);
+        assert_eq!(stats.bind_group_switches, 3);
+        assert_eq!(stats.texture_bytes, 4096);
+        assert_eq!(counter.finish(4096), RenderStats { texture_bytes: 4096, ..Default::default() });
+    }
+
+    #[test]
+    fn test_hud_lines_shorten_big_numbers() {
+        let stats = RenderStats { triangles: 4_800, bytes_written: 512, texture_bytes: 3 * 1_048_576, ..Default::default() };
+        let [draws, memory] = stats.hud_lines();
+        assert!(draws.contains("Triangles: 4.8k"), "{draws}");
+        assert_eq!(memory, "Uploads: 512 B Textures: 3.0 MB");
+    }
+}
@@ -0,0 +1,444 @@
+use crate::graphics::text::{self, ScreenUniform, TextVertex};
+use crate::graphics::{buffers, texture};
+
+// Signed distance field text
+// Instead of how much of each texel a glyph covers, the atlas stores how far the texel is from
+// the glyph edge (above 0.5 inside, below 0.5 outside). Bilinear filtering of a distance is still
+// a distance, so the fragment shader can cut the edge at 0.5 with a smoothstep that is one
+// screen pixel wide. The edges stay crisp at any size instead of turning blurry when scaled up.
+//
+// The field is generated once at startup on the GPU with jump flooding:
+// 1. Seed: every inside texel (coverage >= 0.5) is its own nearest inside seed, every outside
+//    texel its own nearest outside seed.
+// 2. Flood: with step sizes N/2, N/4 ... 1 every texel looks at the 8 texels step away and keeps
+//    the closest seed it finds. After log2(N) passes each texel knows (almost exactly) its
+//    nearest inside and outside texel.
+// 3. Distance: outside texels use the distance to the nearest inside seed, inside texels the
+//    negative distance to the nearest outside seed.
+// The glyph outlines come from the bitmap atlas of the text renderer, the SDF has the same
+// layout so both use the same glyph quads. Neighbours are only taken from the same atlas cell,
+// so glyphs dont bleed into each other.
+
+// Distance in atlas texels that maps to the full 0..1 range, further away is clamped
+const SPREAD: f32 = 4.0;
+const WORKGROUP_SIZE: u32 = 8; // Must match @workgroup_size in sdf_generate.wgsl
+// Seeds are texel coordinates, a 16 bit float would lose precision on big atlases
+const SEED_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+// Filterable and writable from a compute shader without extra features
+const SDF_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const INITIAL_CAPACITY: u64 = 6 * 256; // Vertices, grows when a frame needs more
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct JumpFloodUniform {
+    step: i32, // Texels between a texel and the neighbours it looks at
+    spread: f32,
+    cell_size: [i32; 2], // Atlas cell in texels, flooding stops at its border
+}
+
+pub struct SdfRenderer {
+    pub pipeline: wgpu::RenderPipeline,
+    // Distance in the red channel, same cell layout as the bitmap font atlas
+    #[allow(dead_code)] // Only read back by the tests, the bind group keeps its own view
+    pub glyph_atlas: wgpu::Texture,
+    atlas: texture::TextureBundle,
+    screen_buffer: wgpu::Buffer,
+    screen_bind_group: wgpu::BindGroup,
+    vertex_buffer: wgpu::Buffer,
+    vertex_capacity: u64,
+    vertices: Vec<TextVertex>,
+}
+
+impl SdfRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layouts: &texture::TextureLayoutCache,
+        color_format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> anyhow::Result<Self> {
+        let bitmap = texture::Texture::from_bytes(device, queue, text::ATLAS_BYTES, "SDF Source Atlas")?;
+        let glyph_atlas = generate_sdf(device, queue, &bitmap.texture);
+
+        // Linear filtering is what makes the distance field work, it interpolates the distance
+        let atlas = texture::Texture {
+            texture_view: glyph_atlas.create_view(&wgpu::TextureViewDescriptor::default()),
+            sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            }),
+            texture: glyph_atlas,
+        };
+        let atlas_bundle = texture::TextureBundle::new(device, layouts, &atlas);
+
+        let screen_buffer = buffers::create_uniform_buffer(device, &ScreenUniform::new(width, height));
+        let screen_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("SDF Screen Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let screen_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("SDF Screen Bind Group"),
+            layout: &screen_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: screen_buffer.as_entire_binding(),
+            }],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("SDF Text Pipeline Layout"),
+            bind_group_layouts: &[&atlas_bundle.bind_group_layout, &screen_bind_group_layout],
+            immediate_size: 0,
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("SDF Text Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/sdf.wgsl").into()),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("SDF Text Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[TextVertex::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None, // Always on top, like the bitmap text
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        Ok(Self {
+            pipeline,
+            glyph_atlas: atlas.texture,
+            atlas: atlas_bundle,
+            screen_buffer,
+            screen_bind_group,
+            vertex_buffer: Self::create_vertex_buffer(device, INITIAL_CAPACITY),
+            vertex_capacity: INITIAL_CAPACITY,
+            vertices: Vec::new(),
+        })
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, capacity: u64) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("SDF Text Vertex Buffer"),
+            size: capacity * std::mem::size_of::<TextVertex>() as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    // Positions are in physical pixels, so the projection has to follow the window size
+    pub fn resize(&self, queue: &wgpu::Queue, width: u32, height: u32) {
+        queue.write_buffer(&self.screen_buffer, 0, bytemuck::cast_slice(&[ScreenUniform::new(width, height)]));
+    }
+
+    // position is the top left corner of the first line, size the line height in pixels
+    pub fn draw_text(&mut self, text: &str, position: [f32; 2], size: f32, color: wgpu::Color) {
+        text::layout_text(&mut self.vertices, text, position[0], position[1], size, color);
+    }
+
+    // Upload and draw everything queued since the last call, on top of what is already in view
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) {
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        let needed = self.vertices.len() as u64;
+        if needed > self.vertex_capacity {
+            self.vertex_capacity = needed.next_power_of_two();
+            self.vertex_buffer = Self::create_vertex_buffer(device, self.vertex_capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("SDF Text Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load, // Draw over the finished scene
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+                multiview_mask: None,
+            });
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &self.atlas.bind_group, &[]);
+            render_pass.set_bind_group(1, &self.screen_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..needed * std::mem::size_of::<TextVertex>() as u64));
+            render_pass.draw(0..needed as u32, 0..1);
+        }
+
+        self.vertices.clear();
+    }
+}
+
+// Step sizes of the flood passes, half the cell first and down to 1
+fn jump_flood_steps(cell_size: u32) -> Vec<i32> {
+    let mut step = cell_size.max(2).next_power_of_two() / 2;
+    let mut steps = Vec::new();
+    while step >= 1 {
+        steps.push(step as i32);
+        step /= 2;
+    }
+    steps
+}
+
+// Records the seed, flood and distance passes over the coverage of source and submits them
+// No need to wait, the queue runs them before any later submission that samples the atlas
+fn generate_sdf(device: &wgpu::Device, queue: &wgpu::Queue, source: &wgpu::Texture) -> wgpu::Texture {
+    let size = source.size();
+    let storage_texture = |label, format| device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        // COPY_SRC so the result can be read back and checked
+        usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    // Seeds ping pong between these two, every pass reads one and writes the other
+    let seeds = [storage_texture("SDF Seeds A", SEED_FORMAT), storage_texture("SDF Seeds B", SEED_FORMAT)];
+    let glyph_atlas = storage_texture("SDF Glyph Atlas", SDF_FORMAT);
+    let seed_views = seeds.each_ref().map(|seed| seed.create_view(&wgpu::TextureViewDescriptor::default()));
+    let source_view = source.create_view(&wgpu::TextureViewDescriptor::default());
+    let atlas_view = glyph_atlas.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let bind_group_layout = |label, format| device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some(label),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    // Only read with textureLoad, Rgba32Float isnt filterable
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::StorageTexture {
+                    access: wgpu::StorageTextureAccess::WriteOnly,
+                    format,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+    });
+    // Seed and flood write seeds, the distance pass writes the atlas
+    let seed_layout = bind_group_layout("SDF Seed Bind Group Layout", SEED_FORMAT);
+    let distance_layout = bind_group_layout("SDF Distance Bind Group Layout", SDF_FORMAT);
+
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("SDF Generate Shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("shaders/sdf_generate.wgsl").into()),
+    });
+    let pipeline = |label, layout: &wgpu::BindGroupLayout, entry_point| {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &[layout],
+            immediate_size: 0,
+        });
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some(entry_point),
+            compilation_options: Default::default(),
+            cache: None,
+        })
+    };
+    let seed_pipeline = pipeline("SDF Seed Pipeline", &seed_layout, "cs_seed");
+    let flood_pipeline = pipeline("SDF Flood Pipeline", &seed_layout, "cs_flood");
+    let distance_pipeline = pipeline("SDF Distance Pipeline", &distance_layout, "cs_distance");
+
+    // Every pass gets its own uniform, they are all recorded before anything runs
+    let bind_group = |layout, input: &wgpu::TextureView, output: &wgpu::TextureView, step: i32| {
+        let uniform = JumpFloodUniform {
+            step,
+            spread: SPREAD,
+            cell_size: [text::CELL_WIDTH as i32, text::CELL_HEIGHT as i32],
+        };
+        let buffer = buffers::create_uniform_buffer(device, &uniform);
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("SDF Generate Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(input),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(output),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+        })
+    };
+
+    let mut passes = vec![(&seed_pipeline, bind_group(&seed_layout, &source_view, &seed_views[0], 0))];
+    let mut current = 0;
+    let cell_size = (text::CELL_WIDTH as u32).max(text::CELL_HEIGHT as u32);
+    for step in jump_flood_steps(cell_size) {
+        passes.push((&flood_pipeline, bind_group(&seed_layout, &seed_views[current], &seed_views[1 - current], step)));
+        current = 1 - current;
+    }
+    passes.push((&distance_pipeline, bind_group(&distance_layout, &seed_views[current], &atlas_view, 0)));
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("SDF Generate Encoder"),
+    });
+    {
+        // One pass for all dispatches, wgpu inserts the barriers between them
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("SDF Generate Pass"),
+            timestamp_writes: None,
+        });
+        for (pipeline, bind_group) in &passes {
+            compute_pass.set_pipeline(pipeline);
+            compute_pass.set_bind_group(0, bind_group, &[]);
+            compute_pass.dispatch_workgroups(
+                size.width.div_ceil(WORKGROUP_SIZE),
+                size.height.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+    }
+    queue.submit(std::iter::once(encoder.finish()));
+
+    glyph_atlas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::pipeline_cache::LayoutCache;
+
+    #[test]
+    fn test_jump_flood_steps_halve_down_to_one() {
+        assert_eq!(jump_flood_steps(32), vec![16, 8, 4, 2, 1]);
+        // Not a power of two, the first step still reaches across the whole cell
+        assert_eq!(jump_flood_steps(20), vec![16, 8, 4, 2, 1]);
+        assert_eq!(jump_flood_steps(1), vec![1]);
+    }
+
+    // Generate the field from the real atlas and compare it with the bitmap coverage
+    // Skips when the machine has no adapter at all (no GPU and no software fallback)
+    #[test]
+    fn test_distance_field_matches_coverage() {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let Ok(adapter) = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())) else {
+            eprintln!("No adapter available, skipping SDF generation test");
+            return;
+        };
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).unwrap();
+        let layouts = texture::TextureLayoutCache::new(&device, &mut LayoutCache::new());
+        let sdf = SdfRenderer::new(&device, &queue, &layouts, wgpu::TextureFormat::Rgba8UnormSrgb, 64, 64).unwrap();
+
+        let size = sdf.glyph_atlas.size();
+        // 8 bytes per Rgba16Float texel, the 256 texel wide atlas needs no row padding
+        let bytes_per_row = size.width * 8;
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("SDF Test Readback"),
+            size: (bytes_per_row * size.height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.copy_texture_to_buffer(
+            sdf.glyph_atlas.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &readback,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(size.height),
+                },
+            },
+            size,
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+        readback.slice(..).map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+        let data = readback.slice(..).get_mapped_range();
+        let distances: &[u16] = bytemuck::cast_slice(&data);
+
+        let coverage = image::load_from_memory(text::ATLAS_BYTES).unwrap().to_rgba8();
+        assert_eq!(coverage.dimensions(), (size.width, size.height));
+        // The values are between 0 and 1, positive halfs sort the same as their bits
+        const HALF: u16 = 0x3800; // 0.5 as a 16 bit float
+        let mut edge_texels = 0;
+        for (x, y, pixel) in coverage.enumerate_pixels() {
+            let distance = distances[((y * size.width + x) * 4) as usize];
+            let inside = pixel[3] >= 128;
+            assert_eq!(distance > HALF, inside, "texel {x}, {y}");
+            if distance != 0 && distance != 0x3c00 {
+                edge_texels += 1;
+            }
+        }
+        // Not just a copy of the bitmap, texels near the edges are somewhere in between
+        assert!(edge_texels > 1000, "{edge_texels}");
+    }
+}
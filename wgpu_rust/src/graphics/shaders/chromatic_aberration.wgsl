@@ -0,0 +1,40 @@
+// Chromatic aberration, a lens that doesnt bend every wavelength the same
+// (see ChromaticAberrationPass in graphics/post_process.rs)
+// Red is pushed outwards and blue inwards from the center, green stays where it is, so the
+// color fringes grow towards the edges of the frame like with a real lens
+
+struct ChromaticAberrationUniform {
+    offset: f32, // UV shift of red and blue per unit of distance from the center
+}
+
+@group(0) @binding(0)
+var color_tex: texture_2d<f32>;
+@group(0) @binding(1)
+var color_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> params: ChromaticAberrationUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// Full screen triangle, same as the contact shadow pass
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    // Clip space y points up, texture v points down
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let shift = (in.uv - 0.5) * params.offset;
+    let red = textureSample(color_tex, color_sampler, in.uv + shift).r;
+    let green = textureSample(color_tex, color_sampler, in.uv);
+    let blue = textureSample(color_tex, color_sampler, in.uv - shift).b;
+    return vec4<f32>(red, green.g, blue, green.a);
+}
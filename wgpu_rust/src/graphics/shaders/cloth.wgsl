@@ -0,0 +1,43 @@
+// Draws the cloth straight from the simulated positions (see graphics/cloth.rs)
+// There are no normals in the buffer, the fragment shader gets the face normal from the screen
+// space derivatives of the world position, so the shading is flat per triangle.
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    @location(0) position: vec4<f32>, // w is the inverse mass, not used here
+    @location(1) uv: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+}
+
+// Fixed light from above, the cloth doesnt use the scene light bind groups
+const LIGHT_DIRECTION: vec3<f32> = vec3<f32>(0.4, 0.8, 0.45);
+const CHECKS: f32 = 8.0;
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(in.position.xyz, 1.0);
+    out.world_position = in.position.xyz;
+    out.uv = in.uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = normalize(cross(dpdx(in.world_position), dpdy(in.world_position)));
+    // Both sides of the cloth are lit the same
+    let diffuse = abs(dot(normal, normalize(LIGHT_DIRECTION)));
+    let cell = vec2<i32>(floor(in.uv * CHECKS));
+    let checker = select(vec3<f32>(0.9, 0.9, 0.85), vec3<f32>(0.75, 0.15, 0.15), (cell.x + cell.y) % 2 == 0);
+    return vec4<f32>(checker * (0.25 + 0.75 * diffuse), 1.0);
+}
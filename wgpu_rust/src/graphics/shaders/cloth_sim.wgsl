@@ -0,0 +1,93 @@
+// Position based cloth simulation (see graphics/cloth.rs)
+// cs_integrate moves every vertex with Verlet integration, cs_constraint then pulls the two ends of
+// every distance constraint in one batch back towards their rest length. The batches are built so
+// no two constraints in one of them share a vertex, so they can all run at the same time.
+
+const WORKGROUP_SIZE: u32 = 64u; // Must match WORKGROUP_SIZE in cloth.rs
+
+struct Constraint {
+    a: u32,
+    b: u32,
+    rest_length: f32,
+    _padding: u32,
+}
+
+struct ClothParams {
+    gravity: vec4<f32>, // xyz
+    wind: vec4<f32>, // xyz, force per unit of mass
+    dt: f32,
+    damping: f32, // Fraction of the velocity kept every step
+    time: f32,
+    vertex_count: u32,
+    pin_count: u32,
+}
+
+// One per batch, picked with a dynamic offset
+struct Batch {
+    first: u32,
+    count: u32,
+}
+
+// w is the inverse mass, 0 for pinned vertices so nothing can move them
+@group(0) @binding(0)
+var<storage, read_write> positions: array<vec4<f32>>;
+@group(0) @binding(1)
+var<storage, read_write> prev_positions: array<vec4<f32>>;
+@group(0) @binding(2)
+var<storage, read> constraints: array<Constraint>;
+@group(0) @binding(3)
+var<storage, read> pins: array<u32>;
+@group(0) @binding(4)
+var<uniform> params: ClothParams;
+@group(1) @binding(0)
+var<uniform> batch: Batch;
+
+fn is_pinned(index: u32) -> bool {
+    for (var i = 0u; i < params.pin_count; i++) {
+        if (pins[i] == index) {
+            return true;
+        }
+    }
+    return false;
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn cs_integrate(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if (index >= params.vertex_count) {
+        return;
+    }
+    var position = positions[index];
+    if (is_pinned(index)) {
+        positions[index] = vec4<f32>(position.xyz, 0.0);
+        return;
+    }
+
+    // Verlet: the velocity is whatever moved the vertex last step, no velocity buffer needed
+    let velocity = (position.xyz - prev_positions[index].xyz) * params.damping;
+    // Gusts, the wind is a bit stronger or weaker depending on where and when
+    let gust = 0.7 + 0.3 * sin(params.time * 3.0 + position.x * 2.0 + position.y * 3.0);
+    let acceleration = params.gravity.xyz + params.wind.xyz * gust;
+    prev_positions[index] = position;
+    positions[index] = vec4<f32>(position.xyz + velocity + acceleration * params.dt * params.dt, 1.0);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn cs_constraint(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= batch.count) {
+        return;
+    }
+    let constraint = constraints[batch.first + id.x];
+    let a = positions[constraint.a];
+    let b = positions[constraint.b];
+    let total_weight = a.w + b.w;
+    let delta = b.xyz - a.xyz;
+    let length = length(delta);
+    if (total_weight == 0.0 || length < 1e-6) {
+        return;
+    }
+    // Split the correction by inverse mass, a pinned end doesnt move and the other takes all of it
+    let correction = delta * ((length - constraint.rest_length) / (length * total_weight));
+    positions[constraint.a] = vec4<f32>(a.xyz + correction * a.w, a.w);
+    positions[constraint.b] = vec4<f32>(b.xyz - correction * b.w, b.w);
+}
@@ -0,0 +1,50 @@
+// Compute shader that animates the instance model matrices on the GPU
+// Compute shaders dont draw anything, they just run a function over a grid of "invocations"
+// Here every invocation takes one instance and writes an animated copy of its model matrix
+
+// Same memory layout as InstanceRaw on the Rust side
+struct InstanceRaw {
+    model: mat4x4<f32>,
+    color: vec4<f32>,
+    prev_model: mat4x4<f32>,
+}
+
+struct ComputeParams {
+    time: f32,
+    amplitude: f32,
+    instance_count: u32,
+    _padding: u32,
+}
+
+// The original (rest) transforms, never modified
+@group(0) @binding(0)
+var<storage, read> rest_instances: array<InstanceRaw>;
+// Output buffer, also used as the vertex buffer for instancing in the render pass
+@group(0) @binding(1)
+var<storage, read_write> animated_instances: array<InstanceRaw>;
+@group(0) @binding(2)
+var<uniform> params: ComputeParams;
+
+// 64 invocations per workgroup, dispatch count is calculated on the Rust side
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    // Last workgroup can have more invocations than instances left
+    if (index >= params.instance_count) {
+        return;
+    }
+
+    let model = rest_instances[index].model;
+
+    // Use the instance position on the grid as phase so the instances move like a wave
+    let phase = model[3].x * 0.5 + model[3].z * 0.5;
+    let offset = sin(params.time * 2.0 + phase) * params.amplitude;
+
+    // Translation on Y axis applied after the rest transform
+    var animated = model;
+    animated[3].y = model[3].y + offset;
+    // What is still in the output buffer is last frame's matrix, keep it for the motion vectors
+    animated_instances[index].prev_model = animated_instances[index].model;
+    animated_instances[index].model = animated;
+    animated_instances[index].color = rest_instances[index].color;
+}
@@ -0,0 +1,84 @@
+// Screen space contact shadows
+// Runs once per pixel after the scene is drawn. We rebuild the world position of the pixel from
+// the depth buffer, then walk a few small steps towards the light. If any step ends up behind
+// what the depth buffer has stored at that spot, something is between the pixel and the light.
+// The output is a darkening factor that is multiplied into the scene color with blending.
+
+struct ContactShadowUniform {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    light_position: vec3<f32>,
+    steps: u32,
+    distance: f32, // World space length of the ray march
+    thickness: f32, // How far behind a surface a step can be and still count as occluded
+    shadow_factor: f32, // Main shadow factor the contact shadow gets multiplied with
+    strength: f32,
+}
+
+@group(0) @binding(0)
+var depth_tex: texture_depth_2d;
+@group(0) @binding(1)
+var<uniform> params: ContactShadowUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+// Full screen triangle, 3 vertices big enough to cover the whole screen, no vertex buffer needed
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+fn pixel_to_world(coords: vec2<f32>, depth: f32, size: vec2<f32>) -> vec3<f32> {
+    // Pixel to NDC, Y flipped because pixels grow downwards
+    let ndc = vec2<f32>(coords.x / size.x * 2.0 - 1.0, 1.0 - coords.y / size.y * 2.0);
+    let world = params.inv_view_proj * vec4<f32>(ndc, depth, 1.0);
+    return world.xyz / world.w;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(depth_tex));
+    let depth = textureLoad(depth_tex, vec2<i32>(in.clip_position.xy), 0);
+
+    // Background, nothing to shadow
+    if (depth >= 1.0 || params.steps == 0u) {
+        return vec4<f32>(vec3<f32>(params.shadow_factor), 1.0);
+    }
+
+    let world_position = pixel_to_world(in.clip_position.xy, depth, size);
+    let to_light = normalize(params.light_position - world_position);
+    let step_length = params.distance / f32(params.steps);
+
+    var occlusion = 0.0;
+    for (var i = 1u; i <= params.steps; i = i + 1u) {
+        let sample_world = world_position + to_light * step_length * f32(i);
+        let sample_clip = params.view_proj * vec4<f32>(sample_world, 1.0);
+        let sample_ndc = sample_clip.xyz / sample_clip.w;
+
+        // Left the screen, we have no depth information there
+        if (abs(sample_ndc.x) > 1.0 || abs(sample_ndc.y) > 1.0) {
+            break;
+        }
+
+        let sample_pixel = vec2<f32>((sample_ndc.x * 0.5 + 0.5) * size.x, (0.5 - sample_ndc.y * 0.5) * size.y);
+        let scene_depth = textureLoad(depth_tex, vec2<i32>(sample_pixel), 0);
+        // Compare in linear view distance (clip w) instead of the non linear depth buffer values
+        let scene_world = pixel_to_world(sample_pixel, scene_depth, size);
+        let scene_w = (params.view_proj * vec4<f32>(scene_world, 1.0)).w;
+        let delta = sample_clip.w - scene_w;
+
+        if (delta > 0.001 && delta < params.thickness) {
+            // Closer hits give darker shadows
+            occlusion = 1.0 - f32(i - 1u) / f32(params.steps);
+            break;
+        }
+    }
+
+    let contact = 1.0 - occlusion * params.strength;
+    return vec4<f32>(vec3<f32>(contact * params.shadow_factor), 1.0);
+}
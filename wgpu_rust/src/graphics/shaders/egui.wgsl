@@ -0,0 +1,54 @@
+// egui overlay shader
+// Positions come in points with (0,0) at the top left, colors are sRGB with premultiplied alpha.
+// The target and the textures are sRGB formats, so we do the blending math in linear space.
+
+struct ScreenUniform {
+    size_in_points: vec2<f32>,
+}
+@group(0) @binding(0)
+var<uniform> screen: ScreenUniform;
+
+@group(1) @binding(0)
+var t_egui: texture_2d<f32>;
+@group(1) @binding(1)
+var s_egui: sampler;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) color: vec4<f32>, // sRGB, 0..1 after Unorm8x4 unpacking
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>, // Linear
+}
+
+fn linear_from_srgb(srgb: vec3<f32>) -> vec3<f32> {
+    let cutoff = srgb < vec3<f32>(0.04045);
+    let lower = srgb / vec3<f32>(12.92);
+    let higher = pow((srgb + vec3<f32>(0.055)) / vec3<f32>(1.055), vec3<f32>(2.4));
+    return select(higher, lower, cutoff);
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    // Points to clip space, Y flipped because points grow downwards
+    out.clip_position = vec4<f32>(
+        2.0 * in.position.x / screen.size_in_points.x - 1.0,
+        1.0 - 2.0 * in.position.y / screen.size_in_points.y,
+        0.0,
+        1.0,
+    );
+    out.tex_coords = in.tex_coords;
+    out.color = vec4<f32>(linear_from_srgb(in.color.rgb), in.color.a);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Texture is sRGB so the sample is already linear
+    return in.color * textureSample(t_egui, s_egui, in.tex_coords);
+}
@@ -0,0 +1,73 @@
+// Per pixel motion vectors, how far the surface under each pixel moved since the last frame
+// Temporal effects read this to find where a pixel was on the previous frame
+
+struct MotionUniform {
+    view_proj: mat4x4<f32>,
+    prev_view_proj: mat4x4<f32>, // Camera of the previous frame
+}
+@group(0) @binding(0)
+var<uniform> motion: MotionUniform;
+
+// Same scale as the scene shader, so the velocity lines up with what was drawn
+struct TransformUniform {
+    scale: f32,
+}
+@group(1) @binding(0)
+var<uniform> transform: TransformUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+}
+
+// Current and previous model matrix, see InstanceRaw::desc
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+    @location(10) prev_model_matrix_0: vec4<f32>,
+    @location(11) prev_model_matrix_1: vec4<f32>,
+    @location(12) prev_model_matrix_2: vec4<f32>,
+    @location(13) prev_model_matrix_3: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // Both clip positions go to the fragment shader, the divide by w has to happen per pixel
+    // because perspective divided values dont interpolate linearly across the triangle
+    @location(0) current_clip: vec4<f32>,
+    @location(1) prev_clip: vec4<f32>,
+}
+
+@vertex
+fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let prev_model_matrix = mat4x4<f32>(
+        instance.prev_model_matrix_0,
+        instance.prev_model_matrix_1,
+        instance.prev_model_matrix_2,
+        instance.prev_model_matrix_3,
+    );
+    let local_position = vec4<f32>(model.position * transform.scale, 1.0);
+
+    // Current and previous model-view-projection
+    var out: VertexOutput;
+    out.current_clip = motion.view_proj * model_matrix * local_position;
+    out.prev_clip = motion.prev_view_proj * prev_model_matrix * local_position;
+    out.clip_position = out.current_clip;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec2<f32> {
+    let current_ndc = in.current_clip.xy / in.current_clip.w;
+    let prev_ndc = in.prev_clip.xy / in.prev_clip.w;
+    // NDC spans 2 units across the screen, * 0.5 turns it into a fraction of the screen (UV units)
+    // y still points up like NDC, flip it before offsetting texture coordinates
+    return (current_ndc - prev_ndc) * 0.5;
+}
@@ -0,0 +1,26 @@
+// Side by side preview of the stereo target, one triangle that covers the whole screen
+
+@group(0) @binding(0)
+var t_eyes: texture_2d<f32>;
+@group(0) @binding(1)
+var s_eyes: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // (0,0) (2,0) (0,2) in uv space, the part outside the screen gets clipped
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.tex_coords = uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_eyes, s_eyes, in.tex_coords);
+}
@@ -0,0 +1,50 @@
+// Selection outline (see graphics/outline.rs)
+// The picked instance again, scaled up a little and in one flat color. The stencil test drops
+// every pixel the real instance covers, so only the rim around it is left.
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+}
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    view_position: vec4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// Same model scale as the scene shader, the outline has to grow from the scaled mesh
+struct TransformUniform {
+    scale: f32,
+}
+@group(1) @binding(0)
+var<uniform> transform: TransformUniform;
+
+struct OutlineUniform {
+    color: vec4<f32>,
+    thickness: f32, // Extra scale, 0.05 makes the copy 5% bigger than the mesh
+}
+@group(2) @binding(0)
+var<uniform> outline: OutlineUniform;
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, instance: InstanceInput) -> @builtin(position) vec4<f32> {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    // Scaled around the mesh origin, not pushed along the normals: cube corners have split
+    // normals and would tear open
+    let local = position * transform.scale * (1.0 + outline.thickness);
+    return camera.view_proj * model_matrix * vec4<f32>(local, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return outline.color;
+}
@@ -0,0 +1,78 @@
+// Simplified scene shader used to capture the environment into a cubemap face
+// Same textured + diffuse lighting as shader.wgsl, without the debug render modes
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+}
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) world_position: vec3<f32>,
+};
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+// Same material parameters as the scene shader, only the color is used here
+struct Material {
+    base_color: vec4<f32>,
+    metallic: f32,
+    roughness: f32,
+}
+@group(0) @binding(2)
+var<uniform> material: Material;
+
+// View projection of the cubemap face being captured
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct Light {
+    position: vec3<f32>,
+    color: vec3<f32>,
+}
+@group(2) @binding(0)
+var<uniform> light: Light;
+
+@vertex
+fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.world_normal = model.normal;
+    out.world_position = world_position.xyz;
+    out.clip_position = camera.view_proj * world_position;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let object_color = textureSample(t_diffuse, s_diffuse, in.tex_coords) * material.base_color;
+
+    let ambient_color = light.color * 0.1;
+    let light_dir = normalize(light.position - in.world_position);
+    let diffuse_color = light.color * max(dot(in.world_normal, light_dir), 0.0);
+
+    return vec4<f32>((ambient_color + diffuse_color) * object_color.xyz, object_color.a);
+}
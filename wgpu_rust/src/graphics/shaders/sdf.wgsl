@@ -0,0 +1,45 @@
+// Signed distance field text shader
+// Same quads as the bitmap text, but the atlas holds the distance to the glyph edge (0.5 on the
+// edge, higher inside). The smoothstep around 0.5 is as wide as one screen pixel, so the edge
+// stays sharp no matter how big the text is drawn.
+
+struct ScreenUniform {
+    projection: mat4x4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> screen: ScreenUniform;
+
+@group(0) @binding(0)
+var t_sdf: texture_2d<f32>;
+@group(0) @binding(1)
+var s_sdf: sampler;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = screen.projection * vec4<f32>(in.position, 0.0, 1.0);
+    out.tex_coords = in.tex_coords;
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let distance = textureSample(t_sdf, s_sdf, in.tex_coords).r;
+    // How much the distance changes across one screen pixel, small text gets a softer edge
+    let edge = max(fwidth(distance) * 0.5, 1e-4);
+    let coverage = smoothstep(0.5 - edge, 0.5 + edge, distance);
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
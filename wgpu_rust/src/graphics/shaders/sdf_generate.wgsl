@@ -0,0 +1,102 @@
+// Jump flooding, builds a signed distance field from a coverage atlas (see graphics/sdf.rs)
+// Seed textures hold texel coordinates: xy the nearest inside texel, zw the nearest outside
+// texel, -1 when none was found yet
+
+struct JumpFloodUniform {
+    step: i32,
+    spread: f32, // Distance in texels that maps to the full 0..1 range
+    cell_size: vec2<i32>, // Seeds from another atlas cell are ignored
+}
+
+@group(0) @binding(0)
+var input_tex: texture_2d<f32>;
+@group(0) @binding(1)
+var output_tex: texture_storage_2d<rgba32float, write>;
+@group(0) @binding(2)
+var<uniform> params: JumpFloodUniform;
+
+const NO_SEED: vec2<f32> = vec2<f32>(-1.0);
+const FAR: f32 = 1e9;
+
+fn seed_distance(seed: vec2<f32>, here: vec2<f32>) -> f32 {
+    if (seed.x < 0.0) {
+        return FAR;
+    }
+    return distance(seed, here);
+}
+
+// Every texel is a seed for its own side of the edge
+@compute @workgroup_size(8, 8)
+fn cs_seed(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = vec2<i32>(textureDimensions(input_tex));
+    let coords = vec2<i32>(id.xy);
+    if (any(coords >= size)) {
+        return;
+    }
+    let here = vec2<f32>(coords);
+    let inside = textureLoad(input_tex, coords, 0).a >= 0.5;
+    textureStore(output_tex, coords, select(vec4<f32>(NO_SEED, here), vec4<f32>(here, NO_SEED), inside));
+}
+
+// Keep the closest seeds among this texel and the 8 texels step away
+@compute @workgroup_size(8, 8)
+fn cs_flood(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = vec2<i32>(textureDimensions(input_tex));
+    let coords = vec2<i32>(id.xy);
+    if (any(coords >= size)) {
+        return;
+    }
+    let here = vec2<f32>(coords);
+    let cell = coords / params.cell_size;
+
+    var best = textureLoad(input_tex, coords, 0);
+    var best_inside = seed_distance(best.xy, here);
+    var best_outside = seed_distance(best.zw, here);
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let neighbor = coords + vec2<i32>(x, y) * params.step;
+            if (any(neighbor < vec2<i32>(0)) || any(neighbor >= size) || any(neighbor / params.cell_size != cell)) {
+                continue;
+            }
+            let seeds = textureLoad(input_tex, neighbor, 0);
+            let inside = seed_distance(seeds.xy, here);
+            if (inside < best_inside) {
+                best_inside = inside;
+                best = vec4<f32>(seeds.xy, best.zw);
+            }
+            let outside = seed_distance(seeds.zw, here);
+            if (outside < best_outside) {
+                best_outside = outside;
+                best = vec4<f32>(best.xy, seeds.zw);
+            }
+        }
+    }
+    textureStore(output_tex, coords, best);
+}
+
+@group(0) @binding(1)
+var atlas_tex: texture_storage_2d<rgba16float, write>;
+
+// Distance to the edge, halfway between the texel centers on both sides of it
+// Stored as 0.5 - distance / (2 * spread): 0.5 on the edge, above inside, below outside
+@compute @workgroup_size(8, 8)
+fn cs_distance(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = vec2<i32>(textureDimensions(input_tex));
+    let coords = vec2<i32>(id.xy);
+    if (any(coords >= size)) {
+        return;
+    }
+    let here = vec2<f32>(coords);
+    let seeds = textureLoad(input_tex, coords, 0);
+    let to_inside = seed_distance(seeds.xy, here);
+    let to_outside = seed_distance(seeds.zw, here);
+
+    var signed_distance: f32;
+    if (to_inside == 0.0) {
+        signed_distance = -(to_outside - 0.5);
+    } else {
+        signed_distance = to_inside - 0.5;
+    }
+    let value = clamp(0.5 - signed_distance / (2.0 * params.spread), 0.0, 1.0);
+    textureStore(atlas_tex, coords, vec4<f32>(value, value, value, 1.0));
+}
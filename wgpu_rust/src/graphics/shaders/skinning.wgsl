@@ -0,0 +1,64 @@
+// Linear blend skinning in a compute shader
+// Every invocation takes one vertex in rest pose and blends up to 4 bone matrices
+// weighted by how much each bone influences that vertex.
+// The output is written with the same layout as ModelVertex so the render pass
+// can bind it directly as the vertex buffer.
+
+// ModelVertex is 8 tightly packed floats (position xyz, uv, normal xyz)
+// vec3 in storage buffers has 16 byte alignment, so we read the raw floats instead of a struct
+const FLOATS_PER_VERTEX: u32 = 8u;
+const MAX_BONES: u32 = 128u;
+
+struct VertexSkin {
+    bone_indices: vec4<u32>,
+    bone_weights: vec4<f32>,
+}
+
+struct BoneMatrices {
+    matrices: array<mat4x4<f32>, MAX_BONES>,
+}
+
+@group(0) @binding(0)
+var<storage, read> rest_positions: array<f32>;
+@group(0) @binding(1)
+var<storage, read> vertex_skins: array<VertexSkin>;
+@group(0) @binding(2)
+var<uniform> bones: BoneMatrices;
+@group(0) @binding(3)
+var<storage, read_write> skinned_positions: array<f32>;
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if (index >= arrayLength(&vertex_skins)) {
+        return;
+    }
+
+    let base = index * FLOATS_PER_VERTEX;
+    let position = vec4<f32>(rest_positions[base], rest_positions[base + 1u], rest_positions[base + 2u], 1.0);
+    let normal = vec4<f32>(rest_positions[base + 5u], rest_positions[base + 6u], rest_positions[base + 7u], 0.0);
+
+    // Blend the bone matrices by weight, weights are expected to add up to 1
+    let skin = vertex_skins[index];
+    var skin_matrix = mat4x4<f32>(
+        vec4<f32>(0.0), vec4<f32>(0.0), vec4<f32>(0.0), vec4<f32>(0.0),
+    );
+    for (var i = 0u; i < 4u; i = i + 1u) {
+        let bone = min(skin.bone_indices[i], MAX_BONES - 1u);
+        skin_matrix = skin_matrix + bones.matrices[bone] * skin.bone_weights[i];
+    }
+
+    let skinned_position = skin_matrix * position;
+    // w = 0 on the normal so translation doesnt affect it
+    let skinned_normal = normalize((skin_matrix * normal).xyz);
+
+    skinned_positions[base] = skinned_position.x;
+    skinned_positions[base + 1u] = skinned_position.y;
+    skinned_positions[base + 2u] = skinned_position.z;
+    // Texture coordinates are not affected by skinning
+    skinned_positions[base + 3u] = rest_positions[base + 3u];
+    skinned_positions[base + 4u] = rest_positions[base + 4u];
+    skinned_positions[base + 5u] = skinned_normal.x;
+    skinned_positions[base + 6u] = skinned_normal.y;
+    skinned_positions[base + 7u] = skinned_normal.z;
+}
@@ -0,0 +1,40 @@
+// 2D sprite shader
+// Vertices come in window pixels, the orthographic projection moves them to clip space.
+// The texture color is multiplied by the tint, white keeps it as it is.
+
+struct ScreenUniform {
+    projection: mat4x4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> screen: ScreenUniform;
+
+@group(0) @binding(0)
+var t_sprite: texture_2d<f32>;
+@group(0) @binding(1)
+var s_sprite: sampler;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) tint: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) tint: vec4<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = screen.projection * vec4<f32>(in.position, 0.0, 1.0);
+    out.tex_coords = in.tex_coords;
+    out.tint = in.tint;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_sprite, s_sprite, in.tex_coords) * in.tint;
+}
@@ -0,0 +1,71 @@
+// One direction of the separable subsurface scattering blur
+// Pixels whose material scatters are blurred with their scattering neighbors, each color channel
+// is mixed in by its own amount so red bleeds further than blue. Everything else is copied.
+
+const KERNEL_TAPS: i32 = 8;
+
+struct BlurUniform {
+    direction: vec2<f32>, // One pixel step, horizontal or vertical
+    max_radius: f32, // Pixels, for strength 1
+    depth_falloff: f32,
+    weights: array<vec4<f32>, 2>, // KERNEL_TAPS gaussian weights, index 0 is the center
+}
+
+@group(0) @binding(0)
+var color_tex: texture_2d<f32>;
+@group(0) @binding(1)
+var sss_tex: texture_2d<f32>;
+@group(0) @binding(2)
+var color_sampler: sampler;
+@group(0) @binding(3)
+var<uniform> params: BlurUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+// Full screen triangle, same as the contact shadow pass
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+fn kernel_weight(tap: i32) -> f32 {
+    return params.weights[tap / 4][tap % 4];
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(color_tex));
+    let coords = vec2<i32>(in.clip_position.xy);
+    let center = textureLoad(color_tex, coords, 0);
+    let center_sss = textureLoad(sss_tex, coords, 0);
+    let amount = center_sss.rgb;
+    let strength = max(amount.r, max(amount.g, amount.b));
+    if (strength <= 0.0) {
+        return center;
+    }
+
+    // Stronger scattering spreads the same taps further apart
+    let step = params.direction * params.max_radius * strength / f32(KERNEL_TAPS - 1);
+    var sum = center.rgb * kernel_weight(0);
+    var total = kernel_weight(0);
+    for (var tap = 1; tap < KERNEL_TAPS; tap++) {
+        for (var side = -1; side <= 1; side += 2) {
+            let position = in.clip_position.xy + step * f32(tap * side);
+            let sample_sss = textureLoad(sss_tex, clamp(vec2<i32>(position), vec2<i32>(0), size - 1), 0);
+            // Light only travels inside scattering materials, and less across a jump in depth
+            let scatters = select(0.0, 1.0, any(sample_sss.rgb > vec3<f32>(0.0)));
+            let depth_weight = exp(-abs(sample_sss.a - center_sss.a) * params.depth_falloff);
+            let weight = kernel_weight(tap) * scatters * depth_weight;
+            // Level 0 explicitly, plain textureSample is not allowed after the early return
+            sum += textureSampleLevel(color_tex, color_sampler, position / vec2<f32>(size), 0.0).rgb * weight;
+            total += weight;
+        }
+    }
+
+    return vec4<f32>(mix(center.rgb, sum / total, amount), center.a);
+}
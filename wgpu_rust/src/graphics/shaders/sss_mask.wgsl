@@ -0,0 +1,59 @@
+// SSS channel for the subsurface scattering blur
+// rgb is how much each color channel of this material scatters, alpha the view depth
+// so the blur can tell surfaces at different distances apart
+
+struct SssCameraUniform {
+    view_proj: mat4x4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: SssCameraUniform;
+
+// Same scale as the scene shader, so the mask lines up with what was drawn
+struct TransformUniform {
+    scale: f32,
+}
+@group(1) @binding(0)
+var<uniform> transform: TransformUniform;
+
+struct SssMaterialUniform {
+    amount: vec4<f32>, // sss_color * sss_strength, w unused
+}
+@group(2) @binding(0)
+var<uniform> material: SssMaterialUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+}
+
+// Only the model matrix is needed, see InstanceRaw::desc
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // w of a perspective projection is the distance along the view direction
+    @location(0) view_depth: f32,
+}
+
+@vertex
+fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * model_matrix * vec4<f32>(model.position * transform.scale, 1.0);
+    out.view_depth = out.clip_position.w;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(material.amount.rgb, in.view_depth);
+}
@@ -0,0 +1,78 @@
+// Temporal anti-aliasing resolve
+// Every frame the projection is shifted by a different sub-pixel amount, so each frame samples
+// the edges at slightly different spots. Blending the new frame into the history of the earlier
+// ones averages those samples, like super sampling spread over time.
+
+struct TaaUniform {
+    blend_factor: f32, // How much of the current frame goes into the result
+    history_valid: u32, // 0 on the first frame and after a resize, there is nothing to blend with
+    _padding: vec2<u32>,
+}
+
+@group(0) @binding(0)
+var current_tex: texture_2d<f32>;
+@group(0) @binding(1)
+var history_tex: texture_2d<f32>;
+@group(0) @binding(2)
+var history_sampler: sampler;
+@group(0) @binding(3)
+var velocity_tex: texture_2d<f32>;
+@group(0) @binding(4)
+var<uniform> params: TaaUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+// Full screen triangle, same as the contact shadow pass
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+// Written twice, once to the screen and once to the texture that becomes the next history
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) history: vec4<f32>,
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let size = vec2<i32>(textureDimensions(current_tex));
+    let coords = vec2<i32>(in.clip_position.xy);
+    let current = textureLoad(current_tex, coords, 0);
+
+    // Neighborhood clamp: the history is only trusted inside the color range of the 3x3 pixels
+    // around this one. Anything outside belongs to a surface that is not here anymore (ghosting)
+    var neighborhood_min = current;
+    var neighborhood_max = current;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let neighbor = textureLoad(current_tex, clamp(coords + vec2<i32>(x, y), vec2<i32>(0), size - 1), 0);
+            neighborhood_min = min(neighborhood_min, neighbor);
+            neighborhood_max = max(neighborhood_max, neighbor);
+        }
+    }
+
+    // Velocity is in NDC / 2 with y up, pixels grow downwards so y is flipped
+    let velocity = textureLoad(velocity_tex, coords, 0).xy;
+    let uv = in.clip_position.xy / vec2<f32>(size);
+    let history_uv = uv - vec2<f32>(velocity.x, -velocity.y);
+
+    var out: FragmentOutput;
+    let off_screen = any(history_uv < vec2<f32>(0.0)) || any(history_uv > vec2<f32>(1.0));
+    if (params.history_valid == 0u || off_screen) {
+        // Nothing to reproject from, start the history over with this frame
+        out.color = current;
+    } else {
+        // Level 0 explicitly, plain textureSample is not allowed inside this branch
+        let history = textureSampleLevel(history_tex, history_sampler, history_uv, 0.0);
+        let clamped = clamp(history, neighborhood_min, neighborhood_max);
+        out.color = mix(clamped, current, params.blend_factor);
+    }
+    out.history = out.color;
+    return out;
+}
@@ -0,0 +1,41 @@
+// HUD text shader
+// Vertices come in window pixels, the orthographic projection moves them to clip space.
+// The atlas is white with the glyph coverage in alpha, so the vertex color tints it.
+
+struct ScreenUniform {
+    projection: mat4x4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> screen: ScreenUniform;
+
+@group(0) @binding(0)
+var t_atlas: texture_2d<f32>;
+@group(0) @binding(1)
+var s_atlas: sampler;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = screen.projection * vec4<f32>(in.position, 0.0, 1.0);
+    out.tex_coords = in.tex_coords;
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(t_atlas, s_atlas, in.tex_coords).a;
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
@@ -0,0 +1,37 @@
+// Vignette, darkens the frame towards the corners (see VignettePass in graphics/post_process.rs)
+
+struct VignetteUniform {
+    strength: f32, // How far from the center the darkening reaches, 0 would be no vignette
+    power: f32, // Higher keeps the center bright longer and falls off harder at the edge
+}
+
+@group(0) @binding(0)
+var color_tex: texture_2d<f32>;
+@group(0) @binding(1)
+var color_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> params: VignetteUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// Full screen triangle, same as the contact shadow pass
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    // Clip space y points up, texture v points down
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(color_tex, color_sampler, in.uv);
+    // Clamped so the corners of a strong vignette go black instead of negative
+    let factor = clamp(1.0 - pow(length(in.uv - 0.5) * params.strength, params.power), 0.0, 1.0);
+    return vec4<f32>(color.rgb * factor, color.a);
+}
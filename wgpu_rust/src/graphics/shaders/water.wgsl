@@ -0,0 +1,130 @@
+// Water surface (see graphics/water.rs)
+// The scene was drawn twice before this: mirrored above the water into the reflection target
+// and clipped below it into the refraction target. Here we only look both up, bend the lookups
+// with the animated normal map and blend them by how grazing the view angle is (Fresnel).
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    view_position: vec4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct WaterUniform {
+    reflection_view_proj: mat4x4<f32>, // Mirrored camera the reflection target was drawn with
+    color: vec4<f32>, // Tint of deep water, rgb
+    height: f32, // World y of the surface
+    half_size: f32, // The quad goes from -half_size to half_size on x and z
+    znear: f32, // Camera planes, to turn depth buffer values back into distances
+    zfar: f32,
+}
+
+// Shared clock and screen size, see graphics/globals.rs
+struct GlobalsUniform {
+    time: f32,
+    delta: f32,
+    resolution: vec2<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> water: WaterUniform;
+@group(1) @binding(1)
+var<uniform> globals: GlobalsUniform;
+@group(1) @binding(2)
+var reflection_tex: texture_2d<f32>;
+@group(1) @binding(3)
+var refraction_tex: texture_2d<f32>;
+@group(1) @binding(4)
+var refraction_depth: texture_depth_2d;
+@group(1) @binding(5)
+var target_sampler: sampler; // Clamped, for the two targets
+@group(1) @binding(6)
+var normal_map: texture_2d<f32>;
+@group(1) @binding(7)
+var normal_sampler: sampler; // Repeats, the waves tile
+
+// Normal map repeats every 1 / NORMAL_TILING world units
+const NORMAL_TILING: f32 = 0.25;
+// How far the waves push the reflection and refraction lookups, in uv units
+const DISTORTION: f32 = 0.02;
+// Reflectance looking straight down, about right for water
+const FRESNEL_R0: f32 = 0.02;
+// Water thinner than this turns to foam, where it touches the shore or an object
+const FOAM_DEPTH: f32 = 0.15;
+// Through this much water the bottom is hidden by the tint
+const MURK_DEPTH: f32 = 3.0;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+}
+
+// One quad from 6 vertex indices, no vertex buffer
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[index] * water.half_size;
+    let world_position = vec3<f32>(corner.x, water.height, corner.y);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(world_position, 1.0);
+    out.world_position = world_position;
+    return out;
+}
+
+// Clip space xy to texture uv, v points down
+fn clip_to_uv(clip: vec4<f32>) -> vec2<f32> {
+    let ndc = clip.xy / clip.w;
+    return vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+}
+
+// Depth buffer value (0 near, 1 far) back to the distance from the camera
+fn linear_depth(depth: f32) -> f32 {
+    return water.znear * water.zfar / (water.zfar - depth * (water.zfar - water.znear));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Two layers of the same normal map scrolling in different directions never line up,
+    // so the pattern doesnt look like it just slides
+    let uv = in.world_position.xz * NORMAL_TILING;
+    let time = globals.time;
+    let wave_a = textureSample(normal_map, normal_sampler, uv + vec2<f32>(0.03, 0.02) * time).xyz * 2.0 - 1.0;
+    let wave_b = textureSample(normal_map, normal_sampler, uv * 1.7 + vec2<f32>(-0.02, 0.035) * time).xyz * 2.0 - 1.0;
+    let tangent_normal = normalize(wave_a + wave_b);
+    // The map is in tangent space with z up, the surface is flat so tangent x, y are world x, z
+    let normal = normalize(vec3<f32>(tangent_normal.x, tangent_normal.z, tangent_normal.y));
+    let distortion = tangent_normal.xy * DISTORTION;
+
+    // Reflection: where the mirrored camera saw this point, refraction: this pixel of the frame
+    let reflection_uv = clip_to_uv(water.reflection_view_proj * vec4<f32>(in.world_position, 1.0));
+    let screen_uv = in.clip_position.xy / globals.resolution;
+    let reflection = textureSample(reflection_tex, target_sampler, clamp(reflection_uv + distortion, vec2<f32>(0.0), vec2<f32>(1.0))).rgb;
+    let refraction = textureSample(refraction_tex, target_sampler, clamp(screen_uv + distortion, vec2<f32>(0.0), vec2<f32>(1.0))).rgb;
+
+    // Water thickness along the view ray: what the refraction pass hit minus the surface
+    // Undistorted, foam should hug the objects and not wobble away from them
+    let depth_size = vec2<f32>(textureDimensions(refraction_depth));
+    let texel = vec2<i32>(clamp(screen_uv * depth_size, vec2<f32>(0.0), depth_size - 1.0));
+    let thickness = linear_depth(textureLoad(refraction_depth, texel, 0)) - linear_depth(in.clip_position.z);
+
+    // Deeper water hides more of the bottom
+    let murk = clamp(thickness / MURK_DEPTH, 0.0, 1.0) * 0.7;
+    let below = mix(refraction, water.color.rgb, murk);
+
+    // Schlick: almost all refraction looking down, almost all reflection at grazing angles
+    let view_dir = normalize(camera.view_position.xyz - in.world_position);
+    let cos_theta = max(dot(view_dir, normal), 0.0);
+    let fresnel = FRESNEL_R0 + (1.0 - FRESNEL_R0) * pow(1.0 - cos_theta, 5.0);
+    let color = mix(below, reflection, fresnel);
+
+    let foam = 1.0 - smoothstep(0.0, FOAM_DEPTH, thickness);
+    return vec4<f32>(mix(color, vec3<f32>(0.95), foam), 1.0);
+}
@@ -0,0 +1,215 @@
+use cgmath::SquareMatrix;
+use crate::graphics::{buffers, texture};
+
+// Contact shadows catch the small scale self shadowing (creases, objects touching the ground)
+// that a shadow map is too low resolution to show. It is a full screen pass that only needs
+// the depth buffer of the scene, so it runs after the main render pass and darkens the color
+// target with multiplicative blending (result = scene color * contact shadow factor).
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ContactShadowUniform {
+    view_proj: [[f32; 4]; 4],
+    inv_view_proj: [[f32; 4]; 4],
+    light_position: [f32; 3],
+    steps: u32,
+    distance: f32,
+    thickness: f32,
+    // There is no shadow map yet, so the main shadow factor stays at 1.0 (fully lit)
+    shadow_factor: f32,
+    strength: f32,
+}
+
+pub struct ContactShadowPass {
+    pub pipeline: wgpu::RenderPipeline,
+    pub uniform_buffer: wgpu::Buffer,
+    uniform: ContactShadowUniform,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+impl ContactShadowPass {
+    pub fn new(
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        depth_texture: &texture::Texture,
+    ) -> Self {
+        let identity: [[f32; 4]; 4] = cgmath::Matrix4::<f32>::identity().into();
+        let uniform = ContactShadowUniform {
+            view_proj: identity,
+            inv_view_proj: identity,
+            light_position: [0.0; 3],
+            steps: 16,
+            distance: 0.5,
+            thickness: 0.3,
+            shadow_factor: 1.0,
+            strength: 0.6,
+        };
+        let uniform_buffer = buffers::create_uniform_buffer(device, &uniform);
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Contact Shadow Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, depth_texture, &uniform_buffer);
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Contact Shadow Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Contact Shadow Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/contact_shadow.wgsl").into()),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Contact Shadow Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[], // Full screen triangle is generated from the vertex index
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    // Multiply blending: new color = shader output * color already on screen
+                    blend: Some(wgpu::BlendState {
+                        color: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::Dst,
+                            dst_factor: wgpu::BlendFactor::Zero,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                        // Leave alpha untouched
+                        alpha: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::Zero,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None, // We read the depth buffer as a texture, so it cant be attached
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            uniform_buffer,
+            uniform,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        depth_texture: &texture::Texture,
+        uniform_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Contact Shadow Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&depth_texture.texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    // Depth texture is recreated on resize so the bind group has to follow
+    pub fn resize(&mut self, device: &wgpu::Device, depth_texture: &texture::Texture) {
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, depth_texture, &self.uniform_buffer);
+    }
+
+    pub fn set_steps(&mut self, steps: u32) {
+        self.uniform.steps = steps;
+    }
+
+    pub fn set_distance(&mut self, distance: f32) {
+        self.uniform.distance = distance.max(0.0);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.uniform.steps > 0 && self.uniform.distance > 0.0
+    }
+
+    // Camera and light move every frame, upload the latest matrices and light position
+    pub fn update(&mut self, queue: &wgpu::Queue, view_proj: cgmath::Matrix4<f32>, light_position: [f32; 3]) {
+        self.uniform.view_proj = view_proj.into();
+        self.uniform.inv_view_proj = view_proj.invert().unwrap_or(cgmath::Matrix4::identity()).into();
+        self.uniform.light_position = light_position;
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
+
+    // Runs in its own render pass after the scene pass, loading the existing color
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        color_view: &wgpu::TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Contact Shadow Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load, // Keep the scene we just drew
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes,
+            multiview_mask: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
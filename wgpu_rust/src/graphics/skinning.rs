@@ -0,0 +1,214 @@
+use std::ops::Range;
+use cgmath::SquareMatrix;
+use wgpu::util::DeviceExt;
+use wgpu::BindGroup;
+use crate::model::{Material, Mesh, ModelVertex};
+
+// Skeletal animation skinning done in a compute pass
+// Each vertex is attached to up to 4 bones with a weight per bone. Every frame we upload the
+// bone matrices and the compute shader blends them per vertex (linear blend skinning).
+// The result lands in a buffer with the ModelVertex layout that is then used as the vertex buffer
+// so the render pipeline does not need to know anything about bones.
+
+pub const MAX_BONES: usize = 128; // Must match MAX_BONES in skinning.wgsl
+const WORKGROUP_SIZE: u32 = 64;
+
+// Per vertex bone influences, indices into the bone matrix array and their weights
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VertexSkin {
+    pub bone_indices: [u32; 4],
+    pub bone_weights: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct BoneMatricesUniform {
+    matrices: [[[f32; 4]; 4]; MAX_BONES],
+}
+
+pub struct SkinnedMesh {
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    bone_buffer: wgpu::Buffer,
+    vertex_count: u32,
+    // Written by the compute shader, read as vertex buffer by the render pass
+    pub skinned_positions: wgpu::Buffer,
+}
+
+impl SkinnedMesh {
+    pub fn new(device: &wgpu::Device, vertices: &[ModelVertex], skins: &[VertexSkin]) -> Self {
+        assert_eq!(vertices.len(), skins.len(), "Every vertex needs its bone influences");
+
+        let rest_positions = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Skinning Rest Positions"),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let skin_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Skinning Vertex Skins"),
+            contents: bytemuck::cast_slice(skins),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        // Start with identity bones so the mesh shows in rest pose before the first update
+        let identity: [[f32; 4]; 4] = cgmath::Matrix4::<f32>::identity().into();
+        let bones = BoneMatricesUniform { matrices: [identity; MAX_BONES] };
+        let bone_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Skinning Bone Matrices"),
+            contents: bytemuck::cast_slice(&[bones]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let skinned_positions = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Skinned Positions"),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
+        });
+
+        let storage_entry = |binding: u32, read_only: bool| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Skinning Bind Group Layout"),
+            entries: &[
+                storage_entry(0, true),
+                storage_entry(1, true),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage_entry(3, false),
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Skinning Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: rest_positions.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: skin_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: bone_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: skinned_positions.as_entire_binding() },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Skinning Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Skinning Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/skinning.wgsl").into()),
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Skinning Pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            bind_group,
+            bone_buffer,
+            vertex_count: vertices.len() as u32,
+            skinned_positions,
+        }
+    }
+
+    // Upload the bone matrices, bones past MAX_BONES are ignored and missing ones stay identity
+    pub fn write_bones(&self, queue: &wgpu::Queue, bone_matrices: &[cgmath::Matrix4<f32>]) {
+        let identity: [[f32; 4]; 4] = cgmath::Matrix4::<f32>::identity().into();
+        let mut bones = BoneMatricesUniform { matrices: [identity; MAX_BONES] };
+        for (slot, matrix) in bones.matrices.iter_mut().zip(bone_matrices) {
+            *slot = (*matrix).into();
+        }
+        queue.write_buffer(&self.bone_buffer, 0, bytemuck::cast_slice(&[bones]));
+    }
+
+    pub fn dispatch(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Skinning Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        compute_pass.dispatch_workgroups(self.vertex_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+}
+
+// Simple two bone rig along the Y axis, bone 0 at the bottom and bone 1 at the top
+// The weight of the top bone grows linearly with the vertex height so the middle bends smoothly
+pub fn vertical_two_bone_skin(vertices: &[ModelVertex]) -> Vec<VertexSkin> {
+    let (min_y, max_y) = vertices.iter().fold((f32::MAX, f32::MIN), |(min, max), v| {
+        (min.min(v.position[1]), max.max(v.position[1]))
+    });
+    let height = (max_y - min_y).max(f32::EPSILON);
+
+    vertices
+        .iter()
+        .map(|v| {
+            let top_weight = ((v.position[1] - min_y) / height).clamp(0.0, 1.0);
+            VertexSkin {
+                bone_indices: [0, 1, 0, 0],
+                bone_weights: [1.0 - top_weight, top_weight, 0.0, 0.0],
+            }
+        })
+        .collect()
+}
+
+// Same as DrawModel::draw_mesh_instanced but reading vertices from the skinned buffer
+pub trait DrawSkinned<'a> {
+    fn draw_skinned_mesh_instanced(
+        &mut self,
+        mesh: &'a Mesh,
+        skinned_mesh: &'a SkinnedMesh,
+        material: &'a Material,
+        instances: Range<u32>,
+        camera_bind_group: &'a BindGroup,
+        light_bind_group: &'a BindGroup,
+    );
+}
+
+impl<'a, 'b> DrawSkinned<'b> for wgpu::RenderPass<'a>
+where
+    'b: 'a,
+{
+    fn draw_skinned_mesh_instanced(
+        &mut self,
+        mesh: &'b Mesh,
+        skinned_mesh: &'b SkinnedMesh,
+        material: &'b Material,
+        instances: Range<u32>,
+        camera_bind_group: &'b BindGroup,
+        light_bind_group: &'b BindGroup,
+    ) {
+        self.set_vertex_buffer(0, skinned_mesh.skinned_positions.slice(..));
+        self.set_index_buffer(mesh.index_buffer.buffer.slice(..), mesh.index_buffer.format);
+        self.set_bind_group(0, &material.bind_group, &[]);
+        self.set_bind_group(1, camera_bind_group, &[]);
+        self.set_bind_group(4, light_bind_group, &[]);
+        self.draw_indexed(0..mesh.index_buffer.count, 0, instances);
+    }
+}
@@ -0,0 +1,151 @@
+use crate::graphics::buffers;
+use crate::graphics::camera::{Camera, CameraConfig, CameraUniform};
+
+// Split screen, the same scene seen by several cameras side by side
+// Unlike multiview there is no extra target: the scene pass draws the scene once per camera,
+// set_viewport squeezes each draw into its part of the frame and set_scissor_rect stops it from
+// touching the other parts. Between the draws only the camera bind group changes.
+//
+// The left view follows the desktop camera, the right one watches the scene from above
+
+// Where the overview camera sits, relative to what the desktop camera looks at
+const OVERVIEW_OFFSET: cgmath::Vector3<f32> = cgmath::Vector3::new(0.0, 6.0, 4.0);
+
+// Part of the frame a camera draws into, in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    pub fn aspect(&self) -> f32 {
+        self.width as f32 / self.height.max(1) as f32
+    }
+}
+
+pub struct SplitScreen {
+    pub cameras: Vec<Camera>,
+    // Uniform buffer and bind group for each camera, same order as cameras
+    camera_bindings: Vec<(wgpu::Buffer, wgpu::BindGroup)>,
+    viewports: Vec<Viewport>,
+}
+
+impl SplitScreen {
+    pub fn new(
+        device: &wgpu::Device,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let cameras = (0..2)
+            .map(|_| Camera::new(CameraConfig {
+                eye: (0.0, 1.0, 2.0).into(),
+                target: (0.0, 0.0, 0.0).into(),
+                up: cgmath::Vector3::unit_y(),
+                aspect: 1.0,
+                fovy: 45.0,
+                znear: 0.1,
+                zfar: 100.0,
+            }))
+            .collect::<Vec<_>>();
+        let camera_bindings = cameras.iter()
+            .map(|_| {
+                let buffer = buffers::create_uniform_buffer(device, &CameraUniform::new());
+                let bind_group = CameraUniform::create_bind_group(device, camera_bind_group_layout, &buffer);
+                (buffer, bind_group)
+            })
+            .collect();
+
+        let mut split_screen = Self {
+            viewports: split_viewports(width, height, cameras.len()),
+            cameras,
+            camera_bindings,
+        };
+        split_screen.fit_cameras();
+        split_screen
+    }
+
+    // Viewports are in pixels, so they have to be worked out again for the new size
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.viewports = split_viewports(width, height, self.cameras.len());
+        self.fit_cameras();
+    }
+
+    // Copy the desktop camera into the first view and aim the overview at the same target
+    pub fn follow_camera(&mut self, queue: &wgpu::Queue, camera: &Camera) {
+        let follower = &mut self.cameras[0];
+        follower.eye = camera.eye;
+        follower.target = camera.target;
+        follower.up = camera.up;
+        follower.fovy = camera.fovy;
+        follower.znear = camera.znear;
+        follower.zfar = camera.zfar;
+
+        let overview = &mut self.cameras[1];
+        overview.eye = camera.target + OVERVIEW_OFFSET;
+        overview.target = camera.target;
+        overview.zfar = camera.zfar;
+
+        for (camera, (buffer, _)) in self.cameras.iter().zip(&self.camera_bindings) {
+            let mut camera_uniform = CameraUniform::new();
+            camera_uniform.update_view_proj(camera);
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&[camera_uniform]));
+        }
+    }
+
+    // Each viewport with the bind group of the camera that draws into it
+    pub fn views(&self) -> impl Iterator<Item = (&Viewport, &wgpu::BindGroup)> {
+        self.viewports.iter().zip(self.camera_bindings.iter().map(|(_, bind_group)| bind_group))
+    }
+
+    // A camera with the full window aspect would look squashed in half the width
+    fn fit_cameras(&mut self) {
+        for (camera, viewport) in self.cameras.iter_mut().zip(&self.viewports) {
+            camera.aspect = viewport.aspect();
+        }
+    }
+}
+
+// Side by side columns over the full height, the last one takes the pixels that dont divide evenly
+pub fn split_viewports(width: u32, height: u32, count: usize) -> Vec<Viewport> {
+    let count = count.max(1) as u32;
+    let column = width / count;
+    (0..count)
+        .map(|i| Viewport {
+            x: i * column,
+            y: 0,
+            width: if i == count - 1 { width - i * column } else { column },
+            height,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_viewports_cover_the_frame() {
+        let viewports = split_viewports(801, 600, 2);
+        assert_eq!(viewports, vec![
+            Viewport { x: 0, y: 0, width: 400, height: 600 },
+            Viewport { x: 400, y: 0, width: 401, height: 600 },
+        ]);
+    }
+
+    #[test]
+    fn test_single_viewport_is_the_whole_frame() {
+        assert_eq!(split_viewports(800, 600, 1), vec![Viewport { x: 0, y: 0, width: 800, height: 600 }]);
+        // 0 cameras still gets something to draw into instead of dividing by zero
+        assert_eq!(split_viewports(800, 600, 0).len(), 1);
+    }
+
+    #[test]
+    fn test_half_width_viewport_aspect() {
+        let viewports = split_viewports(1600, 900, 2);
+        assert!((viewports[0].aspect() - 800.0 / 900.0).abs() < 1e-6);
+    }
+}
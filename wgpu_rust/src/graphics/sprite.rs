@@ -0,0 +1,345 @@
+use std::ops::Range;
+use crate::graphics::text::ScreenUniform;
+use crate::graphics::{buffers, texture};
+
+// 2D sprites drawn over the finished 3D frame
+// Every draw() call only records a textured rectangle. render() sorts them by texture so each
+// texture is bound once, writes all quads into one vertex buffer and issues one draw call per
+// texture. Sorting is stable, so sprites with the same texture keep the order they were drawn in,
+// but sprites of different textures are layered in texture order (the one registered first is below).
+
+const INITIAL_CAPACITY: u64 = 6 * 256; // Vertices, grows when a frame needs more
+
+// Rectangle in pixels, (x, y) is the top left corner
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self { x, y, width, height }
+    }
+}
+
+// Index of a texture registered with SpriteBatch::register_texture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SpriteTexture(usize);
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SpriteVertex {
+    position: [f32; 2], // Physical pixels, (0,0) is the top left corner of the window
+    tex_coords: [f32; 2],
+    tint: [f32; 4],
+}
+
+impl SpriteVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<SpriteVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SpriteCommand {
+    texture: SpriteTexture,
+    src: Rect, // Texture pixels
+    dst: Rect, // Window pixels
+    tint: [f32; 4],
+}
+
+pub struct SpriteBatch {
+    pipeline: wgpu::RenderPipeline,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    // Bind group and size in pixels of every registered texture, SpriteTexture indexes into it
+    textures: Vec<(wgpu::BindGroup, [f32; 2])>,
+    screen_buffer: wgpu::Buffer,
+    screen_bind_group: wgpu::BindGroup,
+    vertex_buffer: wgpu::Buffer,
+    vertex_capacity: u64,
+    commands: Vec<SpriteCommand>,
+}
+
+impl SpriteBatch {
+    pub fn new(
+        device: &wgpu::Device,
+        layouts: &texture::TextureLayoutCache,
+        color_format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let texture_bind_group_layout = layouts.texture_bind_group_layout.clone();
+
+        let screen_buffer = buffers::create_uniform_buffer(device, &ScreenUniform::new(width, height));
+        let screen_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Sprite Screen Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let screen_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sprite Screen Bind Group"),
+            layout: &screen_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: screen_buffer.as_entire_binding(),
+            }],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sprite Pipeline Layout"),
+            bind_group_layouts: &[&texture_bind_group_layout, &screen_bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Sprite Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/sprite.wgsl").into()),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sprite Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[SpriteVertex::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            // Quads always face the screen, no culling
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None, // Over the scene, like the HUD
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            texture_bind_group_layout,
+            textures: Vec::new(),
+            screen_buffer,
+            screen_bind_group,
+            vertex_buffer: Self::create_vertex_buffer(device, INITIAL_CAPACITY),
+            vertex_capacity: INITIAL_CAPACITY,
+            commands: Vec::new(),
+        }
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, capacity: u64) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Sprite Vertex Buffer"),
+            size: capacity * std::mem::size_of::<SpriteVertex>() as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    // The bind group keeps the texture alive, the Texture itself can be dropped afterwards
+    pub fn register_texture(&mut self, device: &wgpu::Device, texture: &texture::Texture) -> SpriteTexture {
+        let bind_group = texture::create_bind_group_from_texture(device, &self.texture_bind_group_layout, texture);
+        let size = [texture.texture.width() as f32, texture.texture.height() as f32];
+        self.textures.push((bind_group, size));
+        SpriteTexture(self.textures.len() - 1)
+    }
+
+    // Positions are in physical pixels, so the projection has to follow the window size
+    pub fn resize(&self, queue: &wgpu::Queue, width: u32, height: u32) {
+        queue.write_buffer(&self.screen_buffer, 0, bytemuck::cast_slice(&[ScreenUniform::new(width, height)]));
+    }
+
+    // src is the part of the texture in texture pixels, dst where it goes in window pixels
+    pub fn draw(&mut self, texture: SpriteTexture, src: Rect, dst: Rect, tint: [f32; 4]) {
+        self.commands.push(SpriteCommand { texture, src, dst, tint });
+    }
+
+    // Upload and draw everything recorded since the last call, on top of what is already in view
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) {
+        let sizes: Vec<[f32; 2]> = self.textures.iter().map(|(_, size)| *size).collect();
+        let (vertices, batches) = build_batches(&mut self.commands, &sizes);
+        self.commands.clear();
+        if vertices.is_empty() {
+            return;
+        }
+
+        let needed = vertices.len() as u64;
+        if needed > self.vertex_capacity {
+            self.vertex_capacity = needed.next_power_of_two();
+            self.vertex_buffer = Self::create_vertex_buffer(device, self.vertex_capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Sprite Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load, // Draw over the finished scene
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+            multiview_mask: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(1, &self.screen_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        // One flush per texture
+        for (texture, range) in batches {
+            render_pass.set_bind_group(0, &self.textures[texture.0].0, &[]);
+            render_pass.draw(range, 0..1);
+        }
+    }
+}
+
+// Sorts the commands by texture and turns them into quads, with the vertex range of every texture
+// Commands with a texture that was never registered are skipped
+fn build_batches(
+    commands: &mut [SpriteCommand],
+    texture_sizes: &[[f32; 2]],
+) -> (Vec<SpriteVertex>, Vec<(SpriteTexture, Range<u32>)>) {
+    // sort_by_key is stable, same texture sprites stay in draw order
+    commands.sort_by_key(|command| command.texture);
+
+    let mut vertices = Vec::with_capacity(commands.len() * 6);
+    let mut batches: Vec<(SpriteTexture, Range<u32>)> = Vec::new();
+    for command in commands.iter() {
+        let Some(&[texture_width, texture_height]) = texture_sizes.get(command.texture.0) else {
+            continue;
+        };
+        let start = vertices.len() as u32;
+
+        let (x0, y0) = (command.dst.x, command.dst.y);
+        let (x1, y1) = (x0 + command.dst.width, y0 + command.dst.height);
+        let (u0, v0) = (command.src.x / texture_width, command.src.y / texture_height);
+        let (u1, v1) = (
+            (command.src.x + command.src.width) / texture_width,
+            (command.src.y + command.src.height) / texture_height,
+        );
+        let vertex = |position: [f32; 2], tex_coords: [f32; 2]| SpriteVertex { position, tex_coords, tint: command.tint };
+        // Two triangles per sprite, same winding as the text quads
+        vertices.extend_from_slice(&[
+            vertex([x0, y0], [u0, v0]),
+            vertex([x0, y1], [u0, v1]),
+            vertex([x1, y1], [u1, v1]),
+            vertex([x0, y0], [u0, v0]),
+            vertex([x1, y1], [u1, v1]),
+            vertex([x1, y0], [u1, v0]),
+        ]);
+
+        let end = vertices.len() as u32;
+        match batches.last_mut() {
+            Some((texture, range)) if *texture == command.texture => range.end = end,
+            _ => batches.push((command.texture, start..end)),
+        }
+    }
+    (vertices, batches)
+}
+
+// Test pattern atlas: 2 x 2 cells of cell pixels each, red, green, blue and a black and white checker
+pub fn test_pattern_atlas(cell: u32) -> image::RgbaImage {
+    image::RgbaImage::from_fn(cell * 2, cell * 2, |x, y| {
+        match (x / cell, y / cell) {
+            (0, 0) => image::Rgba([255, 0, 0, 255]),
+            (1, 0) => image::Rgba([0, 255, 0, 255]),
+            (0, 1) => image::Rgba([0, 0, 255, 255]),
+            _ => {
+                // 4 x 4 checker squares inside the last cell
+                let square = (cell / 4).max(1);
+                let value = if (x / square + y / square).is_multiple_of(2) { 255 } else { 0 };
+                image::Rgba([value, value, value, 255])
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(texture: usize, x: f32) -> SpriteCommand {
+        SpriteCommand {
+            texture: SpriteTexture(texture),
+            src: Rect::new(0.0, 0.0, 8.0, 8.0),
+            dst: Rect::new(x, 0.0, 8.0, 8.0),
+            tint: [1.0; 4],
+        }
+    }
+
+    #[test]
+    fn test_interleaved_textures_flush_once_per_texture() {
+        let mut commands = vec![command(1, 0.0), command(0, 1.0), command(1, 2.0), command(2, 3.0), command(0, 4.0), command(1, 5.0)];
+        let (vertices, batches) = build_batches(&mut commands, &[[16.0, 16.0]; 3]);
+
+        assert_eq!(vertices.len(), 6 * 6);
+        let textures: Vec<usize> = batches.iter().map(|(texture, _)| texture.0).collect();
+        assert_eq!(textures, vec![0, 1, 2]);
+        assert_eq!(batches.iter().map(|(_, range)| range.len()).collect::<Vec<_>>(), vec![12, 18, 6]);
+
+        // Same texture sprites keep their draw order, texture 1 was drawn at x 0, 2, 5
+        let first_x: Vec<f32> = batches[1].1.clone().step_by(6).map(|i| vertices[i as usize].position[0]).collect();
+        assert_eq!(first_x, vec![0.0, 2.0, 5.0]);
+    }
+
+    #[test]
+    fn test_src_rect_to_uv() {
+        // Bottom right quarter of a 32 x 16 texture
+        let mut commands = vec![SpriteCommand {
+            texture: SpriteTexture(0),
+            src: Rect::new(16.0, 8.0, 16.0, 8.0),
+            dst: Rect::new(10.0, 20.0, 30.0, 40.0),
+            tint: [1.0; 4],
+        }];
+        let (vertices, batches) = build_batches(&mut commands, &[[32.0, 16.0]]);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(vertices[0].position, [10.0, 20.0]);
+        assert_eq!(vertices[0].tex_coords, [0.5, 0.5]);
+        assert_eq!(vertices[2].position, [40.0, 60.0]);
+        assert_eq!(vertices[2].tex_coords, [1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_unregistered_texture_is_skipped() {
+        let mut commands = vec![command(0, 0.0), command(5, 1.0)];
+        let (vertices, batches) = build_batches(&mut commands, &[[16.0, 16.0]]);
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(batches.len(), 1);
+    }
+}
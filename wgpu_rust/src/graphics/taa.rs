@@ -0,0 +1,352 @@
+use crate::graphics::{buffers, texture};
+
+// Temporal anti-aliasing
+// The projection is shifted by a different sub-pixel offset every frame (the jitter), so over a
+// few frames every pixel gets sampled at several spots inside it. The TAA pass blends the new
+// frame into the history of the earlier ones, the history is reprojected with the motion
+// vectors so moving things dont smear. While it is on the scene renders into scene_texture
+// and the resolve pass writes the result to the screen.
+
+// Halton points used for the jitter before the sequence repeats
+const JITTER_SAMPLES: u32 = 8;
+// 10% new frame, 90% history, lower is smoother but slower to react
+const BLEND_FACTOR: f32 = 0.1;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct TaaUniform {
+    blend_factor: f32,
+    history_valid: u32,
+    _padding: [u32; 2], // Uniforms need 16 byte alignment
+}
+
+pub struct TaaPass {
+    // Result of the previous frame, blended into the current one
+    pub history_texture: texture::Texture,
+    // Written by this frame, copied into history_texture once the pass is done
+    resolve_texture: texture::Texture,
+    // Scene target while TAA is on, the resolve reads it as the current frame
+    pub scene_texture: texture::Texture,
+    pub pipeline: wgpu::RenderPipeline,
+    // Sub-pixel offsets in pixels, between -0.5 and 0.5
+    pub jitter_sequence: Vec<[f32; 2]>,
+    pub frame_index: usize,
+    history_valid: bool,
+    uniform_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+impl TaaPass {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        velocity_texture: &texture::Texture,
+    ) -> Self {
+        let uniform = TaaUniform {
+            blend_factor: BLEND_FACTOR,
+            history_valid: 0,
+            _padding: [0; 2],
+        };
+        let uniform_buffer = buffers::create_uniform_buffer(device, &uniform);
+
+        // Linear, the reprojected position almost never lands on a pixel center
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("TAA History Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::MipmapFilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("TAA Bind Group Layout"),
+            entries: &[
+                texture_entry(0), // Current frame
+                texture_entry(1), // History
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                texture_entry(3), // Velocity
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("TAA Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("TAA Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/taa.wgsl").into()),
+        });
+
+        let target = Some(wgpu::ColorTargetState {
+            format: config.format,
+            blend: None, // The shader already did the blending with the history
+            write_mask: wgpu::ColorWrites::ALL,
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("TAA Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[], // Full screen triangle is generated from the vertex index
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                // Screen and resolve texture
+                targets: &[target.clone(), target],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let (history_texture, resolve_texture, scene_texture) = Self::create_targets(device, config);
+        let bind_group = Self::create_bind_group(
+            device,
+            &bind_group_layout,
+            &scene_texture,
+            &history_texture,
+            &sampler,
+            velocity_texture,
+            &uniform_buffer,
+        );
+
+        let jitter_sequence = (1..=JITTER_SAMPLES)
+            .map(|index| [halton(index, 2) - 0.5, halton(index, 3) - 0.5])
+            .collect();
+
+        Self {
+            history_texture,
+            resolve_texture,
+            scene_texture,
+            pipeline,
+            jitter_sequence,
+            frame_index: 0,
+            history_valid: false,
+            uniform_buffer,
+            sampler,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> (texture::Texture, texture::Texture, texture::Texture) {
+        (
+            texture::Texture::create_render_target(device, config, "TAA History Texture", config.format),
+            texture::Texture::create_render_target(device, config, "TAA Resolve Texture", config.format),
+            texture::Texture::create_render_target(device, config, "TAA Scene Texture", config.format),
+        )
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        scene_texture: &texture::Texture,
+        history_texture: &texture::Texture,
+        sampler: &wgpu::Sampler,
+        velocity_texture: &texture::Texture,
+        uniform_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("TAA Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&scene_texture.texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&history_texture.texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&velocity_texture.texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    // Every target is screen sized, the old history doesnt fit anymore so it starts over
+    // The velocity texture is recreated on resize too, pass the new one
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        velocity_texture: &texture::Texture,
+    ) {
+        (self.history_texture, self.resolve_texture, self.scene_texture) = Self::create_targets(device, config);
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.scene_texture,
+            &self.history_texture,
+            &self.sampler,
+            velocity_texture,
+            &self.uniform_buffer,
+        );
+        self.reset_history();
+    }
+
+    // Next frame shows only itself, for when the history has nothing to do with the new frame
+    pub fn reset_history(&mut self) {
+        self.history_valid = false;
+    }
+
+    // Clip space offset for this frame, multiply it in front of the view projection matrix
+    // Moves every vertex by less than one pixel, wraps around the jitter sequence
+    pub fn jitter_matrix(&self, width: u32, height: u32) -> cgmath::Matrix4<f32> {
+        let [x, y] = self.jitter_sequence[self.frame_index % self.jitter_sequence.len()];
+        // One pixel is 2 / size in NDC, the translation is scaled by w so it survives the divide
+        cgmath::Matrix4::from_translation(cgmath::Vector3::new(
+            x * 2.0 / width.max(1) as f32,
+            y * 2.0 / height.max(1) as f32,
+            0.0,
+        ))
+    }
+
+    // Blend scene_texture with the history into output_view, then keep the result as the next history
+    pub fn render(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        output_view: &wgpu::TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        let uniform = TaaUniform {
+            blend_factor: BLEND_FACTOR,
+            history_valid: self.history_valid as u32,
+            _padding: [0; 2],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("TAA Pass"),
+                color_attachments: &[
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: output_view,
+                        resolve_target: None,
+                        depth_slice: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), // Every pixel is overwritten
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: &self.resolve_texture.texture_view,
+                        resolve_target: None,
+                        depth_slice: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                ],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes,
+                multiview_mask: None,
+            });
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &self.bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        // History cant be read and written in the same pass, so the result goes through resolve_texture
+        encoder.copy_texture_to_texture(
+            self.resolve_texture.texture.as_image_copy(),
+            self.history_texture.texture.as_image_copy(),
+            self.history_texture.texture.size(),
+        );
+
+        self.history_valid = true;
+        self.frame_index += 1;
+    }
+}
+
+// Halton low discrepancy sequence, spreads the points evenly over 0..1 without clumping
+// Radical inverse of index in the given base, 1 in base 2 is 0.5, 2 is 0.25, 3 is 0.75...
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_halton_sequence() {
+        assert_eq!(halton(1, 2), 0.5);
+        assert_eq!(halton(2, 2), 0.25);
+        assert_eq!(halton(3, 2), 0.75);
+        assert!((halton(1, 3) - 1.0 / 3.0).abs() < 1e-6);
+        assert!((halton(2, 3) - 2.0 / 3.0).abs() < 1e-6);
+        assert!((halton(3, 3) - 1.0 / 9.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_jitter_stays_inside_the_pixel() {
+        for index in 1..=JITTER_SAMPLES {
+            for base in [2, 3] {
+                let offset = halton(index, base) - 0.5;
+                assert!((-0.5..0.5).contains(&offset), "{offset}");
+            }
+        }
+    }
+}
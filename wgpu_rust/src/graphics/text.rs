@@ -0,0 +1,384 @@
+use crate::graphics::camera::OPENGL_TO_WGPU_MATRIX;
+use crate::graphics::{buffers, texture};
+
+// Minimal text renderer for the HUD
+// Glyphs come from a baked bitmap font atlas (res/font_atlas.png): printable ASCII 32..126 in a
+// 16 x 6 grid of fixed size cells, white with the coverage stored in alpha. Cell 127 (DEL, never
+// printed) holds a box outline that we draw for any character the atlas doesnt have.
+// Every draw_text call only appends quads to a CPU list, the whole batch is uploaded into one
+// dynamic vertex buffer and drawn in a single overlay pass after the 3D scene.
+
+// The SDF renderer (graphics/sdf.rs) builds its distance field from the same atlas and layout
+pub(crate) const ATLAS_BYTES: &[u8] = include_bytes!("../../res/font_atlas.png");
+const ATLAS_COLUMNS: u32 = 16;
+const ATLAS_ROWS: u32 = 6;
+pub const CELL_WIDTH: f32 = 16.0;
+pub const CELL_HEIGHT: f32 = 32.0;
+const FIRST_GLYPH: u32 = 32; // Space
+const MISSING_GLYPH: u32 = 127; // Box outline
+const INITIAL_CAPACITY: u64 = 6 * 256; // Vertices, grows when a frame needs more
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TextVertex {
+    position: [f32; 2], // Physical pixels, (0,0) is the top left corner of the window
+    tex_coords: [f32; 2],
+    color: [f32; 4],
+}
+
+impl TextVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<TextVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// Also used by the sprite batch, both draw in window pixels
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct ScreenUniform {
+    // Orthographic projection from pixels to clip space
+    projection: [[f32; 4]; 4],
+}
+
+impl ScreenUniform {
+    pub(crate) fn new(width: u32, height: u32) -> Self {
+        // Y grows downwards like window coordinates, so top is 0 and bottom is height
+        let projection = cgmath::ortho(0.0, width.max(1) as f32, height.max(1) as f32, 0.0, -1.0, 1.0);
+        Self {
+            projection: (OPENGL_TO_WGPU_MATRIX * projection).into(),
+        }
+    }
+}
+
+pub struct TextRenderer {
+    pipeline: wgpu::RenderPipeline,
+    atlas: texture::TextureBundle,
+    screen_buffer: wgpu::Buffer,
+    screen_bind_group: wgpu::BindGroup,
+    vertex_buffer: wgpu::Buffer,
+    vertex_capacity: u64,
+    vertices: Vec<TextVertex>,
+}
+
+impl TextRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layouts: &texture::TextureLayoutCache,
+        color_format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> anyhow::Result<Self> {
+        let mut atlas = texture::Texture::from_bytes(device, queue, ATLAS_BYTES, "Font Atlas")?;
+        // Text is usually drawn smaller than the baked size, linear filtering keeps it smooth
+        atlas.sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let atlas = texture::TextureBundle::new(device, layouts, &atlas);
+
+        let screen_buffer = buffers::create_uniform_buffer(device, &ScreenUniform::new(width, height));
+        let screen_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Text Screen Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let screen_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Text Screen Bind Group"),
+            layout: &screen_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: screen_buffer.as_entire_binding(),
+            }],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Text Pipeline Layout"),
+            bind_group_layouts: &[&atlas.bind_group_layout, &screen_bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Text Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/text.wgsl").into()),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Text Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[TextVertex::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    // Glyph edges are partially transparent, blend them over the scene
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            // Quads are always facing the screen, no need for culling
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None, // HUD is always on top of the scene
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let vertex_buffer = Self::create_vertex_buffer(device, INITIAL_CAPACITY);
+
+        Ok(Self {
+            pipeline,
+            atlas,
+            screen_buffer,
+            screen_bind_group,
+            vertex_buffer,
+            vertex_capacity: INITIAL_CAPACITY,
+            vertices: Vec::new(),
+        })
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, capacity: u64) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Text Vertex Buffer"),
+            size: capacity * std::mem::size_of::<TextVertex>() as u64,
+            // COPY_DST so we can rewrite it every frame with queue.write_buffer
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    // Positions are in physical pixels, so the projection has to follow the window size
+    pub fn resize(&self, queue: &wgpu::Queue, width: u32, height: u32) {
+        queue.write_buffer(&self.screen_buffer, 0, bytemuck::cast_slice(&[ScreenUniform::new(width, height)]));
+    }
+
+    // Add the quads for a string to this frame's batch
+    // x, y is the top left corner of the first line, px is the line height in pixels
+    pub fn queue_text(&mut self, text: &str, x: f32, y: f32, px: f32, color: wgpu::Color) {
+        layout_text(&mut self.vertices, text, x, y, px, color);
+    }
+
+    // Upload and draw everything queued since the last call, on top of what is already in view
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) {
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        let needed = self.vertices.len() as u64;
+        if needed > self.vertex_capacity {
+            self.vertex_capacity = needed.next_power_of_two();
+            self.vertex_buffer = Self::create_vertex_buffer(device, self.vertex_capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Text Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load, // Draw over the finished scene
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+                multiview_mask: None,
+            });
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &self.atlas.bind_group, &[]);
+            render_pass.set_bind_group(1, &self.screen_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..needed * std::mem::size_of::<TextVertex>() as u64));
+            render_pass.draw(0..needed as u32, 0..1);
+        }
+
+        self.vertices.clear();
+    }
+}
+
+// Appends two triangles per glyph, no index buffer since nothing is shared between glyphs
+pub(crate) fn layout_text(vertices: &mut Vec<TextVertex>, text: &str, x: f32, y: f32, px: f32, color: wgpu::Color) {
+    let scale = px / CELL_HEIGHT;
+    let (glyph_width, glyph_height) = (CELL_WIDTH * scale, CELL_HEIGHT * scale);
+    let color = [color.r as f32, color.g as f32, color.b as f32, color.a as f32];
+
+    let (mut cursor_x, mut cursor_y) = (x, y);
+    for character in text.chars() {
+        match character {
+            '\n' => {
+                cursor_x = x;
+                cursor_y += glyph_height;
+                continue;
+            }
+            ' ' => {
+                cursor_x += glyph_width;
+                continue;
+            }
+            _ => {}
+        }
+
+        let [u0, v0, u1, v1] = glyph_uv(character);
+        let (x0, y0, x1, y1) = (cursor_x, cursor_y, cursor_x + glyph_width, cursor_y + glyph_height);
+        let vertex = |position: [f32; 2], tex_coords: [f32; 2]| TextVertex { position, tex_coords, color };
+        vertices.extend_from_slice(&[
+            vertex([x0, y0], [u0, v0]),
+            vertex([x0, y1], [u0, v1]),
+            vertex([x1, y1], [u1, v1]),
+            vertex([x0, y0], [u0, v0]),
+            vertex([x1, y1], [u1, v1]),
+            vertex([x1, y0], [u1, v0]),
+        ]);
+        cursor_x += glyph_width;
+    }
+}
+
+// Atlas cell of a character as [u_min, v_min, u_max, v_max], missing glyphs use the box cell
+fn glyph_uv(character: char) -> [f32; 4] {
+    let code = character as u32;
+    let cell = if (FIRST_GLYPH..MISSING_GLYPH).contains(&code) { code } else { MISSING_GLYPH } - FIRST_GLYPH;
+    let (column, row) = (cell % ATLAS_COLUMNS, cell / ATLAS_COLUMNS);
+    let (cell_u, cell_v) = (1.0 / ATLAS_COLUMNS as f32, 1.0 / ATLAS_ROWS as f32);
+    [
+        column as f32 * cell_u,
+        row as f32 * cell_v,
+        (column + 1) as f32 * cell_u,
+        (row + 1) as f32 * cell_v,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::pipeline_cache::LayoutCache;
+
+    const SIZE: u32 = 64;
+
+    #[test]
+    fn test_missing_glyph_uses_box_cell() {
+        assert_eq!(glyph_uv('é'), glyph_uv('\u{7f}'));
+        assert_ne!(glyph_uv('A'), glyph_uv('\u{7f}'));
+    }
+
+    // Render "A" into an offscreen texture and read it back
+    // Skips when the machine has no adapter at all (no GPU and no software fallback)
+    #[test]
+    fn test_renders_a_into_expected_region() {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let Ok(adapter) = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())) else {
+            eprintln!("No adapter available, skipping text render test");
+            return;
+        };
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).unwrap();
+
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Text Test Target"),
+            size: wgpu::Extent3d { width: SIZE, height: SIZE, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let layouts = texture::TextureLayoutCache::new(&device, &mut LayoutCache::new());
+        let mut text = TextRenderer::new(&device, &queue, &layouts, format, SIZE, SIZE).unwrap();
+        // 32px line height means one atlas cell per pixel, glyph covers x 8..24, y 8..40
+        text.queue_text("A", 8.0, 8.0, 32.0, wgpu::Color::WHITE);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        // Clear to black first, the text pass loads whatever is in the target
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Clear"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+            multiview_mask: None,
+        });
+        text.render(&device, &queue, &mut encoder, &view);
+
+        // 64 pixels * 4 bytes is already a multiple of 256, no row padding needed
+        let bytes_per_row = SIZE * 4;
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Text Test Readback"),
+            size: (bytes_per_row * SIZE) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            target.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &readback,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(SIZE),
+                },
+            },
+            wgpu::Extent3d { width: SIZE, height: SIZE, depth_or_array_layers: 1 },
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        readback.slice(..).map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+        let pixels = readback.slice(..).get_mapped_range().to_vec();
+
+        let lit = |x: u32, y: u32| pixels[((y * SIZE + x) * 4) as usize] > 0;
+        let inside = (8..24).flat_map(|x| (8..40).map(move |y| (x, y))).filter(|&(x, y)| lit(x, y)).count();
+        let outside = (0..SIZE).flat_map(|x| (0..SIZE).map(move |y| (x, y)))
+            .filter(|&(x, y)| !(8..24).contains(&x) || !(8..40).contains(&y))
+            .filter(|&(x, y)| lit(x, y))
+            .count();
+
+        assert!(inside > 20, "expected the glyph to cover some pixels, got {inside}");
+        assert_eq!(outside, 0);
+    }
+}
@@ -1,5 +1,6 @@
 use image::GenericImageView;
 use anyhow::Result;
+use crate::graphics::pipeline_cache::{LayoutCache, LayoutKind};
 
 pub struct Texture {
     pub texture: wgpu::Texture,
@@ -27,6 +28,8 @@ impl Texture {
     ) -> Result<Self> {
         let rgba = img.to_rgba8();
         let dimensions = img.dimensions();
+        // Full chain down to 1x1, see mip_level_count
+        let mip_level_count = mip_level_count(dimensions.0, dimensions.1);
 
         // Define size of the texture
         let size = wgpu::Extent3d {
@@ -39,7 +42,7 @@ impl Texture {
             &wgpu::TextureDescriptor {
                 label,
                 size,
-                mip_level_count: 1,
+                mip_level_count,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
                 format: wgpu::TextureFormat::Rgba8UnormSrgb,
@@ -48,27 +51,42 @@ impl Texture {
             }
         );
 
-        // Actual command to move diffuse_rgba bytes from RAM to GPU memory over PCIe bus
-        // We use a queue because we cannot send commands directly to GPU, when GPU is ready
-        // it will process commands in the queue
-        queue.write_texture(
-            // Tells wgpu where to copy the pixel data
-            wgpu::TexelCopyTextureInfo{
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            // Actual pixel data
-            &rgba,
-            // Layout of texture
-            wgpu::TexelCopyBufferLayout{
-                offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
-            },
-            size,
-        );
+        // Mipmaps are smaller copies of the texture, each half the size of the one before
+        // A far away surface samples a level with about one texel per pixel instead of skipping over
+        // most texels of the full image, which is what makes minified textures shimmer
+        // Built on the CPU with the image crate, every level from the full image so the blur
+        // doesnt add up level after level. Done in sRGB space like the texels are stored, not
+        // exactly right for averaging but the difference is hard to see
+        for mip_level in 0..mip_level_count {
+            let (width, height) = mip_size(dimensions.0, dimensions.1, mip_level);
+            let level = if mip_level == 0 {
+                rgba.clone()
+            } else {
+                image::imageops::resize(&rgba, width, height, image::imageops::FilterType::Triangle)
+            };
+
+            // Actual command to move diffuse_rgba bytes from RAM to GPU memory over PCIe bus
+            // We use a queue because we cannot send commands directly to GPU, when GPU is ready
+            // it will process commands in the queue
+            queue.write_texture(
+                // Tells wgpu where to copy the pixel data
+                wgpu::TexelCopyTextureInfo{
+                    texture: &texture,
+                    mip_level,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                // Actual pixel data
+                &level,
+                // Layout of texture
+                wgpu::TexelCopyBufferLayout{
+                    offset: 0,
+                    bytes_per_row: Some(4 * width),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            );
+        }
 
         // If the Texture is the raw film, the TextureView is the lens focusing on a specific part of that film
         // and the sampler as the projector settings that defines how it looks on screen
@@ -76,19 +94,27 @@ impl Texture {
         // into that texture, allowing us to see and use specific parts or aspects of the texture
         // Sampler stores instructions on how to read texture data (filtering, wrapping, etc)
         let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // Trilinear: linear inside a level and linear between the two closest levels
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge, // what to do when uv coords are outside 0.0-1.0
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
             mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::MipmapFilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::MipmapFilterMode::Linear,
             ..Default::default()
         });
 
         Ok(Self { texture, texture_view, sampler })
     }
 
+    // 1x1 texture of one color, white for geometry that only has vertex colors (see vertex.rs)
+    #[allow(dead_code)]
+    pub fn solid_color(device: &wgpu::Device, queue: &wgpu::Queue, rgba: [u8; 4], label: &str) -> Result<Self> {
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba(rgba)));
+        Self::from_image(device, queue, &img, Some(label))
+    }
+
     // Creating a depth texture for depth testing in 3D rendering
     // Depth format needed for creating depth stage of the render pipeline
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
@@ -102,6 +128,16 @@ impl Texture {
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
         label: &str
+    ) -> Self {
+        Self::create_depth_texture_with_format(device, config, label, Self::DEPTH_FORMAT)
+    }
+
+    // Same as create_depth_texture for other depth formats, like depth + stencil
+    pub fn create_depth_texture_with_format(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        label: &str,
+        format: wgpu::TextureFormat,
     ) -> Self {
         // Depth texture needs to be same size as the screen to map 1:1 with pixels
         let size = wgpu::Extent3d {
@@ -115,7 +151,7 @@ impl Texture {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: Self::DEPTH_FORMAT,
+            format,
             // We need to render to it and sample from it in shaders
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
@@ -141,15 +177,123 @@ impl Texture {
 
         Self { texture, texture_view, sampler }
     }
+
+    // Screen sized color texture that a pass renders into and a later pass samples or copies
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        label: &str,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width: config.width.max(1),
+            height: config.height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            // Copies too, so one target can be saved into another (TAA history)
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // Read back 1:1 with the screen pixels, no filtering between them
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::MipmapFilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self { texture, texture_view, sampler }
+    }
+
+    // Bytes of every mip level and layer, what the driver has to keep around at least
+    // Formats without a single copy size (depth24plus, combined depth stencil) count 4 bytes a pixel
+    pub fn memory_size(&self) -> u64 {
+        let size = self.texture.size();
+        let format = self.texture.format();
+        let (block_width, block_height) = format.block_dimensions();
+        let block_bytes = format.block_copy_size(None).unwrap_or(4) as u64;
+        (0..self.texture.mip_level_count())
+            .map(|level| {
+                let (width, height) = mip_size(size.width, size.height, level);
+                let blocks = width.div_ceil(block_width) as u64 * height.div_ceil(block_height) as u64;
+                blocks * block_bytes * size.depth_or_array_layers as u64
+            })
+            .sum()
+    }
 }
 
+// Color and depth pair an extra scene pass renders into, later passes sample both
+// config gives the size and color format, it doesnt have to match the surface size
+pub struct OffscreenTarget {
+    pub color: Texture,
+    pub depth: Texture,
+}
+
+impl OffscreenTarget {
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, label: &str) -> Self {
+        Self {
+            color: Texture::create_render_target(device, config, &format!("{} Color", label), config.format),
+            depth: Texture::create_depth_texture(device, config, &format!("{} Depth", label)),
+        }
+    }
+}
 
 
+
+// Cached container for a loaded texture, the layout is a cheap handle clone of the cached one
+// so every bundle created from the same cache shares a single GPU layout object
 pub struct TextureBundle {
     pub bind_group_layout: wgpu::BindGroupLayout,
     pub bind_group: wgpu::BindGroup,
 }
 
+impl TextureBundle {
+    // Bind group holds its own reference to the texture view and sampler, so the texture
+    // stays alive on the GPU even after the Texture struct is dropped
+    pub fn new(device: &wgpu::Device, layouts: &TextureLayoutCache, texture: &Texture) -> Self {
+        Self {
+            bind_group_layout: layouts.texture_bind_group_layout.clone(),
+            bind_group: create_bind_group_from_texture(device, &layouts.texture_bind_group_layout, texture),
+        }
+    }
+}
+
+// Layouts never change after creation, so we build them once in State::new and reuse them
+// for every texture load instead of asking the device for a new layout each time
+// They come from the LayoutCache, these are handle clones of the same layouts
+pub struct TextureLayoutCache {
+    // Texture and sampler, for the HUD text, sprites and the egui overlay
+    pub texture_bind_group_layout: wgpu::BindGroupLayout,
+    pub depth_bind_group_layout: wgpu::BindGroupLayout,
+    // Texture plus the MaterialUniform, group 0 of the scene shader
+    pub material_bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl TextureLayoutCache {
+    pub fn new(device: &wgpu::Device, layouts: &mut LayoutCache) -> Self {
+        Self {
+            texture_bind_group_layout: (*layouts.get(device, LayoutKind::Texture)).clone(),
+            depth_bind_group_layout: (*layouts.get(device, LayoutKind::Depth)).clone(),
+            material_bind_group_layout: (*layouts.get(device, LayoutKind::Material)).clone(),
+        }
+    }
+}
+
 
 // Bind group layout defines the interface/contract: what types of resources (texture, sampler, etc.)
 // the shader expects at which binding slots. This allows the GPU driver to optimize memory layout
@@ -179,6 +323,42 @@ pub fn create_texture_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGrou
     })
 }
 
+// The texture layout with a third entry, the MaterialUniform of model.rs (base color, metallic,
+// roughness). Model materials use it, the sprites, text and egui keep the plain texture layout.
+pub fn create_material_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+        label: Some("Material Bind Group Layout"),
+    })
+}
+
 pub fn create_depth_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
     device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         entries: &[
@@ -224,13 +404,79 @@ pub fn create_bind_group_from_texture(
     })
 }
 
-// Kept for convenience, loads a texture from raw bytes and creates a bind group
-pub fn load_texture_from_bytes(
+// Texture and parameter buffer of a material, for the material layout
+// Changing the parameters later is a buffer write, the bind group stays the same
+pub fn create_material_bind_group(
     device: &wgpu::Device,
-    queue: &wgpu::Queue,
     bind_group_layout: &wgpu::BindGroupLayout,
-    bytes: &[u8],
-) -> Result<wgpu::BindGroup> {
-    let texture = Texture::from_bytes(device, queue, bytes, "load_texture")?;
-    Ok(create_bind_group_from_texture(device, bind_group_layout, &texture))
-}
\ No newline at end of file
+    texture: &Texture,
+    params_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&texture.texture_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: params_buffer.as_entire_binding(),
+            },
+        ],
+        label: Some("Material Bind Group"),
+    })
+}
+// Levels down to 1x1 along the longer side, 256x64 has 9 (256, 128, ..., 1)
+// Sizes that arent powers of two round down at every level, 5x3 goes 5x3, 2x1, 1x1
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+// Size of a level, never below 1 on either side
+pub fn mip_size(width: u32, height: u32, level: u32) -> (u32, u32) {
+    ((width >> level).max(1), (height >> level).max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::headless::HeadlessContext;
+
+    #[test]
+    fn test_mip_level_count() {
+        assert_eq!(mip_level_count(1, 1), 1);
+        assert_eq!(mip_level_count(256, 256), 9);
+        assert_eq!(mip_level_count(256, 64), 9);
+        assert_eq!(mip_level_count(5, 3), 3);
+        assert_eq!(mip_level_count(1000, 1), 10);
+    }
+
+    #[test]
+    fn test_mip_sizes_of_non_power_of_two() {
+        let sizes = (0..mip_level_count(5, 3)).map(|level| mip_size(5, 3, level)).collect::<Vec<_>>();
+        assert_eq!(sizes, vec![(5, 3), (2, 1), (1, 1)]);
+        // Last level is always 1x1
+        let levels = mip_level_count(1000, 7);
+        assert_eq!(mip_size(1000, 7, levels - 1), (1, 1));
+    }
+
+    #[test]
+    fn test_from_image_uploads_every_level() {
+        let Ok(context) = pollster::block_on(HeadlessContext::new()) else {
+            eprintln!("No adapter available, skipping mipmap test");
+            return;
+        };
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(100, 30, image::Rgba([255, 0, 0, 255])));
+        let texture = Texture::from_image(&context.device, &context.queue, &img, Some("Mip Test")).unwrap();
+        assert_eq!(texture.texture.mip_level_count(), 7);
+        // 100x30, 50x15, 25x7, 12x3, 6x1, 3x1, 1x1 at 4 bytes a pixel
+        assert_eq!(texture.memory_size(), (3000 + 750 + 175 + 36 + 6 + 3 + 1) * 4);
+        // Validation errors (a level with the wrong size) would show up here
+        context.device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+    }
+}
@@ -0,0 +1,57 @@
+// Model transform applied to every vertex before the instance matrix
+// For now only a uniform scale factor, so we can grow/shrink the shape without touching the
+// vertex or instance buffers. The vertex shader multiplies the local position by it.
+
+pub const MIN_SCALE: f32 = 0.1;
+pub const MAX_SCALE: f32 = 5.0;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TransformUniform {
+    pub scale: f32,
+    pub _padding: [f32; 3], // Uniforms need 16 byte alignment
+}
+
+impl TransformUniform {
+    pub fn new(scale: f32) -> Self {
+        Self {
+            scale: scale.clamp(MIN_SCALE, MAX_SCALE),
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Transform Bind Group Layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX, // Only the vertex shader moves vertices
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }
+        ],
+    })
+}
+
+pub fn create_bind_group(
+    device: &wgpu::Device,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    transform_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: transform_buffer.as_entire_binding(),
+            }
+        ],
+        label: Some("Transform Bind Group"),
+    })
+}
@@ -0,0 +1,133 @@
+use std::sync::Arc;
+use cgmath::{MetricSpace, Point3};
+use crate::graphics::instance::Instance;
+use crate::graphics::{buffers, texture};
+use crate::model;
+
+// Transparent objects
+// Opaque geometry can be drawn in any order, the depth buffer sorts it out. Blended geometry
+// cant: the color of a see through pixel depends on what is already behind it, so it has to be
+// drawn after everything opaque, farthest first (painter's algorithm). It also doesnt write
+// depth, or a near transparent surface would hide a farther one drawn after it.
+//
+// We sort whole objects by the distance from the camera to their center, every frame. That is
+// right as long as objects dont intersect, intersecting ones need per triangle sorting or an
+// order independent technique, which we dont do.
+//
+// TransparentQuads is a small test scene: two overlapping half transparent quads one behind
+// the other. From the front the red one covers the blue one, fly around them (T to show them)
+// and from behind it is the other way around.
+
+// Centers of the two test quads, floating above the instance grid
+const QUAD_POSITIONS: [[f32; 3]; 2] = [[0.0, 3.0, -1.0], [0.6, 3.5, -2.0]];
+const QUAD_COLORS: [[u8; 4]; 2] = [[230, 40, 40, 128], [40, 90, 230, 128]];
+const QUAD_HALF_SIZE: f32 = 1.0;
+
+// Indices into positions, farthest from eye first
+pub fn back_to_front(eye: Point3<f32>, positions: &[Point3<f32>]) -> Vec<usize> {
+    let mut order = (0..positions.len()).collect::<Vec<_>>();
+    // total_cmp so a NaN position cant make the sort panic
+    order.sort_by(|&a, &b| eye.distance2(positions[b]).total_cmp(&eye.distance2(positions[a])));
+    order
+}
+
+pub struct TransparentQuads {
+    // One quad mesh, material i is the color of quad i
+    pub model: model::Model,
+    // Transform of every quad, drawn one instance at a time so they can be sorted
+    pub instance_buffer: wgpu::Buffer,
+    pub positions: Vec<Point3<f32>>,
+}
+
+impl TransparentQuads {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        material_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> anyhow::Result<Self> {
+        let s = QUAD_HALF_SIZE;
+        // Facing +z, the transparent pipeline doesnt cull so both sides show
+        let vertices = [
+            ([-s, -s], [0.0, 1.0]),
+            ([s, -s], [1.0, 1.0]),
+            ([s, s], [1.0, 0.0]),
+            ([-s, s], [0.0, 0.0]),
+        ]
+        .map(|([x, y], tex_coords)| model::ModelVertex {
+            position: [x, y, 0.0],
+            tex_coords,
+            normal: [0.0, 0.0, 1.0],
+        })
+        .to_vec();
+        let indices = vec![0, 1, 2, 0, 2, 3];
+
+        let mesh = model::Mesh {
+            name: "Transparent Quad".to_string(),
+            vertex_buffer: buffers::create_model_vertex_buffer(device, &vertices),
+            index_buffer: buffers::IndexBuffer::from_u32_compact(device, &indices),
+            material: 0,
+            bounds: model::vertex_bounds(&vertices),
+            vertices,
+            indices,
+        };
+
+        // Solid color textures, the alpha in them is what makes the quads see through
+        let materials = QUAD_COLORS.iter()
+            .map(|&color| {
+                let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba(color)));
+                let diffuse_texture = Arc::new(texture::Texture::from_image(device, queue, &img, Some("Transparent Quad Texture"))?);
+                Ok(model::Material {
+                    transparent: true,
+                    ..model::Material::new(
+                        device,
+                        material_bind_group_layout,
+                        "Transparent Quad",
+                        diffuse_texture,
+                        model::MaterialUniform::default(),
+                    )
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let positions = QUAD_POSITIONS.map(Point3::from).to_vec();
+        let instance_data = positions.iter()
+            .map(|position| Instance {
+                position: cgmath::Vector3::new(position.x, position.y, position.z),
+                rotation: cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+                color: [1.0; 4], // The textures already have the color
+            }.to_raw())
+            .collect::<Vec<_>>();
+
+        Ok(Self {
+            model: model::Model { meshes: vec![mesh], materials },
+            instance_buffer: buffers::create_instance_buffer(device, instance_data),
+            positions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quads_swap_order_from_the_other_side() {
+        let quads = QUAD_POSITIONS.map(Point3::from);
+
+        // In front of them (+z), the far blue quad goes first and red is blended over it
+        assert_eq!(back_to_front(Point3::new(0.0, 3.0, 5.0), &quads), vec![1, 0]);
+        // Behind them the red one is farther, so the order flips
+        assert_eq!(back_to_front(Point3::new(0.0, 3.0, -8.0), &quads), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_back_to_front_sorts_by_distance() {
+        let positions = [
+            Point3::new(0.0, 0.0, -2.0),
+            Point3::new(0.0, 0.0, -10.0),
+            Point3::new(0.0, 0.0, -5.0),
+        ];
+        assert_eq!(back_to_front(Point3::new(0.0, 0.0, 0.0), &positions), vec![1, 2, 0]);
+        assert!(back_to_front(Point3::new(0.0, 0.0, 0.0), &[]).is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+// Per frame uniform uploads (camera, light, globals, fog) through a staging belt
+// queue.write_buffer makes its own staging copy for every call. The belt keeps a few mapped
+// staging buffers around instead, each write takes the next free bytes of one of them and
+// records a copy into an encoder. After the frame is submitted recall maps them again for reuse.
+//
+// The writes happen in State::update, before the frame encoder exists, so the writer has its own
+// encoder for the copies. take_commands finishes it (and closes the belt), render submits it
+// ahead of the frame encoder so the copies land before any pass reads the buffers, then recall.
+//
+// With staging off every write goes straight to queue.write_buffer, --bench-write-buffer compares the two
+
+// Bigger than all uniforms of a frame together, so one chunk is enough most frames
+const CHUNK_SIZE: wgpu::BufferAddress = 4096;
+
+pub struct UniformWriter {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    belt: wgpu::util::StagingBelt,
+    // Copies recorded since the last take_commands, None when nothing was written
+    encoder: Option<wgpu::CommandEncoder>,
+    staging: bool,
+    // Since the last take_bytes_written, either way of writing counts
+    bytes_written: u64,
+}
+
+impl UniformWriter {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self {
+            device: device.clone(),
+            queue: queue.clone(),
+            belt: wgpu::util::StagingBelt::new(device.clone(), CHUNK_SIZE),
+            encoder: None,
+            staging: true,
+            bytes_written: 0,
+        }
+    }
+
+    pub fn is_staging(&self) -> bool {
+        self.staging
+    }
+
+    // Switching sends whatever is still waiting in the belt first, so no write is lost
+    pub fn set_staging(&mut self, staging: bool) {
+        self.flush();
+        self.staging = staging;
+    }
+
+    pub fn write<T: bytemuck::Pod>(&mut self, buffer: &wgpu::Buffer, value: &T) {
+        let bytes = bytemuck::bytes_of(value);
+        self.bytes_written += bytes.len() as u64;
+        // Belt copies have to be a multiple of 4 bytes, the uniforms are padded to 16 anyway
+        let size = wgpu::BufferSize::new(bytes.len() as u64)
+            .filter(|size| size.get().is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT));
+        let Some(size) = size.filter(|_| self.staging) else {
+            self.queue.write_buffer(buffer, 0, bytes);
+            return;
+        };
+        let encoder = self.encoder.get_or_insert_with(|| {
+            self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Uniform Upload Encoder"),
+            })
+        });
+        // Copies run in the order they were recorded, the last write to a buffer wins
+        self.belt.write_buffer(encoder, buffer, 0, size).copy_from_slice(bytes);
+    }
+
+    // For the render stats, the count starts over after this
+    pub fn take_bytes_written(&mut self) -> u64 {
+        std::mem::take(&mut self.bytes_written)
+    }
+
+    // The copies recorded so far, to be submitted before anything that reads the buffers
+    // Call recall after that submit
+    pub fn take_commands(&mut self) -> Option<wgpu::CommandBuffer> {
+        let encoder = self.encoder.take()?;
+        self.belt.finish();
+        Some(encoder.finish())
+    }
+
+    // Staging buffers of submitted copies go back to the belt once the GPU is done with them
+    pub fn recall(&mut self) {
+        self.belt.recall();
+    }
+
+    // Submit the waiting copies on their own, for work submitted outside of a frame (probe bakes)
+    // and frames that were never rendered (hidden window)
+    pub fn flush(&mut self) {
+        if let Some(commands) = self.take_commands() {
+            self.queue.submit(std::iter::once(commands));
+            self.recall();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::headless::HeadlessContext;
+
+    fn create_buffer(device: &wgpu::Device, size: u64) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Uniform Writer Test Buffer"),
+            size,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn read_buffer(context: &HeadlessContext, buffer: &wgpu::Buffer) -> Vec<u32> {
+        let readback = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Uniform Writer Test Readback"),
+            size: buffer.size(),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.copy_buffer_to_buffer(buffer, 0, &readback, 0, buffer.size());
+        context.queue.submit(std::iter::once(encoder.finish()));
+        readback.slice(..).map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        context.device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+        let data = readback.slice(..).get_mapped_range();
+        bytemuck::cast_slice(&data).to_vec()
+    }
+
+    // Several writes in one frame, to the same buffer and to others, submitted together like render does
+    fn write_one_frame(context: &HeadlessContext, staging: bool) {
+        let mut writer = UniformWriter::new(&context.device, &context.queue);
+        writer.set_staging(staging);
+        let camera = create_buffer(&context.device, 16);
+        let light = create_buffer(&context.device, 32);
+
+        writer.write(&camera, &[1u32, 2, 3, 4]);
+        writer.write(&light, &[5u32, 6, 7, 8, 9, 10, 11, 12]);
+        writer.write(&camera, &[13u32, 14, 15, 16]);
+        let commands = writer.take_commands();
+        assert_eq!(commands.is_some(), staging);
+        context.queue.submit(commands);
+        writer.recall();
+
+        assert_eq!(read_buffer(context, &camera), vec![13, 14, 15, 16]);
+        assert_eq!(read_buffer(context, &light), vec![5, 6, 7, 8, 9, 10, 11, 12]);
+        assert_eq!(writer.take_bytes_written(), 16 + 32 + 16);
+
+        // The next frame reuses the recalled staging buffers
+        writer.write(&camera, &[17u32, 18, 19, 20]);
+        writer.flush();
+        assert_eq!(read_buffer(context, &camera), vec![17, 18, 19, 20]);
+    }
+
+    #[test]
+    fn test_writes_of_one_frame_land_in_their_buffers() {
+        let Ok(context) = pollster::block_on(HeadlessContext::new()) else {
+            eprintln!("No adapter available, skipping uniform writer test");
+            return;
+        };
+        write_one_frame(&context, true);
+        write_one_frame(&context, false);
+    }
+}
@@ -16,16 +16,46 @@
 pub struct Vertex {
     position: [f32; 3], // Fixed size array for position (x, y, z)
     tex_coords: [f32; 2],
+    color: [f32; 3], // Multiplied with the texture, white keeps the texture as it is
+}
+// All fields are f32, so there is no padding between them: 3 + 2 + 3 floats = 32 bytes
+// Pod would refuse to compile if there was any
+
+// Vertex color that leaves the texture as it is, texel * 1.0
+pub const WHITE: [f32; 3] = [1.0, 1.0, 1.0];
+
+// Shapes without a texture still go through the same shader, they sample a 1x1 white texture
+// (Texture::solid_color) so only the vertex colors are left
+#[allow(dead_code)] // Only the tests draw with these, like the shapes below
+impl Vertex {
+    // Textured only, the output is exactly the texture
+    pub const fn textured(position: [f32; 3], tex_coords: [f32; 2]) -> Self {
+        Self { position, tex_coords, color: WHITE }
+    }
+
+    // Color only, meant for the white texture
+    pub const fn colored(position: [f32; 3], color: [f32; 3]) -> Self {
+        Self { position, tex_coords: [0.0, 0.0], color }
+    }
+}
+
+impl Vertex {
+    // For the scene shader, which has no vertex colors. The shapes are flat in the xy plane,
+    // so the normal points along +z towards the viewer
+    pub fn to_model_vertex(self) -> crate::model::ModelVertex {
+        crate::model::ModelVertex { position: self.position, tex_coords: self.tex_coords, normal: [0.0, 0.0, 1.0] }
+    }
 }
 
 
 // Changing the Y text cords doing 1-y flips the texture vertically
+// The pentagon and the complex shape are in the model registry next to the loaded cube, see State::new
 pub const PENT_VERTICES: &[Vertex] = &[
-    Vertex { position: [-0.0868241, 0.49240386, 0.0], tex_coords: [0.4131759, 0.00759614], }, // A
-    Vertex { position: [-0.49513406, 0.06958647, 0.0], tex_coords: [0.0048659444, 0.43031354], }, // B
-    Vertex { position: [-0.21918549, -0.44939706, 0.0], tex_coords: [0.28081453, 0.949397], }, // C
-    Vertex { position: [0.35966998, -0.3473291, 0.0], tex_coords: [0.85967, 0.84732914], }, // D
-    Vertex { position: [0.44147372, 0.2347359, 0.0], tex_coords: [0.9414737, 0.2652641], }, // E
+    Vertex { position: [-0.0868241, 0.49240386, 0.0], tex_coords: [0.4131759, 0.00759614], color: [1.0, 0.0, 0.0], }, // A
+    Vertex { position: [-0.49513406, 0.06958647, 0.0], tex_coords: [0.0048659444, 0.43031354], color: [1.0, 1.0, 0.0], }, // B
+    Vertex { position: [-0.21918549, -0.44939706, 0.0], tex_coords: [0.28081453, 0.949397], color: [0.0, 1.0, 0.0], }, // C
+    Vertex { position: [0.35966998, -0.3473291, 0.0], tex_coords: [0.85967, 0.84732914], color: [0.0, 0.0, 1.0], }, // D
+    Vertex { position: [0.44147372, 0.2347359, 0.0], tex_coords: [0.9414737, 0.2652641], color: [1.0, 0.0, 1.0], }, // E
 ];
 
 // Indices define how vertices are connected to form triangles
@@ -38,13 +68,13 @@ pub const PENT_INDICES: &[u16] = &[
 ];
 
 pub const COMPLEX_SHAPE_VERTICES: &[Vertex] = &[
-    Vertex { position: [-0.5, -0.5, 0.0], tex_coords: [0.0, 0.0], }, // Bottom-left
-    Vertex { position: [0.0, -0.5, 0.0], tex_coords: [0.5, 0.0], },  // Bottom-center
-    Vertex { position: [0.5, -0.5, 0.0], tex_coords: [1.0, 0.0], },  // Bottom-right
-    Vertex { position: [-0.5, 0.0, 0.0], tex_coords: [0.0, 0.5], },  // Middle-left
-    Vertex { position: [0.0, 0.5, 0.0], tex_coords: [0.5, 1.0], },   // Top-center peak
-    Vertex { position: [0.5, 0.0, 0.0], tex_coords: [1.0, 0.5], },   // Middle-right
-    Vertex { position: [0.75, -0.25, 0.0], tex_coords: [1.25, 0.25], }, // Small tip at far right
+    Vertex { position: [-0.5, -0.5, 0.0], tex_coords: [0.0, 0.0], color: [1.0, 0.0, 0.0], }, // Bottom-left
+    Vertex { position: [0.0, -0.5, 0.0], tex_coords: [0.5, 0.0], color: [1.0, 0.5, 0.0], },  // Bottom-center
+    Vertex { position: [0.5, -0.5, 0.0], tex_coords: [1.0, 0.0], color: [1.0, 1.0, 0.0], },  // Bottom-right
+    Vertex { position: [-0.5, 0.0, 0.0], tex_coords: [0.0, 0.5], color: [0.0, 1.0, 0.0], },  // Middle-left
+    Vertex { position: [0.0, 0.5, 0.0], tex_coords: [0.5, 1.0], color: [1.0, 1.0, 1.0], },   // Top-center peak
+    Vertex { position: [0.5, 0.0, 0.0], tex_coords: [1.0, 0.5], color: [0.0, 0.0, 1.0], },   // Middle-right
+    Vertex { position: [0.75, -0.25, 0.0], tex_coords: [1.25, 0.25], color: [0.5, 0.0, 1.0], }, // Small tip at far right
 ];
 
 pub const COMPLEX_SHAPE_INDICES: &[u16] = &[
@@ -55,11 +85,24 @@ pub const COMPLEX_SHAPE_INDICES: &[u16] = &[
     2, 6, 5, // Fifth triangle (Small tip at the far right)
 ];
 
+// Red top, green bottom left, blue bottom right, counter clockwise so it survives back face culling
+// The colors blend across the triangle, the center is a grey mix of all three
+#[allow(dead_code)]
+pub const COLORED_TRIANGLE_VERTICES: &[Vertex] = &[
+    Vertex::colored([0.0, 0.5, 0.0], [1.0, 0.0, 0.0]),
+    Vertex::colored([-0.5, -0.5, 0.0], [0.0, 1.0, 0.0]),
+    Vertex::colored([0.5, -0.5, 0.0], [0.0, 0.0, 1.0]),
+];
+
+#[allow(dead_code)]
+pub const COLORED_TRIANGLE_INDICES: &[u16] = &[0, 1, 2];
+
 // Since we convert all vertex data into a single byte array, we need to specify
 // how the GPU should interpret that byte array back into our Vertex struct
 // Like how long is the position array, where does color start, etc
 // This is done using a VertexBufferLayout
 impl Vertex {
+    #[allow(dead_code)] // Reference only, see vertex_color.wgsl for the shader that matches it
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
             array_stride: size_of::<Vertex>() as wgpu::BufferAddress, // Size of one vertex in bytes
@@ -83,7 +126,171 @@ impl Vertex {
                     shader_location: 1,
                     format: wgpu::VertexFormat::Float32x2, // 2 floats for tex_coords
                 },
+                wgpu::VertexAttribute {
+                    // After position and tex_coords, 5 floats in
+                    offset: (size_of::<[f32; 3]>() + size_of::<[f32; 2]>()) as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x3, // 3 floats for color
+                },
             ]
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::buffers;
+    use crate::graphics::camera::CameraUniform;
+    use crate::graphics::headless::HeadlessContext;
+    use crate::graphics::pipeline_cache::{LayoutCache, LayoutKind};
+    use crate::graphics::{pipeline, texture};
+    use wgpu::util::DeviceExt;
+
+    const SIZE: u32 = 64;
+
+    // Draws with vertex_color.wgsl over black, the identity camera keeps the positions in clip space
+    fn render(context: &HeadlessContext, vertices: &[Vertex], indices: &[u16], texture: &texture::Texture) -> Vec<u8> {
+        let device = &context.device;
+        let texture_layout = LayoutCache::new().get(device, LayoutKind::Texture);
+        let camera_layout = CameraUniform::create_bind_group_layout(device);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Vertex Color Layout"),
+            bind_group_layouts: &[&texture_layout, &camera_layout],
+            immediate_size: 0,
+        });
+        let render_pipeline = pipeline::create_render_pipeline(
+            device,
+            &layout,
+            HeadlessContext::FORMAT,
+            None,
+            &[Vertex::desc()],
+            wgpu::include_wgsl!("shaders/vertex_color.wgsl"),
+            wgpu::BlendState::REPLACE,
+        );
+
+        let texture_bind_group = texture::create_bind_group_from_texture(device, &texture_layout, texture);
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Camera Buffer"),
+            contents: bytemuck::cast_slice(&[CameraUniform::new()]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let camera_bind_group = CameraUniform::create_bind_group(device, &camera_layout, &camera_buffer);
+        let vertex_buffer = buffers::create_vertex_buffer(device, vertices);
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Index Buffer"),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        context.render_to_image(SIZE, SIZE, wgpu::Color::BLACK, |render_pass| {
+            render_pass.set_pipeline(&render_pipeline);
+            render_pass.set_bind_group(0, &texture_bind_group, &[]);
+            render_pass.set_bind_group(1, &camera_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            render_pass.draw_indexed(0..indices.len() as u32, 0, 0..1);
+        })
+    }
+
+    fn pixel(image: &[u8], x: u32, y: u32) -> [u8; 4] {
+        let index = ((y * SIZE + x) * 4) as usize;
+        image[index..index + 4].try_into().unwrap()
+    }
+
+    // What an sRGB texel ends up as in the Rgba8Unorm target, the sampler hands back linear values
+    fn srgb_to_linear(value: u8) -> u8 {
+        let c = value as f32 / 255.0;
+        let linear = if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) };
+        (linear * 255.0).round() as u8
+    }
+
+    #[test]
+    fn test_layout_matches_struct() {
+        let layout = Vertex::desc();
+        assert_eq!(size_of::<Vertex>(), 32);
+        assert_eq!(layout.array_stride, size_of::<Vertex>() as wgpu::BufferAddress);
+
+        // Every attribute starts right where the previous one ends, same order as the fields
+        let vertex = PENT_VERTICES[0];
+        let base = &vertex as *const Vertex as usize;
+        let field_offsets = [
+            &vertex.position as *const _ as usize - base,
+            &vertex.tex_coords as *const _ as usize - base,
+            &vertex.color as *const _ as usize - base,
+        ];
+        for (location, (attribute, offset)) in layout.attributes.iter().zip(field_offsets).enumerate() {
+            assert_eq!(attribute.shader_location, location as u32);
+            assert_eq!(attribute.offset, offset as wgpu::BufferAddress);
+        }
+        let last = layout.attributes.last().unwrap();
+        assert_eq!(last.offset + last.format.size(), layout.array_stride);
+    }
+
+    #[test]
+    fn test_shader_inputs_match_layout() {
+        // The vertex shader reads every attribute at the location desc() gives it
+        let module = wgpu::naga::front::wgsl::parse_str(include_str!("shaders/vertex_color.wgsl")).unwrap();
+        let vs_main = module.entry_points.iter().find(|entry| entry.name == "vs_main").unwrap();
+        let wgpu::naga::TypeInner::Struct { members, .. } = &module.types[vs_main.function.arguments[0].ty].inner else {
+            panic!("vs_main takes a vertex struct");
+        };
+        let locations: Vec<u32> = members.iter()
+            .filter_map(|member| match member.binding {
+                Some(wgpu::naga::Binding::Location { location, .. }) => Some(location),
+                _ => None,
+            })
+            .collect();
+        let attributes: Vec<u32> = Vertex::desc().attributes.iter().map(|attribute| attribute.shader_location).collect();
+        assert_eq!(locations, attributes);
+    }
+
+    #[test]
+    fn test_colored_triangle_renders_vertex_colors() {
+        let Ok(context) = pollster::block_on(HeadlessContext::new()) else {
+            eprintln!("No adapter available, skipping vertex color test");
+            return;
+        };
+        let white = texture::Texture::solid_color(&context.device, &context.queue, [255; 4], "white").unwrap();
+        let image = render(&context, COLORED_TRIANGLE_VERTICES, COLORED_TRIANGLE_INDICES, &white);
+
+        // Close to each corner its own color is by far the strongest, row 0 is the top
+        let [r, g, b, _] = pixel(&image, SIZE / 2, SIZE / 4 + 2);
+        assert!(r > 200 && g < 60 && b < 60, "top should be red, got {:?}", [r, g, b]);
+        let [r, g, b, _] = pixel(&image, SIZE / 4 + 2, SIZE * 3 / 4 - 2);
+        assert!(g > 200 && r < 60 && b < 60, "bottom left should be green, got {:?}", [r, g, b]);
+        let [r, g, b, _] = pixel(&image, SIZE * 3 / 4 - 2, SIZE * 3 / 4 - 2);
+        assert!(b > 200 && r < 60 && g < 60, "bottom right should be blue, got {:?}", [r, g, b]);
+        // Outside the triangle only the clear color
+        assert_eq!(pixel(&image, 1, 1), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_white_vertex_color_keeps_texture() {
+        let Ok(context) = pollster::block_on(HeadlessContext::new()) else {
+            eprintln!("No adapter available, skipping vertex color test");
+            return;
+        };
+        let texel = [200, 100, 50, 255];
+        let texture = texture::Texture::solid_color(&context.device, &context.queue, texel, "solid").unwrap();
+        // Quad over the whole target
+        let vertices = [
+            Vertex::textured([-1.0, -1.0, 0.0], [0.0, 1.0]),
+            Vertex::textured([1.0, -1.0, 0.0], [1.0, 1.0]),
+            Vertex::textured([1.0, 1.0, 0.0], [1.0, 0.0]),
+            Vertex::textured([-1.0, 1.0, 0.0], [0.0, 0.0]),
+        ];
+        let image = render(&context, &vertices, &[0, 1, 2, 0, 2, 3], &texture);
+
+        let expected = [srgb_to_linear(texel[0]), srgb_to_linear(texel[1]), srgb_to_linear(texel[2]), 255];
+        for (x, y) in [(0, 0), (SIZE / 2, SIZE / 2), (SIZE - 1, SIZE - 1)] {
+            let actual = pixel(&image, x, y);
+            for channel in 0..4 {
+                assert!(
+                    actual[channel].abs_diff(expected[channel]) <= 1,
+                    "pixel ({}, {}) is {:?}, texture gives {:?}", x, y, actual, expected
+                );
+            }
+        }
+    }
+}
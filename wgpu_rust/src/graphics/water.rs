@@ -0,0 +1,504 @@
+use cgmath::{Matrix4, Point3};
+use crate::graphics::camera::{Camera, CameraConfig, CameraUniform};
+use crate::graphics::clip::{self, ClipPlane, ClipUniform};
+use crate::graphics::texture::{self, OffscreenTarget};
+use crate::graphics::{buffers, pipeline};
+
+// Planar water with reflection and refraction
+// Before the frame the scene is drawn twice into offscreen targets:
+// - Reflection: from a camera mirrored below the surface, clipped to what is above the water.
+//   A mirror image is just the scene seen from the mirrored eye, so no mirroring of the geometry
+//   (and no flipped winding) is needed. The water shader projects each of its points with the
+//   mirrored camera to find where that point's reflection ended up in the target.
+// - Refraction: from the normal camera, clipped to what is below the water.
+// Both passes reuse the scene shader, they only get their own clip plane bind group.
+// The surface then blends the two with a Fresnel factor, bends both lookups with a scrolling
+// normal map and turns thin water (the refraction depth is close to the surface) into foam.
+
+// World y of the surface, the instances sit at y = 0 so they stick out of it
+pub const WATER_HEIGHT: f32 = -0.4;
+// The quad covers -HALF_SIZE..HALF_SIZE on x and z, more than the default instance grid
+const HALF_SIZE: f32 = 20.0;
+const WATER_COLOR: [f32; 4] = [0.04, 0.18, 0.22, 1.0];
+// The clip planes reach a bit past the surface so no gap shows where objects cross it
+const CLIP_OFFSET: f32 = 0.05;
+// The waves blur both targets anyway, half the window resolution is enough
+const TARGET_DIVISOR: u32 = 2;
+const NORMAL_MAP_SIZE: u32 = 128;
+// Waves in the normal map: whole number of cycles across the tile (x, y), height and phase
+// Whole numbers make the map repeat without a seam
+const WAVES: [(f32, f32, f32, f32); 4] = [
+    (1.0, 2.0, 0.030, 0.0),
+    (3.0, -1.0, 0.015, 1.3),
+    (-2.0, 5.0, 0.008, 2.1),
+    (7.0, 4.0, 0.004, 0.4),
+];
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct WaterUniform {
+    reflection_view_proj: [[f32; 4]; 4],
+    color: [f32; 4],
+    height: f32,
+    half_size: f32,
+    znear: f32,
+    zfar: f32,
+}
+
+pub struct WaterPlane {
+    pub reflection_target: OffscreenTarget,
+    pub refraction_target: OffscreenTarget,
+    pub normal_map: texture::Texture,
+    pub pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    // Cheap handle to State's globals buffer, the bind group is rebuilt with it on resize
+    globals_buffer: wgpu::Buffer,
+    target_sampler: wgpu::Sampler,
+    // Camera uniform of the mirrored camera, the refraction pass uses the normal one
+    reflection_camera_buffer: wgpu::Buffer,
+    reflection_camera_bind_group: wgpu::BindGroup,
+    // Keep above the surface for the reflection, below it for the refraction
+    reflection_clip_bind_group: wgpu::BindGroup,
+    refraction_clip_bind_group: wgpu::BindGroup,
+}
+
+impl WaterPlane {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        config: &wgpu::SurfaceConfiguration,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        clip_bind_group_layout: &wgpu::BindGroupLayout,
+        globals_buffer: &wgpu::Buffer,
+    ) -> Self {
+        let target_config = target_config(config);
+        let reflection_target = OffscreenTarget::new(device, &target_config, "Water Reflection");
+        let refraction_target = OffscreenTarget::new(device, &target_config, "Water Refraction");
+        let normal_map = create_normal_map(device, queue);
+        let target_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Water Target Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let uniform_buffer = buffers::create_uniform_buffer(device, &WaterUniform {
+            reflection_view_proj: [[0.0; 4]; 4],
+            color: WATER_COLOR,
+            height: WATER_HEIGHT,
+            half_size: HALF_SIZE,
+            znear: 0.1,
+            zfar: 100.0,
+        });
+
+        let reflection_camera_buffer = buffers::create_uniform_buffer(device, &CameraUniform::new());
+        let reflection_camera_bind_group = CameraUniform::create_bind_group(device, camera_bind_group_layout, &reflection_camera_buffer);
+
+        let clip_bind_group = |plane: ClipPlane| {
+            let buffer = buffers::create_uniform_buffer(device, &ClipUniform::new(Some(plane)));
+            clip::create_bind_group(device, clip_bind_group_layout, &buffer)
+        };
+        let reflection_clip_bind_group = clip_bind_group(ClipPlane {
+            normal: [0.0, 1.0, 0.0],
+            distance: WATER_HEIGHT - CLIP_OFFSET,
+        });
+        let refraction_clip_bind_group = clip_bind_group(ClipPlane {
+            normal: [0.0, -1.0, 0.0],
+            distance: -(WATER_HEIGHT + CLIP_OFFSET),
+        });
+
+        let texture_entry = |binding: u32, sample_type: wgpu::TextureSampleType| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type,
+            },
+            count: None,
+        };
+        let uniform_entry = |binding: u32, visibility: wgpu::ShaderStages| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let sampler_entry = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        };
+        let color = wgpu::TextureSampleType::Float { filterable: true };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Water Bind Group Layout"),
+            entries: &[
+                uniform_entry(0, wgpu::ShaderStages::VERTEX_FRAGMENT),
+                uniform_entry(1, wgpu::ShaderStages::FRAGMENT),
+                texture_entry(2, color),
+                texture_entry(3, color),
+                texture_entry(4, wgpu::TextureSampleType::Depth),
+                sampler_entry(5),
+                texture_entry(6, color),
+                sampler_entry(7),
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Water Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &bind_group_layout],
+            immediate_size: 0,
+        });
+        // No culling, the surface can be seen from below too
+        let pipeline = pipeline::create_render_pipeline_with_culling(
+            device,
+            &layout,
+            config.format,
+            Some(texture::Texture::DEPTH_FORMAT),
+            &[],
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Water Shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("shaders/water.wgsl").into()),
+            },
+            wgpu::BlendState::REPLACE,
+            wgpu::FrontFace::Ccw,
+            None,
+        );
+
+        let bind_group = create_bind_group(
+            device,
+            &bind_group_layout,
+            &uniform_buffer,
+            globals_buffer,
+            &reflection_target,
+            &refraction_target,
+            &target_sampler,
+            &normal_map,
+        );
+
+        Self {
+            reflection_target,
+            refraction_target,
+            normal_map,
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            uniform_buffer,
+            globals_buffer: globals_buffer.clone(),
+            target_sampler,
+            reflection_camera_buffer,
+            reflection_camera_bind_group,
+            reflection_clip_bind_group,
+            refraction_clip_bind_group,
+        }
+    }
+
+    // Targets follow the window size, the bind group has to point at the new ones
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        let target_config = target_config(config);
+        self.reflection_target = OffscreenTarget::new(device, &target_config, "Water Reflection");
+        self.refraction_target = OffscreenTarget::new(device, &target_config, "Water Refraction");
+        self.bind_group = create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.uniform_buffer,
+            &self.globals_buffer,
+            &self.reflection_target,
+            &self.refraction_target,
+            &self.target_sampler,
+            &self.normal_map,
+        );
+    }
+
+    // Mirror the camera for the reflection pass, call once per frame after the camera moved
+    pub fn update(&self, queue: &wgpu::Queue, camera: &Camera) {
+        let reflection_camera = Camera::new(CameraConfig {
+            eye: mirror_point(camera.eye, WATER_HEIGHT),
+            target: mirror_point(camera.target, WATER_HEIGHT),
+            up: camera.up,
+            aspect: camera.aspect,
+            fovy: camera.fovy,
+            znear: camera.znear,
+            zfar: camera.zfar,
+        });
+        let mut camera_uniform = CameraUniform::new();
+        camera_uniform.update_view_proj(&reflection_camera);
+        queue.write_buffer(&self.reflection_camera_buffer, 0, bytemuck::cast_slice(&[camera_uniform]));
+
+        let reflection_view_proj: Matrix4<f32> = reflection_camera.build_view_projection_matrix();
+        let uniform = WaterUniform {
+            reflection_view_proj: reflection_view_proj.into(),
+            color: WATER_COLOR,
+            height: WATER_HEIGHT,
+            half_size: HALF_SIZE,
+            znear: camera.znear,
+            zfar: camera.zfar,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    // The two scene passes to record before the frame: target, camera and clip plane of each
+    // camera_bind_group is the normal camera, the refraction is seen from there
+    pub fn scene_passes<'a>(
+        &'a self,
+        camera_bind_group: &'a wgpu::BindGroup,
+    ) -> [(&'a OffscreenTarget, &'a wgpu::BindGroup, &'a wgpu::BindGroup); 2] {
+        [
+            (&self.reflection_target, &self.reflection_camera_bind_group, &self.reflection_clip_bind_group),
+            (&self.refraction_target, camera_bind_group, &self.refraction_clip_bind_group),
+        ]
+    }
+
+    // Draw the surface into a pass using the main depth buffer
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera_bind_group: &'a wgpu::BindGroup) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.draw(0..6, 0..1);
+    }
+}
+
+// Same surface format, smaller size
+fn target_config(config: &wgpu::SurfaceConfiguration) -> wgpu::SurfaceConfiguration {
+    wgpu::SurfaceConfiguration {
+        width: (config.width / TARGET_DIVISOR).max(1),
+        height: (config.height / TARGET_DIVISOR).max(1),
+        ..config.clone()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    uniform_buffer: &wgpu::Buffer,
+    globals_buffer: &wgpu::Buffer,
+    reflection_target: &OffscreenTarget,
+    refraction_target: &OffscreenTarget,
+    target_sampler: &wgpu::Sampler,
+    normal_map: &texture::Texture,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Water Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 1, resource: globals_buffer.as_entire_binding() },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&reflection_target.color.texture_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(&refraction_target.color.texture_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::TextureView(&refraction_target.depth.texture_view),
+            },
+            wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::Sampler(target_sampler) },
+            wgpu::BindGroupEntry { binding: 6, resource: wgpu::BindingResource::TextureView(&normal_map.texture_view) },
+            wgpu::BindGroupEntry { binding: 7, resource: wgpu::BindingResource::Sampler(&normal_map.sampler) },
+        ],
+    })
+}
+
+// Same point on the other side of the horizontal plane at height
+fn mirror_point(point: Point3<f32>, height: f32) -> Point3<f32> {
+    Point3::new(point.x, 2.0 * height - point.y, point.z)
+}
+
+// Surface normal of the wave height field at u, v (0..1 across the tile), z up
+fn wave_normal(u: f32, v: f32) -> [f32; 3] {
+    use std::f32::consts::TAU;
+
+    let (mut dx, mut dy) = (0.0, 0.0);
+    for (cycles_x, cycles_y, height, phase) in WAVES {
+        // Derivative of height * sin(TAU * (cycles_x * u + cycles_y * v) + phase)
+        let slope = height * TAU * (TAU * (cycles_x * u + cycles_y * v) + phase).cos();
+        dx += slope * cycles_x;
+        dy += slope * cycles_y;
+    }
+    let length = (dx * dx + dy * dy + 1.0).sqrt();
+    [-dx / length, -dy / length, 1.0 / length]
+}
+
+// RGBA8 pixels of the normal map, -1..1 stored as 0..255
+fn wave_normal_map(size: u32) -> Vec<u8> {
+    (0..size * size)
+        .flat_map(|i| {
+            let (u, v) = ((i % size) as f32 / size as f32, (i / size) as f32 / size as f32);
+            let [x, y, z] = wave_normal(u, v);
+            [x, y, z].map(|c| ((c * 0.5 + 0.5) * 255.0).round() as u8).into_iter().chain([255])
+        })
+        .collect()
+}
+
+// Linear format (not sRGB, these are directions not colors) and a repeating sampler
+fn create_normal_map(device: &wgpu::Device, queue: &wgpu::Queue) -> texture::Texture {
+    let size = wgpu::Extent3d { width: NORMAL_MAP_SIZE, height: NORMAL_MAP_SIZE, depth_or_array_layers: 1 };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Water Normal Map"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    queue.write_texture(
+        texture.as_image_copy(),
+        &wave_normal_map(NORMAL_MAP_SIZE),
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(NORMAL_MAP_SIZE * 4),
+            rows_per_image: Some(NORMAL_MAP_SIZE),
+        },
+        size,
+    );
+    let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("Water Normal Sampler"),
+        address_mode_u: wgpu::AddressMode::Repeat,
+        address_mode_v: wgpu::AddressMode::Repeat,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+    texture::Texture { texture, texture_view, sampler }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mirror_point() {
+        assert_eq!(mirror_point(Point3::new(1.0, 2.0, 3.0), -0.5), Point3::new(1.0, -3.0, 3.0));
+        // Points on the surface stay where they are
+        assert_eq!(mirror_point(Point3::new(4.0, -0.5, 0.0), -0.5), Point3::new(4.0, -0.5, 0.0));
+    }
+
+    #[test]
+    fn test_wave_normals_tile_and_point_up() {
+        for (u, v) in [(0.0, 0.0), (0.25, 0.6), (0.9, 0.1)] {
+            let normal = wave_normal(u, v);
+            let wrapped = wave_normal(u + 1.0, v + 1.0);
+            for axis in 0..3 {
+                assert!((normal[axis] - wrapped[axis]).abs() < 1e-4, "{normal:?} {wrapped:?}");
+            }
+            let length = normal.iter().map(|c| c * c).sum::<f32>().sqrt();
+            assert!((length - 1.0).abs() < 1e-5);
+            assert!(normal[2] > 0.5);
+        }
+    }
+
+    #[test]
+    fn test_normal_map_pixels() {
+        let pixels = wave_normal_map(8);
+        assert_eq!(pixels.len(), 8 * 8 * 4);
+        // Blue is the up axis, it stays in the upper half everywhere
+        assert!(pixels.chunks(4).all(|pixel| pixel[2] > 191 && pixel[3] == 255));
+    }
+
+    // Builds the whole thing and records both scene passes (empty) and the surface draw,
+    // so a mismatch between the shader and the bind group layouts fails here
+    // Same backends as the app: GL cant textureLoad a depth texture, and the app never runs on it
+    // Skips when the machine has none of them
+    #[test]
+    fn test_water_draws_without_validation_errors() {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::PRIMARY,
+            ..Default::default()
+        });
+        let Ok(adapter) = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())) else {
+            eprintln!("No adapter available, skipping water test");
+            return;
+        };
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).unwrap();
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: 64,
+            height: 48,
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+        };
+        let camera_layout = CameraUniform::create_bind_group_layout(&device);
+        let clip_layout = clip::create_bind_group_layout(&device);
+        let globals_buffer = buffers::create_uniform_buffer(&device, &crate::graphics::globals::GlobalsUniform::new(64, 48));
+        let camera_buffer = buffers::create_uniform_buffer(&device, &CameraUniform::new());
+        let camera_bind_group = CameraUniform::create_bind_group(&device, &camera_layout, &camera_buffer);
+
+        let mut water = WaterPlane::new(&device, &queue, &config, &camera_layout, &clip_layout, &globals_buffer);
+        water.resize(&device, &wgpu::SurfaceConfiguration { width: 80, height: 60, ..config.clone() });
+        let camera = Camera::new(CameraConfig {
+            eye: (0.0, 2.0, 4.0).into(),
+            target: (0.0, 0.0, 0.0).into(),
+            up: cgmath::Vector3::unit_y(),
+            aspect: 4.0 / 3.0,
+            fovy: 45.0,
+            znear: 0.1,
+            zfar: 100.0,
+        });
+        water.update(&queue, &camera);
+
+        let frame = OffscreenTarget::new(&device, &wgpu::SurfaceConfiguration { width: 80, height: 60, ..config }, "Water Test");
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let clear_pass = |encoder: &mut wgpu::CommandEncoder, target: &OffscreenTarget| {
+            drop(encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target.color.texture_view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLUE), store: wgpu::StoreOp::Store },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &target.depth.texture_view,
+                    depth_ops: Some(wgpu::Operations { load: wgpu::LoadOp::Clear(1.0), store: wgpu::StoreOp::Store }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+                multiview_mask: None,
+            }));
+        };
+        for (target, _, _) in water.scene_passes(&camera_bind_group) {
+            clear_pass(&mut encoder, target);
+        }
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &frame.color.texture_view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: wgpu::StoreOp::Store },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &frame.depth.texture_view,
+                    depth_ops: Some(wgpu::Operations { load: wgpu::LoadOp::Clear(1.0), store: wgpu::StoreOp::Store }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+                multiview_mask: None,
+            });
+            water.draw(&mut render_pass, &camera_bind_group);
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+        device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+    }
+}
@@ -0,0 +1,148 @@
+use winit::window::Window;
+use crate::graphics::egui_renderer::EguiRenderer;
+use crate::graphics::profiler::FrameStats;
+use crate::graphics::texture::TextureLayoutCache;
+
+// egui overlay for tweaking the scene at runtime (only built with the `gui` feature)
+// Immediate mode UI: every frame we describe the whole panel again and egui tells us what
+// changed. The panel edits a GuiSettings copy, State compares it with the current values and
+// calls its own setters for whatever the user touched.
+// There is no MSAA toggle: the renderer draws to single sample targets only, so there is
+// nothing to switch yet.
+
+// Values the panel can change, filled from State before the panel runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GuiSettings {
+    pub camera_speed: f32,
+    pub light_color: [f32; 3],
+    pub light_position: [f32; 3],
+    pub clear_color: [f32; 3],
+    pub instances_per_row: u32,
+    pub vignette_strength: f32,
+    pub vignette_power: f32,
+    pub chromatic_aberration: f32,
+}
+
+pub struct Gui {
+    context: egui::Context,
+    winit_state: egui_winit::State,
+    renderer: EguiRenderer,
+    // Shown read only at the bottom of the panel, State hands it over every frame
+    stats: FrameStats,
+}
+
+impl Gui {
+    pub fn new(
+        device: &wgpu::Device,
+        layouts: &TextureLayoutCache,
+        color_format: wgpu::TextureFormat,
+        window: &Window,
+    ) -> Self {
+        let context = egui::Context::default();
+        let winit_state = egui_winit::State::new(
+            context.clone(),
+            egui::ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            None,
+            Some(device.limits().max_texture_dimension_2d as usize),
+        );
+
+        Self {
+            context,
+            winit_state,
+            renderer: EguiRenderer::new(device, layouts, color_format),
+            stats: FrameStats::default(),
+        }
+    }
+
+    // Give the event to egui first, true means egui wants it and the app should ignore it
+    pub fn on_window_event(&mut self, window: &Window, event: &winit::event::WindowEvent) -> bool {
+        let response = self.winit_state.on_window_event(window, event);
+        let is_pointer_event = matches!(
+            event,
+            winit::event::WindowEvent::CursorMoved { .. }
+                | winit::event::WindowEvent::MouseInput { .. }
+                | winit::event::WindowEvent::MouseWheel { .. }
+        );
+        response.consumed || (is_pointer_event && self.context.wants_pointer_input())
+    }
+
+    pub fn set_frame_stats(&mut self, stats: FrameStats) {
+        self.stats = stats;
+    }
+
+    // Run the panel and draw it over view
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        window: &Window,
+        settings: &mut GuiSettings,
+    ) {
+        let raw_input = self.winit_state.take_egui_input(window);
+        let stats = &self.stats;
+        let output = self.context.run(raw_input, |context| Self::panel(context, settings, stats));
+        self.winit_state.handle_platform_output(window, output.platform_output);
+
+        let primitives = self.context.tessellate(output.shapes, output.pixels_per_point);
+        let size = window.inner_size();
+        self.renderer.update_textures(device, queue, &output.textures_delta);
+        self.renderer.render(
+            device,
+            queue,
+            encoder,
+            view,
+            &primitives,
+            [size.width, size.height],
+            output.pixels_per_point,
+        );
+        self.renderer.free_textures(&output.textures_delta);
+    }
+
+    fn panel(context: &egui::Context, settings: &mut GuiSettings, stats: &FrameStats) {
+        egui::Window::new("Tweaks").default_width(220.0).show(context, |ui| {
+            ui.add(egui::Slider::new(&mut settings.camera_speed, 0.01..=1.0).text("Camera speed"));
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Light color");
+                ui.color_edit_button_rgb(&mut settings.light_color);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Light position");
+                for axis in &mut settings.light_position {
+                    ui.add(egui::DragValue::new(axis).speed(0.1));
+                }
+            });
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Clear color");
+                ui.color_edit_button_rgb(&mut settings.clear_color);
+            });
+
+            ui.separator();
+            ui.add(egui::Slider::new(&mut settings.instances_per_row, 1..=20).text("Instances per row"));
+
+            // 0 turns the effect off
+            ui.separator();
+            ui.add(egui::Slider::new(&mut settings.vignette_strength, 0.0..=2.0).text("Vignette strength"));
+            ui.add(egui::Slider::new(&mut settings.vignette_power, 0.5..=4.0).text("Vignette power"));
+            ui.add(egui::Slider::new(&mut settings.chromatic_aberration, 0.0..=0.05).text("Chromatic aberration"));
+
+            // Read only, nothing to tweak. Missing when the adapter has no timestamp queries
+            let timings = stats.gpu_timing_lines();
+            if !timings.is_empty() {
+                ui.separator();
+                ui.collapsing("GPU timings", |ui| {
+                    for line in timings {
+                        ui.label(line);
+                    }
+                });
+            }
+        });
+    }
+}
@@ -3,11 +3,90 @@ use winit::keyboard::KeyCode;
 
 pub struct InputHandler;
 
+// Which mapping CursorMoved uses for the clear color
+pub enum MouseColorMapping {
+    #[allow(dead_code)] // Only picked by editing MOUSE_COLOR_MAPPING
+    Linear, // x is red, y is green
+    Hsv,    // x is hue, y is value
+}
+
+pub const MOUSE_COLOR_MAPPING: MouseColorMapping = MouseColorMapping::Hsv;
+
+// Left and right stick as (x, y), y is up
+pub type Sticks = ((f32, f32), (f32, f32));
+
+// Analog sticks of the first connected gamepad, polled once per frame
+// Without the gamepad feature (or without a pad) poll never has sticks and the keys stay in charge
+pub struct GamepadHandler {
+    #[cfg(feature = "gamepad")]
+    gilrs: Option<gilrs::Gilrs>,
+}
+
+impl GamepadHandler {
+    #[cfg(feature = "gamepad")]
+    pub fn new() -> Self {
+        let gilrs = gilrs::Gilrs::new()
+            .inspect_err(|e| log::warn!("No gamepad support: {}", e))
+            .ok();
+        Self { gilrs }
+    }
+
+    #[cfg(not(feature = "gamepad"))]
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    // gilrs only updates the pad state while its events are read, so they are drained first
+    #[cfg(feature = "gamepad")]
+    pub fn poll(&mut self) -> Option<Sticks> {
+        use gilrs::Axis;
+
+        let gilrs = self.gilrs.as_mut()?;
+        while gilrs.next_event().is_some() {}
+        let (_, pad) = gilrs.gamepads().next()?;
+        Some((
+            (pad.value(Axis::LeftStickX), pad.value(Axis::LeftStickY)),
+            (pad.value(Axis::RightStickX), pad.value(Axis::RightStickY)),
+        ))
+    }
+
+    #[cfg(not(feature = "gamepad"))]
+    pub fn poll(&mut self) -> Option<Sticks> {
+        None
+    }
+}
+
 pub enum InputAction {
     None,
     Exit,
-    ToggleShape,
-    ToggleDepthVisualization,
+    CycleModel,
+    CycleRenderMode,
+    ToggleComputeAnimation,
+    ToggleSkinning,
+    ScaleUp,
+    ScaleDown,
+    BakeProbes,
+    ToggleCulling,
+    ToggleDebugLines,
+    ShowBoundingBoxes(bool), // true while the key is held
+    ToggleXray,
+    ToggleMultiview,
+    ReloadModel,
+    ToggleClipPlane,
+    ToggleTransparentQuads,
+    ToggleMotionVectors,
+    ToggleTaa,
+    CyclePresentMode,
+    FrameScene,
+    ReloadShader,
+    ToggleSubsurfaceScattering,
+    SimulateSuspendResume,
+    ToggleCloth,
+    ToggleSplitScreen,
+    ToggleWater,
+    ToggleFog,
+    OpenWindow,
+    ToggleLodDebug,
 }
 
 impl InputHandler {
@@ -19,12 +98,43 @@ impl InputHandler {
                 event_loop.exit();
                 InputAction::Exit
             }
-            (KeyCode::Space, true) => InputAction::ToggleShape,
-            (KeyCode::KeyV, true) => InputAction::ToggleDepthVisualization,
+            (KeyCode::Space, true) => InputAction::CycleModel,
+            (KeyCode::KeyV, true) => InputAction::CycleRenderMode, // V for view
+            (KeyCode::KeyC, true) => InputAction::ToggleComputeAnimation,
+            (KeyCode::KeyK, true) => InputAction::ToggleSkinning,
+            // Equal is the + key without shift on most layouts
+            (KeyCode::Equal | KeyCode::NumpadAdd, true) => InputAction::ScaleUp,
+            (KeyCode::Minus | KeyCode::NumpadSubtract, true) => InputAction::ScaleDown,
+            (KeyCode::KeyP, true) => InputAction::BakeProbes,
+            (KeyCode::KeyB, true) => InputAction::ToggleCulling, // B for back faces
+            (KeyCode::KeyL, true) => InputAction::ToggleDebugLines,
+            // Reacts to both press and release so the boxes only show while H is held
+            (KeyCode::KeyH, held) => InputAction::ShowBoundingBoxes(held),
+            (KeyCode::KeyX, true) => InputAction::ToggleXray,
+            (KeyCode::KeyM, true) => InputAction::ToggleMultiview,
+            (KeyCode::KeyR, true) => InputAction::ReloadModel,
+            (KeyCode::KeyO, true) => InputAction::ToggleClipPlane, // O for open, cuts the scene open
+            (KeyCode::KeyT, true) => InputAction::ToggleTransparentQuads,
+            (KeyCode::KeyN, true) => InputAction::ToggleMotionVectors,
+            (KeyCode::KeyJ, true) => InputAction::ToggleTaa, // J for jitter
+            (KeyCode::F9, true) => InputAction::CyclePresentMode,
+            (KeyCode::Home, true) => InputAction::FrameScene,
+            (KeyCode::F5, true) => InputAction::ReloadShader, // R already reloads the model
+            (KeyCode::KeyU, true) => InputAction::ToggleSubsurfaceScattering, // U for under the surface
+            (KeyCode::KeyF, true) => InputAction::ToggleCloth, // F for fabric
+            (KeyCode::Digit2, true) => InputAction::ToggleSplitScreen, // 2 cameras
+            (KeyCode::KeyI, true) => InputAction::ToggleWater, // I for island
+            (KeyCode::KeyG, true) => InputAction::ToggleFog, // G for gray distance
+            (KeyCode::F2, true) => InputAction::OpenWindow, // N is taken by the motion vectors
+            (KeyCode::F3, true) => InputAction::ToggleLodDebug,
+            // Runs the same suspended + resumed as the OS would, desktop never sends them on its own
+            (KeyCode::F8, true) => InputAction::SimulateSuspendResume,
             _ => InputAction::None,
         }
     }
 
+    // The cursor position and the surface size are both physical pixels, so the ratio is the same
+    // at any scale factor and the corners map to the same colors on a 1x and a 2x monitor
     pub fn calculate_color_from_mouse(x: f64, y: f64, width: u32, height: u32) -> wgpu::Color {
         // Get window dimensions
         let width = width as f64;
@@ -39,4 +149,108 @@ impl InputHandler {
             a: 1.0,
         }
     }
+
+    // Hue goes around the color wheel left to right, value is bright at the top and black at the bottom
+    // HSV is defined on the colors we see, but the surface is sRGB and expects linear values
+    // (it encodes them on write), so the result is converted to linear. Without that the dark end
+    // of the window would look washed out
+    pub fn calculate_hsv_from_mouse(x: f64, y: f64, width: u32, height: u32) -> wgpu::Color {
+        let hue = (x / width as f64).clamp(0.0, 1.0);
+        let value = 1.0 - (y / height as f64).clamp(0.0, 1.0);
+        let [r, g, b] = hsv_to_rgb(hue, 1.0, value);
+        wgpu::Color {
+            r: srgb_to_linear(r),
+            g: srgb_to_linear(g),
+            b: srgb_to_linear(b),
+            a: 1.0,
+        }
+    }
+
+    pub fn color_from_mouse(mapping: MouseColorMapping, x: f64, y: f64, width: u32, height: u32) -> wgpu::Color {
+        match mapping {
+            MouseColorMapping::Linear => Self::calculate_color_from_mouse(x, y, width, height),
+            MouseColorMapping::Hsv => Self::calculate_hsv_from_mouse(x, y, width, height),
+        }
+    }
+}
+
+// All in 0..1, hue 0 and 1 are both red
+fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> [f64; 3] {
+    // Six sectors of 60 degrees, in each one channel is full, one is off and one ramps
+    let sector = (hue.rem_euclid(1.0) * 6.0).min(5.999_999);
+    let fraction = sector.fract();
+    let chroma = value * saturation;
+    let min = value - chroma;
+    let rising = min + chroma * fraction;
+    let falling = value - chroma * fraction;
+    match sector as u32 {
+        0 => [value, rising, min],
+        1 => [falling, value, min],
+        2 => [min, value, rising],
+        3 => [min, falling, value],
+        4 => [rising, min, value],
+        _ => [value, min, falling],
+    }
+}
+
+// Inverse of the sRGB transfer function the surface applies
+fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_color(color: wgpu::Color, expected: [f64; 3]) {
+        let actual = [color.r, color.g, color.b];
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-6, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn test_linear_mapping() {
+        let color = InputHandler::calculate_color_from_mouse(50.0, 150.0, 100, 200);
+        assert_color(color, [0.5, 0.75, 0.3]);
+        // Outside the window is clamped
+        let color = InputHandler::calculate_color_from_mouse(-10.0, 400.0, 100, 200);
+        assert_color(color, [0.0, 1.0, 0.3]);
+    }
+
+    #[test]
+    fn test_mappings_dont_depend_on_scale_factor() {
+        // Same spot of the window on a 1x monitor and a 2x one
+        let (x, y, width, height) = (120.0, 45.0, 400, 300);
+        let one = InputHandler::calculate_hsv_from_mouse(x, y, width, height);
+        let two = InputHandler::calculate_hsv_from_mouse(x * 2.0, y * 2.0, width * 2, height * 2);
+        assert_color(two, [one.r, one.g, one.b]);
+        let one = InputHandler::calculate_color_from_mouse(x, y, width, height);
+        let two = InputHandler::calculate_color_from_mouse(x * 2.0, y * 2.0, width * 2, height * 2);
+        assert_color(two, [one.r, one.g, one.b]);
+    }
+
+    #[test]
+    fn test_hsv_hue_points() {
+        // Top row is full value, so the primaries come out exactly
+        let at = |x| InputHandler::calculate_hsv_from_mouse(x, 0.0, 600, 100);
+        assert_color(at(0.0), [1.0, 0.0, 0.0]);
+        assert_color(at(100.0), [1.0, 1.0, 0.0]);
+        assert_color(at(200.0), [0.0, 1.0, 0.0]);
+        assert_color(at(300.0), [0.0, 1.0, 1.0]);
+        assert_color(at(400.0), [0.0, 0.0, 1.0]);
+        assert_color(at(500.0), [1.0, 0.0, 1.0]);
+        // Right edge wraps around to red
+        assert_color(at(600.0), [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_hsv_value_is_gamma_corrected() {
+        // Bottom is black
+        assert_color(InputHandler::calculate_hsv_from_mouse(0.0, 100.0, 600, 100), [0.0, 0.0, 0.0]);
+        // Half value is 0.5 in sRGB, about 0.214 once linear
+        let color = InputHandler::calculate_hsv_from_mouse(0.0, 50.0, 600, 100);
+        assert!((color.r - 0.214).abs() < 1e-3, "{}", color.r);
+        assert_eq!((color.g, color.b), (0.0, 0.0));
+    }
 }
\ No newline at end of file
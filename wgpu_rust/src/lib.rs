@@ -5,18 +5,49 @@ mod graphics;
 mod model;
 
 mod resources;
+mod assets;
+mod config;
+#[cfg(not(target_arch = "wasm32"))]
+mod bench;
+#[cfg(feature = "gui")]
+mod gui;
 
 pub use app::App;
+// For the integration tests (tests/headless.rs), a device without a window
+pub use graphics::headless::HeadlessContext;
 
 
 
 // Setup logging and run the event loop
-pub fn run() -> anyhow::Result<()> {
+// config_path is the window settings file, None looks for wgpu_rust.toml next to the binary
+pub fn run(config_path: Option<std::path::PathBuf>) -> anyhow::Result<()> {
     env_logger::init();
 
+    // --bench <frames> renders offscreen and exits instead of opening a window, see bench.rs
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(options) = bench::BenchOptions::from_args(std::env::args())? {
+        return bench::run(&options);
+    }
+
+    // --trace-file <path> writes the GPU time of every pass to a CSV file
+    let trace_file = graphics::profiler::trace_file_from_args(std::env::args());
+    let config = config::AppConfig::load(config_path.as_deref());
+
     let event_loop = winit::event_loop::EventLoop::with_user_event().build()?;
-    let mut app = App::new();
+    let mut app = App::new(
+        trace_file,
+        config,
+        #[cfg(target_arch = "wasm32")]
+        &event_loop,
+    );
     event_loop.run_app(&mut app)?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+// Entry point of the web build, wasm-bindgen calls it once the module is loaded (assets/index.html)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen(start)]
+pub fn run_web() -> Result<(), wasm_bindgen::JsValue> {
+    run(None).map_err(|e| wasm_bindgen::JsValue::from_str(&format!("{:#}", e)))
+}
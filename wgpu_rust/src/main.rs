@@ -1,8 +1,11 @@
+use std::path::PathBuf;
 use wgpu_rust::run;
 
 
 fn main() -> anyhow::Result<()> {
-    run()?;
+    // --config <path> picks the settings file, see config.rs
+    let config_path = std::env::args().skip_while(|arg| arg != "--config").nth(1).map(PathBuf::from);
+    run(config_path)?;
 
     Ok(())
 }
\ No newline at end of file
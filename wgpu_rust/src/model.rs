@@ -1,7 +1,12 @@
 use std::ops::Range;
+use std::sync::Arc;
 use wgpu::{BindGroup, VertexBufferLayout};
+use crate::graphics::bounds::Aabb;
+use crate::graphics::buffers::{self, IndexBuffer};
 use crate::graphics::texture;
 
+pub mod registry;
+
 pub struct Model {
     pub meshes: Vec<Mesh>,
     pub materials: Vec<Material>,
@@ -9,16 +14,140 @@ pub struct Model {
 
 pub struct Material {
     pub name: String,
-    pub diffuse_texture: texture::Texture,
+    pub diffuse_texture: Arc<texture::Texture>, // Shared with other materials using the same file
+    // Texture, sampler and params_buffer, built with the material layout (texture.rs)
     pub bind_group: BindGroup,
+    // What the shader reads from params_buffer, write_params sends changes to it
+    // (State::material_mut does that for you)
+    pub base_color: [f32; 4],
+    pub metallic: f32,
+    pub roughness: f32,
+    pub params_buffer: wgpu::Buffer,
+    // Drawn after everything opaque, blended and sorted back to front (see graphics/transparency.rs)
+    pub transparent: bool,
+    // Subsurface scattering, 0 for none (see graphics/post_process.rs)
+    pub sss_strength: f32,
+    pub sss_color: [f32; 3], // How far each color channel bleeds, red travels the furthest in skin
+}
+
+// The per material parameters as the shader sees them, group 0 binding 2 of shader.wgsl
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniform {
+    pub base_color: [f32; 4],
+    pub metallic: f32,
+    pub roughness: f32,
+    pub _padding: [f32; 2], // Uniform structs are sized in multiples of 16 bytes
+}
+
+impl Default for MaterialUniform {
+    // White and fully rough: the texture as it is with no highlight, how materials looked before
+    fn default() -> Self {
+        Self { base_color: [1.0; 4], metallic: 0.0, roughness: 1.0, _padding: [0.0; 2] }
+    }
+}
+
+impl Material {
+    // Opaque and without subsurface scattering, set those after for the materials that have them
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        name: &str,
+        diffuse_texture: Arc<texture::Texture>,
+        params: MaterialUniform,
+    ) -> Self {
+        let params_buffer = buffers::create_uniform_buffer(device, &params);
+        let bind_group = texture::create_material_bind_group(device, layout, &diffuse_texture, &params_buffer);
+        Self {
+            name: name.to_string(),
+            diffuse_texture,
+            bind_group,
+            base_color: params.base_color,
+            metallic: params.metallic,
+            roughness: params.roughness,
+            params_buffer,
+            transparent: false,
+            sss_strength: 0.0,
+            sss_color: [1.0; 3],
+        }
+    }
+
+    pub fn params(&self) -> MaterialUniform {
+        MaterialUniform {
+            base_color: self.base_color,
+            metallic: self.metallic,
+            roughness: self.roughness,
+            _padding: [0.0; 2],
+        }
+    }
+
+    // Only the buffer changes, every bind group and pipeline using the material stays as it is
+    pub fn write_params(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&self.params()));
+    }
+}
+
+// Material borrowed for editing, the new parameters go to the GPU when it is dropped
+//   if let Some(mut material) = state.material_mut(0) { material.roughness = 0.2; }
+pub struct MaterialMut<'a> {
+    material: &'a mut Material,
+    queue: &'a wgpu::Queue,
+}
+
+impl<'a> MaterialMut<'a> {
+    pub fn new(material: &'a mut Material, queue: &'a wgpu::Queue) -> Self {
+        Self { material, queue }
+    }
+}
+
+impl std::ops::Deref for MaterialMut<'_> {
+    type Target = Material;
+
+    fn deref(&self) -> &Material {
+        self.material
+    }
+}
+
+impl std::ops::DerefMut for MaterialMut<'_> {
+    fn deref_mut(&mut self) -> &mut Material {
+        self.material
+    }
+}
+
+impl Drop for MaterialMut<'_> {
+    fn drop(&mut self) {
+        self.material.write_params(self.queue);
+    }
 }
 
 pub struct Mesh {
     pub name: String,
     pub vertex_buffer: wgpu::Buffer,
-    pub index_buffer: wgpu::Buffer,
-    pub num_elements: u32,
+    pub index_buffer: IndexBuffer, // Knows its own format (u16 or u32) and index count
     pub material: usize,
+    // CPU copy of the vertices, needed to build GPU side effects like skinning from the same data
+    pub vertices: Vec<ModelVertex>,
+    // CPU copy of the indices, used to build simplified LOD versions of the mesh
+    pub indices: Vec<u32>,
+    // Local space box around the vertices, computed once when the mesh is loaded
+    pub bounds: Aabb,
+}
+
+impl Model {
+    // Nothing to draw, what State shows once the last model is removed from the registry
+    pub fn empty() -> Self {
+        Self { meshes: Vec::new(), materials: Vec::new() }
+    }
+
+    // Box around every mesh of the model, in model space
+    pub fn bounds(&self) -> Aabb {
+        self.meshes.iter().fold(Aabb::EMPTY, |bounds, mesh| bounds.union(&mesh.bounds))
+    }
+}
+
+// Box for Mesh::bounds, every place that builds a mesh computes it from the same vertices
+pub fn vertex_bounds(vertices: &[ModelVertex]) -> Aabb {
+    Aabb::from_points(vertices.iter().map(|vertex| vertex.position.into()))
 }
 
 
@@ -84,12 +213,12 @@ where
         light_bind_group: &'b BindGroup,
     ) {
         self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
-        self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        self.set_index_buffer(mesh.index_buffer.buffer.slice(..), mesh.index_buffer.format);
         self.set_bind_group(0, &material.bind_group, &[]);
         self.set_bind_group(1, camera_bind_group, &[]);
         // Skip bindings on 3 and 4. Done on state.rs set globally
         self.set_bind_group(4, light_bind_group, &[]);
-        self.draw_indexed(0..mesh.num_elements, 0, instances);
+        self.draw_indexed(0..mesh.index_buffer.count, 0, instances);
     }
 
     // Draw the entire model by drawing each mesh in it
@@ -170,10 +299,10 @@ where
         light_bind_group: &'b BindGroup,
     ) {
         self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
-        self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        self.set_index_buffer(mesh.index_buffer.buffer.slice(..), mesh.index_buffer.format);
         self.set_bind_group(0, camera_bind_group, &[]);
         self.set_bind_group(1, light_bind_group, &[]);
-        self.draw_indexed(0..mesh.num_elements, 0, instances);
+        self.draw_indexed(0..mesh.index_buffer.count, 0, instances);
     }
 
     fn draw_light_model(
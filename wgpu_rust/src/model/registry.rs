@@ -0,0 +1,199 @@
+// Every model the scene can show by name, Space steps through them and only the active one is drawn
+// State rebuilds a lot from the drawn model (LODs, skinning, bounds, see set_model), so the active
+// model doesnt sit in here: it is handed out to State::obj_model and the entry keeps None until
+// another one is activated and it comes back.
+// Generic over the model so the bookkeeping can be tested without a GPU.
+
+pub struct ModelEntry<M> {
+    pub name: String,
+    // File it was loaded from, None for models built in code (the vertex.rs shapes)
+    pub source: Option<String>,
+    model: Option<M>,
+}
+
+// set_active_model takes either
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModelRef<'a> {
+    Index(usize),
+    Name(&'a str),
+}
+
+impl From<usize> for ModelRef<'_> {
+    fn from(index: usize) -> Self {
+        ModelRef::Index(index)
+    }
+}
+
+impl<'a> From<&'a str> for ModelRef<'a> {
+    fn from(name: &'a str) -> Self {
+        ModelRef::Name(name)
+    }
+}
+
+pub struct ModelRegistry<M> {
+    entries: Vec<ModelEntry<M>>,
+    active: Option<usize>,
+}
+
+impl<M> Default for ModelRegistry<M> {
+    fn default() -> Self {
+        Self { entries: Vec::new(), active: None }
+    }
+}
+
+impl<M> ModelRegistry<M> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Added at the end, not active yet
+    pub fn add(&mut self, name: &str, source: Option<&str>, model: M) -> usize {
+        self.entries.push(ModelEntry {
+            name: name.to_string(),
+            source: source.map(str::to_string),
+            model: Some(model),
+        });
+        self.entries.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn active(&self) -> Option<usize> {
+        self.active
+    }
+
+    pub fn active_entry(&self) -> Option<&ModelEntry<M>> {
+        self.entries.get(self.active?)
+    }
+
+    pub fn find(&self, model: ModelRef) -> Option<usize> {
+        match model {
+            ModelRef::Index(index) => (index < self.entries.len()).then_some(index),
+            ModelRef::Name(name) => self.entries.iter().position(|entry| entry.name == name),
+        }
+    }
+
+    pub fn find_source(&self, source: &str) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.source.as_deref() == Some(source))
+    }
+
+    // The one after the active one, wrapping around, the first when nothing is active
+    pub fn next(&self) -> Option<usize> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        Some(self.active.map_or(0, |active| (active + 1) % self.entries.len()))
+    }
+
+    // Make index the active one and hand out its model. outgoing is what was drawn until now, it
+    // goes back into the entry it came from (or is dropped when nothing was active, like the
+    // empty placeholder State draws with an empty registry)
+    // Activating the active one again gives outgoing straight back
+    pub fn activate(&mut self, index: usize, outgoing: M) -> M {
+        if self.active == Some(index) {
+            return outgoing;
+        }
+        if let Some(active) = self.active {
+            self.entries[active].model = Some(outgoing);
+        }
+        self.active = Some(index);
+        self.entries[index].model.take().expect("inactive entries keep their model")
+    }
+
+    // New model for an entry that isnt active, a reload of its file. Returns it when the entry
+    // is active, the caller draws it then
+    pub fn replace(&mut self, index: usize, model: M) -> Option<M> {
+        if self.active == Some(index) {
+            return Some(model);
+        }
+        self.entries[index].model = Some(model);
+        None
+    }
+
+    // Removes the entry and returns its model, None for the active one since State holds that
+    // one. The active index moves along with the entries after it, removing the active entry
+    // leaves nothing active (see State::remove_model for what is drawn next)
+    pub fn remove(&mut self, index: usize) -> Option<M> {
+        let entry = self.entries.remove(index);
+        self.active = match self.active {
+            Some(active) if active == index => None,
+            Some(active) if active > index => Some(active - 1),
+            active => active,
+        };
+        entry.model
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry(names: &[&'static str]) -> ModelRegistry<&'static str> {
+        let mut registry = ModelRegistry::new();
+        for name in names {
+            registry.add(name, None, *name);
+        }
+        registry
+    }
+
+    #[test]
+    fn test_activate_swaps_models_in_and_out() {
+        let mut registry = registry(&["cube", "pentagon"]);
+        assert_eq!(registry.active(), None);
+        assert_eq!(registry.activate(0, "placeholder"), "cube");
+        assert_eq!(registry.active_entry().unwrap().name, "cube");
+
+        // The cube goes back into its entry when the pentagon is drawn
+        assert_eq!(registry.activate(1, "cube"), "pentagon");
+        assert_eq!(registry.activate(0, "pentagon"), "cube");
+        assert_eq!(registry.activate(0, "cube"), "cube");
+    }
+
+    #[test]
+    fn test_next_wraps_around() {
+        let mut registry = registry(&["a", "b", "c"]);
+        assert_eq!(registry.next(), Some(0));
+        registry.activate(2, "placeholder");
+        assert_eq!(registry.next(), Some(0));
+        registry.activate(0, "c");
+        assert_eq!(registry.next(), Some(1));
+        assert_eq!(ModelRegistry::<u32>::new().next(), None);
+    }
+
+    #[test]
+    fn test_find_by_index_or_name() {
+        let registry = registry(&["cube", "pentagon"]);
+        assert_eq!(registry.find("pentagon".into()), Some(1));
+        assert_eq!(registry.find(1.into()), Some(1));
+        assert_eq!(registry.find("sphere".into()), None);
+        assert_eq!(registry.find(2.into()), None);
+    }
+
+    #[test]
+    fn test_remove_keeps_the_active_index_pointing_at_the_same_entry() {
+        let mut registry = registry(&["a", "b", "c"]);
+        registry.activate(2, "placeholder");
+        assert_eq!(registry.remove(0), Some("a"));
+        assert_eq!(registry.active_entry().unwrap().name, "c");
+
+        // The active one is gone, nothing is active until State picks the next
+        assert_eq!(registry.remove(1), None);
+        assert_eq!(registry.active(), None);
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[test]
+    fn test_replace_only_hands_back_the_active_model() {
+        let mut registry = registry(&["a", "b"]);
+        registry.activate(0, "placeholder");
+        assert_eq!(registry.replace(0, "a2"), Some("a2"));
+        assert_eq!(registry.replace(1, "b2"), None);
+        assert_eq!(registry.activate(1, "a2"), "b2");
+    }
+}
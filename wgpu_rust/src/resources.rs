@@ -1,39 +1,121 @@
 use std::io::{BufReader, Cursor};
-use wgpu::hal::dx12::ShaderModel::_5_1;
+use anyhow::Context;
 use wgpu::util::DeviceExt;
+use std::path::PathBuf;
+use crate::assets::manager::{ResourceKey, ResourceManager};
 use crate::graphics::{buffers, texture};
+use crate::graphics::vertex::Vertex;
 use crate::model;
 
+// Where a resource file lives, res/ is copied next to the build output by build.rs
+pub fn res_path(file_name: &str) -> PathBuf {
+    std::path::Path::new(env!("OUT_DIR"))
+        .join("res")
+        .join(file_name)
+}
+
+// Scene shader is read at runtime so it can be edited while the app runs (F5 reloads it)
+pub const SCENE_SHADER: &str = "shaders/shader.wgsl";
+
+// res/ of the source tree while it is still around, so edits show up without a rebuild
+// Otherwise the copy build.rs put next to the build output
+pub fn hot_reload_path(file_name: &str) -> PathBuf {
+    let source = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("res").join(file_name);
+    if source.exists() { source } else { res_path(file_name) }
+}
+
 // Load a text file as a String
 // read to string assumes file is valid utf-8
 pub async fn load_string(file_name: &str) -> anyhow::Result<String> {
-    // Determine path relative to executable/output directory
-    let path = std::path::Path::new(env!("OUT_DIR"))
-        .join("res")
-        .join(file_name);
-
-    // Read file from disk
-    let txt = std::fs::read_to_string(&path)?;
-    Ok(txt)
+    read_string(file_name)
+        .await
+        .with_context(|| format!("Unable to load resource {}", file_name))
 }
 
 // Load a binary file as a Vec<u8>
-pub async fn load_binary(file_name: &str) -> anyhow::Result<Vec<u8>> {
-    let path = std::path::Path::new(env!("OUT_DIR"))
-        .join("res")
-        .join(file_name);
+pub async fn load_bytes(file_name: &str) -> anyhow::Result<Vec<u8>> {
+    read_bytes(file_name)
+        .await
+        .with_context(|| format!("Unable to load resource {}", file_name))
+}
 
-    let data = std::fs::read(&path)?;
-    Ok(data)
+// Native reads the copy of res/ that build.rs put next to the build output
+#[cfg(not(target_arch = "wasm32"))]
+async fn read_string(file_name: &str) -> anyhow::Result<String> {
+    Ok(std::fs::read_to_string(res_path(file_name))?)
 }
 
-pub async fn load_texture(
-    file_name: &str,
-    device: &wgpu::Device,
-    queue: &wgpu::Queue,
-) -> anyhow::Result<texture::Texture> {
-    let data = load_binary(file_name).await?;
-    texture::Texture::from_bytes(device, queue, &data, file_name)
+#[cfg(not(target_arch = "wasm32"))]
+async fn read_bytes(file_name: &str) -> anyhow::Result<Vec<u8>> {
+    Ok(std::fs::read(res_path(file_name))?)
+}
+
+// The browser has no file system, res/ has to be served next to the page and is fetched over http
+// Nothing blocks on the web, awaiting the fetch hands control back to the browser until it is done
+#[cfg(target_arch = "wasm32")]
+async fn read_string(file_name: &str) -> anyhow::Result<String> {
+    let response = fetch(file_name).await?;
+    let text = wasm_bindgen_futures::JsFuture::from(response.text().map_err(js_error)?)
+        .await
+        .map_err(js_error)?;
+    text.as_string().context("Response is not text")
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn read_bytes(file_name: &str) -> anyhow::Result<Vec<u8>> {
+    let response = fetch(file_name).await?;
+    let buffer = wasm_bindgen_futures::JsFuture::from(response.array_buffer().map_err(js_error)?)
+        .await
+        .map_err(js_error)?;
+    Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+}
+
+// A relative url, so the browser resolves it against the url of the page
+#[cfg(target_arch = "wasm32")]
+async fn fetch(file_name: &str) -> anyhow::Result<web_sys::Response> {
+    use wasm_bindgen::JsCast;
+
+    let window = web_sys::window().context("No window to fetch from")?;
+    let response = wasm_bindgen_futures::JsFuture::from(window.fetch_with_str(&format!("res/{}", file_name)))
+        .await
+        .map_err(js_error)?;
+    let response: web_sys::Response = response.dyn_into().map_err(js_error)?;
+    // fetch only fails on network errors, a 404 is still a response
+    if !response.ok() {
+        anyhow::bail!("HTTP {} {}", response.status(), response.status_text());
+    }
+    Ok(response)
+}
+
+// Errors from JavaScript are plain JsValues, not std errors
+#[cfg(target_arch = "wasm32")]
+fn js_error(value: wasm_bindgen::JsValue) -> anyhow::Error {
+    anyhow::anyhow!("{:?}", value)
+}
+
+// CPU side of a model: the parsed obj, its materials and the raw bytes of their textures
+// Building this only touches the disk and the CPU, so it can happen on any thread
+// (see assets/loader.rs), upload_model then turns it into GPU buffers and textures
+pub struct ModelData {
+    pub file_name: String,
+    pub meshes: Vec<MeshData>,
+    pub materials: Vec<MaterialData>,
+}
+
+pub struct MeshData {
+    pub vertices: Vec<model::ModelVertex>,
+    pub indices: Vec<u32>,
+    pub material: usize,
+}
+
+pub struct MaterialData {
+    pub name: String,
+    pub diffuse_texture_name: String,
+    pub diffuse_bytes: Vec<u8>, // Still encoded (png, jpg...), decoded when uploading
+    pub alpha: f32, // "d" (dissolve) in the mtl file, 1 is opaque
+    pub params: model::MaterialUniform, // Kd, Ns and the PBR extension, see material_params
+    pub sss_strength: f32,
+    pub sss_color: [f32; 3],
 }
 
 pub async fn load_model(
@@ -41,8 +123,14 @@ pub async fn load_model(
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     layout: &wgpu::BindGroupLayout,
+    resource_manager: &mut ResourceManager,
 ) -> anyhow::Result<model::Model> {
-    let obj_text = load_string(&file_name).await?;
+    let data = read_model(file_name).await?;
+    upload_model(data, device, queue, layout, resource_manager)
+}
+
+pub async fn read_model(file_name: &str) -> anyhow::Result<ModelData> {
+    let obj_text = load_string(file_name).await?;
     let obj_cursor = Cursor::new(obj_text);
     let mut obj_reader = BufReader::new(obj_cursor);
 
@@ -55,26 +143,33 @@ pub async fn load_model(
             ..Default::default()
         },
         |p| async move {
-            let mat_text = load_string(&p).await.unwrap();
+            // tobj errors cant carry our message, so it is logged before it is lost
+            let mat_text = load_string(&p).await.map_err(|e| {
+                log::error!("{:#}", e);
+                tobj::LoadError::OpenFileFailed
+            })?;
             tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
         },
-    ).await?;
+    ).await.with_context(|| format!("Unable to parse {}", file_name))?;
 
     let mut materials = Vec::new();
-    // Create materials from the loaded obj materials
-    for m in obj_materials? {
-        let diffuse_texture = load_texture(&m.diffuse_texture, device, queue).await?;
-        let bind_group = texture::create_bind_group_from_texture(&device, layout, &diffuse_texture);
-
-        // Store the material we got from the obj file into the Rust Material struct
-        materials.push(model::Material {
+    // Read the texture files of the obj materials, decoding them is left for the upload
+    let obj_materials = obj_materials.with_context(|| format!("Unable to load the materials of {}", file_name))?;
+    for m in obj_materials {
+        let diffuse_bytes = load_bytes(&m.diffuse_texture).await?;
+        let (sss_strength, sss_color) = parse_sss(&m.unknown_param);
+        let params = material_params(&m);
+        materials.push(MaterialData {
             name: m.name,
-            diffuse_texture,
-            bind_group,
+            diffuse_texture_name: m.diffuse_texture,
+            diffuse_bytes,
+            alpha: m.dissolve.clamp(0.0, 1.0),
+            params,
+            sss_strength,
+            sss_color,
         })
     }
 
-    // Save every mesh in the model along with its buffers and material
     let meshes = models
         .into_iter()
         .map(|m| {
@@ -109,20 +204,177 @@ pub async fn load_model(
                 })
                 .collect::<Vec<_>>();
 
+            MeshData {
+                vertices,
+                indices: m.mesh.indices,
+                material: m.mesh.material_id.unwrap_or(0),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    Ok(ModelData {
+        file_name: file_name.to_string(),
+        meshes,
+        materials,
+    })
+}
+
+// GPU side of loading a model, has to run where the device and queue live
+pub fn upload_model(
+    data: ModelData,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+    resource_manager: &mut ResourceManager,
+) -> anyhow::Result<model::Model> {
+    let mut materials = Vec::new();
+    // Create materials from the loaded obj materials
+    // Materials sharing an image file (or a reloaded model) get the texture already on the GPU
+    for m in data.materials {
+        let transparent = m.alpha < 1.0;
+        let diffuse_texture = if transparent {
+            // The shader takes alpha from the texture, so the material alpha is baked into a
+            // copy of it, cached separately from the untouched file
+            let key = ResourceKey::Name(format!("{}#alpha={}", m.diffuse_texture_name, m.alpha));
+            resource_manager.get_or_insert_texture(key, |key| {
+                let img = image::load_from_memory(&m.diffuse_bytes)?;
+                texture::Texture::from_image(device, queue, &with_alpha(img, m.alpha), Some(&key.label()))
+            })?
+        } else {
+            let key = ResourceKey::Path(res_path(&m.diffuse_texture_name));
+            resource_manager.get_or_create_texture(device, queue, key, &m.diffuse_bytes)?
+        };
+
+        // Store the material we got from the obj file into the Rust Material struct
+        materials.push(model::Material {
+            transparent,
+            sss_strength: m.sss_strength,
+            sss_color: m.sss_color,
+            ..model::Material::new(device, layout, &m.name, diffuse_texture, m.params)
+        })
+    }
+
+    // Save every mesh in the model along with its buffers and material
+    let meshes = data.meshes
+        .into_iter()
+        .map(|m| {
             // Create vertex and index buffers for the mesh
-            let vertex_buffer = buffers::create_model_vertex_buffer(&device, &vertices);
-            let index_buffer = buffers::create_model_index_buffer(&device, &m.mesh.indices);
+            let vertex_buffer = buffers::create_model_vertex_buffer(device, &m.vertices);
+            // 16 bit indices when the mesh is small enough
+            let index_buffer = buffers::IndexBuffer::from_u32_compact(device, &m.indices);
+            let bounds = model::vertex_bounds(&m.vertices);
 
             // Create and return the mesh struct with its buffers, name, and material
             model::Mesh {
-                name: file_name.to_string(),
+                name: data.file_name.clone(),
                 vertex_buffer,
                 index_buffer,
-                num_elements: m.mesh.indices.len() as u32,
-                material: m.mesh.material_id.unwrap_or(0),
+                material: m.material,
+                vertices: m.vertices,
+                indices: m.indices,
+                bounds,
             }
         })
         .collect::<Vec<_>>();
 
     Ok(model::Model { meshes, materials })
-}
\ No newline at end of file
+}
+
+// Model of one of the hand written shapes in graphics/vertex.rs, one mesh with one material
+// Goes into the model registry like a loaded obj, so Space can switch to it
+pub fn shape_model(
+    device: &wgpu::Device,
+    name: &str,
+    vertices: &[Vertex],
+    indices: &[u16],
+    material: model::Material,
+) -> model::Model {
+    let vertices = vertices.iter().map(|vertex| vertex.to_model_vertex()).collect::<Vec<_>>();
+    let indices = indices.iter().map(|&index| index as u32).collect::<Vec<_>>();
+    let mesh = model::Mesh {
+        name: name.to_string(),
+        vertex_buffer: buffers::create_model_vertex_buffer(device, &vertices),
+        index_buffer: buffers::IndexBuffer::from_u32_compact(device, &indices),
+        material: 0,
+        bounds: model::vertex_bounds(&vertices),
+        vertices,
+        indices,
+    };
+    model::Model { meshes: vec![mesh], materials: vec![material] }
+}
+
+// Subsurface scattering is not part of the mtl format, tobj keeps lines it doesnt know like
+// "sss_strength 0.6" and "sss_color 1.0 0.4 0.25". Missing or broken values mean no scattering
+fn parse_sss(params: &std::collections::HashMap<String, String>) -> (f32, [f32; 3]) {
+    let strength = params.get("sss_strength")
+        .and_then(|value| value.trim().parse::<f32>().ok())
+        .unwrap_or(0.0);
+    let color = params.get("sss_color")
+        .and_then(|value| {
+            let channels = value.split_whitespace().map(str::parse::<f32>).collect::<Result<Vec<_>, _>>().ok()?;
+            <[f32; 3]>::try_from(channels).ok()
+        })
+        .unwrap_or([1.0; 3]);
+    (strength, color)
+}
+
+// Parameters for the shader from the mtl material
+// base_color is Kd, the alpha stays 1 because d is already baked into the texture (with_alpha).
+// tobj fills a missing Kd with black, that would hide the texture, so all zero reads as white.
+// Roughness comes from "Pr" of the PBR extension when there is one, otherwise from the Ns
+// exponent: Ns = 2 / roughness^2 - 2, the inverse of what the shader does. Metallic is "Pm", 0 without
+fn material_params(m: &tobj::Material) -> model::MaterialUniform {
+    let [r, g, b] = if m.diffuse == [0.0; 3] { [1.0; 3] } else { m.diffuse };
+    let extension = |key: &str| m.unknown_param.get(key).and_then(|value| value.trim().parse::<f32>().ok());
+    let roughness = extension("Pr").unwrap_or_else(|| (2.0 / (m.shininess.max(0.0) + 2.0)).sqrt());
+    model::MaterialUniform {
+        base_color: [r, g, b, 1.0],
+        metallic: extension("Pm").unwrap_or(0.0).clamp(0.0, 1.0),
+        roughness: roughness.clamp(0.0, 1.0),
+        ..Default::default()
+    }
+}
+
+// Multiply the alpha of every pixel, so a material with d 0.5 on a solid texture is half see through
+fn with_alpha(img: image::DynamicImage, alpha: f32) -> image::DynamicImage {
+    let mut rgba = img.to_rgba8();
+    for pixel in rgba.pixels_mut() {
+        pixel[3] = (pixel[3] as f32 * alpha).round() as u8;
+    }
+    image::DynamicImage::ImageRgba8(rgba)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_bytes_reads_copy_in_out_dir() {
+        let bytes = pollster::block_on(load_bytes("happy-tree.png")).unwrap();
+        assert!(image::load_from_memory(&bytes).is_ok());
+    }
+
+    #[test]
+    fn test_material_params_from_mtl_values() {
+        let mut m = tobj::Material { diffuse: [0.8, 0.5, 0.2], shininess: 98.0, ..Default::default() };
+        let params = material_params(&m);
+        assert_eq!(params.base_color, [0.8, 0.5, 0.2, 1.0]);
+        assert!((params.roughness - (2.0f32 / 100.0).sqrt()).abs() < 1e-6);
+        assert_eq!(params.metallic, 0.0);
+
+        // The PBR extension wins over Ns, a missing Kd doesnt turn the texture black
+        m.diffuse = [0.0; 3];
+        m.unknown_param.insert("Pr".to_string(), "0.3".to_string());
+        m.unknown_param.insert("Pm".to_string(), "1".to_string());
+        let params = material_params(&m);
+        assert_eq!(params.base_color, [1.0; 4]);
+        assert_eq!(params.roughness, 0.3);
+        assert_eq!(params.metallic, 1.0);
+    }
+
+    #[test]
+    fn test_load_error_names_the_resource() {
+        let error = pollster::block_on(load_string("missing.obj")).unwrap_err();
+        assert!(format!("{:#}", error).contains("missing.obj"));
+    }
+}
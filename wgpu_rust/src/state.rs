@@ -1,41 +1,97 @@
-use crate::model::{DrawLight, Vertex};
+use crate::model::DrawLight;
 use std::sync::Arc;
-use cgmath::{InnerSpace, Rotation3, Zero};
+use std::sync::atomic::{AtomicBool, Ordering};
+use cgmath::{EuclideanSpace, InnerSpace, Rotation3, Zero};
 use winit::window::Window;
-use crate::graphics::{vertex, pipeline, texture, camera, buffers, light};
+use crate::graphics::{texture, camera, buffers, light, picking, adapter, profiler, clip, present_mode, error_log, headless};
+use crate::graphics::error_log::GpuErrorLog;
+use crate::graphics::render_mode::RenderMode;
+use crate::graphics::render_stats::{RenderStats, RenderStatsCounter};
+use crate::graphics::uniform_writer::UniformWriter;
+use crate::graphics::present_mode::PresentModePreference;
 use crate::graphics::camera::CameraUniform;
-use crate::graphics::instance::{Instance, InstanceRaw};
+use crate::graphics::instance::Instance;
 use crate::graphics::camera_controller::CameraController;
 use crate::{model, resources};
-use crate::graphics::light::LightUniform;
-use crate::graphics::pipeline::create_render_pipeline;
-
-// Struct to tell shader what render mode to use
-// Light switch for depth visualization
+use crate::model::registry::{ModelRef, ModelRegistry};
+use crate::graphics::vertex::{COMPLEX_SHAPE_INDICES, COMPLEX_SHAPE_VERTICES, PENT_INDICES, PENT_VERTICES};
+use crate::graphics::light::{FogUniform, LightUniform};
+use crate::graphics::pipeline::{self as pipelines, StencilTestPipeline};
+use crate::graphics::pipeline_cache::{LayoutCache, LayoutKind, PipelineCache, PipelineKey, VertexLayouts};
+use crate::graphics::compute::InstanceAnimation;
+use crate::graphics::skinning::{self, DrawSkinned, SkinnedMesh};
+use crate::graphics::shadow::ContactShadowPass;
+use crate::graphics::motion_vectors::MotionVectorPass;
+use crate::graphics::taa::TaaPass;
+use crate::graphics::cloth::ClothSim;
+use crate::graphics::post_process::{ChromaticAberrationPass, SubsurfaceScatteringPass, VignettePass};
+use crate::graphics::globals::GlobalsUniform;
+use crate::graphics::transform::{self, TransformUniform};
+use crate::graphics::light_probe::{self, ProbeCapture};
+use crate::graphics::text::TextRenderer;
+use crate::graphics::sdf::SdfRenderer;
+use crate::graphics::sprite::{self, Rect, SpriteBatch, SpriteTexture};
+use crate::graphics::debug_lines::DebugLines;
+use crate::graphics::curves::{self, BezierCurve, CurveRenderer};
+use crate::graphics::bounds::{self, Aabb};
+use crate::graphics::lod::{self, DrawLod, LodMesh};
+use crate::graphics::multiview::MultiviewState;
+use crate::graphics::split_screen::SplitScreen;
+use crate::graphics::water::WaterPlane;
+use crate::graphics::outline::{self, OutlinePass};
+use crate::graphics::transparency::{self, TransparentQuads};
+use crate::graphics::profiler::{self as gpu_profiler, FrameStats, GpuProfiler, PipelineStats, PipelineStatsQuery};
+use crate::assets::loader::{AssetHandle, AssetLoader, AssetState, LoadResult, WatchedAsset};
+use crate::assets::manager::{ResourceKey, ResourceManager};
+
+// Struct to tell shader what render mode to use, see graphics/render_mode.rs
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 struct RenderModeUniform {
-    mode: u32, // 0 = normal, 1 = depth visualization, add more future?
-    _padding: [u32; 3], // GPU requires 16 byte alignment for uniforms
+    mode: u32, // RenderMode::id
+    selected_instance: u32, // Instance picked with the mouse, NO_SELECTION if none
+    _padding: [u32; 2], // GPU requires 16 byte alignment for uniforms
 }
 
+// Same value as NO_SELECTION in shader.wgsl
+const NO_SELECTION: u32 = u32::MAX;
+
 // THE ENGINE
 // GPU context. Live inside APP, holds device, queue, surface, config, translates logic into
 // binary commands for GPU
 pub struct State {
-    surface: wgpu::Surface<'static>,
+    // Kept to make a new surface after a suspend, see suspend and resume
+    instance: wgpu::Instance,
+    // None while suspended, the window it drew to may be gone (Android, web)
+    surface: Option<wgpu::Surface<'static>>,
     device: wgpu::Device,
-    queue: wgpu::Queue,
+    pub(crate) queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
-    clear_color: wgpu::Color,
+    // What the surface can present with, F9 cycles through these
+    present_modes: Vec<wgpu::PresentMode>,
+    pub(crate) clear_color: wgpu::Color,
     is_surface_configured: bool,
-
-    pub(crate) window: Arc<Window>,
-    render_pipeline: wgpu::RenderPipeline,
-
-    diffuse_bind_group: wgpu::BindGroup,
-    diffuse_texture: texture::Texture,
-    diffuse_bind_group_layout: wgpu::BindGroupLayout,
+    // Window fully hidden by other windows, nothing to show so we skip rendering
+    occluded: bool,
+    // Physical pixels per logical pixel of the monitor the window is on (2.0 on most HiDPI screens)
+    // Everything is rendered in physical pixels, the HUD multiplies its sizes by this to stay readable
+    scale_factor: f64,
+    // Set from wgpu callbacks when the device is gone, App rebuilds the whole State
+    device_lost: Arc<AtomicBool>,
+    // Validation errors of setup, shader reloads and anything uncaptured, see graphics/error_log.rs
+    gpu_errors: GpuErrorLog,
+
+    // None for State::new_headless (--bench), it draws into textures the caller owns
+    window: Option<Arc<Window>>,
+    render_pipeline: Arc<wgpu::RenderPipeline>,
+    // Kept to rebuild the scene pipelines when the shader is reloaded
+    render_pipeline_layout: wgpu::PipelineLayout,
+    // Same as render_pipeline but draws back faces too, for debugging winding problems
+    render_pipeline_no_cull: Arc<wgpu::RenderPipeline>,
+    culling_enabled: bool,
+
+    // Layouts shared by every texture bind group, created once
+    texture_layouts: texture::TextureLayoutCache,
 
     camera: camera::Camera,
     camera_uniform: CameraUniform,
@@ -43,43 +99,260 @@ pub struct State {
     camera_bind_group: wgpu::BindGroup,
     pub(crate) camera_controller: CameraController,
 
-    instances: Vec<Instance>,
-    instance_buffer: wgpu::Buffer,
+    pub(crate) instances: Vec<Instance>,
+    pub(crate) instance_buffer: wgpu::Buffer,
+    // Optional GPU animation of the instances, when disabled we draw the static instance buffer
+    instance_animation: InstanceAnimation,
+    instances_per_row: u32,
+    compute_animation_enabled: bool,
 
     depth_texture: texture::Texture, // Used for depth testing
     depth_visualization_texture: texture::Texture, // Used for depth visualization
-    depth_visualization_mode: bool,
+    render_mode: RenderMode,
     depth_texture_bind_group: wgpu::BindGroup,
-    depth_texture_bind_group_layout: wgpu::BindGroupLayout,
 
+    render_mode_uniform: RenderModeUniform,
     render_mode_buffer: wgpu::Buffer,
     render_mode_bind_group: wgpu::BindGroup,
 
-    obj_model: model::Model,
+    // Shared clock and resolution, bound next to the render mode in group 3
+    globals: GlobalsUniform,
+    globals_buffer: wgpu::Buffer,
+    start_time: std::time::Instant,
+    // Camera, light, globals and fog go through its staging belt, submitted with the frame
+    uniforms: UniformWriter,
+    // Draws and uploads counted while the frame is recorded, render_stats has the last whole frame
+    render_stats_counter: RenderStatsCounter,
+    render_stats: RenderStats,
+
+    // Mouse picking, spheres are in local mesh space and moved per instance when picking
+    mesh_bounding_sphere: (cgmath::Point3<f32>, f32),
+    picked_instance: Option<usize>,
+
+    pub(crate) obj_model: model::Model,
+    // Every model Space steps through, obj_model is the active one lent out of it
+    models: ModelRegistry<model::Model>,
+    // One skinned copy per mesh of obj_model, drawn instead of the static mesh when enabled
+    skinned_meshes: Vec<SkinnedMesh>,
+    skinning_enabled: bool,
+    skeleton_angle: f32,
 
     light_uniform: LightUniform,
     light_buffer: wgpu::Buffer,
-    light_bind_group_layout: wgpu::BindGroupLayout,
-    light_bind_group: wgpu::BindGroup,
-
-    light_render_pipeline: wgpu::RenderPipeline,
+    fog_uniform: FogUniform,
+    fog_buffer: wgpu::Buffer,
+    pub(crate) light_bind_group: wgpu::BindGroup,
+
+    light_render_pipeline: Arc<wgpu::RenderPipeline>,
+
+    contact_shadow_pass: ContactShadowPass,
+
+    // Velocity texture for temporal effects, see graphics/motion_vectors.rs
+    motion_vector_pass: MotionVectorPass,
+    motion_vectors_enabled: bool,
+    // Instance model matrices of the last frame, written into the instance buffer as prev_model
+    prev_instance_transforms: Vec<cgmath::Matrix4<f32>>,
+
+    // Temporal anti-aliasing, needs the motion vectors to reproject its history
+    taa_pass: TaaPass,
+    taa_enabled: bool,
+
+    // Screen space subsurface scattering for materials with sss_strength, see graphics/post_process.rs
+    sss_pass: SubsurfaceScatteringPass,
+    sss_enabled: bool,
+
+    // Lens effects over the finished frame, both off (and skipped) at 0, see graphics/post_process.rs
+    vignette_pass: VignettePass,
+    vignette_strength: f32,
+    vignette_power: f32,
+    chromatic_aberration_pass: ChromaticAberrationPass,
+    chromatic_aberration_offset: f32,
+
+    // Uniform scale applied to the model in the vertex shader
+    scale: f32,
+    transform_buffer: wgpu::Buffer,
+    transform_bind_group: wgpu::BindGroup,
+
+    // Cross section plane, None draws everything
+    clip_plane: Option<clip::ClipPlane>,
+    clip_buffer: wgpu::Buffer,
+    clip_bind_group: wgpu::BindGroup,
+
+    // Environment probes for local reflections, baked on demand
+    light_probes: Vec<ProbeCapture>,
+    // Used by instances that are not close to any probe
+    default_environment_bind_group: wgpu::BindGroup,
+    // Contiguous instance ranges and the probe they use, so we can draw them in few calls
+    probe_runs: Vec<(std::ops::Range<u32>, Option<usize>)>,
+
+    // HUD text drawn over the scene, signed distance field text when compute shaders are
+    // available and the bitmap atlas otherwise (WebGL has no compute)
+    text_renderer: TextRenderer,
+    sdf_renderer: Option<SdfRenderer>,
+    // Cloth blown by the camera wind, F, None without compute shaders
+    cloth: Option<ClothSim>,
+    cloth_enabled: bool,
+    last_frame: std::time::Instant,
+    fps: f32, // Smoothed so the counter is readable
+    // 2D overlay with the crosshair and the atlas test pattern, shown with the debug lines
+    sprite_batch: SpriteBatch,
+    sprite_white: SpriteTexture, // 1x1 white, tinted for solid rectangles
+    sprite_atlas: SpriteTexture,
+
+    // Gizmos: axes, ground grid and instance bounding boxes
+    debug_lines: DebugLines,
+    debug_lines_enabled: bool,
+    // Smooth paths (the light orbit) drawn with the debug lines
+    curve_renderer: CurveRenderer,
+    show_bounding_boxes: bool, // Only while the key is held
+    debug_grid_size: u32,
+    mesh_bounds: Aabb, // Local space box around the model
+
+    // Stencil masking, separate depth + stencil target so the main depth format stays sampleable
+    stencil_texture: texture::Texture,
+    stencil_mask_pipeline: wgpu::RenderPipeline,
+    stencil_test_pipeline: StencilTestPipeline,
+    xray_enabled: bool, // See through the picked instance using the stencil mask
+    // Rim around the picked instance, also from the stencil mask, see graphics/outline.rs
+    outline_pass: OutlinePass,
+    outline_color: [f32; 4],
+    outline_thickness: f32,
+
+    // Simplified copies of every mesh of obj_model, picked per instance by camera distance
+    lod_meshes: Vec<LodMesh>,
+    // Levels picked last frame, the hysteresis in lod::select_levels starts from them
+    instance_lods: Vec<usize>,
+    // Draw every LOD level in one flat color instead of the material, see LOD_DEBUG_COLORS
+    lod_debug: bool,
+    lod_debug_bind_groups: Vec<wgpu::BindGroup>,
+
+    // Blended pipeline for transparent materials, drawn after the opaque ones (graphics/transparency.rs)
+    transparent_pipeline: Arc<wgpu::RenderPipeline>,
+    transparent_quads: TransparentQuads,
+    show_transparent_quads: bool,
+
+    // Stereo rendering for VR, draws the scene once per eye into a wide texture when enabled
+    multiview: MultiviewState,
+    multiview_enabled: bool,
+    // Desktop camera and an overview side by side, 2
+    split_screen: SplitScreen,
+    split_screen_enabled: bool,
+    // Reflecting and refracting water plane below the instances, I
+    water: WaterPlane,
+    water_enabled: bool,
+
+    // GPU counters for the main pass and compute animation, None if the adapter cant do it
+    pipeline_stats: Option<PipelineStatsQuery>,
+    // GPU time of the main passes, None when timestamp queries are not supported
+    gpu_profiler: Option<GpuProfiler>,
+
+    // File loading on a background thread, results are uploaded in update
+    asset_loader: AssetLoader,
+    // Model being loaded and its file name, replaces the registry entry from the same file once
+    // it arrives (or is added as a new one)
+    streamed_model: Option<(String, WatchedAsset<model::Model>)>,
+    // File name of each streamed texture, it is also the cache key once uploaded
+    streamed_textures: Vec<(String, WatchedAsset<Arc<texture::Texture>>)>,
+    // Textures and shaders by path or name, so shared files are only uploaded once
+    resource_manager: ResourceManager,
+    // Pipeline variants built on first use, so the reload only rebuilds the scene shader ones
+    pipeline_cache: PipelineCache,
+
+    // Needs the window for its input, so there is none headless
+    #[cfg(feature = "gui")]
+    gui: Option<crate::gui::Gui>,
 }
 
 const NUM_INSTANCES_PER_ROW: u32 = 10;
+// Distances toggle_fog uses, the grid is 30 units across so its far rows end up fully fogged
+const FOG_START: f32 = 5.0;
+const FOG_END: f32 = 25.0;
+// Flag to start with the compute pass enabled, can be toggled at runtime
+const COMPUTE_ANIMATION_ENABLED: bool = false;
+// Degrees per second the light moves around the Y axis (used to be 1 degree per frame at ~60 fps)
+const LIGHT_ORBIT_SPEED: f32 = 60.0;
+// Extra room around the scene when framing it, 1.0 would touch the edges of the view
+const FRAME_MARGIN: f32 = 1.1;
+// Largest distance in world units between a drawn curve and the real one
+const CURVE_MAX_ERROR: f32 = 0.01;
+// How much the scale changes per key press
+const SCALE_STEP: f32 = 0.1;
 const INSTANCE_DISPLACEMENT: cgmath::Vector3<f32> = cgmath::Vector3::new(
     NUM_INSTANCES_PER_ROW as f32 * 0.5, 0.0, NUM_INSTANCES_PER_ROW as f32 * 0.5,);
+// Probes float above the left and right halves of the instance grid
+const LIGHT_PROBE_POSITIONS: [[f32; 3]; 2] = [[-7.5, 2.0, -1.5], [6.0, 2.0, -1.5]];
+const LIGHT_PROBE_RADIUS: f32 = 12.0;
+// HUD text size (line height) in physical pixels
+const HUD_TEXT_SIZE: f32 = 20.0;
+// Size in pixels of one cell of the sprite test atlas, and how big the sprites are drawn
+const SPRITE_ATLAS_CELL: u32 = 16;
+const SPRITE_SIZE: f32 = 48.0;
+// Default number of cells per side of the debug ground grid
+const DEBUG_GRID_SIZE: u32 = 30;
+// Stencil value left by one layer of mask geometry (mask pipeline increments from 0)
+const STENCIL_MASK_REF: u8 = 1;
+// Name of the light cube shader in the ResourceManager and its PipelineKey
+const LIGHT_SHADER_ID: &str = "light.wgsl";
+// Max camera distance of each LOD level, the last level covers everything beyond
+const LOD_DISTANCES: [f32; 3] = [10.0, 25.0, 50.0];
+// Tint of each level in the LOD debug view, green is the full mesh
+const LOD_DEBUG_COLORS: [[u8; 4]; 3] = [[40, 200, 40, 255], [230, 200, 30, 255], [220, 50, 40, 255]];
+
+// Everything derived from the model vertices, rebuilt whenever the model changes
+struct ModelGeometry {
+    bounding_sphere: (cgmath::Point3<f32>, f32),
+    bounds: Aabb,
+    lod_meshes: Vec<LodMesh>,
+    skinned_meshes: Vec<SkinnedMesh>,
+}
+
+impl ModelGeometry {
+    fn new(device: &wgpu::Device, obj_model: &model::Model) -> Self {
+        // Bounding sphere around all the meshes of the model, used for mouse picking
+        let all_vertices = obj_model.meshes.iter()
+            .flat_map(|mesh| mesh.vertices.iter().copied())
+            .collect::<Vec<_>>();
+        let mesh_bounding_sphere = picking::bounding_sphere(&all_vertices);
+        // Axis aligned box around the model for the debug bounding boxes and camera framing
+        let mesh_bounds = obj_model.bounds();
+
+        // LOD levels for every mesh, built once per loaded model
+        let lod_meshes = obj_model.meshes.iter()
+            .map(|mesh| LodMesh::from_mesh(device, &mesh.vertices, &mesh.indices, &LOD_DISTANCES))
+            .collect::<Vec<_>>();
+
+        // Two bone rig for every mesh in the model so we can twist it with update_skeleton
+        let skinned_meshes = obj_model.meshes.iter()
+            .map(|mesh| {
+                let skins = skinning::vertical_two_bone_skin(&mesh.vertices);
+                SkinnedMesh::new(device, &mesh.vertices, &skins)
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            bounding_sphere: mesh_bounding_sphere,
+            bounds: mesh_bounds,
+            lod_meshes,
+            skinned_meshes,
+        }
+    }
+}
 
 // Defined methods for the Window we create
 impl State {
     // Handshake with GPU to see what it supports and create device/queue
     // Make method async because some adapters/devices may take time to initialize
     // Constructor to initialize State
-    pub async fn new(window: Arc<Window>) -> anyhow::Result<State> {
+    pub async fn new(window: Arc<Window>, present_mode_preference: PresentModePreference) -> anyhow::Result<State> {
         let size = window.inner_size();
 
         // Instance is "The Manager" knows every GPU backend available
+        // WGPU_BACKEND can force a backend, see graphics/adapter.rs
+        let backends = adapter::backends_from_env();
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::PRIMARY,
+            backends,
+            // WGPU_VALIDATION and WGPU_DEBUG, see graphics/error_log.rs
+            flags: error_log::instance_flags(),
             ..Default::default()
         });
 
@@ -89,29 +362,12 @@ impl State {
 
         // Handler for graphics card, to get info about it and create device/queue
         // The actual selected GPU
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface), // Find adapter compatible with our surface
-                force_fallback_adapter: false, // If true will use software rendering
-            })
-            .await?;
+        // WGPU_ADAPTER_NAME can pick a specific one
+        let adapter = adapter::select_adapter(&instance, &surface, backends).await?;
 
         // Device is connection to GPU, Queue is needed to send commands since
         // We cannot say to gpu "Draw now" we send commands and wait for gpu to process them
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor {
-                label: None,
-                required_features: wgpu::Features::empty(),
-                experimental_features: wgpu::ExperimentalFeatures::disabled(),
-                required_limits: wgpu::Limits {
-                    max_bind_groups: 6,
-                    ..wgpu::Limits::default()
-                },
-                memory_hints: Default::default(),
-                trace: wgpu::Trace::Off,
-            })
-            .await?;
+        let (device, queue) = adapter::request_device(&adapter).await?;
 
         // Config for surface. This will define how surface creates SurfaceTextures
         let surface_caps = surface.get_capabilities(&adapter);
@@ -127,40 +383,79 @@ impl State {
             format: surface_format, // how SurfaceTextures will be stored
             width: size.width, // in pixels, usually matches window size
             height: size.height,
-            present_mode: surface_caps.present_modes[0], // how to sync surface with display
+            // how to sync surface with display, vsync or low latency, see graphics/present_mode.rs
+            present_mode: present_mode::choose_present_mode(present_mode_preference, &surface_caps.present_modes),
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
             desired_maximum_frame_latency: 2,
         };
 
-        // Load image into RAM
-        let diffuse_bytes = include_bytes!("../assets/happy-tree.png");
+        Self::from_device(
+            instance,
+            &adapter,
+            device,
+            queue,
+            config,
+            surface_caps.present_modes,
+            Some((window, surface)),
+        ).await
+    }
 
-        // Create bind group layout
-        let diffuse_bind_group_layout =
-            texture::create_texture_bind_group_layout(&device);
+    // State without a window or surface, for the benchmark (bench.rs)
+    // Any adapter will do since there is no surface to support, the frames go into a texture of
+    // config.format that the caller passes to render_offscreen
+    pub async fn new_headless(width: u32, height: u32) -> anyhow::Result<State> {
+        // Same backends as the windowed State, so the numbers are for the API the app really uses
+        let (instance, adapter) = headless::request_adapter(adapter::backends_from_env(), false).await?;
+        log::info!("Headless adapter: {}", adapter.get_info().name);
+        let (device, queue) = adapter::request_device(&adapter).await?;
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        Self::from_device(instance, &adapter, device, queue, config, Vec::new(), None).await
+    }
 
-        let depth_texture_bind_group_layout =
-            texture::create_depth_bind_group_layout(&device);
+    // Everything after the device, shared by the window and headless constructors
+    async fn from_device(
+        instance: wgpu::Instance,
+        adapter: &wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        config: wgpu::SurfaceConfiguration,
+        present_modes: Vec<wgpu::PresentMode>,
+        window_surface: Option<(Arc<Window>, wgpu::Surface<'static>)>,
+    ) -> anyhow::Result<State> {
+        let gpu_errors = GpuErrorLog::new();
+        let device_lost = watch_device(&device, &gpu_errors);
+        let (window, surface) = window_surface.unzip();
+
+        // Load image into RAM, from res/ on native and fetched next to the page on the web
+        let diffuse_bytes = resources::load_bytes("happy-tree.png").await?;
+
+        // Create bind group layouts once, every texture load below reuses them
+        let mut layout_cache = LayoutCache::new();
+        let mut pipeline_cache = PipelineCache::new();
+        let texture_layouts = texture::TextureLayoutCache::new(&device, &mut layout_cache);
+        let mut resource_manager = ResourceManager::new();
 
         // Helper method to transform image bytes into Texture object in GPU memory
         // Textures are not only image data, but is a combination of:
         // The raw pixel data in VRAM - the usage of that data (sampling in shaders)
         // and the instructions on how to look at that data ("lens" and "projector settings")
-        let diffuse_texture = texture::Texture::from_bytes(
-            &device,
-            &queue,
-            diffuse_bytes,
-            "happy-tree.png",
-        )?;
-
-        // Create bind group from texture
-        let diffuse_bind_group =
-            texture::create_bind_group_from_texture(
-                &device,
-                &diffuse_bind_group_layout,
-                &diffuse_texture,
-            );
+        // Uploaded once into the ResourceManager, the shapes below bind it through their materials
+        // Setup phases below run in error scopes, a failed one ends State::new with its label
+        // instead of a panic somewhere in wgpu
+        let diffuse_texture = gpu_errors.capture(&device, "happy-tree.png texture", || {
+            let key = ResourceKey::Path(resources::res_path("happy-tree.png"));
+            resource_manager.get_or_create_texture(&device, &queue, key, &diffuse_bytes)
+        }).await??;
 
         // Create camera with config
         let camera = camera::Camera::new(camera::CameraConfig {
@@ -181,11 +476,12 @@ impl State {
         camera_uniform.update_view_proj(&camera);
 
         // Create uniform buffer(GPU) for camera (The container)
-        let camera_buffer = buffers::create_uniform_buffer(&device, &camera_uniform);
+        let camera_buffer = gpu_errors.capture(&device, "camera buffer", || {
+            buffers::create_uniform_buffer(&device, &camera_uniform)
+        }).await?;
 
         // Create bind group layout for camera uniform
-        let camera_bind_group_layout =
-            CameraUniform::create_bind_group_layout(&device);
+        let camera_bind_group_layout = layout_cache.get(&device, LayoutKind::Camera);
 
         // Create bind group for camera uniform (The connection)
         // We use a bind group for each resource (texture, uniform buffer, etc)
@@ -203,35 +499,16 @@ impl State {
         // Create controls for the camera with a given speed
         let camera_controller = CameraController::new(0.1);
 
-        const SPACE_BETWEEN: f32 = 3.0;
-
-        // Generate a list of positions and rotations for instances based on a grid
-        // mapping over X and Z axis to create rows and columns
-        let instances = (0..NUM_INSTANCES_PER_ROW).flat_map(|z| {
-            (0..NUM_INSTANCES_PER_ROW).map(move |x| {
-                let x = SPACE_BETWEEN * (x as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
-                let z = SPACE_BETWEEN * (z as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
-
-                let position = cgmath::Vector3 { x, y: 0.0, z };
-
-                let rotation = if position.is_zero() {
-                    // Needed so object at (0,0,0) wont get scaled to zero
-                    // Quaternions can affect scale if not created correctly
-                    cgmath::Quaternion::from_axis_angle(cgmath::Vector3::unit_z(), cgmath::Deg(0.0))
-                } else {
-                    cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(45.0))
-                };
-
-                Instance {
-                    position, rotation,
-                }
-            })
-        }).collect::<Vec<_>>();
+        let instances = create_instances(NUM_INSTANCES_PER_ROW);
 
         // Convert instances to raw data for GPU
         let instance_data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
+        // Compute pass reads the same rest transforms and writes animated copies
+        let instance_animation = InstanceAnimation::new(&device, &instance_data, 0.5);
         // Create instance buffer in GPU memory
-        let instance_buffer = buffers::create_instance_buffer(&device, instance_data);
+        let instance_buffer = gpu_errors.capture(&device, "instance buffer", || {
+            buffers::create_instance_buffer(&device, instance_data)
+        }).await?;
 
 
 
@@ -242,15 +519,60 @@ impl State {
             a: 1.0,
         };
 
-        let obj_model =
+        let cube =
             resources::load_model(
                 "cube.obj",
                 &device,
                 &queue,
-                &diffuse_bind_group_layout,
+                &texture_layouts.material_bind_group_layout,
+                &mut resource_manager,
             )
             .await?;
 
+        // The hand written shapes share the tree texture uploaded above
+        let shape_material = |name: &str| {
+            model::Material::new(
+                &device,
+                &texture_layouts.material_bind_group_layout,
+                name,
+                Arc::clone(&diffuse_texture),
+                model::MaterialUniform::default(),
+            )
+        };
+        let pentagon = resources::shape_model(&device, "pentagon", PENT_VERTICES, PENT_INDICES, shape_material("pentagon"));
+        let complex_shape = resources::shape_model(
+            &device,
+            "complex shape",
+            COMPLEX_SHAPE_VERTICES,
+            COMPLEX_SHAPE_INDICES,
+            shape_material("complex shape"),
+        );
+
+        let mut models = ModelRegistry::new();
+        models.add("cube", Some("cube.obj"), cube);
+        models.add("pentagon", None, pentagon);
+        models.add("complex shape", None, complex_shape);
+        let obj_model = models.activate(0, model::Model::empty());
+
+        let ModelGeometry { bounding_sphere: mesh_bounding_sphere, bounds: mesh_bounds, lod_meshes, skinned_meshes } =
+            ModelGeometry::new(&device, &obj_model);
+
+        // Solid color textures standing in for the materials in the LOD debug view
+        let lod_debug_bind_groups = LOD_DEBUG_COLORS.iter()
+            .map(|&color| {
+                let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba(color)));
+                let texture = Arc::new(texture::Texture::from_image(&device, &queue, &img, Some("LOD Debug Texture"))?);
+                // Only the bind group is kept, it holds on to the texture and parameter buffer
+                let material = model::Material::new(
+                    &device,
+                    &texture_layouts.material_bind_group_layout,
+                    "LOD Debug",
+                    texture,
+                    model::MaterialUniform::default(),
+                );
+                Ok(material.bind_group)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
 
         let depth_texture = texture::Texture::create_depth_texture(&device, &config, "Depth Texture");
         let depth_visualization_texture = texture::Texture::create_depth_texture(&device, &config, "Depth Visualization Texture");
@@ -258,42 +580,93 @@ impl State {
         // Create bind group using depth bind group layout
         let depth_texture_bind_group = texture::create_bind_group_from_texture(
             &device,
-            &depth_texture_bind_group_layout,
+            &texture_layouts.depth_bind_group_layout,
             &depth_texture,
         );
 
+        // Screen space contact shadows read the depth texture after the main pass
+        let contact_shadow_pass = ContactShadowPass::new(&device, config.format, &depth_texture);
+
         // Create render mode uniform buffer
         let render_mode_uniform = RenderModeUniform {
             mode: 0, // Start in normal mode
-            _padding: [0; 3],
+            selected_instance: NO_SELECTION,
+            _padding: [0; 2],
         };
 
         let render_mode_buffer = buffers::create_uniform_buffer(&device, &render_mode_uniform);
 
+        // Time and resolution for animated shader effects, written every frame in update
+        let globals = GlobalsUniform::new(config.width, config.height);
+        let globals_buffer = buffers::create_uniform_buffer(&device, &globals);
+        let uniforms = UniformWriter::new(&device, &queue);
+
+        // All 8 bind group slots are taken (8 is as many as most adapters allow), so the globals
+        // share this group with the render mode, both are settings for the whole frame
         let render_mode_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Render Mode Bind Group Layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1, // Globals
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
                 },
-                count: None,
-            }],
+            ],
         });
 
         let render_mode_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Render Mode Bind Group"),
             layout: &render_mode_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: render_mode_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: render_mode_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: globals_buffer.as_entire_binding(),
+                },
+            ],
         });
 
 
+        // Model transform, starts at scale 1.0 so the scene looks the same as before
+        let scale = 1.0;
+        let transform_buffer = buffers::create_uniform_buffer(&device, &TransformUniform::new(scale));
+        let transform_bind_group_layout = transform::create_bind_group_layout(&device);
+        let transform_bind_group = transform::create_bind_group(
+            &device,
+            &transform_bind_group_layout,
+            &transform_buffer,
+        );
+
+        // Draws the same instances with the same scale, so it shares the transform layout
+        let motion_vector_pass = MotionVectorPass::new(&device, &config, &transform_bind_group_layout);
+        let prev_instance_transforms = instances.iter().map(Instance::model_matrix).collect::<Vec<_>>();
+        let taa_pass = TaaPass::new(&device, &config, &motion_vector_pass.velocity_texture);
+        let sss_pass = SubsurfaceScatteringPass::new(&device, &queue, &config, &transform_bind_group_layout, &obj_model.materials);
+        let vignette_pass = VignettePass::new(&device, &config);
+        let chromatic_aberration_pass = ChromaticAberrationPass::new(&device, &config);
+
+        let clip_buffer = buffers::create_uniform_buffer(&device, &clip::ClipUniform::new(None));
+        let clip_bind_group_layout = clip::create_bind_group_layout(&device);
+        let clip_bind_group = clip::create_bind_group(&device, &clip_bind_group_layout, &clip_buffer);
+
         // Light creation
         let light_uniform = LightUniform {
             position: [2.0, 2.0, 2.0],
@@ -303,13 +676,17 @@ impl State {
         };
 
         let light_buffer = buffers::create_uniform_buffer(&device, &light_uniform);
+        // Off until toggled, see set_fog
+        let fog_uniform = FogUniform::disabled();
+        let fog_buffer = buffers::create_uniform_buffer(&device, &fog_uniform);
 
         // Create bind group for light uniform
-        let light_bind_group_layout = light::create_bind_group_layout(&device);
+        let light_bind_group_layout = layout_cache.get(&device, LayoutKind::Light);
         let light_bind_group = light::create_bind_group_from_light(
             &device,
             &light_bind_group_layout,
-            &light_buffer
+            &light_buffer,
+            &fog_buffer,
         );
 
         // We create a separate pipeline for the light source because it has a diff shader
@@ -323,30 +700,52 @@ impl State {
                 }
             );
 
-            let shader = wgpu::ShaderModuleDescriptor {
-                label: Some("Light Shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("graphics/shaders/light.wgsl").into()),
-            };
-            create_render_pipeline(
+            let shader = resource_manager.get_or_create_shader(
                 &device,
-                &layout,
-                config.format,
-                Some(texture::Texture::DEPTH_FORMAT),
-                &[vertex::Vertex::desc()],
-                shader,
-            )
+                ResourceKey::Name(LIGHT_SHADER_ID.to_string()),
+                include_str!("graphics/shaders/light.wgsl"),
+            );
+            // Draws the loaded model meshes, so their vertex layout
+            let key = PipelineKey::new(LIGHT_SHADER_ID, config.format, VertexLayouts::Model);
+            pipeline_cache.get_or_create(&device, key, &layout, &shader)
         };
 
+        // Environment probes, every probe shares the same capture pipeline
+        let environment_bind_group_layout = light_probe::create_environment_bind_group_layout(&device);
+        let default_environment_bind_group =
+            light_probe::create_default_environment_bind_group(&device, &environment_bind_group_layout);
+        let probe_pipeline = ProbeCapture::create_pipeline(
+            &device,
+            &texture_layouts.material_bind_group_layout,
+            &camera_bind_group_layout,
+            &light_bind_group_layout,
+        );
+        let light_probes = LIGHT_PROBE_POSITIONS.iter()
+            .map(|position| ProbeCapture::new(
+                &device,
+                probe_pipeline.clone(),
+                &camera_bind_group_layout,
+                &environment_bind_group_layout,
+                (*position).into(),
+                LIGHT_PROBE_RADIUS,
+            ))
+            .collect::<Vec<_>>();
+        let instance_positions = instances.iter().map(|instance| instance.position).collect::<Vec<_>>();
+        let probe_runs = light_probe::assign_probes(&instance_positions, &light_probes);
+
         // Create pipeline layout, which describes the bind groups that will be used in the render pipeline
         let render_pipeline_layout = device.create_pipeline_layout(
             &wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
                 bind_group_layouts: &[ // this defines the group number we will use on shader
-                    &diffuse_bind_group_layout, // -> 1
+                    &texture_layouts.material_bind_group_layout, // -> 0
                     &camera_bind_group_layout,
-                    &depth_texture_bind_group_layout,
+                    &texture_layouts.depth_bind_group_layout,
                     &render_mode_bind_group_layout,
                     &light_bind_group_layout, // -> 4
+                    &transform_bind_group_layout, // -> 5
+                    &environment_bind_group_layout, // -> 6
+                    &clip_bind_group_layout, // -> 7
                 ],
                 immediate_size: 0,
             });
@@ -355,33 +754,137 @@ impl State {
         // GPU driver compiles shaders and optimizes the pipeline for the specific GPU
         // To do the optimization, GPU needs to know the SHAPE of the data, but it doesnt care
         // about the actual data. This allows to build the pipeline once, and swap out data buffers
-        let render_pipeline = {
-            let shader = wgpu::ShaderModuleDescriptor {
-                label: Some("Normal Shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("graphics/shaders/shader.wgsl").into()),
-            };
-            create_render_pipeline(
+        // The scene shader is shared by the normal, no cull, transparent and stencil pipelines, compiled once
+        // It is read from res/ at runtime for hot reloading, the copy built into the binary is a fallback
+        let scene_shader_source = resources::load_string(resources::SCENE_SHADER)
+            .await
+            .unwrap_or_else(|e| {
+                log::warn!("{:#}, using the built in scene shader", e);
+                include_str!("../res/shaders/shader.wgsl").to_string()
+            });
+        let scene_shader = resource_manager.get_or_create_shader(
+            &device,
+            ResourceKey::Name(pipelines::SCENE_SHADER_ID.to_string()),
+            &clip::scene_shader_source(
+                &scene_shader_source,
+                device.features().contains(wgpu::Features::CLIP_DISTANCES),
+            ),
+        );
+        let pipelines::ScenePipelines {
+            render: render_pipeline,
+            no_cull: render_pipeline_no_cull,
+            transparent: transparent_pipeline,
+            stencil_mask: stencil_mask_pipeline,
+            stencil_test: stencil_test_pipeline,
+        } = gpu_errors.capture(&device, "scene pipelines", || {
+            pipelines::create_scene_pipelines(
                 &device,
+                &mut pipeline_cache,
                 &render_pipeline_layout,
+                &scene_shader,
                 config.format,
-                Some(texture::Texture::DEPTH_FORMAT),
-                &[model::ModelVertex::desc(), InstanceRaw::desc()],
-                shader,
+                STENCIL_MASK_REF,
             )
-        };
+        }).await?;
+        let transparent_quads = TransparentQuads::new(&device, &queue, &texture_layouts.material_bind_group_layout)?;
+
+        // Text overlay for the HUD, positions are in physical pixels
+        let text_renderer = TextRenderer::new(&device, &queue, &texture_layouts, config.format, config.width, config.height)?;
+        let sdf_renderer = adapter.get_downlevel_capabilities().flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+            .then(|| SdfRenderer::new(&device, &queue, &texture_layouts, config.format, config.width, config.height))
+            .transpose()?;
+
+        // Cloth is simulated in compute shaders too, skipped the same way
+        let cloth = adapter.get_downlevel_capabilities().flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+            .then(|| ClothSim::new(&device, config.format, texture::Texture::DEPTH_FORMAT, &camera_bind_group_layout));
+
+        let mut sprite_batch = SpriteBatch::new(&device, &texture_layouts, config.format, config.width, config.height);
+        let white = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4])));
+        let white_texture = texture::Texture::from_image(&device, &queue, &white, Some("Sprite White"))?;
+        let sprite_white = sprite_batch.register_texture(&device, &white_texture);
+        let atlas = image::DynamicImage::ImageRgba8(sprite::test_pattern_atlas(SPRITE_ATLAS_CELL));
+        let mut atlas_texture = texture::Texture::from_image(&device, &queue, &atlas, Some("Sprite Test Atlas"))?;
+        // Nearest filtering so the checker cell stays sharp when scaled up
+        atlas_texture.sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let sprite_atlas = sprite_batch.register_texture(&device, &atlas_texture);
+
+        // Stencil mask and test pipelines share the main layout and shader
+        let stencil_texture = texture::Texture::create_depth_texture_with_format(
+            &device,
+            &config,
+            "Stencil Texture",
+            pipelines::STENCIL_FORMAT,
+        );
+        let outline_pass = OutlinePass::new(
+            &device,
+            config.format,
+            &camera_bind_group_layout,
+            &transform_bind_group_layout,
+            STENCIL_MASK_REF,
+        );
+
+        let debug_lines = DebugLines::new(
+            &device,
+            config.format,
+            texture::Texture::DEPTH_FORMAT,
+            &camera_bind_group_layout,
+        );
+
+        let curve_renderer = CurveRenderer::new(&device, config.format, &camera_bind_group_layout, CURVE_MAX_ERROR);
+
+        let multiview = MultiviewState::new(
+            &device,
+            &config,
+            &camera_bind_group_layout,
+            adapter.features().contains(wgpu::Features::MULTIVIEW),
+        );
+        let split_screen = SplitScreen::new(&device, &camera_bind_group_layout, config.width, config.height);
+        let water = WaterPlane::new(
+            &device,
+            &queue,
+            &config,
+            &camera_bind_group_layout,
+            &clip_bind_group_layout,
+            &globals_buffer,
+        );
 
-        Ok(Self {
+        let pipeline_stats = device.features()
+            .contains(wgpu::Features::PIPELINE_STATISTICS_QUERY)
+            .then(|| PipelineStatsQuery::new(&device));
+        let gpu_profiler = device.features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| GpuProfiler::new(&device, &queue));
+
+        #[cfg(feature = "gui")]
+        let gui = window.as_ref().map(|window| crate::gui::Gui::new(&device, &texture_layouts, config.format, window));
+
+        let mut state = Self {
+            instance,
             surface,
             device,
             queue,
             config,
+            present_modes,
             is_surface_configured: false,
+            scale_factor: window.as_ref().map_or(1.0, |window| window.scale_factor()),
+            occluded: false,
+            device_lost,
+            gpu_errors,
             window,
             clear_color,
             render_pipeline,
-            diffuse_bind_group,
-            diffuse_bind_group_layout,
-            diffuse_texture,
+            render_pipeline_layout,
+            render_pipeline_no_cull,
+            culling_enabled: true,
+            texture_layouts,
             camera,
             camera_uniform,
             camera_buffer,
@@ -389,218 +892,1990 @@ impl State {
             camera_controller,
             instances,
             instance_buffer,
+            instance_animation,
+            instances_per_row: NUM_INSTANCES_PER_ROW,
+            compute_animation_enabled: COMPUTE_ANIMATION_ENABLED,
             depth_texture,
             depth_visualization_texture,
             depth_texture_bind_group,
-            depth_texture_bind_group_layout,
-            depth_visualization_mode: false,
+            render_mode: RenderMode::Normal,
+            render_mode_uniform,
             render_mode_buffer,
             render_mode_bind_group,
+            globals,
+            globals_buffer,
+            uniforms,
+            render_stats_counter: RenderStatsCounter::default(),
+            render_stats: RenderStats::default(),
+            start_time: std::time::Instant::now(),
+            mesh_bounding_sphere,
+            picked_instance: None,
             obj_model,
+            models,
+            skinned_meshes,
+            skinning_enabled: false,
+            skeleton_angle: 0.0,
             light_uniform,
+            fog_uniform,
+            fog_buffer,
             light_buffer,
-            light_bind_group_layout,
             light_bind_group,
             light_render_pipeline,
-        })
+            contact_shadow_pass,
+            motion_vector_pass,
+            motion_vectors_enabled: false,
+            prev_instance_transforms,
+            taa_pass,
+            taa_enabled: false,
+            sss_pass,
+            sss_enabled: false,
+            vignette_pass,
+            vignette_strength: 0.0,
+            vignette_power: 2.0,
+            chromatic_aberration_pass,
+            chromatic_aberration_offset: 0.0,
+            scale,
+            transform_buffer,
+            transform_bind_group,
+            clip_plane: None,
+            clip_buffer,
+            clip_bind_group,
+            light_probes,
+            default_environment_bind_group,
+            probe_runs,
+            text_renderer,
+            sdf_renderer,
+            cloth,
+            cloth_enabled: false,
+            last_frame: std::time::Instant::now(),
+            fps: 0.0,
+            sprite_batch,
+            sprite_white,
+            sprite_atlas,
+            debug_lines,
+            debug_lines_enabled: false,
+            curve_renderer,
+            show_bounding_boxes: false,
+            debug_grid_size: DEBUG_GRID_SIZE,
+            mesh_bounds,
+            stencil_texture,
+            stencil_mask_pipeline,
+            stencil_test_pipeline,
+            xray_enabled: false,
+            outline_pass,
+            outline_color: outline::DEFAULT_COLOR,
+            outline_thickness: outline::DEFAULT_THICKNESS,
+            lod_meshes,
+            instance_lods: Vec::new(),
+            lod_debug: false,
+            lod_debug_bind_groups,
+            transparent_pipeline,
+            transparent_quads,
+            show_transparent_quads: false,
+            multiview,
+            multiview_enabled: false,
+            split_screen,
+            split_screen_enabled: false,
+            water,
+            water_enabled: false,
+            pipeline_stats,
+            gpu_profiler,
+            asset_loader: AssetLoader::new(),
+            streamed_model: None,
+            streamed_textures: Vec::new(),
+            resource_manager,
+            pipeline_cache,
+            #[cfg(feature = "gui")]
+            gui,
+        };
+
+        // Capture the probes once so reflections show up from the first frame
+        state.bake_light_probes();
+
+        Ok(state)
     }
 
     // Method to resize the surface when window size changes
     // Surface is a collection of buffers that need the right memory size to store the needed
     // amount of pixels, and that amount changes when window is resized
     pub fn resize(&mut self, width: u32, height: u32) {
-        // If check to avoid 0 sized surfaces -> panic in wgpu
-        if width > 0 && height > 0 {
-            self.config.width = width;
-            self.config.height = height;
-            self.surface.configure(&self.device, &self.config);
+        // 0 sized surfaces panic in wgpu, this happens when the window is minimized
+        // Keep the old configuration and stop rendering until we get a real size back
+        if !is_renderable_size(width, height) {
+            self.is_surface_configured = false;
+            return;
+        }
+
+        self.config.width = width;
+        self.config.height = height;
+        // While suspended only the config is updated, resume configures the new surface with it
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
             self.is_surface_configured = true;
-            // Recreate depth texture for new size
-            // Important this is done after surface is configured
-            // we pass the actual and updated self fields, else we would be creating
-            // depth texture with old size before the update
-            self.depth_texture = texture::Texture::create_depth_texture(&self.device, &self.config, "Depth Texture");
-            self.depth_visualization_texture = texture::Texture::create_depth_texture(&self.device, &self.config, "Depth Visualization Texture");
-
-            // For depth visualization mode, recreate bind group when resize is called
-            // so we have the correct depth texture
-            self.depth_texture_bind_group = texture::create_bind_group_from_texture(
-                &self.device,
-                &self.depth_texture_bind_group_layout,
-                &self.depth_visualization_texture,
-            )
         }
+        // Recreate depth texture for new size
+        // Important this is done after surface is configured
+        // we pass the actual and updated self fields, else we would be creating
+        // depth texture with old size before the update
+        self.depth_texture = texture::Texture::create_depth_texture(&self.device, &self.config, "Depth Texture");
+        self.depth_visualization_texture = texture::Texture::create_depth_texture(&self.device, &self.config, "Depth Visualization Texture");
+
+        // For depth visualization mode, recreate bind group when resize is called
+        // so we have the correct depth texture
+        self.depth_texture_bind_group = texture::create_bind_group_from_texture(
+            &self.device,
+            &self.texture_layouts.depth_bind_group_layout,
+            &self.depth_visualization_texture,
+        );
+        self.contact_shadow_pass.resize(&self.device, &self.depth_texture);
+        self.motion_vector_pass.resize(&self.device, &self.config);
+        self.taa_pass.resize(&self.device, &self.config, &self.motion_vector_pass.velocity_texture);
+        self.sss_pass.resize(&self.device, &self.config);
+        self.vignette_pass.resize(&self.device, &self.config);
+        self.chromatic_aberration_pass.resize(&self.device, &self.config);
+        self.globals.resolution = [width as f32, height as f32];
+        self.uniforms.write(&self.globals_buffer, &self.globals);
+        self.text_renderer.resize(&self.queue, width, height);
+        if let Some(sdf_renderer) = &self.sdf_renderer {
+            sdf_renderer.resize(&self.queue, width, height);
+        }
+        self.sprite_batch.resize(&self.queue, width, height);
+        self.stencil_texture = texture::Texture::create_depth_texture_with_format(
+            &self.device,
+            &self.config,
+            "Stencil Texture",
+            pipelines::STENCIL_FORMAT,
+        );
+        self.multiview.resize(&self.device, &self.config);
+        self.split_screen.resize(width, height);
+        self.water.resize(&self.device, &self.config);
+    }
+
+    pub fn set_occluded(&mut self, occluded: bool) {
+        self.occluded = occluded;
+        // render stops asking for frames while occluded, kick the loop again
+        if !occluded
+            && let Some(window) = &self.window
+        {
+            window.request_redraw();
+        }
+    }
+
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Acquire)
     }
 
     pub fn set_clear_color(&mut self, clear_color: wgpu::Color) {
         self.clear_color = clear_color;
+        // The fog follows the background, otherwise the far instances fade into a different color
+        if self.fog_uniform.enabled != 0 {
+            self.set_fog(clear_color, self.fog_uniform.start, self.fog_uniform.end);
+        }
+    }
+
+    // Fade everything between start and end units from the camera into color (see FogUniform)
+    // The clear color moves with the mouse and takes the fog color along, see set_clear_color
+    pub fn set_fog(&mut self, color: wgpu::Color, start: f32, end: f32) {
+        self.write_fog(FogUniform::new(color, start, end));
+    }
+
+    // The near instances stay clear, the far end of the grid is gone
+    pub fn toggle_fog(&mut self) {
+        if self.fog_uniform.enabled != 0 {
+            self.write_fog(FogUniform::disabled());
+        } else {
+            self.set_fog(self.clear_color, FOG_START, FOG_END);
+        }
+        log::info!("Fog: {}", self.fog_uniform.enabled != 0);
+    }
+
+    fn write_fog(&mut self, fog: FogUniform) {
+        self.fog_uniform = fog;
+        self.uniforms.write(&self.fog_buffer, &fog);
+    }
+
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
     }
 
     pub fn config(&self) -> &wgpu::SurfaceConfiguration {
         &self.config
     }
 
-    pub fn toggle_shape(&mut self) {
-        // Toggle logic: if 0 and method called, set to 1
-        // Potentially use enum if more shapes are added
-        // Removed implementation
-        //self.active_shape = if self.active_shape == 0 { 1 } else { 0 };
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.config.present_mode
+    }
+
+    // Takes effect on the next frame, only the surface is reconfigured, nothing else depends on it
+    pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) {
+        if !self.present_modes.contains(&mode) {
+            log::warn!("Present mode {:?} is not supported (available: {:?})", mode, self.present_modes);
+            return;
+        }
+        self.config.present_mode = mode;
+        // A minimized window keeps its old configuration, resize applies the new mode later
+        if self.is_surface_configured && let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
+        log::info!("Present mode: {:?}", mode);
     }
 
-    pub fn toggle_depth_visualization(&mut self) {
-        // Simple toggle for depth visualization mode
-        self.depth_visualization_mode = !self.depth_visualization_mode;
+    // The OS is taking the window away (Android going to the background, some browsers), the
+    // surface would be invalid from now on so it is dropped. Everything else lives on the device
+    // and stays, resume only has to make a new surface
+    pub fn suspend(&mut self) {
+        self.surface = None;
+        self.is_surface_configured = false;
+    }
 
-        // Update render uniform buffer with new mode
-        let render_mode_uniform = RenderModeUniform {
-            mode: if self.depth_visualization_mode { 1 } else { 0 },
-            _padding: [0; 3],
+    // New surface for the same window, configured with the config we had before the suspend
+    // Nothing to do if we were never suspended, so desktop (one resumed at startup) is unaffected
+    pub fn resume(&mut self) -> anyhow::Result<()> {
+        if self.surface.is_some() {
+            return Ok(());
+        }
+        let Some(window) = &self.window else {
+            return Ok(());
         };
+        let surface = self.instance.create_surface(window.clone())?;
+        // A minimized window comes back with a Resized event, that configures it
+        if is_renderable_size(self.config.width, self.config.height) {
+            surface.configure(&self.device, &self.config);
+            self.is_surface_configured = true;
+        }
+        self.surface = Some(surface);
+        Ok(())
+    }
 
-        // Write to the GPU buffer in what mode we want to be
-        self.queue.write_buffer(
-            &self.render_mode_buffer,
-            0,
-            bytemuck::cast_slice(&[render_mode_uniform]),
-        );
+    pub fn is_suspended(&self) -> bool {
+        self.surface.is_none()
     }
 
-    pub fn window(&self) -> &Arc<Window> {
-        &self.window
+    pub fn cycle_present_mode(&mut self) {
+        self.set_present_mode(present_mode::next_present_mode(self.config.present_mode, &self.present_modes));
     }
 
-    pub fn update(&mut self) {
-        // Camera update
-        self.camera_controller.update_camera(&mut self.camera);
-        self.camera_uniform.update_view_proj(&self.camera);
-        self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+    // Space, the next registered model after the active one
+    pub fn cycle_model(&mut self) {
+        if let Some(next) = self.models.next() {
+            self.set_active_model(next);
+        }
+    }
 
-        // Light Update
-        let old_position: cgmath::Vector3<_> = self.light_uniform.position.into();
-        self.light_uniform.position =
-            (cgmath::Quaternion::from_axis_angle((0.0, 1.0, 0.0).into(), cgmath::Deg(1.0))
-                * old_position)
-                .into();
-        self.queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&[self.light_uniform]));
+    // Name of the drawn model for the title and HUD, None when the registry is empty
+    pub fn active_model_name(&self) -> Option<&str> {
+        self.models.active_entry().map(|entry| entry.name.as_str())
     }
 
-    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        self.window.request_redraw();
+    // Draw another registered model, by index or name. False when there is no such model
+    pub fn set_active_model<'a>(&mut self, model: impl Into<ModelRef<'a>>) -> bool {
+        let Some(index) = self.models.find(model.into()) else {
+            return false;
+        };
+        if self.models.active() == Some(index) {
+            return true;
+        }
+        let outgoing = std::mem::replace(&mut self.obj_model, model::Model::empty());
+        let incoming = self.models.activate(index, outgoing);
+        self.set_model(incoming);
+        true
+    }
 
-        // Cant render if surface is not configured
-        if !self.is_surface_configured {
-            return Ok(());
+    // Register a model under name, it becomes the drawn one when nothing is drawn yet
+    pub fn add_model(&mut self, name: &str, source: Option<&str>, model: model::Model) -> usize {
+        let index = self.models.add(name, source, model);
+        if self.models.active().is_none() {
+            self.set_active_model(index);
         }
+        index
+    }
 
-        // Get the next frame to render to
-        let output = self.surface.get_current_texture()?;
-        // Control how the render interacts with the texture
-        // A texture is the 2D array of pixels that we will draw to and then present to screen
-        // Texture view is how we going to use that texture in the render pass
-        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+    // Unregister a model. Removing the drawn one moves on to the model that took its place in the
+    // list, with none left only the clear color (and whatever else is enabled) is drawn
+    #[allow(dead_code)] // No key removes models yet
+    pub fn remove_model<'a>(&mut self, model: impl Into<ModelRef<'a>>) -> bool {
+        let Some(index) = self.models.find(model.into()) else {
+            return false;
+        };
+        let was_active = self.models.active() == Some(index);
+        self.models.remove(index);
+        if was_active {
+            if self.models.is_empty() {
+                self.set_model(model::Model::empty());
+            } else {
+                self.set_active_model(index % self.models.len());
+            }
+        }
+        true
+    }
 
-        // Create actual commands to send to GPU. Builds a command buffer
-        // Modern graphics expect commands to be stored in a command buffer before being sent
-        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Render Encoder"),
+    // Edit a material of the drawn model, base color, metallic and roughness are written to its
+    // uniform buffer when the returned guard is dropped. None when index is out of range
+    #[allow(dead_code)] // No key edits materials yet
+    pub fn material_mut(&mut self, index: usize) -> Option<model::MaterialMut<'_>> {
+        let material = self.obj_model.materials.get_mut(index)?;
+        Some(model::MaterialMut::new(material, &self.queue))
+    }
+
+    // Draw a mesh of the drawn model with another of its materials. Every pipeline takes any
+    // material bind group, so this only changes which one is bound for the mesh next frame
+    #[allow(dead_code)] // No key switches materials yet
+    pub fn set_mesh_material(&mut self, mesh: usize, material: usize) -> bool {
+        if material >= self.obj_model.materials.len() {
+            return false;
+        }
+        let Some(mesh) = self.obj_model.meshes.get_mut(mesh) else {
+            return false;
+        };
+        mesh.material = material;
+        true
+    }
+
+    // Load an obj file from res/ in the background, it is registered under its file name once ready
+    pub fn load_model(&mut self, file_name: &str) {
+        if let Some((loading, asset)) = &self.streamed_model {
+            match &asset.state {
+                AssetState::Loading if loading == file_name => return, // Already on its way
+                AssetState::Failed(error) => log::info!("Retrying model load, last attempt failed: {}", error),
+                _ => {}
+            }
+        }
+        let handle = self.asset_loader.request_model(file_name);
+        self.streamed_model = Some((file_name.to_string(), WatchedAsset::new(handle)));
+        log::info!("Loading {} in the background", file_name);
+    }
+
+    // Normal, depth, grayscale, UV and back to normal
+    pub fn cycle_render_mode(&mut self) {
+        self.render_mode = self.render_mode.next();
+        log::info!("Render mode: {:?}", self.render_mode);
+
+        // Update render uniform buffer with new mode
+        self.render_mode_uniform.mode = self.render_mode.id();
+        self.write_render_mode();
+    }
+
+    // Write to the GPU buffer in what mode we want to be
+    fn write_render_mode(&self) {
+        let bytes = bytemuck::bytes_of(&self.render_mode_uniform);
+        self.queue.write_buffer(&self.render_mode_buffer, 0, bytes);
+        self.render_stats_counter.record(|stats| stats.bytes_written += bytes.len() as u64);
+    }
+
+    // Cast a ray from the cursor (physical pixels) and select the closest instance it hits
+    // Clicking on empty space clears the selection
+    // Picks against the rest transforms, the compute animation offset is not taken into account
+    pub fn pick_instance(&mut self, x: f64, y: f64) -> Option<usize> {
+        let view_proj = self.camera.build_view_projection_matrix();
+        let ray = picking::screen_to_ray(x, y, self.config.width, self.config.height, view_proj);
+
+        // Model scale is applied in the vertex shader, so scale the sphere the same way
+        let (local_center, radius) = self.mesh_bounding_sphere;
+        let (local_center, radius) = (local_center * self.scale, radius * self.scale);
+        self.picked_instance = ray.and_then(|ray| {
+            let spheres = self.instances.iter()
+                .map(|instance| {
+                    // Rotation doesnt change a sphere, only the rotated center position matters
+                    let center = instance.position + instance.rotation * local_center.to_vec();
+                    (cgmath::Point3::from_vec(center), radius)
+                })
+                .collect::<Vec<_>>();
+            picking::pick_closest(&ray, &spheres)
         });
 
-        // RenderPass has all the methods for actual drawing.
-        // Here we populate with shaders, buffers, textures, etc
-        {
-            // Begin a render pass borrows the encoder mutably so thats why
-            // we have this nested scope so later we can call encoder.finish()
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view, // specific texture memory to draw to
-                    resolve_target: None, // anti-aliasing resolve target
-                    depth_slice: None, //
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(self.clear_color), // Clear color before drawing
-                        store: wgpu::StoreOp::Store, // Store the result in memory after render pass
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.texture_view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0), // Clear depth to farthest
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
-                occlusion_query_set: None,
-                timestamp_writes: None,
-                multiview_mask: None,
-            });
+        log::info!("Picked instance: {:?}", self.picked_instance);
+        self.render_mode_uniform.selected_instance = self.picked_instance
+            .map(|index| index as u32)
+            .unwrap_or(NO_SELECTION);
+        self.write_render_mode();
+        self.picked_instance
+    }
+
+    // Number of ray march steps for contact shadows, 0 disables the pass
+    pub fn set_contact_shadow_steps(&mut self, steps: u32) {
+        self.contact_shadow_pass.set_steps(steps);
+    }
 
+    // World space length of the contact shadow ray, 0 disables the pass
+    pub fn set_contact_shadow_distance(&mut self, distance: f32) {
+        self.contact_shadow_pass.set_distance(distance);
+    }
+
+    // World space box around every instance of the model, with the model scale applied
+    pub fn scene_bounds(&self) -> Aabb {
+        let local = self.mesh_bounds.scaled(self.scale);
+        self.instances.iter()
+            .map(|instance| local.transformed(&instance.model_matrix()))
+            .fold(Aabb::EMPTY, |bounds, instance_bounds| bounds.union(&instance_bounds))
+    }
 
+    // Move the camera back (or closer) until the whole scene fits the view, keeping the direction it looks in
+    pub fn frame_scene(&mut self) {
+        let bounds = self.scene_bounds();
+        let view_dir = self.camera.target - self.camera.eye;
+        let Some((eye, target)) = bounds::frame_bounds(&bounds, self.camera.fovy, self.camera.aspect, view_dir, FRAME_MARGIN) else {
+            log::info!("Nothing to frame, the scene is empty");
+            return;
+        };
+        self.camera.eye = eye;
+        self.camera.target = target;
+        // Big models end up far away, the far plane has to reach past the back of the scene
+        let far_side = (eye - bounds.center()).magnitude() + bounds.radius();
+        self.camera.zfar = self.camera.zfar.max(far_side * 1.1);
+        log::info!("Framed scene, camera at {:?} looking at {:?}", eye, target);
+    }
 
-            // Removed impl. Using model loader instead
-            // Buffer selection based on active shape
-            // If active_shape is 0, use first buffers, else use second buffers
-            //let (vertex_buffer, index_buffer, num_indices) = if self.active_shape == 0 {
-            //    (&self.vertex_buffer, &self.index_buffer, self.num_indices)
-            //} else {
-            //    (&self.vertex_buffer_2, &self.index_buffer_2, self.num_indices_2)
-            //};
+    // Put the camera somewhere else, the controller keeps moving it from there
+    pub fn set_camera_view(&mut self, eye: cgmath::Point3<f32>, target: cgmath::Point3<f32>) {
+        self.camera.eye = eye;
+        self.camera.target = target;
+    }
 
+    // Change the model scale by a number of steps (negative shrinks), clamped to a sane range
+    pub fn adjust_scale(&mut self, steps: f32) {
+        let uniform = TransformUniform::new(self.scale + steps * SCALE_STEP);
+        self.scale = uniform.scale;
+        self.queue.write_buffer(&self.transform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+        self.render_stats_counter.record(|stats| stats.bytes_written += size_of::<TransformUniform>() as u64);
+        log::info!("Model scale: {:.1}", self.scale);
+    }
 
+    pub fn toggle_transparent_quads(&mut self) {
+        self.show_transparent_quads = !self.show_transparent_quads;
+        log::info!("Transparent test quads: {}", self.show_transparent_quads);
+    }
 
-            // Set the vertex buffer to use
-            // Method 1st param, is what buffer slot to use for this vertex buffer
-            // We can have multiple vertex buffers bound at once (positions, colors, uvs, etc)
-            // Second param, slice of the buffer to use, we can store multiple meshes in one buffer
-            // (..) means use full buffer
-            // Removed implementation for single model loaded from obj
-            //render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+    // Cut away everything behind the plane (see graphics/clip.rs), None shows the whole scene again
+    pub fn set_clip_plane(&mut self, plane: Option<clip::ClipPlane>) {
+        self.clip_plane = plane;
+        self.queue.write_buffer(&self.clip_buffer, 0, bytemuck::cast_slice(&[clip::ClipUniform::new(plane)]));
+        self.render_stats_counter.record(|stats| stats.bytes_written += size_of::<clip::ClipUniform>() as u64);
+    }
 
-            // Set the instance buffer at slot 1 for instanced rendering
-            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+    // Vertical cut through the middle of the instance grid, only the right half (+x) stays
+    // Cut instances are open on the plane, turn culling off (B) to see their inside faces
+    pub fn toggle_clip_plane(&mut self) {
+        let plane = match self.clip_plane {
+            Some(_) => None,
+            None => Some(clip::ClipPlane { normal: [1.0, 0.0, 0.0], distance: 0.0 }),
+        };
+        self.set_clip_plane(plane);
+        log::info!("Clip plane: {:?}", plane);
+    }
 
+    // Swap between back face culling and drawing both sides of every triangle
+    // If a shape has holes with culling on but looks complete with it off, its winding is wrong
+    pub fn toggle_culling(&mut self) {
+        self.culling_enabled = !self.culling_enabled;
+        let cull_mode = if self.culling_enabled { Some(wgpu::Face::Back) } else { None };
+        log::info!("Cull mode: {:?}, front face: {:?}", cull_mode, wgpu::FrontFace::Ccw);
+    }
 
-            // Set new PIPELINE for light source, we want to draw it with a different shader and only use camera and light bind groups
-            render_pass.set_pipeline(&self.light_render_pipeline);
-            render_pass.draw_light_model(
-                &self.obj_model,
-                &self.camera_bind_group,
-                &self.light_bind_group,
-            );
+    pub fn toggle_debug_lines(&mut self) {
+        self.debug_lines_enabled = !self.debug_lines_enabled;
+        log::info!("Debug lines enabled: {}", self.debug_lines_enabled);
+    }
 
-            // Here we set the pipeline (shaders + fixed function state) and issue draw commands
-            render_pass.set_pipeline(&self.render_pipeline);
+    // Starts from the flat rest pose every time it is turned on
+    pub fn toggle_cloth(&mut self) {
+        let Some(cloth) = &mut self.cloth else {
+            log::warn!("Cloth needs compute shaders, not supported on this adapter");
+            return;
+        };
+        self.cloth_enabled = !self.cloth_enabled;
+        if self.cloth_enabled {
+            cloth.reset(&self.queue);
+        }
+        log::info!("Cloth enabled: {}", self.cloth_enabled);
+    }
+
+    // Bounding boxes are shown while the key is held, on top of the other gizmos
+    pub fn set_show_bounding_boxes(&mut self, show: bool) {
+        self.show_bounding_boxes = show;
+    }
 
+    pub fn set_debug_grid_size(&mut self, size: u32) {
+        self.debug_grid_size = size;
+    }
 
-            // Set the bind group for the depth texture
-            render_pass.set_bind_group(2, &self.depth_texture_bind_group, &[]);
-            // Set the bind group for the render mode uniform
-            render_pass.set_bind_group(3, &self.render_mode_bind_group, &[]);
-            // Set the bind group for the light uniform
-            //render_pass.set_bind_group(4, &self.light_bind_group, &[]);
+    // Queue this frame's gizmos, they are uploaded right before the render pass
+    fn queue_debug_lines(&mut self) {
+        if !self.debug_lines_enabled {
+            return;
+        }
 
-            // Index buffer is a memory optimization to reuse vertices for multiple triangles
-            // We create a matrix of indices saying what vertices are shared between triangles
-            // This way we dont have to duplicate vertex data in memory
-            //render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        self.debug_lines.grid(self.debug_grid_size, 1.0, [0.4, 0.4, 0.4]);
+        self.debug_lines.axes(cgmath::Point3::new(0.0, 0.0, 0.0), 2.0);
+
+        if self.show_bounding_boxes && !self.mesh_bounds.is_empty() {
+            let local_corners = self.mesh_bounds.scaled(self.scale).corners();
+            for instance in &self.instances {
+                // Rotate the local box corners with the instance, so the box follows the model
+                let corners = local_corners.map(|local| {
+                    cgmath::Point3::from_vec(instance.position + instance.rotation * local.to_vec())
+                });
+                self.debug_lines.box_corners(&corners, [1.0, 1.0, 0.0]);
+            }
+        }
+    }
 
+    // Circle the light travels on, the orbit is around the y axis so only the radius and height change
+    fn debug_curves(&self) -> Vec<BezierCurve> {
+        if !self.debug_lines_enabled {
+            return Vec::new();
+        }
+        let [x, y, z] = self.light_uniform.position;
+        curves::horizontal_circle([0.0, y, 0.0], x.hypot(z), [1.0, 0.8, 0.2, 0.8])
+    }
 
-            use model::DrawModel;
-            // Draw call
-            // Draw the model with instancing
-            render_pass.draw_model_instanced(
-                &self.obj_model,
-                0..self.instances.len() as u32,
-                &self.camera_bind_group,
-                &self.light_bind_group
-            );
-        } // Scope ends here, so render_pass is dropped and encoder can be used again
+    pub fn toggle_xray(&mut self) {
+        self.xray_enabled = !self.xray_enabled;
+        log::info!("X-ray through picked instance: {}", self.xray_enabled);
+    }
 
+    // Nothing reads the velocity texture yet, so this only shows up in a GPU capture
+    pub fn toggle_motion_vectors(&mut self) {
+        self.motion_vectors_enabled = !self.motion_vectors_enabled;
+        log::info!("Motion vectors: {}", self.motion_vectors_enabled);
+    }
 
-        // Submit commands to GPU queue for execution
-        // Submit will accept anything that implements IntoIterator<Item=&CommandBuffer>
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+    pub fn toggle_taa(&mut self) {
+        self.taa_enabled = !self.taa_enabled;
+        // Whatever is in the history is from before it was turned off
+        self.taa_pass.reset_history();
+        log::info!("TAA: {}", self.taa_enabled);
+    }
 
-        Ok(())
+    // Extra mask and blur passes after the scene, only materials with sss_strength are affected
+    // The multiview eyes dont get it
+    pub fn set_sss_enabled(&mut self, enabled: bool) {
+        self.sss_enabled = enabled;
+        log::info!("Subsurface scattering: {}", self.sss_enabled);
+    }
+
+    pub fn is_sss_enabled(&self) -> bool {
+        self.sss_enabled
+    }
+
+    // TAA blends with the history of one camera, the split views would smear into each other
+    fn taa_active(&self) -> bool {
+        self.taa_enabled && !self.multiview_enabled && !self.split_screen_enabled
+    }
+
+    pub fn toggle_split_screen(&mut self) {
+        self.split_screen_enabled = !self.split_screen_enabled;
+        log::info!("Split screen: {}", self.split_screen_enabled);
+    }
+
+    // The reflection and refraction passes only run while the water is on
+    // Not drawn in split screen, the targets are rendered for the desktop camera only
+    // Flat color per LOD level instead of the textures, green is the full mesh (LOD_DEBUG_COLORS)
+    pub fn toggle_lod_debug(&mut self) {
+        self.lod_debug = !self.lod_debug;
+        log::info!("LOD debug colors: {}", self.lod_debug);
+    }
+
+    pub fn toggle_water(&mut self) {
+        self.water_enabled = !self.water_enabled;
+        log::info!("Water: {}", self.water_enabled);
+    }
+
+    pub fn toggle_multiview(&mut self) {
+        self.multiview_enabled = !self.multiview_enabled;
+        log::info!(
+            "Multiview: {} (single pass supported: {})",
+            self.multiview_enabled,
+            self.multiview.supports_single_pass,
+        );
+    }
+
+    // Read the drawn model from disk again on the asset thread, it replaces the current one when ready
+    // The shapes from vertex.rs have no file, nothing to reload for those
+    pub fn reload_model(&mut self) {
+        let Some(source) = self.models.active_entry().and_then(|entry| entry.source.clone()) else {
+            log::info!("The active model was not loaded from a file, nothing to reload");
+            return;
+        };
+        self.load_model(&source);
+    }
+
+    // Read the scene shader from res/ again and rebuild every pipeline that uses it
+    // A shader that doesnt compile (or no longer matches the layout) is logged and the old pipelines stay
+    pub fn reload_shader(&mut self) {
+        let path = resources::hot_reload_path(resources::SCENE_SHADER);
+        let source = match std::fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(e) => {
+                log::error!("Unable to read {}: {}", path.display(), e);
+                return;
+            }
+        };
+        let source = clip::scene_shader_source(&source, self.device.features().contains(wgpu::Features::CLIP_DISTANCES));
+
+        // Errors go to the uncaptured error handler by default
+        // In a scope they are handed back to us instead, the new objects are just invalid
+        let result = pollster::block_on(self.gpu_errors.capture(&self.device, "shader reload", || {
+            let shader = Arc::new(self.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(pipelines::SCENE_SHADER_ID),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            }));
+            // Cached variants of the old shader go, the ones drawn right now are held by self until replaced
+            self.pipeline_cache.invalidate_shader(pipelines::SCENE_SHADER_ID);
+            let pipelines = pipelines::create_scene_pipelines(
+                &self.device,
+                &mut self.pipeline_cache,
+                &self.render_pipeline_layout,
+                &shader,
+                self.config.format,
+                STENCIL_MASK_REF,
+            );
+            (shader, pipelines)
+        }));
+        let Ok((shader, pipelines)) = result else {
+            log::error!("Shader reload failed, keeping the old pipelines");
+            // The invalid ones shouldnt be handed out later
+            self.pipeline_cache.invalidate_shader(pipelines::SCENE_SHADER_ID);
+            return;
+        };
+
+        self.render_pipeline = pipelines.render;
+        self.render_pipeline_no_cull = pipelines.no_cull;
+        self.transparent_pipeline = pipelines.transparent;
+        self.stencil_mask_pipeline = pipelines.stencil_mask;
+        self.stencil_test_pipeline = pipelines.stencil_test;
+        self.resource_manager.replace_shader(ResourceKey::Name(pipelines::SCENE_SHADER_ID.to_string()), shader);
+        log::info!("Reloaded {}", path.display());
+    }
+
+    // Texture loaded in the background, streamed_texture gives it back once uploaded
+    #[allow(dead_code)] // No material swaps textures at runtime yet
+    pub fn stream_texture(&mut self, file_name: &str) -> AssetHandle {
+        let handle = self.asset_loader.request_texture(file_name);
+        self.streamed_textures.push((file_name.to_string(), WatchedAsset::new(handle)));
+        handle
+    }
+
+    #[allow(dead_code)]
+    pub fn streamed_texture(&self, handle: AssetHandle) -> Option<&Arc<texture::Texture>> {
+        self.streamed_textures.iter().find(|(_, asset)| asset.handle == handle)?.1.get()
+    }
+
+    // Swap in a new model and rebuild what depends on its vertices
+    fn set_model(&mut self, obj_model: model::Model) {
+        let geometry = ModelGeometry::new(&self.device, &obj_model);
+        self.sss_pass.write_materials(&self.device, &self.queue, &obj_model.materials);
+        self.obj_model = obj_model;
+        self.mesh_bounding_sphere = geometry.bounding_sphere;
+        self.mesh_bounds = geometry.bounds;
+        self.lod_meshes = geometry.lod_meshes;
+        self.skinned_meshes = geometry.skinned_meshes;
+        // Reflections still show the old model
+        self.bake_light_probes();
+    }
+
+    // GPU upload of whatever the asset thread finished, the disk work is already done
+    fn receive_assets(&mut self) {
+        for result in self.asset_loader.finished() {
+            match result {
+                LoadResult::Model(handle, data) => {
+                    let Some((file_name, asset)) = self.streamed_model.as_mut().filter(|(_, asset)| asset.handle == handle) else {
+                        continue; // Superseded by a newer request
+                    };
+                    let model = data.and_then(|data| {
+                        resources::upload_model(
+                            data,
+                            &self.device,
+                            &self.queue,
+                            &self.texture_layouts.material_bind_group_layout,
+                            &mut self.resource_manager,
+                        )
+                    });
+                    match model {
+                        Ok(model) => {
+                            // The model moves into the registry, nothing left to watch
+                            let file_name = std::mem::take(file_name);
+                            self.streamed_model = None;
+                            match self.models.find_source(&file_name) {
+                                Some(index) => {
+                                    if let Some(model) = self.models.replace(index, model) {
+                                        self.set_model(model);
+                                    }
+                                    log::info!("{} reloaded", file_name);
+                                }
+                                None => {
+                                    let name = file_name.trim_end_matches(".obj");
+                                    self.add_model(name, Some(&file_name), model);
+                                    log::info!("{} loaded", file_name);
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            log::error!("Failed to load model: {:#}", e);
+                            asset.state = AssetState::Failed(e.to_string());
+                        }
+                    }
+                }
+                LoadResult::Texture(handle, bytes) => {
+                    let Some((file_name, asset)) = self.streamed_textures.iter_mut().find(|(_, asset)| asset.handle == handle) else {
+                        continue;
+                    };
+                    let key = ResourceKey::Path(resources::res_path(file_name));
+                    let texture = bytes.and_then(|bytes| {
+                        self.resource_manager.get_or_create_texture(&self.device, &self.queue, key, &bytes)
+                    });
+                    asset.state = match texture {
+                        Ok(texture) => AssetState::Loaded(texture),
+                        Err(e) => {
+                            log::error!("Failed to load texture: {:#}", e);
+                            AssetState::Failed(e.to_string())
+                        }
+                    };
+                }
+            }
+        }
+    }
+
+    // Entry point for a VR runtime, view projection matrix of each eye for the next frames
+    // Once called the eyes stop following the desktop camera
+    #[allow(dead_code)] // Nothing drives a headset yet
+    pub fn set_vr_eye_matrices(&mut self, left: cgmath::Matrix4<f32>, right: cgmath::Matrix4<f32>) {
+        self.multiview.set_eye_matrices(left, right);
+    }
+
+    // Bind groups the scene shader needs besides the ones DrawModel sets (material, camera, light)
+    fn set_scene_bind_groups<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_bind_group(2, &self.depth_texture_bind_group, &[]);
+        render_pass.set_bind_group(3, &self.render_mode_bind_group, &[]);
+        render_pass.set_bind_group(5, &self.transform_bind_group, &[]);
+        render_pass.set_bind_group(6, &self.default_environment_bind_group, &[]);
+        render_pass.set_bind_group(7, &self.clip_bind_group, &[]);
+    }
+
+    // Start a pass that writes the stencil mask, draw the mask geometry into the returned pass
+    // Clears the stencil first, so only geometry drawn in this pass defines the mask
+    pub fn begin_stencil_mask<'a>(&'a self, encoder: &'a mut wgpu::CommandEncoder) -> wgpu::RenderPass<'a> {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Stencil Mask Pass"),
+            color_attachments: &[], // Mask has no color output
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.stencil_texture.texture_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(0),
+                    store: wgpu::StoreOp::Store, // Kept for render_with_stencil_mask
+                }),
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+            multiview_mask: None,
+        });
+        render_pass.set_pipeline(&self.stencil_mask_pipeline);
+        self.set_scene_bind_groups(&mut render_pass);
+        render_pass
+    }
+
+    // Finish the mask pass, after this the stencil buffer holds the mask
+    // Passes record until they are dropped, so ending one is just dropping it
+    pub fn end_stencil_mask(&self, render_pass: wgpu::RenderPass) {
+        drop(render_pass);
+    }
+
+    // Start a pass on top of view where draws only land inside the mask
+    pub fn render_with_stencil_mask<'a>(
+        &'a self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        view: &'a wgpu::TextureView,
+    ) -> wgpu::RenderPass<'a> {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Stencil Test Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load, // Draw over what is already there
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.stencil_texture.texture_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load, // The mask we just wrote
+                    store: wgpu::StoreOp::Store,
+                }),
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+            multiview_mask: None,
+        });
+        self.stencil_test_pipeline.apply(&mut render_pass);
+        self.set_scene_bind_groups(&mut render_pass);
+        render_pass
+    }
+
+    // X-ray: the picked instance becomes the mask, and everything except it is drawn again inside
+    // the mask with a fresh depth buffer, so we see whatever is hidden behind it
+    fn render_xray(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        use model::DrawModel;
+        let Some(picked) = self.picked_instance.filter(|_| self.xray_enabled) else {
+            return;
+        };
+        let picked = picked as u32;
+        let instance_count = self.instances.len() as u32;
+
+        let mut mask_pass = self.begin_stencil_mask(encoder);
+        mask_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        mask_pass.draw_model_instanced(&self.obj_model, picked..picked + 1, &self.camera_bind_group, &self.light_bind_group);
+        self.end_stencil_mask(mask_pass);
+
+        let mut render_pass = self.render_with_stencil_mask(encoder, view);
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        for instances in [0..picked, picked + 1..instance_count] {
+            render_pass.draw_model_instanced(&self.obj_model, instances, &self.camera_bind_group, &self.light_bind_group);
+        }
+    }
+
+    // Outline: the picked instance becomes the mask again and a bigger flat copy of it is drawn
+    // outside the mask. Without a pick nothing runs, the frame is drawn from scratch anyway
+    fn render_outline(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        use model::DrawModel;
+        let Some(picked) = self.picked_instance else {
+            return;
+        };
+        let picked = picked as u32;
+
+        // Clears the stencil, whatever the x-ray or an older selection left is gone
+        let mut mask_pass = self.begin_stencil_mask(encoder);
+        mask_pass.set_vertex_buffer(1, self.active_instance_buffer().slice(..));
+        mask_pass.draw_model_instanced(&self.obj_model, picked..picked + 1, &self.camera_bind_group, &self.light_bind_group);
+        self.end_stencil_mask(mask_pass);
+
+        let mut render_pass = self.render_with_stencil_mask(encoder, view);
+        self.outline_pass.draw(
+            &mut render_pass,
+            &self.obj_model,
+            self.active_instance_buffer(),
+            picked,
+            &self.camera_bind_group,
+            &self.transform_bind_group,
+        );
+    }
+
+    pub fn toggle_compute_animation(&mut self) {
+        self.compute_animation_enabled = !self.compute_animation_enabled;
+        log::info!("Compute animation enabled: {}", self.compute_animation_enabled);
+    }
+
+    pub fn toggle_skinning(&mut self) {
+        self.skinning_enabled = !self.skinning_enabled;
+        log::info!("Skinning enabled: {}", self.skinning_enabled);
+    }
+
+    // Upload new bone matrices and run the skinning compute pass for every skinned mesh
+    // Submitted right away so the skinned buffers are ready before the next render pass
+    pub fn update_skeleton(&mut self, bone_matrices: &[cgmath::Matrix4<f32>]) {
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Skinning Encoder"),
+        });
+        for skinned_mesh in &self.skinned_meshes {
+            skinned_mesh.write_bones(&self.queue, bone_matrices);
+            skinned_mesh.dispatch(&mut encoder);
+        }
+        self.queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    // Render every probe cubemap from the current scene
+    // Light and instances move, so this is a snapshot until the next bake
+    pub fn bake_light_probes(&mut self) {
+        // The bake reads the light, its latest upload has to be on the GPU first
+        self.uniforms.flush();
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Probe Bake Encoder"),
+        });
+        for probe in &self.light_probes {
+            probe.bake(self, &mut encoder, probe.position);
+        }
+        self.queue.submit(std::iter::once(encoder.finish()));
+        log::info!("Baked {} light probes", self.light_probes.len());
+    }
+
+    // Queue text to be drawn on top of this frame, x and y are physical pixels from the top left
+    // px is the line height, characters the font doesnt have are drawn as a box
+    pub fn draw_text(&mut self, text: &str, x: f32, y: f32, px: f32, color: wgpu::Color) {
+        match &mut self.sdf_renderer {
+            Some(sdf_renderer) => sdf_renderer.draw_text(text, [x, y], px, color),
+            None => self.text_renderer.queue_text(text, x, y, px, color),
+        }
+    }
+
+    // Window moved to a monitor with another scale factor (or the setting changed)
+    // The surface size comes with the Resized event that follows, only the HUD sizes change here
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+    }
+
+    // Crosshair in the middle of the window and the 4 atlas cells in the bottom right corner
+    fn draw_sprites(&mut self) {
+        let (width, height) = (self.config.width as f32, self.config.height as f32);
+        let full = Rect::new(0.0, 0.0, 1.0, 1.0);
+        let white = [1.0, 1.0, 1.0, 0.8];
+        let scale = self.scale_factor as f32;
+        let (arm, thickness) = (10.0 * scale, 2.0 * scale);
+        self.sprite_batch.draw(self.sprite_white, full, Rect::new(width * 0.5 - arm, height * 0.5 - thickness * 0.5, arm * 2.0, thickness), white);
+        self.sprite_batch.draw(self.sprite_white, full, Rect::new(width * 0.5 - thickness * 0.5, height * 0.5 - arm, thickness, arm * 2.0), white);
+
+        // Every cell is its own draw() call, they still end up in a single flush
+        let cell = SPRITE_ATLAS_CELL as f32;
+        let size = SPRITE_SIZE * scale;
+        for (i, (column, row)) in [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)].into_iter().enumerate() {
+            let x = width - (4 - i) as f32 * (size + 4.0 * scale);
+            let y = height - size - 10.0 * scale;
+            self.sprite_batch.draw(
+                self.sprite_atlas,
+                Rect::new(column * cell, row * cell, cell, cell),
+                Rect::new(x, y, size, size),
+                [1.0; 4],
+            );
+        }
+    }
+
+    // FPS counter, name of the shape being drawn and the frame stats in the top left corner
+    fn draw_hud(&mut self) {
+        let now = std::time::Instant::now();
+        let frame_time = now.duration_since(self.last_frame).as_secs_f32();
+        self.last_frame = now;
+        if frame_time > 0.0 {
+            // Exponential moving average, mostly the last ~10 frames
+            self.fps = if self.fps == 0.0 { 1.0 / frame_time } else { self.fps * 0.9 + 0.1 / frame_time };
+        }
+
+        let shape = self.active_model_name().unwrap_or("none").to_string();
+        let stats = self.frame_stats();
+        let mut hud = format!("FPS: {:.0}\nShape: {}", stats.fps, shape);
+        if self.pipeline_stats.is_some() {
+            hud.push_str(&format!(
+                "\nVertices: {}\nPrimitives: {}\nFragments: {}\nCompute: {}",
+                stats.pipeline.vertex_invocations,
+                stats.pipeline.primitives,
+                stats.pipeline.fragment_invocations,
+                stats.pipeline.compute_invocations,
+            ));
+        }
+        // Per pass GPU times, only with timestamp queries
+        for line in stats.gpu_timing_lines().into_iter().chain(self.render_stats.hud_lines()) {
+            hud.push('\n');
+            hud.push_str(&line);
+        }
+        // Sizes are in logical pixels, the text renderers draw in physical ones
+        let scale = self.scale_factor as f32;
+        self.draw_text(&hud, 10.0 * scale, 10.0 * scale, HUD_TEXT_SIZE * scale, wgpu::Color::WHITE);
+    }
+
+    // Counters of the last frame whose results made it back, all zero when unsupported
+    pub fn pipeline_statistics(&self) -> PipelineStats {
+        self.pipeline_stats.as_ref().map(PipelineStatsQuery::latest).unwrap_or_default()
+    }
+
+    // What the HUD and the gui show, the GPU numbers are from a frame or more ago
+    pub fn frame_stats(&self) -> FrameStats {
+        FrameStats {
+            fps: self.fps,
+            pipeline: self.pipeline_statistics(),
+            gpu_passes: self.gpu_profiler.as_ref().map(|profiler| profiler.latest().to_vec()).unwrap_or_default(),
+        }
+    }
+
+    // Draw calls, triangles, uploads and texture memory of the last rendered frame (graphics/render_stats.rs)
+    pub fn render_stats(&self) -> RenderStats {
+        self.render_stats
+    }
+
+    // Uploads since the last frame belong to this one, update() writes most of them
+    fn finish_render_stats(&mut self) {
+        let uniform_bytes = self.uniforms.take_bytes_written();
+        self.render_stats_counter.record(|stats| stats.bytes_written += uniform_bytes);
+        self.render_stats = self.render_stats_counter.finish(self.resource_manager.texture_bytes());
+    }
+
+    // Triangles of the full detail model for every instance, what --bench counts per frame
+    // LOD levels and back face culling mean the GPU really does less, so this is an upper bound
+    pub fn scene_triangle_count(&self) -> u64 {
+        let model_triangles: u64 = self.obj_model.meshes.iter().map(|mesh| mesh.index_buffer.count as u64 / 3).sum();
+        model_triangles * self.instances.len() as u64
+    }
+
+    // Off writes the uniforms with queue.write_buffer again, to compare the two in --bench
+    pub fn set_uniform_staging(&mut self, staging: bool) {
+        self.uniforms.set_staging(staging);
+    }
+
+    pub fn uniform_staging(&self) -> bool {
+        self.uniforms.is_staging()
+    }
+
+    pub fn gpu_errors(&self) -> &GpuErrorLog {
+        &self.gpu_errors
+    }
+
+    // Append the GPU pass timings of every frame to a CSV file (--trace-file)
+    pub fn start_gpu_trace(&mut self, path: &std::path::Path) {
+        let Some(profiler) = &mut self.gpu_profiler else {
+            log::warn!("Timestamp queries are not supported by this adapter, no GPU trace is written");
+            return;
+        };
+        if let Err(e) = profiler.start_trace(path) {
+            log::error!("Unable to create the GPU trace file {}: {}", path.display(), e);
+        }
+    }
+
+    // Flush and close the GPU trace, call before the app exits
+    pub fn finalize_profiler(&mut self) {
+        if let Some(profiler) = &mut self.gpu_profiler {
+            profiler.finalize();
+        }
+    }
+
+    // Summary of every GPU error this State ran into, printed when the app exits
+    pub fn report_gpu_errors(&self) {
+        if let Some(report) = self.gpu_errors.report() {
+            log::warn!("{}", report);
+        }
+    }
+
+    // Only App calls this, and App always makes its States with a window
+    pub fn window(&self) -> &Arc<Window> {
+        self.window.as_ref().expect("headless State has no window")
+    }
+
+    // Seconds since the app started, the same clock the shaders see
+    // Not read outside the shaders yet, it is here so new systems use it instead of their own timer
+    #[allow(dead_code)]
+    pub fn time(&self) -> f32 {
+        self.globals.time
+    }
+
+    // Length of the last frame in seconds, clamped to globals::MAX_DELTA
+    pub fn delta_time(&self) -> f32 {
+        self.globals.delta
+    }
+
+    pub fn update(&mut self) {
+        // Uploads of a frame that was never rendered (hidden window) go out on their own
+        self.uniforms.flush();
+        self.receive_assets();
+
+        // Clock first, everything below animates with the same delta
+        self.globals.advance(self.start_time.elapsed().as_secs_f32());
+        self.uniforms.write(&self.globals_buffer, &self.globals);
+
+        // Camera update
+        self.camera_controller.update_camera(&mut self.camera);
+        self.camera_uniform.update_view_proj(&self.camera);
+        // Sub-pixel jitter for TAA, only the scene camera gets it, the motion vectors stay unjittered
+        let mut scene_view_proj = self.camera.build_view_projection_matrix();
+        if self.taa_active() {
+            scene_view_proj = self.taa_pass.jitter_matrix(self.config.width, self.config.height) * scene_view_proj;
+            self.camera_uniform.set_view_proj(scene_view_proj, self.camera.get_eye());
+        }
+        if self.sss_enabled {
+            self.sss_pass.update(&self.queue, scene_view_proj);
+        }
+        self.uniforms.write(&self.camera_buffer, &self.camera_uniform);
+        if self.multiview_enabled {
+            self.multiview.follow_camera(&self.camera);
+        }
+        if self.split_screen_enabled {
+            self.split_screen.follow_camera(&self.queue, &self.camera);
+        }
+        if self.water_enabled {
+            self.water.update(&self.queue, &self.camera);
+        }
+
+        // Light Update, one full orbit every 6 seconds
+        let old_position: cgmath::Vector3<_> = self.light_uniform.position.into();
+        let orbit_step = cgmath::Deg(LIGHT_ORBIT_SPEED * self.delta_time());
+        self.light_uniform.position =
+            (cgmath::Quaternion::from_axis_angle((0.0, 1.0, 0.0).into(), orbit_step)
+                * old_position)
+                .into();
+        self.uniforms.write(&self.light_buffer, &self.light_uniform);
+
+        // Contact shadows need the same camera and light as the scene
+        if self.contact_shadow_pass.is_enabled() {
+            self.contact_shadow_pass.update(
+                &self.queue,
+                self.camera.build_view_projection_matrix(),
+                self.light_uniform.position,
+            );
+        }
+
+        // Skeleton update, bottom bone stays still and the top bone twists back and forth
+        if self.skinning_enabled {
+            self.skeleton_angle += 2.0;
+            let twist = cgmath::Deg(45.0 * self.skeleton_angle.to_radians().sin());
+            let bones = [
+                cgmath::Matrix4::from_angle_y(cgmath::Deg(0.0)),
+                cgmath::Matrix4::from_angle_y(twist),
+            ];
+            self.update_skeleton(&bones);
+        }
+
+        // Compute animation update, the dispatch itself is recorded in render before the render pass
+        if self.compute_animation_enabled {
+            self.instance_animation.update(&self.queue, self.delta_time());
+        }
+
+        // The camera controller decides the wind, so walking around blows the cloth
+        let delta_time = self.delta_time();
+        if self.cloth_enabled
+            && let Some(cloth) = &mut self.cloth
+        {
+            cloth.update(&self.queue, delta_time, self.camera_controller.wind(&self.camera));
+        }
+
+        // Last frame's camera and instance matrices for the motion vectors
+        // The compute animation keeps its own previous matrices on the GPU, see compute.wgsl
+        self.motion_vector_pass.update(&self.queue, self.camera.build_view_projection_matrix());
+        let instance_data = self.instances.iter()
+            .zip(&self.prev_instance_transforms)
+            .map(|(instance, prev)| instance.to_raw_with_prev(*prev))
+            .collect::<Vec<_>>();
+        let instance_bytes = bytemuck::cast_slice::<_, u8>(&instance_data);
+        self.queue.write_buffer(&self.instance_buffer, 0, instance_bytes);
+        self.render_stats_counter.record(|stats| stats.bytes_written += instance_bytes.len() as u64);
+        self.prev_instance_transforms = self.instances.iter().map(Instance::model_matrix).collect();
+    }
+
+    // Reflection and refraction of the water, the scene clipped at the surface into each target
+    // Has to run before the main pass, the water surface samples both
+    fn render_water_targets(&self, encoder: &mut wgpu::CommandEncoder, instance_lods: &[usize]) {
+        for (target, camera_bind_group, clip_bind_group) in self.water.scene_passes(&self.camera_bind_group) {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Water Target Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target.color.texture_view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.clear_color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &target.depth.texture_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+                multiview_mask: None,
+            });
+            self.draw_scene_clipped(&mut render_pass, camera_bind_group, clip_bind_group, instance_lods);
+        }
+    }
+
+    // Normal desktop view: scene pass into view, then the effects that read its depth buffer
+    fn render_scene(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        instance_lods: &[usize],
+        stats: Option<&PipelineStatsQuery>,
+    ) {
+        // RenderPass has all the methods for actual drawing.
+        // Here we populate with shaders, buffers, textures, etc
+        {
+            // Begin a render pass borrows the encoder mutably so thats why
+            // we have this nested scope so later we can call encoder.finish()
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view, // specific texture memory to draw to
+                    resolve_target: None, // anti-aliasing resolve target
+                    depth_slice: None, //
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.clear_color), // Clear color before drawing
+                        store: wgpu::StoreOp::Store, // Store the result in memory after render pass
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture.texture_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0), // Clear depth to farthest
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: self.gpu_profiler.as_ref()
+                    .and_then(|profiler| profiler.render_pass_writes(gpu_profiler::SCENE_PASS)),
+                multiview_mask: None,
+            });
+            if let Some(stats) = stats {
+                render_pass.begin_pipeline_statistics_query(&stats.query_set, profiler::RENDER_QUERY);
+            }
+            if self.split_screen_enabled {
+                // Same pass for every camera, only the viewport and the camera bind group change
+                for (viewport, camera_bind_group) in self.split_screen.views() {
+                    render_pass.set_viewport(
+                        viewport.x as f32,
+                        viewport.y as f32,
+                        viewport.width as f32,
+                        viewport.height as f32,
+                        0.0,
+                        1.0,
+                    );
+                    render_pass.set_scissor_rect(viewport.x, viewport.y, viewport.width, viewport.height);
+                    self.draw_scene(&mut render_pass, camera_bind_group, instance_lods);
+                }
+            } else {
+                self.draw_scene(&mut render_pass, &self.camera_bind_group, instance_lods);
+                // After the transparent quads, the water is opaque and writes depth, so quads
+                // below the surface still show through the refraction target
+                if self.water_enabled {
+                    self.water.draw(&mut render_pass, &self.camera_bind_group);
+                }
+            }
+            if stats.is_some() {
+                render_pass.end_pipeline_statistics_query();
+            }
+        } // Scope ends here, so render_pass is dropped and encoder can be used again
+
+        // See through the picked instance, uses its own depth so it goes before the contact shadows
+        self.render_xray(encoder, view);
+
+        // Velocity of every pixel, drawn into its own texture so it doesnt touch the frame
+        if self.motion_vectors_enabled || self.taa_active() {
+            self.motion_vector_pass.render(
+                encoder,
+                &self.obj_model,
+                self.active_instance_buffer(),
+                0..self.instances.len() as u32,
+                &self.transform_bind_group,
+            );
+        }
+
+        // Darken small creases using the depth buffer written by the pass above
+        self.contact_shadow_pass.render(
+            encoder,
+            view,
+            self.gpu_profiler.as_ref().and_then(|profiler| profiler.render_pass_writes(gpu_profiler::SHADOW_PASS)),
+        );
+
+        // Last, so nothing darkens or covers the selection
+        self.render_outline(encoder, view);
+    }
+
+    // Everything in the main scene pass, shared with the multiview eye passes
+    // camera_bind_group picks the point of view, the rest of the scene state is the same
+    pub(crate) fn draw_scene<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a wgpu::BindGroup,
+        instance_lods: &[usize],
+    ) {
+        self.draw_scene_clipped(render_pass, camera_bind_group, &self.clip_bind_group, instance_lods);
+    }
+
+    // Same with a different clip plane, the water passes keep only one side of the surface
+    fn draw_scene_clipped<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a wgpu::BindGroup,
+        clip_bind_group: &'a wgpu::BindGroup,
+        instance_lods: &[usize],
+    ) {
+        // Removed impl. Using model loader instead
+        // Buffer selection based on active shape
+        // If active_shape is 0, use first buffers, else use second buffers
+        //let (vertex_buffer, index_buffer, num_indices) = if self.active_shape == 0 {
+        //    (&self.vertex_buffer, &self.index_buffer, self.num_indices)
+        //} else {
+        //    (&self.vertex_buffer_2, &self.index_buffer_2, self.num_indices_2)
+        //};
+
+
+
+        // Set the vertex buffer to use
+        // Method 1st param, is what buffer slot to use for this vertex buffer
+        // We can have multiple vertex buffers bound at once (positions, colors, uvs, etc)
+        // Second param, slice of the buffer to use, we can store multiple meshes in one buffer
+        // (..) means use full buffer
+        // Removed implementation for single model loaded from obj
+        //render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+
+        // Set the instance buffer at slot 1 for instanced rendering
+        render_pass.set_vertex_buffer(1, self.active_instance_buffer().slice(..));
+
+
+        // Set new PIPELINE for light source, we want to draw it with a different shader and only use camera and light bind groups
+        render_pass.set_pipeline(&self.light_render_pipeline);
+        render_pass.draw_light_model(
+            &self.obj_model,
+            camera_bind_group,
+            &self.light_bind_group,
+        );
+        self.render_stats_counter.record(|stats| {
+            for mesh in &self.obj_model.meshes {
+                stats.record_draw(mesh.index_buffer.count, 0..1);
+                stats.bind_group_switches += 2; // Camera and light
+            }
+        });
+
+        // Here we set the pipeline (shaders + fixed function state) and issue draw commands
+        let render_pipeline = if self.culling_enabled {
+            &self.render_pipeline
+        } else {
+            &self.render_pipeline_no_cull
+        };
+        render_pass.set_pipeline(render_pipeline);
+
+
+        // Set the bind group for the depth texture
+        render_pass.set_bind_group(2, &self.depth_texture_bind_group, &[]);
+        // Set the bind group for the render mode uniform
+        render_pass.set_bind_group(3, &self.render_mode_bind_group, &[]);
+        // Set the bind group for the light uniform
+        //render_pass.set_bind_group(4, &self.light_bind_group, &[]);
+        // Set the bind group for the model transform (scale)
+        render_pass.set_bind_group(5, &self.transform_bind_group, &[]);
+        // Set the bind group for the clip plane
+        render_pass.set_bind_group(7, clip_bind_group, &[]);
+        self.render_stats_counter.record(|stats| stats.bind_group_switches += 4);
+
+        // Index buffer is a memory optimization to reuse vertices for multiple triangles
+        // We create a matrix of indices saying what vertices are shared between triangles
+        // This way we dont have to duplicate vertex data in memory
+        //render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+
+
+        // Draw call
+        // Draw the model with instancing
+        // Instances are drawn in runs that share the same environment probe
+        for (instances, probe) in &self.probe_runs {
+            let environment_bind_group = probe
+                .map(|index| &self.light_probes[index].bind_group)
+                .unwrap_or(&self.default_environment_bind_group);
+            render_pass.set_bind_group(6, environment_bind_group, &[]);
+            self.render_stats_counter.record(|stats| stats.bind_group_switches += 1);
+
+            if self.skinning_enabled {
+                // Same draw but with vertices coming from the skinning compute pass
+                for (mesh, skinned_mesh) in self.obj_model.meshes.iter().zip(&self.skinned_meshes) {
+                    if self.obj_model.materials[mesh.material].transparent {
+                        continue;
+                    }
+                    render_pass.draw_skinned_mesh_instanced(
+                        mesh,
+                        skinned_mesh,
+                        &self.obj_model.materials[mesh.material],
+                        instances.clone(),
+                        camera_bind_group,
+                        &self.light_bind_group,
+                    );
+                    self.record_mesh_draw(mesh.index_buffer.count, instances.clone());
+                }
+            } else {
+                // Split the run again by LOD level, skinning always uses the full mesh
+                for (instances, level) in lod::lod_runs(instances.clone(), instance_lods) {
+                    for (mesh, lod_mesh) in self.obj_model.meshes.iter().zip(&self.lod_meshes) {
+                        if self.obj_model.materials[mesh.material].transparent {
+                            continue; // Drawn sorted in draw_transparent
+                        }
+                        let material_bind_group = if self.lod_debug {
+                            &self.lod_debug_bind_groups[level.min(self.lod_debug_bind_groups.len() - 1)]
+                        } else {
+                            &self.obj_model.materials[mesh.material].bind_group
+                        };
+                        render_pass.draw_lod_mesh_instanced(
+                            lod_mesh,
+                            level,
+                            material_bind_group,
+                            instances.clone(),
+                            camera_bind_group,
+                            &self.light_bind_group,
+                        );
+                        self.record_mesh_draw(lod_mesh.levels[level].index_buffer.count, instances.clone());
+                    }
+                }
+            }
+        }
+
+        // Debug lines are opaque too, they change the pipeline and reuse the scene camera
+        self.debug_lines.draw(render_pass, camera_bind_group);
+
+        if self.cloth_enabled
+            && let Some(cloth) = &self.cloth
+        {
+            cloth.draw(render_pass, camera_bind_group);
+        }
+
+        // Transparent last, they need everything behind them already drawn
+        self.draw_transparent(render_pass, camera_bind_group, clip_bind_group);
+    }
+
+    // When the compute animation is enabled we use the buffer written by the compute pass
+    fn active_instance_buffer(&self) -> &wgpu::Buffer {
+        if self.compute_animation_enabled {
+            &self.instance_animation.output_buffer
+        } else {
+            &self.instance_buffer
+        }
+    }
+
+    // Every transparent mesh instance and test quad, farthest from the camera first
+    // Each one is its own draw call so the order can change every frame
+    fn draw_transparent<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a wgpu::BindGroup,
+        clip_bind_group: &'a wgpu::BindGroup,
+    ) {
+        use model::DrawModel;
+
+        // (mesh, material, instance buffer, instance index) and where it is in the world
+        let mut draws = Vec::new();
+        let mut positions = Vec::new();
+        // Sorted by the rest positions, close enough while the compute animation moves them
+        let instance_buffer = self.active_instance_buffer();
+        for mesh in &self.obj_model.meshes {
+            let material = &self.obj_model.materials[mesh.material];
+            if !material.transparent {
+                continue;
+            }
+            for (index, instance) in self.instances.iter().enumerate() {
+                draws.push((mesh, material, instance_buffer, index as u32));
+                positions.push(cgmath::Point3::from_vec(instance.position));
+            }
+        }
+        if self.show_transparent_quads {
+            let quads = &self.transparent_quads;
+            for (index, position) in quads.positions.iter().enumerate() {
+                draws.push((&quads.model.meshes[0], &quads.model.materials[index], &quads.instance_buffer, index as u32));
+                positions.push(*position);
+            }
+        }
+        if draws.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.transparent_pipeline);
+        self.set_scene_bind_groups(render_pass);
+        render_pass.set_bind_group(7, clip_bind_group, &[]);
+        self.render_stats_counter.record(|stats| stats.bind_group_switches += 6);
+        // Multiview eyes sit right next to the camera, its eye is good enough for both
+        for index in transparency::back_to_front(self.camera.eye, &positions) {
+            let (mesh, material, instance_buffer, instance) = draws[index];
+            render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+            render_pass.draw_mesh_instanced(mesh, material, instance..instance + 1, camera_bind_group, &self.light_bind_group);
+            self.record_mesh_draw(mesh.index_buffer.count, instance..instance + 1);
+        }
+    }
+
+    // A draw of the scene pipeline, the helpers in model.rs, lod.rs and skinning.rs all set the
+    // material, camera and light bind groups before it
+    fn record_mesh_draw(&self, index_count: u32, instances: std::ops::Range<u32>) {
+        self.render_stats_counter.record(|stats| {
+            stats.record_draw(index_count, instances);
+            stats.bind_group_switches += 3;
+        });
+    }
+
+    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        // Hidden window, dont render and dont request more frames until it shows up again
+        // Same while suspended, resume asks for the next frame
+        if self.occluded || self.is_suspended() {
+            return Ok(());
+        }
+
+        if let Some(window) = &self.window {
+            window.request_redraw();
+        }
+
+        // Cant render if surface is not configured
+        if !self.is_surface_configured {
+            return Ok(());
+        }
+        let Some(surface) = &self.surface else {
+            return Ok(());
+        };
+
+        // Get the next frame to render to
+        let output = surface.get_current_texture()?;
+        // Control how the render interacts with the texture
+        // A texture is the 2D array of pixels that we will draw to and then present to screen
+        // Texture view is how we going to use that texture in the render pass
+        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Create actual commands to send to GPU. Builds a command buffer
+        // Modern graphics expect commands to be stored in a command buffer before being sent
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render Encoder"),
+        });
+        self.encode_frame(&mut encoder, &view);
+
+        // Submit commands to GPU queue for execution
+        // Submit will accept anything that implements IntoIterator<Item=&CommandBuffer>
+        // The uniform copies go first, the passes read what update wrote
+        let uploads = self.uniforms.take_commands();
+        self.queue.submit(uploads.into_iter().chain(std::iter::once(encoder.finish())));
+        self.uniforms.recall();
+        self.start_readbacks();
+        output.present();
+
+        Ok(())
+    }
+
+    // Same frame as render into a texture of config.format instead of the surface, for --bench
+    // Nothing waits for the GPU here, the caller polls the device when it needs the result
+    pub fn render_offscreen(&mut self, view: &wgpu::TextureView) {
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Offscreen Render Encoder"),
+        });
+        self.encode_frame(&mut encoder, view);
+        let uploads = self.uniforms.take_commands();
+        self.queue.submit(uploads.into_iter().chain(std::iter::once(encoder.finish())));
+        self.uniforms.recall();
+        self.start_readbacks();
+    }
+
+    fn start_readbacks(&mut self) {
+        if let Some(pipeline_stats) = &mut self.pipeline_stats {
+            pipeline_stats.start_readback();
+        }
+        if let Some(profiler) = &mut self.gpu_profiler {
+            profiler.start_readback();
+        }
+    }
+
+    // Every pass of a frame, from the compute animation to the HUD, drawn into view
+    fn encode_frame(&mut self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        // Results of an earlier frame, then only record again once the readback buffer is free
+        if let Some(pipeline_stats) = &mut self.pipeline_stats {
+            pipeline_stats.poll_results(&self.device);
+        }
+        let record_stats = self.pipeline_stats.as_ref().is_some_and(PipelineStatsQuery::can_record);
+
+        // Same for the timestamps, the passes of the desktop view that run this frame get timed
+        // A pass listed here has to write its queries, unwritten ones cant be resolved
+        let taa_active = self.taa_active();
+        let desktop_view = !self.multiview_enabled;
+        let lens_effects = self.vignette_strength > 0.0 || self.chromatic_aberration_offset != 0.0;
+        if let Some(profiler) = &mut self.gpu_profiler {
+            profiler.poll_results(&self.device);
+            let passes = [
+                (gpu_profiler::COMPUTE_PASS, self.compute_animation_enabled),
+                (gpu_profiler::SCENE_PASS, desktop_view),
+                (gpu_profiler::TAA_PASS, taa_active),
+                (gpu_profiler::SHADOW_PASS, desktop_view && self.contact_shadow_pass.is_enabled()),
+                (gpu_profiler::POST_PASS, desktop_view && lens_effects),
+            ];
+            let passes = passes.iter().filter(|(_, runs)| *runs).map(|(pass, _)| *pass).collect::<Vec<_>>();
+            profiler.begin_frame(&passes);
+        }
+
+        // Compute pass goes first in the same encoder, so the animated instance buffer
+        // is written before the render pass reads it as a vertex buffer
+        if self.compute_animation_enabled {
+            self.instance_animation.dispatch(
+                encoder,
+                self.pipeline_stats.as_ref().filter(|_| record_stats),
+                self.gpu_profiler.as_ref().and_then(|profiler| profiler.compute_pass_writes(gpu_profiler::COMPUTE_PASS)),
+            );
+        }
+        if self.cloth_enabled
+            && let Some(cloth) = &self.cloth
+        {
+            cloth.dispatch(encoder);
+        }
+
+        // LOD level of every instance for this frame, all meshes share the same distances
+        let eye = self.camera.get_eye();
+        let instance_lods = self.lod_meshes.first()
+            .map(|lod_mesh| {
+                let distances = self.instances.iter().map(|instance| (instance.position - eye.to_vec()).magnitude());
+                lod::select_levels(&lod_mesh.max_distances(), distances, &self.instance_lods)
+            })
+            .unwrap_or_default();
+        self.instance_lods.clone_from(&instance_lods);
+
+        // Gizmo vertices have to be in the buffer before the render pass uses it
+        self.queue_debug_lines();
+        self.debug_lines.prepare(&self.device, &self.queue);
+        // Curves have their own pass after the scene, only in the desktop view
+        let curves = self.debug_curves();
+
+        if self.multiview_enabled {
+            // Both eyes go into the wide target, the side by side preview replaces the normal view
+            self.multiview.render(self, encoder, eye, &instance_lods);
+            self.multiview.draw_preview(encoder, view);
+        } else {
+            let stats = self.pipeline_stats.as_ref().filter(|_| record_stats);
+            // Lens effects go last, aberration first and the vignette darkens its result
+            // Each one that is on takes the place of the screen for everything before it
+            let vignette_enabled = self.vignette_strength > 0.0;
+            let aberration_enabled = self.chromatic_aberration_offset != 0.0;
+            let vignette_input = if vignette_enabled { self.vignette_pass.input_view() } else { view };
+            let frame_view = if aberration_enabled { self.chromatic_aberration_pass.input_view() } else { vignette_input };
+            // Scene goes into the TAA target, the resolve blends it with the history onto the frame
+            // There is no tone mapping yet, so TAA is the last step before the lens effects
+            let resolved_view = if self.taa_active() { &self.taa_pass.scene_texture.texture_view } else { frame_view };
+            if self.water_enabled && !self.split_screen_enabled {
+                self.render_water_targets(encoder, &instance_lods);
+            }
+            if self.sss_enabled {
+                // Lit frame into the SSS target, the blur writes it to where the scene would have gone
+                self.render_scene(encoder, &self.sss_pass.scene_texture.texture_view, &instance_lods, stats);
+                self.sss_pass.render(
+                    encoder,
+                    &self.obj_model,
+                    self.active_instance_buffer(),
+                    0..self.instances.len() as u32,
+                    &self.transform_bind_group,
+                    resolved_view,
+                );
+            } else {
+                self.render_scene(encoder, resolved_view, &instance_lods, stats);
+            }
+            // After the blur so the lines stay sharp, the depth buffer is still the scene one
+            self.curve_renderer.draw(
+                &self.device,
+                &self.queue,
+                encoder,
+                resolved_view,
+                &self.depth_texture.texture_view,
+                &self.camera_bind_group,
+                &curves,
+            );
+            if self.taa_active() {
+                let timestamp_writes = self.gpu_profiler.as_ref()
+                    .and_then(|profiler| profiler.render_pass_writes(gpu_profiler::TAA_PASS));
+                self.taa_pass.render(&self.queue, encoder, frame_view, timestamp_writes);
+            }
+            // Both effects are timed as one post pass, whichever runs first writes the begin
+            let post_writes = |first: bool, last: bool| {
+                self.gpu_profiler.as_ref()
+                    .and_then(|profiler| profiler.render_pass_span_writes(gpu_profiler::POST_PASS, first, last))
+            };
+            if aberration_enabled {
+                self.chromatic_aberration_pass.render(encoder, vignette_input, post_writes(true, !vignette_enabled));
+            }
+            if vignette_enabled {
+                self.vignette_pass.render(encoder, view, post_writes(!aberration_enabled, true));
+            }
+        }
+
+        // Only the desktop view records statistics, the multiview passes are not counted
+        let resolve_stats = record_stats && !self.multiview_enabled;
+        if let Some(pipeline_stats) = self.pipeline_stats.as_mut().filter(|_| resolve_stats) {
+            pipeline_stats.resolve(encoder, self.compute_animation_enabled);
+        }
+        if let Some(profiler) = &mut self.gpu_profiler {
+            profiler.resolve(encoder);
+        }
+
+        // Sprites go over the 3D scene but under the HUD text
+        if self.debug_lines_enabled {
+            self.draw_sprites();
+            self.sprite_batch.render(&self.device, &self.queue, encoder, view);
+        }
+
+        // Every scene pass of the frame is recorded by now, the HUD shows this frame's counts
+        self.finish_render_stats();
+
+        // HUD goes last so it is drawn over everything else
+        self.draw_hud();
+        self.text_renderer.render(&self.device, &self.queue, encoder, view);
+        if let Some(sdf_renderer) = &mut self.sdf_renderer {
+            sdf_renderer.render(&self.device, &self.queue, encoder, view);
+        }
+
+        // Tweaking panel on top of the HUD
+        #[cfg(feature = "gui")]
+        self.render_gui(encoder, view);
+    }
+}
+
+// Setters for the runtime tweaking overlay, only the gui feature calls them for now
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+impl State {
+    pub fn set_camera_speed(&mut self, speed: f32) {
+        self.camera_controller.set_speed(speed);
+    }
+
+    // Color of the picked instance's outline (alpha blends it) and how much bigger than the
+    // instance it is drawn, 0 hides it
+    pub fn set_outline(&mut self, color: [f32; 4], thickness: f32) {
+        self.outline_color = color;
+        self.outline_thickness = thickness.clamp(0.0, outline::MAX_THICKNESS);
+        self.outline_pass.set_params(&self.queue, self.outline_color, self.outline_thickness);
+    }
+
+    // Strength 0 turns the vignette off, the frame then goes straight to the screen
+    pub fn set_vignette(&mut self, strength: f32, power: f32) {
+        self.vignette_strength = strength.max(0.0);
+        self.vignette_power = power.max(0.0);
+        if self.vignette_strength > 0.0 {
+            self.vignette_pass.set_params(&self.queue, self.vignette_strength, self.vignette_power);
+        }
+    }
+
+    // Offset 0 turns the aberration off, negative swaps the sides red and blue move to
+    pub fn set_chromatic_aberration(&mut self, offset: f32) {
+        self.chromatic_aberration_offset = offset;
+        if offset != 0.0 {
+            self.chromatic_aberration_pass.set_offset(&self.queue, offset);
+        }
+    }
+
+    pub fn set_light_color(&mut self, color: [f32; 3]) {
+        self.light_uniform.color = color;
+        self.uniforms.write(&self.light_buffer, &self.light_uniform);
+    }
+
+    // The light keeps orbiting around the Y axis from the new position
+    pub fn set_light_position(&mut self, position: [f32; 3]) {
+        self.light_uniform.position = position;
+        self.uniforms.write(&self.light_buffer, &self.light_uniform);
+    }
+
+    // Rebuild the instance grid with per_row x per_row instances
+    // Everything sized by the instance count (buffers, compute pass, probe runs) is recreated
+    pub fn set_instances_per_row(&mut self, per_row: u32) {
+        let per_row = per_row.max(1);
+        self.instances_per_row = per_row;
+        self.instances = create_instances(per_row);
+
+        let instance_data = self.instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
+        self.instance_animation = InstanceAnimation::new(&self.device, &instance_data, 0.5);
+        self.instance_buffer = buffers::create_instance_buffer(&self.device, instance_data);
+        self.prev_instance_transforms = self.instances.iter().map(Instance::model_matrix).collect();
+
+        let instance_positions = self.instances.iter().map(|instance| instance.position).collect::<Vec<_>>();
+        self.probe_runs = light_probe::assign_probes(&instance_positions, &self.light_probes);
+        // Levels of the old grid belong to other positions, the new one starts without hysteresis
+        self.instance_lods.clear();
+
+        // Old index might not exist anymore
+        self.picked_instance = None;
+        self.render_mode_uniform.selected_instance = NO_SELECTION;
+        self.write_render_mode();
+    }
+}
+
+#[cfg(feature = "gui")]
+impl State {
+    // Let egui look at the event first, true means the app should ignore it
+    pub fn gui_wants_event(&mut self, event: &winit::event::WindowEvent) -> bool {
+        match (&mut self.gui, &self.window) {
+            (Some(gui), Some(window)) => gui.on_window_event(window, event),
+            _ => false,
+        }
+    }
+
+    fn render_gui(&mut self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let current = crate::gui::GuiSettings {
+            camera_speed: self.camera_controller.speed(),
+            light_color: self.light_uniform.color,
+            light_position: self.light_uniform.position,
+            clear_color: [self.clear_color.r as f32, self.clear_color.g as f32, self.clear_color.b as f32],
+            instances_per_row: self.instances_per_row,
+            vignette_strength: self.vignette_strength,
+            vignette_power: self.vignette_power,
+            chromatic_aberration: self.chromatic_aberration_offset,
+        };
+        let mut settings = current;
+        let frame_stats = self.frame_stats();
+        let (Some(gui), Some(window)) = (&mut self.gui, &self.window) else {
+            return;
+        };
+        gui.set_frame_stats(frame_stats);
+        gui.render(&self.device, &self.queue, encoder, view, window, &mut settings);
+
+        // Only touch what changed, some setters (instance count) are expensive
+        if settings.camera_speed != current.camera_speed {
+            self.set_camera_speed(settings.camera_speed);
+        }
+        if settings.light_color != current.light_color {
+            self.set_light_color(settings.light_color);
+        }
+        if settings.light_position != current.light_position {
+            self.set_light_position(settings.light_position);
+        }
+        if settings.clear_color != current.clear_color {
+            let [r, g, b] = settings.clear_color;
+            self.set_clear_color(wgpu::Color { r: r as f64, g: g as f64, b: b as f64, a: 1.0 });
+        }
+        if settings.instances_per_row != current.instances_per_row {
+            self.set_instances_per_row(settings.instances_per_row);
+        }
+        if (settings.vignette_strength, settings.vignette_power) != (current.vignette_strength, current.vignette_power) {
+            self.set_vignette(settings.vignette_strength, settings.vignette_power);
+        }
+        if settings.chromatic_aberration != current.chromatic_aberration {
+            self.set_chromatic_aberration(settings.chromatic_aberration);
+        }
+    }
+}
+
+// Surfaces can only be configured with a non zero size
+fn is_renderable_size(width: u32, height: u32) -> bool {
+    width > 0 && height > 0
+}
+
+// Flag that turns true when the device is lost or runs out of memory
+// Replacing the uncaptured error handler also replaces the default one that panics, everything
+// uncaptured goes to the error log instead (WGPU_PANIC_ON_ERROR brings the panic back)
+fn watch_device(device: &wgpu::Device, errors: &GpuErrorLog) -> Arc<AtomicBool> {
+    let device_lost = Arc::new(AtomicBool::new(false));
+
+    let lost = Arc::clone(&device_lost);
+    device.set_device_lost_callback(move |reason, message| {
+        log::error!("Device lost ({:?}): {}", reason, message);
+        lost.store(true, Ordering::Release);
+    });
+
+    let lost = Arc::clone(&device_lost);
+    let errors = errors.clone();
+    device.on_uncaptured_error(Arc::new(move |error| {
+        if let wgpu::Error::OutOfMemory { .. } = error {
+            log::error!("Device out of memory: {}", error);
+            lost.store(true, Ordering::Release);
+        }
+        errors.uncaptured(error);
+    }));
+
+    device_lost
+}
+
+// Generate a list of positions and rotations for instances based on a grid
+// mapping over X and Z axis to create rows and columns
+fn create_instances(per_row: u32) -> Vec<Instance> {
+    const SPACE_BETWEEN: f32 = 3.0;
+
+    (0..per_row).flat_map(|z| {
+        (0..per_row).map(move |x| {
+            let color = grid_color(x, z, per_row);
+            let x = SPACE_BETWEEN * (x as f32 - per_row as f32 / 2.0);
+            let z = SPACE_BETWEEN * (z as f32 - per_row as f32 / 2.0);
+
+            let position = cgmath::Vector3 { x, y: 0.0, z };
+
+            let rotation = if position.is_zero() {
+                // Needed so object at (0,0,0) wont get scaled to zero
+                // Quaternions can affect scale if not created correctly
+                cgmath::Quaternion::from_axis_angle(cgmath::Vector3::unit_z(), cgmath::Deg(0.0))
+            } else {
+                cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(45.0))
+            };
+
+            Instance {
+                position, rotation, color,
+            }
+        })
+    }).collect()
+}
+
+// Gradient over the grid, red grows along x and blue along z
+// Kept light so the texture still shows through the tint
+fn grid_color(x: u32, z: u32, per_row: u32) -> [f32; 4] {
+    let last = per_row.saturating_sub(1).max(1) as f32;
+    let (tx, tz) = (x as f32 / last, z as f32 / last);
+    [0.5 + 0.5 * tx, 0.7, 0.5 + 0.5 * tz, 1.0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::instance::InstanceRaw;
+
+    #[test]
+    fn test_grid_color_gradient_corners() {
+        assert_eq!(grid_color(0, 0, 10), [0.5, 0.7, 0.5, 1.0]);
+        assert_eq!(grid_color(9, 9, 10), [1.0, 0.7, 1.0, 1.0]);
+        // Single instance grid doesnt divide by zero
+        assert_eq!(grid_color(0, 0, 1), [0.5, 0.7, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn test_minimized_window_size_is_not_renderable() {
+        assert!(!is_renderable_size(0, 0));
+        assert!(!is_renderable_size(0, 600));
+        assert!(!is_renderable_size(800, 0));
+        assert!(is_renderable_size(800, 600));
+    }
+
+    // The default scene: the cube for every instance plus the light cube, nothing transparent
+    // Skips like the bench test without an adapter for the backends the app uses
+    #[test]
+    fn test_render_stats_count_the_default_scene() {
+        if pollster::block_on(headless::request_adapter(adapter::backends_from_env(), false)).is_err() {
+            eprintln!("No adapter available, skipping render stats test");
+            return;
+        }
+        let mut state = pollster::block_on(State::new_headless(64, 64)).unwrap();
+        let target = state.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("Render Stats Test Target"),
+            size: wgpu::Extent3d { width: 64, height: 64, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: state.config().format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        state.update();
+        state.render_offscreen(&view);
+
+        let stats = state.render_stats();
+        let meshes = state.obj_model.meshes.len() as u64;
+        let instances = state.instances.len() as u64;
+        let full_detail = state.obj_model.meshes.iter().map(|mesh| mesh.index_buffer.count as u64 / 3).sum::<u64>();
+        assert_eq!(stats.instances, meshes * (1 + instances));
+        // The light cube, then every mesh once per probe run, more when the run splits up by LOD
+        let min_draws = meshes * (1 + state.probe_runs.len() as u64);
+        assert!(stats.draw_calls as u64 >= min_draws, "{stats:?}");
+        // LODs only ever take triangles away
+        assert!(stats.triangles > 0 && stats.triangles <= full_detail * (1 + instances), "{stats:?}");
+        assert!(stats.bytes_written >= instances * size_of::<InstanceRaw>() as u64, "{stats:?}");
+        assert_eq!(stats.texture_bytes, state.resource_manager.texture_bytes());
+
+        // Counted again from zero, not added to the last frame
+        state.update();
+        state.render_offscreen(&view);
+        assert_eq!(state.render_stats().draw_calls, stats.draw_calls);
+        assert_eq!(state.render_stats().triangles, stats.triangles);
+    }
+
+    // Skips when the machine has no adapter at all (no GPU and no software fallback)
+    #[test]
+    fn test_destroyed_device_is_reported_lost() {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let Ok(adapter) = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())) else {
+            eprintln!("No adapter available, skipping device lost test");
+            return;
+        };
+        let (device, _queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).unwrap();
+
+        let device_lost = watch_device(&device, &GpuErrorLog::new());
+        assert!(!device_lost.load(Ordering::Acquire));
+
+        device.destroy();
+        let _ = device.poll(wgpu::PollType::wait_indefinitely());
+        assert!(device_lost.load(Ordering::Acquire));
     }
 }
@@ -0,0 +1,83 @@
+// Smoke tests of the renderer without a window, run anywhere with a software adapter
+// Every test skips when the machine has no adapter at all
+
+use wgpu_rust::HeadlessContext;
+
+fn context(test: &str) -> Option<HeadlessContext> {
+    match pollster::block_on(HeadlessContext::new()) {
+        Ok(context) => Some(context),
+        Err(e) => {
+            eprintln!("No adapter available ({e}), skipping {test}");
+            None
+        }
+    }
+}
+
+#[test]
+fn test_clear_color_fills_the_image() {
+    let Some(context) = context("clear color test") else {
+        return;
+    };
+    let clear_color = wgpu::Color { r: 1.0, g: 0.0, b: 0.2, a: 1.0 };
+    // 5 pixels wide, 20 bytes per row, so the readback has to strip the row padding
+    let pixels = context.render_to_image(5, 3, clear_color, |_| {});
+
+    assert_eq!(pixels.len(), 5 * 3 * 4);
+    // 0.2 * 255 = 51
+    assert!(pixels.chunks(4).all(|pixel| pixel == [255, 0, 51, 255]), "{pixels:?}");
+}
+
+#[test]
+fn test_draw_goes_into_the_same_pass() {
+    let Some(context) = context("draw test") else {
+        return;
+    };
+    let device = &context.device;
+    // One triangle bigger than the screen, painted white
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Headless Test Shader"),
+        source: wgpu::ShaderSource::Wgsl(
+            "@vertex fn vs_main(@builtin(vertex_index) i: u32) -> @builtin(position) vec4<f32> {
+                let uv = vec2<f32>(f32((i << 1u) & 2u), f32(i & 2u));
+                return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+            }
+            @fragment fn fs_main() -> @location(0) vec4<f32> {
+                return vec4<f32>(1.0);
+            }"
+            .into(),
+        ),
+    });
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Headless Test Pipeline"),
+        layout: None,
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(HeadlessContext::FORMAT.into())],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview_mask: None,
+        cache: None,
+    });
+
+    // Only the left half is drawn, the right half keeps the clear color
+    let pixels = context.render_to_image(8, 4, wgpu::Color::BLACK, |render_pass| {
+        render_pass.set_pipeline(&pipeline);
+        render_pass.set_scissor_rect(0, 0, 4, 4);
+        render_pass.draw(0..3, 0..1);
+    });
+
+    for (index, pixel) in pixels.chunks(4).enumerate() {
+        let expected = if index % 8 < 4 { [255, 255, 255, 255] } else { [0, 0, 0, 255] };
+        assert_eq!(pixel, expected, "pixel {index}");
+    }
+}